@@ -20,9 +20,11 @@ try:
     if lib_path.exists():
         _rust = ctypes.CDLL(str(lib_path))
         
-        # Define signature for NEW function name
-        _rust.process_and_sign_cycle.argtypes = [ctypes.c_char_p, ctypes.c_size_t]
-        _rust.process_and_sign_cycle.restype = ctypes.c_double
+        # Signatures from rust_core/include/rust_core.h
+        _rust.lwas_sign_cycle.argtypes = [ctypes.c_char_p, ctypes.c_size_t, ctypes.c_char_p, ctypes.c_void_p]
+        _rust.lwas_sign_cycle.restype = ctypes.c_int
+        _rust.lwas_last_error.argtypes = [ctypes.c_char_p, ctypes.c_size_t]
+        _rust.lwas_last_error.restype = ctypes.c_size_t
         
         RUST_AVAILABLE = True
     else:
@@ -30,6 +32,12 @@ try:
 except Exception as e:
     print(f"[NEXUS] ⚠️ CRITICAL: Could not load Rust DLL. Error: {e}")
 
+class LwasCycleResult(ctypes.Structure):
+    _fields_ = [("index", ctypes.c_double), ("seq", ctypes.c_uint64), ("hash", ctypes.c_char * 129)]
+
+# Ledger the kernel signs into; None lets Rust use LWAS_LEDGER_PATH / sovereign.ledger
+LEDGER_PATH = os.environ.get("NEXUS_LEDGER_PATH")
+
 # 2. THE WRAPPER FUNCTION
 def calculate_global_entropy_rust(bio_stream, mkt_stream, nrg_stream):
     """
@@ -46,14 +54,19 @@ def calculate_global_entropy_rust(bio_stream, mkt_stream, nrg_stream):
         "energy_data_stream": nrg_stream,
     }).encode('utf-8')
     
-    # Execute Foreign Function (New Name)
-    result = _rust.process_and_sign_cycle(payload, len(payload))
+    # Execute Foreign Function
+    out = LwasCycleResult()
+    ledger = LEDGER_PATH.encode('utf-8') if LEDGER_PATH else None
+    status = _rust.lwas_sign_cycle(payload, len(payload), ledger, ctypes.byref(out))
     
-    # Error Handling (from Rust error codes)
-    if result == -1.0: print("[NEXUS] Rust Error: Null Pointer")
-    if result == -2.0: print("[NEXUS] Rust Error: Bad JSON")
+    # Error Handling (LWAS_STATUS_* codes, see rust_core.h)
+    if status != 0:
+        message = ctypes.create_string_buffer(512)
+        _rust.lwas_last_error(message, len(message))
+        print(f"[NEXUS] Rust Error {status}: {message.value.decode('utf-8', 'replace')}")
+        return 0.0 # Fail-safe
     
-    return result
+    return out.index
 
 if __name__ == "__main__":
     # Self-test
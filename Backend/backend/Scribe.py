@@ -67,24 +67,25 @@ def load_ledger_data(ledger_path="sovereign.ledger"):
                 if not line or line.startswith('#'):
                     continue
                 
-                # Parse ledger line (format: TIMESTAMP | HASH | DATA)
-                parts = line.split('|')
-                if len(parts) >= 3:
-                    timestamp = parts[0].strip()
-                    ledger_hash = parts[1].strip()
+                # Parse ledger line (format: v1 | SEQ | TIMESTAMP | PREV_HASH | HASH | DATA)
+                # Integrity is checked by `ledger verify`; this only reads the records
+                parts = line.split(' | ', 5)
+                if len(parts) == 6 and parts[0] == "v1":
+                    timestamp = datetime.fromtimestamp(int(parts[2])).isoformat()
+                    ledger_hash = parts[4]
                     
                     # Try to parse JSON data
                     try:
-                        data = json.loads(parts[2].strip())
+                        data = json.loads(parts[5])
                         entries.append({
                             "timestamp": timestamp,
                             "hash": ledger_hash[:64],  # Truncate for display
                             "bio_stress": data.get("bio_stress", 0),
                             "market_stress": data.get("market_stress", 0),
-                            "entropy": data.get("entropy", 0),
+                            "entropy": data.get("entropy", data.get("index", 0)),
                             "action": data.get("action", "UNKNOWN")
                         })
-                    except json.JSONDecodeError:
+                    except (json.JSONDecodeError, ValueError):
                         continue
     except Exception as e:
         print(f"[SCRIBE] Error reading ledger: {e}")
@@ -0,0 +1,184 @@
+#ifndef RUST_CORE_H
+#define RUST_CORE_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define LWAS_ABI_VERSION 1
+
+// Length of a hex SHA-512 plus its NUL terminator.
+#define LWAS_HASH_LEN 129
+
+// Capacity of `LwasLedgerReport::reason`, NUL included.
+#define LWAS_REASON_LEN 256
+
+typedef enum LwasStatus {
+  LWAS_STATUS_OK = 0,
+  LWAS_STATUS_NULL_POINTER = 1,
+  LWAS_STATUS_INVALID_UTF8 = 2,
+  LWAS_STATUS_INVALID_JSON = 3,
+  LWAS_STATUS_INVALID_ARGUMENT = 4,
+  LWAS_STATUS_BUFFER_TOO_SMALL = 5,
+  LWAS_STATUS_LEDGER_IO = 6,
+  LWAS_STATUS_LEDGER_CORRUPT = 7,
+  LWAS_STATUS_NOT_FOUND = 8,
+  LWAS_STATUS_INSUFFICIENT_CREDITS = 9,
+  LWAS_STATUS_CONFLICT = 10,
+  LWAS_STATUS_STORAGE = 11,
+  LWAS_STATUS_INTERNAL = 12,
+} LwasStatus;
+
+// Opaque handle to an `EconomyEngine`; free it with `lwas_economy_free`.
+typedef struct LwasEconomy LwasEconomy;
+
+// Result of signing one cycle.
+typedef struct LwasCycleResult {
+  double index;
+  // Position of the record in the ledger
+  uint64_t seq;
+  // Hex SHA-512 of the record, NUL-terminated
+  char hash[LWAS_HASH_LEN];
+} LwasCycleResult;
+
+// Result of verifying a ledger. `first_bad_line` is 0 when `valid` is set or the file could not be opened.
+typedef struct LwasLedgerReport {
+  bool valid;
+  uint64_t records;
+  uint64_t legacy_lines;
+  uint64_t first_bad_line;
+  // Why the first bad line failed, NUL-terminated; empty when valid
+  char reason[LWAS_REASON_LEN];
+} LwasLedgerReport;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+uint32_t lwas_abi_version(void);
+
+// Copies the calling thread's last error message into `buf` (NUL-terminated, truncated
+// to `len`). Returns the buffer size the full message needs, NUL included.
+//
+// # Safety
+// `buf` must be null or point to `len` writable bytes.
+size_t lwas_last_error(char *buf, size_t len);
+
+// Scores one JSON `InputPayload` and appends it to the ledger at `ledger_path`
+// (null for `LWAS_LEDGER_PATH` / `sovereign.ledger`).
+//
+// # Safety
+// `payload` must point to `len` readable bytes, `ledger_path` must be null or a valid
+// C string, and `out` must point to a writable `LwasCycleResult`.
+LwasStatus lwas_sign_cycle(const uint8_t *payload,
+                           size_t len,
+                           const char *ledger_path,
+                           LwasCycleResult *out);
+
+// Signs every payload of a JSON array, in order, into one ledger. `out` receives one
+// result per payload and `*written` how many were signed; on failure the payloads
+// before the failing one stay signed. Fails up front with `LWAS_STATUS_BUFFER_TOO_SMALL`
+// (and `*written` set to the count needed) if `out_len` is too short.
+//
+// # Safety
+// `payloads` must point to `len` readable bytes, `ledger_path` must be null or a valid
+// C string, `out` must point to `out_len` writable results and `written` to a `size_t`.
+LwasStatus lwas_sign_cycles_batch(const uint8_t *payloads,
+                                  size_t len,
+                                  const char *ledger_path,
+                                  LwasCycleResult *out,
+                                  size_t out_len,
+                                  size_t *written);
+
+// Verifies the whole ledger at `ledger_path` (null for the default path). Returns
+// `LWAS_STATUS_OK` when it verifies, `LWAS_STATUS_LEDGER_CORRUPT` when a line fails and
+// `LWAS_STATUS_LEDGER_IO` when it cannot be read; `out` is filled in every case.
+//
+// # Safety
+// `ledger_path` must be null or a valid C string and `out` must point to a writable
+// `LwasLedgerReport`.
+LwasStatus lwas_verify_ledger(const char *ledger_path, LwasLedgerReport *out);
+
+// An in-memory economy with the built-in catalog. Never returns null.
+LwasEconomy *lwas_economy_new(void);
+
+// Opens (or creates) a persistent economy in `data_dir`, snapshotting every
+// `snapshot_interval` events (0 for never).
+//
+// # Safety
+// `data_dir` must be a valid C string and `out` must point to a writable pointer.
+LwasStatus lwas_economy_open(const char *data_dir, uint64_t snapshot_interval, LwasEconomy **out);
+
+// Frees a handle from `lwas_economy_new` or `lwas_economy_open`. Null is ignored.
+//
+// # Safety
+// `economy` must be null or a handle that has not been freed yet.
+void lwas_economy_free(LwasEconomy *economy);
+
+// Mints `amount_minor` hundredths of a credit for `user_id`; `*balance_minor` receives
+// the new balance (`balance_minor` may be null).
+//
+// # Safety
+// `economy` must be a live handle, `user_id` a valid C string and `balance_minor` null
+// or writable.
+LwasStatus lwas_economy_mint(const LwasEconomy *economy,
+                             const char *user_id,
+                             int64_t amount_minor,
+                             int64_t *balance_minor);
+
+// Buys `module_name` for `user_id` at its catalog price; `*balance_minor` receives the
+// new balance (`balance_minor` may be null).
+//
+// # Safety
+// `economy` must be a live handle, the names valid C strings and `balance_minor` null
+// or writable.
+LwasStatus lwas_economy_unlock(const LwasEconomy *economy,
+                               const char *user_id,
+                               const char *module_name,
+                               int64_t *balance_minor);
+
+// `*balance_minor` receives `user_id`'s balance in hundredths of a credit;
+// `LWAS_STATUS_NOT_FOUND` for a user the economy has never seen.
+//
+// # Safety
+// `economy` must be a live handle, `user_id` a valid C string and `balance_minor` writable.
+LwasStatus lwas_economy_balance(const LwasEconomy *economy,
+                                const char *user_id,
+                                int64_t *balance_minor);
+
+// `*entitled` receives whether `user_id` may use `module_name` right now.
+//
+// # Safety
+// `economy` must be a live handle, the names valid C strings and `entitled` writable.
+LwasStatus lwas_economy_check_entitlement(const LwasEconomy *economy,
+                                          const char *user_id,
+                                          const char *module_name,
+                                          bool *entitled);
+
+// `*count` receives the number of journal entries.
+//
+// # Safety
+// `economy` must be a live handle and `count` writable.
+LwasStatus lwas_economy_transaction_count(const LwasEconomy *economy, uint64_t *count);
+
+// Original entry point, kept for existing callers: returns -1.0 for a null or empty
+// buffer and -2.0 for bad JSON, and only logs ledger failures. New code should use
+// `lwas_sign_cycle` (see `ffi`).
+//
+// # Safety
+// `data_ptr` must point to `data_len` readable bytes.
+double process_and_sign_cycle(const uint8_t *data_ptr, size_t data_len);
+
+// Verifies the ledger at `LWAS_LEDGER_PATH` (default `sovereign.ledger`).
+// Returns true if all hashes form a valid chain, false if corruption detected
+bool verify_ledger_integrity(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RUST_CORE_H */
@@ -0,0 +1,202 @@
+//! Actix middleware that resolves the caller's API key and checks the route's scopes
+//! before the handler runs. Routes missing from `route_access` are closed to everyone.
+
+use actix_web::body::{BoxBody, EitherBody};
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use super::{AuthError, KeyStore, Principal, Scope};
+use crate::metrics::FailureKind;
+
+/// Who may call a route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    /// No key needed
+    Public,
+    /// Any valid key
+    Authenticated,
+    /// A key holding at least one of these scopes
+    AnyOf(&'static [Scope]),
+}
+
+const ADMIN_READ: &[Scope] = &[Scope::AdminRead];
+const READ: &[Scope] = &[Scope::AdminRead, Scope::UserReadOwn];
+const ENTITLEMENT_READ: &[Scope] = &[Scope::AdminRead, Scope::UserReadOwn, Scope::ServiceUnlock];
+const SPEND: &[Scope] = &[Scope::ServiceUnlock, Scope::UserSpendOwn];
+
+/// The policy for a route, by method and registered pattern; `None` means denied.
+pub fn route_access(method: &Method, pattern: &str) -> Option<Policy> {
+    let policy = match (method.as_str(), pattern) {
+        ("GET", "/health") => Policy::Public,
+        ("GET", "/api/catalog") => Policy::Authenticated,
+        ("POST", "/api/mint_credits") | ("POST", "/api/reverse") => Policy::AnyOf(&[Scope::AdminMint]),
+        ("POST", "/api/refund") | ("POST", "/api/admin/renewals/run") => Policy::AnyOf(&[Scope::AdminManage]),
+        ("GET", "/api/balances")
+        | ("GET", "/api/ledger/accounts")
+        | ("GET", "/api/telemetry")
+        | ("GET", "/api/telemetry/history")
+        | ("GET", "/metrics")
+        | ("GET", "/api/admin/storage/verify") => Policy::AnyOf(ADMIN_READ),
+        ("GET", "/api/balance")
+        | ("GET", "/api/transactions")
+        | ("GET", "/api/transactions/{id}")
+        | ("GET", "/api/events")
+        | ("GET", "/api/users/{user_id}/statement") => Policy::AnyOf(READ),
+        ("GET", "/api/entitlements") | ("GET", "/api/entitlements/check") => Policy::AnyOf(ENTITLEMENT_READ),
+        ("POST", "/api/unlock_module") | ("POST", "/api/entitlements/trial") | ("POST", "/api/entitlements/auto_renew") => {
+            Policy::AnyOf(SPEND)
+        }
+        ("POST", "/api/entitlements/grant") => Policy::AnyOf(&[Scope::ServiceUnlock]),
+        ("POST", "/api/transfer") => Policy::AnyOf(&[Scope::UserSpendOwn]),
+        ("POST", "/api/akashic/seal") => Policy::AnyOf(&[Scope::ServiceSeal]),
+        ("POST", "/api/akashic/trace") | ("GET", "/api/akashic/verify") => Policy::AnyOf(&[Scope::ServiceSeal, Scope::AdminRead]),
+        // Checks a chain sent in the body; reads nothing from the server
+        ("POST", "/api/akashic/verify") => Policy::Authenticated,
+        _ => return None,
+    };
+    Some(policy)
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        if self.is_unauthenticated() {
+            StatusCode::UNAUTHORIZED
+        } else {
+            StatusCode::FORBIDDEN
+        }
+    }
+
+    /// Same `{"error", "kind"}` body as the economy errors.
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        let mut response = HttpResponse::build(self.status_code());
+        if self.is_unauthenticated() {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        let mut response = response.json(serde_json::json!({
+            "error": self.to_string(),
+            "kind": self.kind(),
+        }));
+        response.extensions_mut().insert(FailureKind(self.kind()));
+        response
+    }
+}
+
+/// Handlers take a `Principal` argument to see who is calling.
+impl FromRequest for Principal {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<Principal>().cloned().ok_or(AuthError::MissingKey))
+    }
+}
+
+/// `Authorization: Bearer <token>`, or `X-API-Key: <token>`.
+fn bearer_token(req: &ServiceRequest) -> Option<String> {
+    let headers = req.headers();
+    if let Some(value) = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        return value.strip_prefix("Bearer ").map(|t| t.trim().to_string());
+    }
+    headers.get("X-API-Key").and_then(|v| v.to_str().ok()).map(|t| t.trim().to_string())
+}
+
+#[derive(Clone)]
+pub struct ApiKeyAuth {
+    /// `None` lets every request through with every scope
+    keys: Option<Arc<Mutex<KeyStore>>>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(keys: KeyStore) -> Self {
+        ApiKeyAuth { keys: Some(Arc::new(Mutex::new(keys))) }
+    }
+
+    /// No checks at all: for tests and local development only.
+    pub fn allow_all() -> Self {
+        ApiKeyAuth { keys: None }
+    }
+
+    fn authorize(&self, req: &ServiceRequest) -> Result<Option<Principal>, AuthError> {
+        let policy = req.match_pattern().and_then(|pattern| route_access(req.method(), &pattern));
+        let Some(keys) = &self.keys else {
+            return Ok(Some(Principal { key_id: "dev".to_string(), scopes: Scope::ALL.to_vec(), user_id: None, granted: Scope::ALL.to_vec() }));
+        };
+        if policy == Some(Policy::Public) {
+            return Ok(None);
+        }
+
+        let token = bearer_token(req).ok_or(AuthError::MissingKey)?;
+        let mut keys = keys.lock().unwrap();
+        if let Err(e) = keys.refresh() {
+            eprintln!("⚠️ Keeping previously loaded API keys: {}", e);
+        }
+        let key = keys.authenticate(&token)?;
+        let granted = match policy {
+            Some(Policy::AnyOf(allowed)) => {
+                let granted: Vec<Scope> = key.scopes.iter().copied().filter(|s| allowed.contains(s)).collect();
+                if granted.is_empty() {
+                    return Err(AuthError::Forbidden(allowed.to_vec()));
+                }
+                granted
+            }
+            Some(_) => Vec::new(),
+            None => return Err(AuthError::Forbidden(Vec::new())),
+        };
+        Ok(Some(Principal { key_id: key.id.clone(), scopes: key.scopes.clone(), user_id: key.user_id.clone(), granted }))
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyAuthMiddleware { service: Rc::new(service), auth: self.clone() }))
+    }
+}
+
+pub struct ApiKeyAuthMiddleware<S> {
+    service: Rc<S>,
+    auth: ApiKeyAuth,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        match self.auth.authorize(&req) {
+            Ok(principal) => {
+                if let Some(principal) = principal {
+                    req.extensions_mut().insert(principal);
+                }
+                let service = self.service.clone();
+                Box::pin(async move { service.call(req).await.map(ServiceResponse::map_into_left_body) })
+            }
+            // Answered here, so the rejection never reaches the handler
+            Err(e) => {
+                let response = e.error_response();
+                Box::pin(ready(Ok(req.into_response(response).map_into_right_body())))
+            }
+        }
+    }
+}
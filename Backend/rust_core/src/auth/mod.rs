@@ -0,0 +1,330 @@
+//! API keys for the economy server.
+//!
+//! A key is shown once as `lwas_<id>_<secret>`; only the SHA-256 of the secret is kept,
+//! in `api_keys.json` next to the event log. Each key carries scopes and, for the
+//! `*-own` scopes, the one user it may act for. Keys are created and revoked with the
+//! `lwas_keys` CLI; the server picks up changes to the file without a restart.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+mod middleware;
+
+pub use middleware::{route_access, ApiKeyAuth, Policy};
+
+pub const KEYS_FILE: &str = "api_keys.json";
+const TOKEN_PREFIX: &str = "lwas";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    /// Mint credits and reverse mints
+    #[serde(rename = "admin:mint")]
+    AdminMint,
+    /// Read every user's balances and transactions, the ledger and storage health
+    #[serde(rename = "admin:read")]
+    AdminRead,
+    /// Refunds and the renewal job
+    #[serde(rename = "admin:manage")]
+    AdminManage,
+    /// Read the bound user's balance, transactions and entitlements
+    #[serde(rename = "user:read-own")]
+    UserReadOwn,
+    /// Spend the bound user's credits: unlocks, trials, transfers
+    #[serde(rename = "user:spend-own")]
+    UserSpendOwn,
+    /// Unlock, grant and check modules for any user
+    #[serde(rename = "service:unlock")]
+    ServiceUnlock,
+    /// Seal decisions into the Akashic chain and trace them back
+    #[serde(rename = "service:seal")]
+    ServiceSeal,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 7] = [
+        Scope::AdminMint,
+        Scope::AdminRead,
+        Scope::AdminManage,
+        Scope::UserReadOwn,
+        Scope::UserSpendOwn,
+        Scope::ServiceUnlock,
+        Scope::ServiceSeal,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scope::AdminMint => "admin:mint",
+            Scope::AdminRead => "admin:read",
+            Scope::AdminManage => "admin:manage",
+            Scope::UserReadOwn => "user:read-own",
+            Scope::UserSpendOwn => "user:spend-own",
+            Scope::ServiceUnlock => "service:unlock",
+            Scope::ServiceSeal => "service:seal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    /// Limited to the key's bound user.
+    pub fn is_own(&self) -> bool {
+        matches!(self, Scope::UserReadOwn | Scope::UserSpendOwn)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    /// Hex SHA-256 of the secret part of the token
+    pub secret_hash: String,
+    pub scopes: Vec<Scope>,
+    /// The user `*-own` scopes act for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<u64>,
+}
+
+/// The authenticated caller, available to handlers through request extensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub key_id: String,
+    pub scopes: Vec<Scope>,
+    pub user_id: Option<String>,
+    /// The caller's scopes that let it through the current route
+    pub granted: Vec<Scope>,
+}
+
+impl Principal {
+    /// The one user this request may touch, when only `*-own` scopes let it through.
+    pub fn restricted_to(&self) -> Option<&str> {
+        if !self.granted.is_empty() && self.granted.iter().all(Scope::is_own) {
+            Some(self.user_id.as_deref().unwrap_or(""))
+        } else {
+            None
+        }
+    }
+
+    pub fn check_user(&self, user_id: &str) -> Result<(), AuthError> {
+        match self.restricted_to() {
+            Some(own) if own != user_id => Err(AuthError::WrongUser(user_id.to_string())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    MissingKey,
+    InvalidKey,
+    Revoked(String),
+    /// The key has none of the scopes the route accepts
+    Forbidden(Vec<Scope>),
+    /// An own-scope key used for another user
+    WrongUser(String),
+}
+
+impl AuthError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuthError::MissingKey => "missing_api_key",
+            AuthError::InvalidKey => "invalid_api_key",
+            AuthError::Revoked(_) => "revoked_api_key",
+            AuthError::Forbidden(_) => "forbidden",
+            AuthError::WrongUser(_) => "forbidden",
+        }
+    }
+
+    pub fn is_unauthenticated(&self) -> bool {
+        matches!(self, AuthError::MissingKey | AuthError::InvalidKey | AuthError::Revoked(_))
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingKey => write!(f, "An API key is required (Authorization: Bearer <key>)"),
+            AuthError::InvalidKey => write!(f, "Invalid API key"),
+            AuthError::Revoked(id) => write!(f, "API key '{}' has been revoked", id),
+            AuthError::Forbidden(scopes) if scopes.is_empty() => write!(f, "This route is not open to API keys"),
+            AuthError::Forbidden(scopes) => {
+                let names: Vec<&str> = scopes.iter().map(Scope::name).collect();
+                write!(f, "This route requires one of the scopes: {}", names.join(", "))
+            }
+            AuthError::WrongUser(user_id) => write!(f, "This API key may not act for user '{}'", user_id),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Compares without an early exit, so timing does not reveal how much of a hash matched.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeysFile {
+    keys: Vec<ApiKey>,
+}
+
+/// The key file plus an in-memory copy, reloaded when the file changes on disk.
+pub struct KeyStore {
+    path: PathBuf,
+    keys: Vec<ApiKey>,
+    modified: Option<SystemTime>,
+}
+
+impl KeyStore {
+    /// Opens `dir/api_keys.json`; a missing file is an empty store.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, String> {
+        let mut store = KeyStore { path: dir.as_ref().join(KEYS_FILE), keys: Vec::new(), modified: None };
+        store.reload()?;
+        Ok(store)
+    }
+
+    fn reload(&mut self) -> Result<(), String> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        self.keys = match fs::read(&self.path) {
+            Ok(bytes) => {
+                serde_json::from_slice::<KeysFile>(&bytes).map_err(|e| format!("{}: {}", self.path.display(), e))?.keys
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("{}: {}", self.path.display(), e)),
+        };
+        self.modified = modified;
+        Ok(())
+    }
+
+    /// Picks up keys created or revoked by the CLI since the last look.
+    pub fn refresh(&mut self) -> Result<(), String> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified != self.modified {
+            self.reload()?;
+        }
+        Ok(())
+    }
+
+    /// Atomically rewrites the key file (temp file, fsync, rename).
+    fn save(&mut self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        let io = |e: std::io::Error| format!("Cannot write {}: {}", self.path.display(), e);
+        let json = serde_json::to_vec_pretty(&KeysFile { keys: self.keys.clone() }).map_err(|e| e.to_string())?;
+        let mut file = File::create(&tmp).map_err(io)?;
+        file.write_all(&json).and_then(|_| file.sync_all()).map_err(io)?;
+        fs::rename(&tmp, &self.path).map_err(io)?;
+        self.modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        Ok(())
+    }
+
+    /// Creates a key and returns it with the token, which is not stored and cannot be shown again.
+    pub fn create(&mut self, name: &str, scopes: Vec<Scope>, user_id: Option<String>) -> Result<(ApiKey, String), String> {
+        if scopes.is_empty() {
+            return Err("A key needs at least one scope".to_string());
+        }
+        if scopes.iter().any(Scope::is_own) && user_id.is_none() {
+            return Err("Scopes ending in -own need the user the key acts for".to_string());
+        }
+        self.refresh()?;
+        let id = hex(&rand::random::<[u8; 6]>());
+        let secret = hex(&rand::random::<[u8; 32]>());
+        let key = ApiKey {
+            id: id.clone(),
+            name: name.to_string(),
+            secret_hash: hash_secret(&secret),
+            scopes,
+            user_id,
+            created_at: unix_now(),
+            revoked_at: None,
+        };
+        self.keys.push(key.clone());
+        self.save()?;
+        Ok((key, format!("{}_{}_{}", TOKEN_PREFIX, id, secret)))
+    }
+
+    pub fn revoke(&mut self, id: &str) -> Result<ApiKey, String> {
+        self.refresh()?;
+        let key = self.keys.iter_mut().find(|k| k.id == id).ok_or_else(|| format!("No API key with id '{}'", id))?;
+        key.revoked_at.get_or_insert_with(unix_now);
+        let key = key.clone();
+        self.save()?;
+        Ok(key)
+    }
+
+    pub fn keys(&self) -> &[ApiKey] {
+        &self.keys
+    }
+
+    /// Resolves a `lwas_<id>_<secret>` token to its key.
+    pub fn authenticate(&self, token: &str) -> Result<&ApiKey, AuthError> {
+        let (id, secret) = token
+            .strip_prefix(TOKEN_PREFIX)
+            .and_then(|rest| rest.strip_prefix('_'))
+            .and_then(|rest| rest.split_once('_'))
+            .ok_or(AuthError::InvalidKey)?;
+        let key = self.keys.iter().find(|k| k.id == id).ok_or(AuthError::InvalidKey)?;
+        if !constant_time_eq(&key.secret_hash, &hash_secret(secret)) {
+            return Err(AuthError::InvalidKey);
+        }
+        if key.revoked_at.is_some() {
+            return Err(AuthError::Revoked(key.id.clone()));
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lwas_auth_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_keys_are_hashed_and_revocable() {
+        let dir = temp_dir("keys");
+        let mut store = KeyStore::open(&dir).unwrap();
+        let (key, token) = store.create("webhook", vec![Scope::ServiceUnlock], None).unwrap();
+        assert!(!fs::read_to_string(dir.join(KEYS_FILE)).unwrap().contains(token.rsplit('_').next().unwrap()));
+        assert_eq!(store.authenticate(&token).unwrap().id, key.id);
+        assert_eq!(store.authenticate(&format!("{}x", token)), Err(AuthError::InvalidKey));
+        assert!(store.create("reader", vec![Scope::UserReadOwn], None).is_err());
+
+        // A second handle, as the CLI would have, revokes; the first sees it on refresh
+        KeyStore::open(&dir).unwrap().revoke(&key.id).unwrap();
+        store.refresh().unwrap();
+        assert_eq!(store.authenticate(&token), Err(AuthError::Revoked(key.id)));
+    }
+}
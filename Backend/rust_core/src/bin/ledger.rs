@@ -0,0 +1,46 @@
+//! Sovereign Ledger tools.
+//!
+//!     ledger verify [PATH] [--json]
+//!
+//! PATH defaults to `LWAS_LEDGER_PATH`, then `sovereign.ledger`. Exits 0 when the whole
+//! chain verifies, 1 when a record fails (the report names the first bad line) and 2
+//! on bad usage.
+
+use rust_core::scribe::{ledger_path, verify_ledger};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: ledger verify [PATH] [--json]";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some("verify") {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+    let mut path = None;
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if arg.starts_with('-') || path.is_some() => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let report = verify_ledger(path.unwrap_or_else(ledger_path));
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).expect("report serializes"));
+    } else if report.valid {
+        println!("✓ {}", report);
+        if let Some(head) = &report.head {
+            println!("head {}", head);
+        }
+    } else {
+        println!("✗ {}", report);
+    }
+    if report.valid { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
@@ -0,0 +1,76 @@
+//! Admin CLI for the economy server's API keys.
+//!
+//!     lwas_keys create --name stripe-webhook --scope admin:mint --scope service:unlock
+//!     lwas_keys create --name alice-app --scope user:read-own --scope user:spend-own --user alice
+//!     lwas_keys list
+//!     lwas_keys revoke <id>
+//!
+//! Keys are kept in `api_keys.json` under `--data-dir` (default `LWAS_DATA_DIR`, then
+//! `economy_data`); a running server picks up changes without a restart.
+
+use rust_core::auth::{KeyStore, Scope};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: lwas_keys [--data-dir DIR] <create --name NAME --scope SCOPE... [--user USER_ID] | list | revoke ID>";
+
+fn run(args: Vec<String>) -> Result<(), String> {
+    let mut data_dir = std::env::var("LWAS_DATA_DIR").unwrap_or_else(|_| "economy_data".to_string());
+    let mut name = None;
+    let mut scopes = Vec::new();
+    let mut user_id = None;
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--data-dir" => data_dir = value()?,
+            "--name" => name = Some(value()?),
+            "--scope" => {
+                let scope = value()?;
+                let names: Vec<&str> = Scope::ALL.iter().map(Scope::name).collect();
+                scopes.push(
+                    Scope::from_name(&scope).ok_or_else(|| format!("Unknown scope '{}' (one of: {})", scope, names.join(", ")))?,
+                );
+            }
+            "--user" => user_id = Some(value()?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut store = KeyStore::open(&data_dir)?;
+    match positional.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["create"] => {
+            let name = name.ok_or("create needs --name")?;
+            let (key, token) = store.create(&name, scopes, user_id)?;
+            println!("Created API key {} ({})", key.id, name);
+            println!("{}", token);
+            eprintln!("Store this token now; it cannot be shown again.");
+        }
+        ["list"] => {
+            for key in store.keys() {
+                let scopes: Vec<&str> = key.scopes.iter().map(Scope::name).collect();
+                let status = if key.revoked_at.is_some() { "revoked" } else { "active" };
+                let user = key.user_id.as_deref().map(|u| format!(" user={}", u)).unwrap_or_default();
+                println!("{}  {:8} {}  [{}]{}", key.id, status, key.name, scopes.join(" "), user);
+            }
+        }
+        ["revoke", id] => {
+            let key = store.revoke(id)?;
+            println!("Revoked API key {} ({})", key.id, key.name);
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Server-owned module catalog: what each module costs, how it is billed, which
+//! subscription tier includes it and which modules must be held first.
+//!
+//! Clients name a module; the price always comes from here.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use super::{EconomyError, Money};
+
+/// Subscription tiers, cheapest first. A tier includes every module of the tiers below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tier {
+    Singularity,
+    Aeterna,
+    Vortex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Billing {
+    /// Paid once, held forever
+    OneTime,
+    /// Paid every `SUBSCRIPTION_PERIOD_SECS`, renewed automatically
+    Monthly,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogModule {
+    pub name: String,
+    pub title: String,
+    pub tier: Tier,
+    pub price: Money,
+    pub billing: Billing,
+    /// Length of the free trial; `None` when the module has no trial
+    #[serde(default)]
+    pub trial_days: Option<u32>,
+    /// Modules the user must hold before this one can be unlocked or trialled
+    #[serde(default)]
+    pub requires: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Catalog {
+    modules: BTreeMap<String, CatalogModule>,
+}
+
+#[derive(Deserialize)]
+struct CatalogFile {
+    modules: Vec<CatalogModule>,
+}
+
+impl Catalog {
+    /// Fails if a module is listed twice or requires an unknown module or itself (directly or not).
+    pub fn new(modules: Vec<CatalogModule>) -> Result<Self, String> {
+        let mut by_name = BTreeMap::new();
+        for module in modules {
+            if module.price.is_negative() {
+                return Err(format!("Module '{}' has a negative price", module.name));
+            }
+            if let Some(existing) = by_name.insert(module.name.clone(), module) {
+                return Err(format!("Module '{}' is listed twice", existing.name));
+            }
+        }
+        let catalog = Catalog { modules: by_name };
+        for module in catalog.modules.values() {
+            for required in &module.requires {
+                if !catalog.modules.contains_key(required) {
+                    return Err(format!("Module '{}' requires unknown module '{}'", module.name, required));
+                }
+            }
+            if catalog.all_requirements(&module.name).contains(&module.name) {
+                return Err(format!("Module '{}' requires itself", module.name));
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Reads `{"modules": [...]}` from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file: CatalogFile = serde_json::from_slice(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Catalog::new(file.modules)
+    }
+
+    /// The modules sold through the Stripe tiers.
+    pub fn builtin() -> Self {
+        let module = |name: &str, title: &str, tier, price, billing, trial_days, requires: &[&str]| CatalogModule {
+            name: name.to_string(),
+            title: title.to_string(),
+            tier,
+            price: Money::credits(price),
+            billing,
+            trial_days,
+            requires: requires.iter().map(|r| r.to_string()).collect(),
+        };
+        Catalog::new(vec![
+            module("basic_analytics", "Basic Analytics", Tier::Singularity, 1000, Billing::Monthly, Some(7), &[]),
+            module("advanced_arbitrage", "Advanced Arbitrage", Tier::Aeterna, 5000, Billing::Monthly, Some(7), &["basic_analytics"]),
+            module("ai_insights", "AI Insights", Tier::Aeterna, 4000, Billing::Monthly, Some(14), &["basic_analytics"]),
+            module("enterprise_support", "Enterprise Support", Tier::Vortex, 20000, Billing::Monthly, None, &[]),
+            module("custom_integrations", "Custom Integrations", Tier::Vortex, 15000, Billing::OneTime, None, &["ai_insights"]),
+        ])
+        .expect("built-in catalog is valid")
+    }
+
+    pub fn get(&self, name: &str) -> Result<&CatalogModule, EconomyError> {
+        self.modules.get(name).ok_or_else(|| EconomyError::ModuleNotFound(name.to_string()))
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = &CatalogModule> {
+        self.modules.values()
+    }
+
+    /// Modules included in `tier`.
+    pub fn tier_modules(&self, tier: Tier) -> Vec<&CatalogModule> {
+        self.modules.values().filter(|m| m.tier <= tier).collect()
+    }
+
+    fn all_requirements(&self, name: &str) -> HashSet<String> {
+        let mut seen = HashSet::new();
+        let mut pending: Vec<&str> = self.modules.get(name).map_or(Vec::new(), |m| m.requires.iter().map(String::as_str).collect());
+        while let Some(next) = pending.pop() {
+            if seen.insert(next.to_string()) {
+                if let Some(module) = self.modules.get(next) {
+                    pending.extend(module.requires.iter().map(String::as_str));
+                }
+            }
+        }
+        seen
+    }
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Catalog::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog_rejects_cycles_and_unknown_prerequisites() {
+        let mut modules: Vec<CatalogModule> = Catalog::builtin().modules().cloned().collect();
+        assert_eq!(Catalog::builtin().tier_modules(Tier::Singularity).len(), 1);
+
+        modules.iter_mut().find(|m| m.name == "basic_analytics").unwrap().requires = vec!["custom_integrations".into()];
+        assert!(Catalog::new(modules.clone()).unwrap_err().contains("requires itself"));
+
+        modules[0].requires = vec!["missing".into()];
+        assert!(Catalog::new(modules).unwrap_err().contains("unknown module 'missing'"));
+    }
+}
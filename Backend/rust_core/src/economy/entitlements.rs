@@ -0,0 +1,185 @@
+//! Who may use which module, and until when.
+//!
+//! Entitlements are never stored directly: unlocks, trials, renewals and grants carry a
+//! `Grant` on their transaction, refunds revoke, and replaying the journal rebuilds the
+//! current set. Expiry is evaluated against the clock when asked, so nothing has to
+//! happen at the moment a trial or subscription runs out.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{Transaction, TransactionType};
+
+/// Length of one subscription period.
+pub const SUBSCRIPTION_PERIOD_SECS: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntitlementKind {
+    Permanent,
+    Trial,
+    Subscription,
+}
+
+/// The access a transaction confers on its module.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grant {
+    pub kind: EntitlementKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Charge the catalog price and extend when `expires_at` passes
+    #[serde(default)]
+    pub auto_renew: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entitlement {
+    pub module_name: String,
+    pub kind: EntitlementKind,
+    pub granted_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    pub auto_renew: bool,
+    /// The transaction that last granted or renewed it
+    pub transaction_id: String,
+}
+
+impl Entitlement {
+    /// The entitlement `transaction` confers, as of the moment it was posted.
+    pub fn granted_by(transaction: &Transaction) -> Option<Entitlement> {
+        let grant = match (&transaction.grant, transaction.transaction_type) {
+            (Some(grant), _) => grant.clone(),
+            // Unlocks from before the catalog were permanent
+            (None, TransactionType::Unlock) => Grant { kind: EntitlementKind::Permanent, expires_at: None, auto_renew: false },
+            (None, _) => return None,
+        };
+        Some(Entitlement {
+            module_name: transaction.module_name.clone()?,
+            kind: grant.kind,
+            granted_at: transaction.timestamp,
+            expires_at: grant.expires_at,
+            auto_renew: grant.auto_renew,
+            transaction_id: transaction.id.clone(),
+        })
+    }
+
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+/// Answer to "may this user use this module right now?".
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntitlementCheck {
+    pub user_id: String,
+    pub module_name: String,
+    pub entitled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entitlement: Option<Entitlement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LapsedEntitlement {
+    pub user_id: String,
+    pub module_name: String,
+    pub reason: String,
+}
+
+/// Outcome of one renewal pass.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RenewalReport {
+    pub renewed: Vec<Transaction>,
+    /// Auto-renewal was switched off for these, e.g. for lack of credits
+    pub lapsed: Vec<LapsedEntitlement>,
+}
+
+/// Applies `transaction`'s effect on its user's entitlements.
+pub(crate) fn apply_grant(entitlements: &mut BTreeMap<String, Entitlement>, transaction: &Transaction) {
+    let Some(module) = &transaction.module_name else {
+        return;
+    };
+    if transaction.transaction_type == TransactionType::Refund {
+        entitlements.remove(module);
+        return;
+    }
+    let Some(mut entitlement) = Entitlement::granted_by(transaction) else {
+        return;
+    };
+    if let Some(existing) = entitlements.get(module).filter(|_| transaction.transaction_type == TransactionType::Renewal) {
+        entitlement.granted_at = existing.granted_at;
+    }
+    entitlements.insert(module.clone(), entitlement);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{EconomyEngine, EconomyError, Money};
+    use super::*;
+
+    fn engine(credits: i64) -> EconomyEngine {
+        let engine = EconomyEngine::new();
+        engine.mint_credits("alice".into(), Money::credits(credits), None).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_catalog_price_and_prerequisites_apply() {
+        let engine = engine(20000);
+        let early = engine.unlock_module("alice".into(), "ai_insights".into(), None, None);
+        assert!(matches!(early, Err(EconomyError::MissingPrerequisite { .. })));
+        let cheap = engine.unlock_module("alice".into(), "basic_analytics".into(), Some(Money::credits(1)), None);
+        assert!(matches!(cheap, Err(EconomyError::PriceMismatch { .. })));
+
+        let alice = engine.unlock_module("alice".into(), "basic_analytics".into(), None, None).unwrap();
+        assert_eq!(alice.credits, Money::credits(19000));
+        let again = engine.unlock_module("alice".into(), "basic_analytics".into(), None, None);
+        assert_eq!(again, Err(EconomyError::AlreadyEntitled("basic_analytics".into())));
+        engine.unlock_module("alice".into(), "ai_insights".into(), None, None).unwrap();
+
+        let subscription = engine.check_entitlement("alice", "ai_insights").entitlement.unwrap();
+        assert_eq!(subscription.kind, EntitlementKind::Subscription);
+        assert!(subscription.auto_renew);
+        assert!(matches!(engine.unlock_module("alice".into(), "nope".into(), None, None), Err(EconomyError::ModuleNotFound(_))));
+    }
+
+    #[test]
+    fn test_renewal_charges_then_lapses_without_credits() {
+        let engine = engine(2500);
+        engine.unlock_module("alice".into(), "basic_analytics".into(), None, None).unwrap();
+        let first = engine.check_entitlement("alice", "basic_analytics").entitlement.unwrap();
+        let expiry = first.expires_at.unwrap();
+
+        assert!(engine.renew_due(expiry - 1).renewed.is_empty());
+        let report = engine.renew_due(expiry);
+        assert_eq!(report.renewed.len(), 1);
+        assert_eq!(report.renewed[0].transaction_type, TransactionType::Renewal);
+        let renewed = engine.check_entitlement("alice", "basic_analytics").entitlement.unwrap();
+        assert_eq!(renewed.expires_at, Some(expiry + SUBSCRIPTION_PERIOD_SECS));
+        assert_eq!(renewed.granted_at, first.granted_at);
+        assert_eq!(engine.get_balance("alice").unwrap().credits, Money::credits(500));
+
+        // 5.00 left is not enough for another month
+        let report = engine.renew_due(expiry + SUBSCRIPTION_PERIOD_SECS);
+        assert_eq!(report.lapsed.len(), 1);
+        assert!(!engine.check_entitlement("alice", "basic_analytics").entitlement.unwrap().auto_renew);
+        assert!(engine.renew_due(expiry + 2 * SUBSCRIPTION_PERIOD_SECS).lapsed.is_empty());
+    }
+
+    #[test]
+    fn test_trial_is_offered_once() {
+        let engine = engine(100);
+        let trial = engine.start_trial("alice".into(), "basic_analytics".into(), false, None).unwrap();
+        assert_eq!(trial.kind, EntitlementKind::Trial);
+        assert!(engine.get_balance("alice").unwrap().unlocked_modules.contains(&"basic_analytics".to_string()));
+
+        // Not set to auto-renew, so the trial just ends
+        assert!(engine.renew_due(trial.expires_at.unwrap()).renewed.is_empty());
+        let again = engine.start_trial("alice".into(), "basic_analytics".into(), false, None);
+        assert!(matches!(again, Err(EconomyError::AlreadyEntitled(_))));
+        let no_trial = engine.start_trial("alice".into(), "enterprise_support".into(), false, None);
+        assert!(matches!(no_trial, Err(EconomyError::TrialUnavailable(_))));
+
+        let converting = engine.set_auto_renew("alice", "basic_analytics", true).unwrap();
+        assert!(converting.auto_renew);
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+
+use super::{Currency, Money};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EconomyError {
+    InvalidRequest(String),
+    InvalidAmount(String),
+    UnknownCurrency(String),
+    CurrencyMismatch { expected: Currency, found: Currency },
+    Overflow,
+    UserNotFound(String),
+    InsufficientCredits { available: Money, required: Money },
+    TransactionNotFound(String),
+    AlreadyReversed { original: String, by: String },
+    NotReversible(String),
+    Unbalanced(String),
+    ModuleNotFound(String),
+    /// The client's expected price differs from the catalog's.
+    PriceMismatch { module_name: String, price: Money, expected: Money },
+    MissingPrerequisite { module_name: String, requires: String },
+    AlreadyEntitled(String),
+    TrialUnavailable(String),
+    NotEntitled { user_id: String, module_name: String },
+    /// The idempotency key already belongs to a committed transaction.
+    DuplicateRequest { key: String, transaction_id: String },
+    /// The idempotency key was first used with a different request.
+    IdempotencyKeyReused(String),
+    /// The request would take the caller past a spending cap within its window.
+    SpendingCapExceeded { cap: String, limit: Money, spent: Money, requested: Money },
+    PersistenceDisabled,
+    Storage(String),
+}
+
+impl EconomyError {
+    /// Stable machine-readable name, sent to clients next to the message.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EconomyError::InvalidRequest(_) => "invalid_request",
+            EconomyError::InvalidAmount(_) => "invalid_amount",
+            EconomyError::UnknownCurrency(_) => "unknown_currency",
+            EconomyError::CurrencyMismatch { .. } => "currency_mismatch",
+            EconomyError::Overflow => "overflow",
+            EconomyError::UserNotFound(_) => "user_not_found",
+            EconomyError::InsufficientCredits { .. } => "insufficient_credits",
+            EconomyError::TransactionNotFound(_) => "transaction_not_found",
+            EconomyError::AlreadyReversed { .. } => "already_reversed",
+            EconomyError::NotReversible(_) => "not_reversible",
+            EconomyError::Unbalanced(_) => "unbalanced",
+            EconomyError::ModuleNotFound(_) => "module_not_found",
+            EconomyError::PriceMismatch { .. } => "price_mismatch",
+            EconomyError::MissingPrerequisite { .. } => "missing_prerequisite",
+            EconomyError::AlreadyEntitled(_) => "already_entitled",
+            EconomyError::TrialUnavailable(_) => "trial_unavailable",
+            EconomyError::NotEntitled { .. } => "not_entitled",
+            EconomyError::DuplicateRequest { .. } => "duplicate_request",
+            EconomyError::IdempotencyKeyReused(_) => "idempotency_key_reused",
+            EconomyError::SpendingCapExceeded { .. } => "spending_cap_exceeded",
+            EconomyError::PersistenceDisabled => "persistence_disabled",
+            EconomyError::Storage(_) => "storage",
+        }
+    }
+}
+
+impl fmt::Display for EconomyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EconomyError::InvalidRequest(reason) => write!(f, "Invalid request: {}", reason),
+            EconomyError::InvalidAmount(reason) => write!(f, "Invalid amount: {}", reason),
+            EconomyError::UnknownCurrency(code) => write!(f, "Unknown currency '{}'", code),
+            EconomyError::CurrencyMismatch { expected, found } => {
+                write!(f, "Currency mismatch: expected {}, got {}", expected.code(), found.code())
+            }
+            EconomyError::Overflow => write!(f, "Amount out of range"),
+            EconomyError::UserNotFound(user_id) => write!(f, "User '{}' not found", user_id),
+            EconomyError::InsufficientCredits { available, required } => {
+                write!(f, "Insufficient credits: {} available, {} required", available, required)
+            }
+            EconomyError::TransactionNotFound(id) => write!(f, "Transaction '{}' not found", id),
+            EconomyError::AlreadyReversed { original, by } => write!(f, "Transaction '{}' was already undone by '{}'", original, by),
+            EconomyError::NotReversible(reason) => write!(f, "Cannot undo transaction: {}", reason),
+            EconomyError::Unbalanced(reason) => write!(f, "Unbalanced journal entry: {}", reason),
+            EconomyError::ModuleNotFound(name) => write!(f, "Module '{}' is not in the catalog", name),
+            EconomyError::PriceMismatch { module_name, price, expected } => {
+                write!(f, "Module '{}' costs {}, not {}", module_name, price, expected)
+            }
+            EconomyError::MissingPrerequisite { module_name, requires } => {
+                write!(f, "Module '{}' requires '{}'", module_name, requires)
+            }
+            EconomyError::AlreadyEntitled(name) => write!(f, "Module '{}' is already active", name),
+            EconomyError::TrialUnavailable(reason) => write!(f, "Trial unavailable: {}", reason),
+            EconomyError::NotEntitled { user_id, module_name } => {
+                write!(f, "User '{}' has no entitlement to '{}'", user_id, module_name)
+            }
+            EconomyError::DuplicateRequest { key, transaction_id } => {
+                write!(f, "Idempotency key '{}' was already used by transaction '{}'", key, transaction_id)
+            }
+            EconomyError::IdempotencyKeyReused(key) => {
+                write!(f, "Idempotency key '{}' was already used with a different request", key)
+            }
+            EconomyError::SpendingCapExceeded { cap, limit, spent, requested } => {
+                write!(f, "Spending cap {} of {} reached: {} already spent, {} requested", cap, limit, spent, requested)
+            }
+            EconomyError::PersistenceDisabled => write!(f, "Persistence is disabled"),
+            EconomyError::Storage(reason) => write!(f, "Storage error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EconomyError {}
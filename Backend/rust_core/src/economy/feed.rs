@@ -0,0 +1,207 @@
+//! Live feed of committed changes for `GET /api/events`. Every event the engine records
+//! becomes one `FeedEvent`, numbered like the event log, with the transaction it posted
+//! and the balances and entitlements it left behind. The most recent events are kept so
+//! a client that reconnects with the last id it saw misses nothing.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use super::{EconomyEvent, EconomyState, Entitlement, Transaction, UserBalance};
+
+/// Events kept for resuming clients.
+pub const DEFAULT_BACKLOG: usize = 10_000;
+
+/// Events a subscriber may fall behind by before it is dropped; it then reconnects
+/// and catches up from the backlog.
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// An entitlement as `id` left it; `None` once it has been revoked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntitlementChange {
+    pub user_id: String,
+    pub module_name: String,
+    pub entitlement: Option<Entitlement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeedEvent {
+    /// The event's sequence number in the event log
+    pub id: u64,
+    pub transaction: Option<Transaction>,
+    /// Every user account the event moved, after the move
+    pub balances: Vec<UserBalance>,
+    pub entitlements: Vec<EntitlementChange>,
+}
+
+impl FeedEvent {
+    /// What `event`, already applied to `state`, changed.
+    pub fn new(id: u64, event: &EconomyEvent, state: &EconomyState) -> Self {
+        let mut feed_event = FeedEvent { id, transaction: None, balances: Vec::new(), entitlements: Vec::new() };
+        let entitlement_of = |user_id: &str, module_name: &str| EntitlementChange {
+            user_id: user_id.to_string(),
+            module_name: module_name.to_string(),
+            entitlement: state.entitlement(user_id, module_name).cloned(),
+        };
+        match event {
+            EconomyEvent::AutoRenewChanged { user_id, module_name, .. } => {
+                feed_event.entitlements.push(entitlement_of(user_id, module_name));
+            }
+            // `apply` may have renamed a replayed legacy entry, so take it from the journal
+            _ => {
+                let Some(transaction) = state.transactions.last().cloned() else {
+                    return feed_event;
+                };
+                if transaction.is_posted() {
+                    for user_id in transaction.postings.iter().filter_map(|p| p.account.user_id()) {
+                        if !feed_event.balances.iter().any(|b| b.user_id == user_id) {
+                            feed_event.balances.extend(state.user_balance(user_id));
+                        }
+                    }
+                    if let (Some(user_id), Some(module_name)) = (transaction.user_id(), &transaction.module_name) {
+                        feed_event.entitlements.push(entitlement_of(user_id, module_name));
+                    }
+                }
+                feed_event.transaction = Some(transaction);
+            }
+        }
+        feed_event
+    }
+
+    /// Whether `user_id` has a part in this event.
+    pub fn involves(&self, user_id: &str) -> bool {
+        self.balances.iter().any(|b| b.user_id == user_id)
+            || self.entitlements.iter().any(|e| e.user_id == user_id)
+            || self.transaction.as_ref().is_some_and(|t| t.involves(user_id))
+    }
+
+    /// This event as `user_id` may see it: other users' balances and entitlements are
+    /// left out. `None` sees everything.
+    pub fn visible_to(&self, user_id: Option<&str>) -> FeedEvent {
+        let Some(user_id) = user_id else {
+            return self.clone();
+        };
+        FeedEvent {
+            id: self.id,
+            transaction: self.transaction.clone(),
+            balances: self.balances.iter().filter(|b| b.user_id == user_id).cloned().collect(),
+            entitlements: self.entitlements.iter().filter(|e| e.user_id == user_id).cloned().collect(),
+        }
+    }
+}
+
+struct Subscriber {
+    /// `None` receives every user's events
+    user_id: Option<String>,
+    sender: mpsc::Sender<Arc<FeedEvent>>,
+}
+
+struct Inner {
+    last_id: u64,
+    backlog: VecDeque<Arc<FeedEvent>>,
+    subscribers: Vec<Subscriber>,
+}
+
+/// A new subscriber's starting point and its live events.
+pub struct Subscription {
+    /// Events after the id the client resumed from, oldest first
+    pub backlog: Vec<Arc<FeedEvent>>,
+    /// The client resumed from an id no longer (or never) in the backlog and must
+    /// reload its state
+    pub resync: bool,
+    /// Id of the newest event at the time of subscribing
+    pub last_event_id: u64,
+    pub receiver: mpsc::Receiver<Arc<FeedEvent>>,
+}
+
+pub struct EventFeed {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+impl EventFeed {
+    /// A feed whose first event will be `last_id + 1`.
+    pub fn new(last_id: u64, capacity: usize) -> Self {
+        EventFeed { capacity, inner: Mutex::new(Inner { last_id, backlog: VecDeque::new(), subscribers: Vec::new() }) }
+    }
+
+    pub fn last_id(&self) -> u64 {
+        self.inner.lock().unwrap().last_id
+    }
+
+    /// Keeps `event` for resuming clients and sends it to every subscriber it concerns.
+    /// A subscriber that has gone away or fallen too far behind is dropped.
+    pub fn publish(&self, event: FeedEvent) {
+        let event = Arc::new(event);
+        let mut inner = self.inner.lock().unwrap();
+        inner.last_id = event.id;
+        if self.capacity > 0 {
+            if inner.backlog.len() == self.capacity {
+                inner.backlog.pop_front();
+            }
+            inner.backlog.push_back(event.clone());
+        }
+        inner.subscribers.retain(|s| {
+            if s.user_id.as_deref().is_some_and(|u| !event.involves(u)) {
+                return !s.sender.is_closed();
+            }
+            s.sender.try_send(event.clone()).is_ok()
+        });
+    }
+
+    /// Subscribes to the events of `user_id`, or of everyone, starting after `after`
+    /// (or from now).
+    pub fn subscribe(&self, user_id: Option<String>, after: Option<u64>) -> Subscription {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER);
+        let mut inner = self.inner.lock().unwrap();
+        let last_event_id = inner.last_id;
+        let oldest = inner.backlog.front().map_or(last_event_id + 1, |e| e.id);
+        let (backlog, resync) = match after {
+            None => (Vec::new(), false),
+            Some(after) if after > last_event_id || after + 1 < oldest => (Vec::new(), true),
+            Some(after) => {
+                let mine = |e: &&Arc<FeedEvent>| user_id.as_deref().is_none_or(|u| e.involves(u));
+                (inner.backlog.iter().filter(|e| e.id > after).filter(mine).cloned().collect(), false)
+            }
+        };
+        inner.subscribers.push(Subscriber { user_id, sender });
+        Subscription { backlog, resync, last_event_id, receiver }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{EconomyEngine, Money};
+
+    #[test]
+    fn test_feed_resumes_and_filters_by_user() {
+        let engine = EconomyEngine::new().with_event_backlog(3);
+        engine.mint_credits("alice".into(), Money::credits(5000), None).unwrap();
+        let mut live = engine.subscribe_events(Some("bob".into()), None);
+        engine.mint_credits("bob".into(), Money::credits(100), None).unwrap();
+        engine.transfer("alice".into(), "bob".into(), Money::credits(700), None, None).unwrap();
+        engine.unlock_module("alice".into(), "basic_analytics".into(), None, None).unwrap();
+
+        // Bob sees his mint and the transfer, with only his own balance
+        let first = live.receiver.try_recv().unwrap();
+        assert_eq!(first.id, 2);
+        let transfer = live.receiver.try_recv().unwrap();
+        assert_eq!(transfer.balances.len(), 2);
+        let seen = transfer.visible_to(Some("bob"));
+        assert_eq!((seen.balances.len(), seen.balances[0].credits), (1, Money::credits(800)));
+        assert!(live.receiver.try_recv().is_err());
+
+        let resumed = engine.subscribe_events(Some("alice".into()), Some(2));
+        assert_eq!(resumed.backlog.iter().map(|e| e.id).collect::<Vec<_>>(), vec![3, 4]);
+        let unlock = &resumed.backlog[1];
+        assert_eq!(unlock.entitlements[0].module_name, "basic_analytics");
+        assert!(unlock.entitlements[0].entitlement.is_some());
+        assert!(!resumed.resync);
+
+        // Event 1 has left the backlog of three, and 9 has not happened yet
+        assert!(engine.subscribe_events(None, Some(0)).resync);
+        assert!(engine.subscribe_events(None, Some(9)).resync);
+        assert_eq!(engine.subscribe_events(None, Some(4)).last_event_id, 4);
+    }
+}
@@ -0,0 +1,331 @@
+//! Reading the journal back: filtered, cursor-paginated transaction listings, per-user
+//! statements with running balances, and CSV rendering of both.
+//!
+//! Cursors are journal positions, so a page stays stable while new transactions are
+//! appended behind it.
+
+use serde::Serialize;
+
+use super::{AccountId, Currency, EconomyError, EconomyState, Money, Transaction, TransactionStatus, TransactionType};
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Every field narrows the result; `None` matches everything. Amounts compare against
+/// the transaction's gross amount and times are inclusive unix seconds.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionFilter {
+    pub user_id: Option<String>,
+    pub transaction_type: Option<TransactionType>,
+    pub min_amount: Option<Money>,
+    pub max_amount: Option<Money>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub status: Option<TransactionStatus>,
+}
+
+impl TransactionFilter {
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        if let Some(user_id) = &self.user_id {
+            if !transaction.involves(user_id) {
+                return false;
+            }
+        }
+        if self.status.is_some_and(|s| s != transaction.status) {
+            return false;
+        }
+        if self.transaction_type.is_some_and(|t| t != transaction.transaction_type) {
+            return false;
+        }
+        if self.from.is_some_and(|from| transaction.timestamp < from)
+            || self.to.is_some_and(|to| transaction.timestamp > to)
+        {
+            return false;
+        }
+        if self.min_amount.is_some() || self.max_amount.is_some() {
+            let Ok(amount) = transaction.amount() else {
+                return false;
+            };
+            let minor = amount.minor_units();
+            if self.min_amount.is_some_and(|m| minor < m.minor_units())
+                || self.max_amount.is_some_and(|m| minor > m.minor_units())
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Newest first. Pass `next_cursor` back to get the following page.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatementLine {
+    pub transaction_id: String,
+    pub timestamp: u64,
+    pub transaction_type: TransactionType,
+    /// Change to the user's balance
+    pub amount: Money,
+    /// Balance after this line
+    pub balance: Money,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// Oldest first, so `balance` reads as a running total from `opening_balance`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Statement {
+    pub user_id: String,
+    pub opening_balance: Money,
+    pub closing_balance: Money,
+    pub lines: Vec<StatementLine>,
+    pub next_cursor: Option<String>,
+}
+
+fn parse_cursor(cursor: Option<&str>) -> Result<Option<usize>, EconomyError> {
+    cursor
+        .map(|c| c.parse().map_err(|_| EconomyError::InvalidRequest(format!("invalid cursor '{}'", c))))
+        .transpose()
+}
+
+fn page_size(limit: Option<usize>) -> Result<usize, EconomyError> {
+    match limit.unwrap_or(DEFAULT_PAGE_SIZE) {
+        0 => Err(EconomyError::InvalidRequest("limit must be at least 1".to_string())),
+        n => Ok(n.min(MAX_PAGE_SIZE)),
+    }
+}
+
+impl EconomyState {
+    /// Transactions matching `filter`, newest first, starting below `cursor`.
+    pub fn list_transactions(
+        &self,
+        filter: &TransactionFilter,
+        cursor: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<TransactionPage, EconomyError> {
+        let end = parse_cursor(cursor)?.unwrap_or(self.transactions.len()).min(self.transactions.len());
+        let limit = page_size(limit)?;
+
+        let mut transactions = Vec::new();
+        let mut next_cursor = None;
+        for (position, transaction) in self.transactions[..end].iter().enumerate().rev() {
+            if !filter.matches(transaction) {
+                continue;
+            }
+            if transactions.len() == limit {
+                next_cursor = Some((position + 1).to_string());
+                break;
+            }
+            transactions.push(transaction.clone());
+        }
+        Ok(TransactionPage { transactions, next_cursor })
+    }
+
+    /// `user_id`'s posted entries between `from` and `to`, oldest first, starting after `cursor`.
+    pub fn statement(
+        &self,
+        user_id: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+        cursor: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Statement, EconomyError> {
+        let account = AccountId::user(user_id);
+        if !self.accounts.contains_key(&account) {
+            return Err(EconomyError::UserNotFound(user_id.to_string()));
+        }
+        let start = parse_cursor(cursor)?.unwrap_or(0).min(self.transactions.len());
+        let limit = page_size(limit)?;
+
+        // Everything before the page, in or out of the time window, is opening balance
+        let mut balance = Money::zero(Currency::Credits);
+        let mut lines = Vec::new();
+        let mut opening_balance = None;
+        let mut next_cursor = None;
+        for (position, transaction) in self.transactions.iter().enumerate() {
+            let Some(amount) = transaction.amount_for(&account).filter(|_| transaction.is_posted()) else {
+                continue;
+            };
+            let in_page = position >= start
+                && from.is_none_or(|from| transaction.timestamp >= from)
+                && to.is_none_or(|to| transaction.timestamp <= to);
+            if in_page && lines.len() == limit {
+                next_cursor = Some(position.to_string());
+                break;
+            }
+            if in_page && opening_balance.is_none() {
+                opening_balance = Some(balance);
+            }
+            if to.is_some_and(|to| transaction.timestamp > to) {
+                break;
+            }
+            balance = balance.checked_add(amount)?;
+            if in_page {
+                lines.push(StatementLine {
+                    transaction_id: transaction.id.clone(),
+                    timestamp: transaction.timestamp,
+                    transaction_type: transaction.transaction_type,
+                    amount,
+                    balance,
+                    module_name: transaction.module_name.clone(),
+                    memo: transaction.memo.clone(),
+                });
+            }
+        }
+
+        Ok(Statement {
+            user_id: user_id.to_string(),
+            opening_balance: opening_balance.unwrap_or(balance),
+            closing_balance: balance,
+            lines,
+            next_cursor,
+        })
+    }
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break. Text that a
+/// spreadsheet would run as a formula gets a leading `'`; plain numbers such as negative
+/// amounts are left alone.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) && value.parse::<f64>().is_err() {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn csv_row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+    format!("{}\n", fields.join(","))
+}
+
+fn type_name(transaction_type: TransactionType) -> String {
+    transaction_type.name().to_string()
+}
+
+/// One row per posting, so multi-leg entries export without losing detail; one with no
+/// amount for a rejected attempt without postings.
+pub fn transactions_csv(transactions: &[Transaction]) -> String {
+    let mut csv = csv_row(&["id", "timestamp", "type", "account", "amount", "currency", "module_name", "reverses", "memo", "status"]);
+    for transaction in transactions {
+        let transaction_type = type_name(transaction.transaction_type);
+        let timestamp = transaction.timestamp.to_string();
+        let row = |account: &str, amount: &str, currency: &str| {
+            csv_row(&[
+                &transaction.id,
+                &timestamp,
+                &transaction_type,
+                account,
+                amount,
+                currency,
+                transaction.module_name.as_deref().unwrap_or(""),
+                transaction.reverses.as_deref().unwrap_or(""),
+                transaction.memo.as_deref().unwrap_or(""),
+                transaction.status.name(),
+            ])
+        };
+        for posting in &transaction.postings {
+            csv.push_str(&row(&posting.account.to_string(), &posting.amount.amount(), posting.amount.currency().code()));
+        }
+        // A rejected attempt whose amount was unknown has no postings but still gets a row
+        if let (true, Some(user_id)) = (transaction.postings.is_empty(), &transaction.attempt_user_id) {
+            csv.push_str(&row(&AccountId::user(user_id).to_string(), "", ""));
+        }
+    }
+    csv
+}
+
+pub fn statement_csv(statement: &Statement) -> String {
+    let mut csv = csv_row(&["transaction_id", "timestamp", "type", "amount", "balance", "currency", "module_name", "memo"]);
+    for line in &statement.lines {
+        csv.push_str(&csv_row(&[
+            &line.transaction_id,
+            &line.timestamp.to_string(),
+            &type_name(line.transaction_type),
+            &line.amount.amount(),
+            &line.balance.amount(),
+            line.amount.currency().code(),
+            line.module_name.as_deref().unwrap_or(""),
+            line.memo.as_deref().unwrap_or(""),
+        ]));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::EconomyEngine;
+    use super::*;
+
+    fn engine() -> EconomyEngine {
+        let engine = EconomyEngine::new();
+        engine.mint_credits("alice".into(), Money::credits(10000), None).unwrap();
+        engine.mint_credits("bob".into(), Money::credits(500), None).unwrap();
+        engine.unlock_module("alice".into(), "basic_analytics".into(), None, None).unwrap();
+        engine.transfer("alice".into(), "bob".into(), Money::credits(1000), Some("split, \"lunch\"".into()), None).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_filters_and_cursor_pagination() {
+        let engine = engine();
+        let alice = TransactionFilter { user_id: Some("alice".into()), ..Default::default() };
+
+        let first = engine.list_transactions(&alice, None, Some(2)).unwrap();
+        let types: Vec<_> = first.transactions.iter().map(|t| t.transaction_type).collect();
+        assert_eq!(types, vec![TransactionType::Transfer, TransactionType::Unlock]);
+        let second = engine.list_transactions(&alice, first.next_cursor.as_deref(), Some(2)).unwrap();
+        assert_eq!(second.transactions.len(), 1);
+        assert_eq!(second.transactions[0].transaction_type, TransactionType::Mint);
+        assert_eq!(second.next_cursor, None);
+
+        let large = TransactionFilter {
+            min_amount: Some(Money::credits(1000)),
+            max_amount: Some(Money::credits(5000)),
+            ..Default::default()
+        };
+        let page = engine.list_transactions(&large, None, None).unwrap();
+        assert_eq!(page.transactions.len(), 2);
+        let mints = TransactionFilter { transaction_type: Some(TransactionType::Mint), to: Some(0), ..Default::default() };
+        assert!(engine.list_transactions(&mints, None, None).unwrap().transactions.is_empty());
+        assert!(engine.list_transactions(&alice, Some("x"), None).is_err());
+    }
+
+    #[test]
+    fn test_statement_running_balance_and_csv() {
+        let engine = engine();
+        let statement = engine.statement("alice", None, None, None, None).unwrap();
+        let balances: Vec<_> = statement.lines.iter().map(|l| l.balance.amount()).collect();
+        assert_eq!(balances, vec!["100.00", "90.00", "80.00"]);
+        assert_eq!(statement.opening_balance, Money::credits(0));
+        assert_eq!(statement.closing_balance, engine.get_balance("alice").unwrap().credits);
+
+        let page = engine.statement("alice", None, None, None, Some(1)).unwrap();
+        let rest = engine.statement("alice", None, None, page.next_cursor.as_deref(), None).unwrap();
+        assert_eq!(rest.opening_balance, Money::credits(10000));
+        assert_eq!(rest.lines.len(), 2);
+
+        let csv = statement_csv(&statement);
+        assert!(csv.ends_with("transfer,-10.00,80.00,CRD,,\"split, \"\"lunch\"\"\"\n"), "{}", csv);
+        assert!(matches!(engine.statement("nobody", None, None, None, None), Err(EconomyError::UserNotFound(_))));
+
+        // Client text that a spreadsheet would evaluate is defused; amounts stay numbers
+        assert_eq!(csv_field("=HYPERLINK(\"http://x.test\")"), "\"'=HYPERLINK(\"\"http://x.test\"\")\"");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("-1+cmd"), "'-1+cmd");
+        assert_eq!(csv_field("\tx"), "'\tx");
+        assert_eq!(csv_field("-10.00"), "-10.00");
+    }
+}
@@ -0,0 +1,286 @@
+//! Double-entry bookkeeping types.
+//!
+//! Every transaction is a journal entry whose postings sum to zero: credits minted for
+//! a user come out of the system treasury, module purchases move credits into
+//! revenue, and refunds and reversals post the exact opposite of the entry they undo.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::{EconomyError, Grant, Money};
+
+/// A ledger account. Serialized as `user:<id>`, `treasury` or `revenue`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum AccountId {
+    User(String),
+    /// Source of minted credits; its balance is minus the credits in circulation.
+    Treasury,
+    /// Credits spent on modules.
+    Revenue,
+}
+
+impl AccountId {
+    pub fn user(user_id: &str) -> Self {
+        AccountId::User(user_id.to_string())
+    }
+
+    pub fn user_id(&self) -> Option<&str> {
+        match self {
+            AccountId::User(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountId::User(id) => write!(f, "user:{}", id),
+            AccountId::Treasury => write!(f, "treasury"),
+            AccountId::Revenue => write!(f, "revenue"),
+        }
+    }
+}
+
+impl TryFrom<String> for AccountId {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "treasury" => Ok(AccountId::Treasury),
+            "revenue" => Ok(AccountId::Revenue),
+            other => other
+                .strip_prefix("user:")
+                .map(AccountId::user)
+                .ok_or_else(|| format!("unknown account '{}'", other)),
+        }
+    }
+}
+
+impl From<AccountId> for String {
+    fn from(account: AccountId) -> Self {
+        account.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Posting {
+    pub account: AccountId,
+    pub amount: Money, // Change to the account's balance
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionType {
+    Mint,
+    Unlock,
+    Transfer,
+    /// Undoes an unlock and revokes the module.
+    Refund,
+    /// Undoes an erroneous mint.
+    Reversal,
+    /// Free time-limited access to a module.
+    Trial,
+    /// Automatic charge extending a subscription.
+    Renewal,
+    /// Access granted at no charge, e.g. paid for through Stripe.
+    Grant,
+}
+
+impl TransactionType {
+    /// The name used in JSON, CSV and metrics labels.
+    pub fn name(self) -> &'static str {
+        match self {
+            TransactionType::Mint => "mint",
+            TransactionType::Unlock => "unlock",
+            TransactionType::Transfer => "transfer",
+            TransactionType::Refund => "refund",
+            TransactionType::Reversal => "reversal",
+            TransactionType::Trial => "trial",
+            TransactionType::Renewal => "renewal",
+            TransactionType::Grant => "grant",
+        }
+    }
+}
+
+/// Whether an entry moved money. Rejected entries only record an attempt that was
+/// refused, e.g. for exceeding a rate limit or spending cap, and change no balance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    #[default]
+    Posted,
+    Rejected,
+}
+
+impl TransactionStatus {
+    /// The name used in JSON, CSV and query filters.
+    pub fn name(self) -> &'static str {
+        match self {
+            TransactionStatus::Posted => "posted",
+            TransactionStatus::Rejected => "rejected",
+        }
+    }
+}
+
+/// The client's `Idempotency-Key` and a fingerprint of the request it came with, so a
+/// retry is recognised and a reused key with a different request is refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyKey {
+    pub key: String,
+    pub fingerprint: String,
+    /// The API key that sent it; each API key has its own namespace of idempotency keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl IdempotencyKey {
+    /// What the journal indexes the key under. Header values cannot hold a line break,
+    /// so scoped keys never clash with each other or with unscoped ones.
+    pub fn scoped(&self) -> String {
+        match &self.scope {
+            Some(scope) => format!("{}\n{}", scope, self.key),
+            None => self.key.clone(),
+        }
+    }
+}
+
+/// A journal entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: String,
+    pub transaction_type: TransactionType,
+    pub postings: Vec<Posting>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    /// For refunds and reversals: the transaction being undone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverses: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// The module access this entry confers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant: Option<Grant>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency: Option<Box<IdempotencyKey>>,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub status: TransactionStatus,
+    /// For rejected entries: the error `kind` the attempt was refused with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejection: Option<String>,
+    /// For rejected entries: like refusals since the previous entry that were not journaled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppressed: Option<u64>,
+    /// For rejected entries: the user the attempt was for, and the recipient of a
+    /// transfer. Kept apart from the postings, which are empty when the amount was unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempt_user_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempt_counterparty: Option<String>,
+    /// The API key that asked for the entry, where it is recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+}
+
+impl Transaction {
+    pub fn is_posted(&self) -> bool {
+        self.status == TransactionStatus::Posted
+    }
+
+    /// Fails unless the postings share one currency and sum to zero.
+    pub fn check_balanced(&self) -> Result<(), EconomyError> {
+        let first = self
+            .postings
+            .first()
+            .ok_or_else(|| EconomyError::Unbalanced(format!("{} has no postings", self.id)))?;
+        let total = self
+            .postings
+            .iter()
+            .try_fold(Money::zero(first.amount.currency()), |sum, p| sum.checked_add(p.amount))?;
+        if total.minor_units() != 0 {
+            return Err(EconomyError::Unbalanced(format!("{} postings sum to {}", self.id, total)));
+        }
+        Ok(())
+    }
+
+    /// The first user account touched, which owns any module change; for a rejected
+    /// entry, the user the attempt was for.
+    pub fn user_id(&self) -> Option<&str> {
+        self.postings.iter().find_map(|p| p.account.user_id()).or(self.attempt_user_id.as_deref())
+    }
+
+    /// Whether the entry posts to `user_id`'s account or, if rejected, was attempted for
+    /// or towards them.
+    pub fn involves(&self, user_id: &str) -> bool {
+        self.postings.iter().any(|p| p.account.user_id() == Some(user_id))
+            || self.attempt_user_id.as_deref() == Some(user_id)
+            || self.attempt_counterparty.as_deref() == Some(user_id)
+    }
+
+    /// Gross size of the entry: the sum of its positive postings.
+    pub fn amount(&self) -> Result<Money, EconomyError> {
+        let currency = self.postings.first().map_or(super::Currency::Credits, |p| p.amount.currency());
+        self.postings
+            .iter()
+            .filter(|p| p.amount.is_positive())
+            .try_fold(Money::zero(currency), |sum, p| sum.checked_add(p.amount))
+    }
+
+    /// Net change to one account.
+    pub fn amount_for(&self, account: &AccountId) -> Option<Money> {
+        let mut postings = self.postings.iter().filter(|p| &p.account == account);
+        let first = postings.next()?.amount;
+        postings.try_fold(first, |sum, p| sum.checked_add(p.amount).ok())
+    }
+
+    /// The opposite postings, as used by refunds and reversals.
+    pub fn negated_postings(&self) -> Result<Vec<Posting>, EconomyError> {
+        self.postings
+            .iter()
+            .map(|p| Ok(Posting { account: p.account.clone(), amount: p.amount.checked_neg()? }))
+            .collect()
+    }
+}
+
+/// Transaction layout written by event logs before the double-entry ledger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LegacyTransaction {
+    pub id: String,
+    pub user_id: String,
+    pub amount: Money,
+    pub transaction_type: String,
+    pub timestamp: u64,
+}
+
+impl LegacyTransaction {
+    /// The journal entry the old single-balance transaction stood for.
+    pub fn to_entry(&self, module_name: Option<&str>) -> Result<Transaction, EconomyError> {
+        let user = AccountId::user(&self.user_id);
+        let (transaction_type, other) = match module_name {
+            Some(_) => (TransactionType::Unlock, AccountId::Revenue),
+            None => (TransactionType::Mint, AccountId::Treasury),
+        };
+        Ok(Transaction {
+            id: self.id.clone(),
+            transaction_type,
+            postings: vec![
+                Posting { account: other, amount: self.amount.checked_neg()? },
+                Posting { account: user, amount: self.amount },
+            ],
+            module_name: module_name.map(str::to_string),
+            reverses: None,
+            memo: None,
+            grant: None,
+            idempotency: None,
+            timestamp: self.timestamp,
+            status: TransactionStatus::Posted,
+            rejection: None,
+            suppressed: None,
+            attempt_user_id: None,
+            attempt_counterparty: None,
+            actor: None,
+        })
+    }
+}
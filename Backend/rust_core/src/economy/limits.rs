@@ -0,0 +1,111 @@
+//! Spending caps, and the rejected journal entries that record refused attempts.
+//!
+//! Caps are checked inside the engine lock against the posted entries of a sliding
+//! window, so concurrent requests cannot both slip under one. An attempt that would
+//! exceed a cap is journaled with status `rejected` and changes no balance.
+
+use serde::{Deserialize, Serialize};
+
+use super::{AccountId, AmountInput, Currency, EconomyError, EconomyState, Money, Posting, Transaction, TransactionType};
+
+pub const HOUR_SECS: u64 = 3600;
+pub const DAY_SECS: u64 = 86_400;
+
+/// Limits on how fast credits may move; a missing cap is no limit.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpendingCaps {
+    /// Most one user may spend on module unlocks in any 24 hours
+    #[serde(default)]
+    pub unlock_per_user_per_day: Option<AmountInput>,
+    /// Most one API key may mint in any hour
+    #[serde(default)]
+    pub mint_per_key_per_hour: Option<AmountInput>,
+}
+
+impl SpendingCaps {
+    /// Fails if a cap is not a valid credit amount, so a bad config is caught at startup.
+    pub fn validate(&self) -> Result<(), EconomyError> {
+        Self::cap(&self.unlock_per_user_per_day)?;
+        Self::cap(&self.mint_per_key_per_hour)?;
+        Ok(())
+    }
+
+    fn cap(amount: &Option<AmountInput>) -> Result<Option<Money>, EconomyError> {
+        amount.as_ref().map(|a| a.to_money(Currency::Credits)).transpose()
+    }
+
+    pub fn unlock_cap(&self) -> Result<Option<Money>, EconomyError> {
+        Self::cap(&self.unlock_per_user_per_day)
+    }
+
+    pub fn mint_cap(&self) -> Result<Option<Money>, EconomyError> {
+        Self::cap(&self.mint_per_key_per_hour)
+    }
+}
+
+/// A request refused before it reached the engine, e.g. by the rate limiter, to be
+/// journaled as a rejected entry. Fields the request did not carry are left out.
+#[derive(Debug, Clone)]
+pub struct RejectedAttempt {
+    pub transaction_type: TransactionType,
+    pub user_id: String,
+    /// Recipient of a transfer
+    pub counterparty: Option<String>,
+    pub amount: Option<Money>,
+    pub module_name: Option<String>,
+    pub actor: Option<String>,
+    /// Error `kind` the request was answered with
+    pub reason: String,
+    /// Like attempts refused since the last one journaled, recorded with this one
+    pub suppressed: u64,
+}
+
+impl EconomyState {
+    /// Gross amount of the posted entries stamped after `since` that `counts` selects.
+    /// Scans back from the newest entry, so the cost is the size of the window.
+    pub fn spent_since(&self, since: u64, counts: impl Fn(&Transaction) -> bool) -> Result<Money, EconomyError> {
+        let mut spent = Money::zero(Currency::Credits);
+        for transaction in self.transactions.iter().rev().take_while(|t| t.timestamp > since) {
+            if transaction.is_posted() && counts(transaction) {
+                spent = spent.checked_add(transaction.amount()?)?;
+            }
+        }
+        Ok(spent)
+    }
+
+    /// Fails with `SpendingCapExceeded` if `requested` on top of what `counts` selects in
+    /// the last `window` seconds would go over `cap`.
+    pub fn check_cap(
+        &self,
+        name: &str,
+        cap: Money,
+        window: u64,
+        now: u64,
+        requested: Money,
+        counts: impl Fn(&Transaction) -> bool,
+    ) -> Result<(), EconomyError> {
+        let spent = self.spent_since(now.saturating_sub(window), counts)?;
+        if spent.checked_add(requested)?.minor_units() > cap.minor_units() {
+            return Err(EconomyError::SpendingCapExceeded { cap: name.to_string(), limit: cap, spent, requested });
+        }
+        Ok(())
+    }
+}
+
+/// The postings `attempt` would have made; none where its amount is unknown.
+pub(super) fn attempted_postings(attempt: &RejectedAttempt) -> Result<Vec<Posting>, EconomyError> {
+    let user = AccountId::user(&attempt.user_id);
+    let postings = match (attempt.transaction_type, attempt.amount, &attempt.counterparty) {
+        (TransactionType::Mint, Some(amount), _) => {
+            vec![Posting { account: AccountId::Treasury, amount: amount.checked_neg()? }, Posting { account: user, amount }]
+        }
+        (TransactionType::Unlock, Some(amount), _) => {
+            vec![Posting { account: user, amount: amount.checked_neg()? }, Posting { account: AccountId::Revenue, amount }]
+        }
+        (TransactionType::Transfer, Some(amount), Some(to)) => {
+            vec![Posting { account: user, amount: amount.checked_neg()? }, Posting { account: AccountId::user(to), amount }]
+        }
+        _ => Vec::new(),
+    };
+    Ok(postings)
+}
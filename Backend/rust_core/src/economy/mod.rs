@@ -1,100 +1,1017 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use entitlements::apply_grant;
+use std::path::Path;
+use std::sync::Mutex;
+
+mod catalog;
+mod entitlements;
+mod feed;
+mod error;
+mod history;
+mod ledger;
+mod limits;
+mod money;
+mod store;
+
+pub use catalog::{Billing, Catalog, CatalogModule, Tier};
+pub use entitlements::{
+    Entitlement, EntitlementCheck, EntitlementKind, Grant, LapsedEntitlement, RenewalReport, SUBSCRIPTION_PERIOD_SECS,
+};
+
+pub use error::EconomyError;
+pub use history::{statement_csv, transactions_csv, Statement, StatementLine, TransactionFilter, TransactionPage};
+pub use feed::{EntitlementChange, EventFeed, FeedEvent, Subscription, DEFAULT_BACKLOG};
+pub use limits::{RejectedAttempt, SpendingCaps, DAY_SECS, HOUR_SECS};
+pub use ledger::{AccountId, IdempotencyKey, LegacyTransaction, Posting, Transaction, TransactionStatus, TransactionType};
+pub use money::{AmountInput, Currency, Money};
+pub use store::{ConsistencyReport, EventLog, LoggedEvent};
+
+/// A user's view of the ledger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserBalance {
     pub user_id: String,
-    pub credits: f64,
+    pub credits: Money,
     pub unlocked_modules: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {
-    pub id: String,
-    pub user_id: String,
-    pub amount: f64,
-    pub transaction_type: String,
-    pub timestamp: u64,
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountBalance {
+    pub account: AccountId,
+    pub balance: Money,
+}
+
+/// How many entries of one type the journal holds, and their combined gross amount.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransactionTotals {
+    pub transaction_type: TransactionType,
+    pub count: u64,
+    pub amount: Money,
+    /// Rejected attempts of this type, which are not in `count` or `amount`
+    pub rejected: u64,
+}
+
+/// A committed state change. The event log is the source of truth; balances are
+/// whatever replaying it produces.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EconomyEvent {
+    Posted { transaction: Transaction },
+    // Written before the double-entry ledger; replayed as the equivalent entries
+    CreditsMinted { transaction: LegacyTransaction },
+    ModuleUnlocked { module_name: String, transaction: LegacyTransaction },
+    /// Renewal switched on or off; moves no money, so it is not a transaction
+    AutoRenewChanged { user_id: String, module_name: String, auto_renew: bool, timestamp: u64 },
+}
+
+impl EconomyEvent {
+    /// The journal entry this event posts, if it posts one.
+    pub fn transaction(&self) -> Option<Result<Transaction, EconomyError>> {
+        match self {
+            EconomyEvent::Posted { transaction } => Some(Ok(transaction.clone())),
+            EconomyEvent::CreditsMinted { transaction } => Some(transaction.to_entry(None)),
+            EconomyEvent::ModuleUnlocked { module_name, transaction } => Some(transaction.to_entry(Some(module_name))),
+            EconomyEvent::AutoRenewChanged { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EconomyState {
+    pub accounts: HashMap<AccountId, Money>,
+    /// User id -> module name -> the user's access to it, expired or not
+    pub entitlements: HashMap<String, BTreeMap<String, Entitlement>>,
+    pub transactions: Vec<Transaction>,
+    /// Original transaction id -> the refund or reversal that undid it
+    pub reversed_by: HashMap<String, String>,
+    /// Transaction id -> position in `transactions`
+    #[serde(default)]
+    pub positions: HashMap<String, usize>,
+    /// Idempotency key -> the transaction committed under it
+    #[serde(default)]
+    pub idempotency_keys: HashMap<String, String>,
+}
+
+impl EconomyState {
+    pub fn balance(&self, account: &AccountId) -> Money {
+        self.accounts.get(account).copied().unwrap_or(Money::zero(Currency::Credits))
+    }
+
+    pub fn user_balance(&self, user_id: &str) -> Option<UserBalance> {
+        let credits = *self.accounts.get(&AccountId::user(user_id))?;
+        Some(UserBalance {
+            user_id: user_id.to_string(),
+            credits,
+            unlocked_modules: self.active_modules(user_id, now()),
+        })
+    }
+
+    fn active_modules(&self, user_id: &str, now: u64) -> Vec<String> {
+        self.entitlements.get(user_id).map_or_else(Vec::new, |entitlements| {
+            entitlements.values().filter(|e| e.is_active(now)).map(|e| e.module_name.clone()).collect()
+        })
+    }
+
+    pub fn entitlement(&self, user_id: &str, module_name: &str) -> Option<&Entitlement> {
+        self.entitlements.get(user_id)?.get(module_name)
+    }
+
+    pub fn transaction(&self, id: &str) -> Option<&Transaction> {
+        self.positions.get(id).map(|&i| &self.transactions[i])
+    }
+
+    pub fn transaction_for_key(&self, key: &str) -> Option<&Transaction> {
+        self.idempotency_keys.get(key).and_then(|id| self.transaction(id))
+    }
+
+    /// `user_id`'s balance as it stood right after transaction `id`, i.e. what the
+    /// request that posted it originally returned.
+    pub fn user_balance_after(&self, id: &str, user_id: &str) -> Option<UserBalance> {
+        let end = *self.positions.get(id)?;
+        let account = AccountId::user(user_id);
+        let mut credits = Money::zero(Currency::Credits);
+        let mut entitlements = BTreeMap::new();
+        for transaction in self.transactions[..=end].iter().filter(|t| t.is_posted()) {
+            if let Some(amount) = transaction.amount_for(&account) {
+                credits = credits.checked_add(amount).ok()?;
+            }
+            if transaction.user_id() == Some(user_id) {
+                apply_grant(&mut entitlements, transaction);
+            }
+        }
+        let at = self.transactions[end].timestamp;
+        Some(UserBalance {
+            user_id: user_id.to_string(),
+            credits,
+            unlocked_modules: entitlements.values().filter(|e| e.is_active(at)).map(|e| e.module_name.clone()).collect(),
+        })
+    }
+
+    /// The balances `transaction` would leave behind, without changing anything. Fails if
+    /// it is unbalanced, would overdraw a user, or undoes something already undone.
+    pub fn preview(&self, transaction: &Transaction) -> Result<HashMap<AccountId, Money>, EconomyError> {
+        transaction.check_balanced()?;
+        if self.positions.contains_key(&transaction.id) {
+            return Err(EconomyError::InvalidRequest(format!("transaction id '{}' is already taken", transaction.id)));
+        }
+        if let Some(idempotency) = &transaction.idempotency {
+            if let Some(existing) = self.idempotency_keys.get(&idempotency.scoped()) {
+                return Err(EconomyError::DuplicateRequest {
+                    key: idempotency.key.clone(),
+                    transaction_id: existing.clone(),
+                });
+            }
+        }
+        if let Some(original) = &transaction.reverses {
+            if self.transaction(original).is_none() {
+                return Err(EconomyError::TransactionNotFound(original.clone()));
+            }
+            if let Some(by) = self.reversed_by.get(original) {
+                return Err(EconomyError::AlreadyReversed { original: original.clone(), by: by.clone() });
+            }
+        }
+
+        let mut updated: HashMap<AccountId, Money> = HashMap::new();
+        for posting in &transaction.postings {
+            let current = updated.get(&posting.account).copied().unwrap_or_else(|| self.balance(&posting.account));
+            updated.insert(posting.account.clone(), current.checked_add(posting.amount)?);
+        }
+        // Only the system accounts may go below zero
+        for (account, balance) in &updated {
+            if account.user_id().is_some() && balance.is_negative() {
+                let available = self.balance(account);
+                return Err(EconomyError::InsufficientCredits { available, required: available.checked_sub(*balance)? });
+            }
+        }
+        Ok(updated)
+    }
+
+    pub fn apply(&mut self, event: &EconomyEvent) -> Result<(), EconomyError> {
+        let mut transaction = match event.transaction() {
+            Some(transaction) => transaction?,
+            None => return self.apply_auto_renew(event),
+        };
+        // Pre-ledger ids were only second-granular; give later duplicates a unique suffix
+        if !matches!(event, EconomyEvent::Posted { .. }) && self.positions.contains_key(&transaction.id) {
+            transaction.id = format!("{}_{}", transaction.id, self.transactions.len() + 1);
+        }
+        // Kept for audit only: no balance, entitlement or idempotency key changes
+        if !transaction.is_posted() {
+            if self.positions.contains_key(&transaction.id) {
+                return Err(EconomyError::InvalidRequest(format!("transaction id '{}' is already taken", transaction.id)));
+            }
+            self.positions.insert(transaction.id.clone(), self.transactions.len());
+            self.transactions.push(transaction);
+            return Ok(());
+        }
+        let updated = self.preview(&transaction)?;
+        self.accounts.extend(updated);
+
+        if let Some(user_id) = transaction.user_id() {
+            apply_grant(self.entitlements.entry(user_id.to_string()).or_default(), &transaction);
+        }
+        if let Some(original) = &transaction.reverses {
+            self.reversed_by.insert(original.clone(), transaction.id.clone());
+        }
+        if let Some(idempotency) = &transaction.idempotency {
+            self.idempotency_keys.insert(idempotency.scoped(), transaction.id.clone());
+        }
+        self.positions.insert(transaction.id.clone(), self.transactions.len());
+        self.transactions.push(transaction);
+        Ok(())
+    }
+
+    fn apply_auto_renew(&mut self, event: &EconomyEvent) -> Result<(), EconomyError> {
+        if let EconomyEvent::AutoRenewChanged { user_id, module_name, auto_renew, .. } = event {
+            let entitlement = self
+                .entitlements
+                .get_mut(user_id)
+                .and_then(|e| e.get_mut(module_name))
+                .ok_or_else(|| EconomyError::NotEntitled { user_id: user_id.clone(), module_name: module_name.clone() })?;
+            entitlement.auto_renew = *auto_renew;
+        }
+        Ok(())
+    }
+
+    /// Sum of every account; zero whenever the books balance.
+    pub fn trial_balance(&self) -> Result<Money, EconomyError> {
+        self.accounts.values().try_fold(Money::zero(Currency::Credits), |sum, b| sum.checked_add(*b))
+    }
+}
+
+fn now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
 }
 
 pub struct EconomyEngine {
-    balances: Arc<Mutex<HashMap<String, UserBalance>>>,
-    transactions: Arc<Mutex<Vec<Transaction>>>,
+    state: Mutex<EconomyState>,
+    // None for a purely in-memory engine
+    log: Option<Mutex<EventLog>>,
+    catalog: Catalog,
+    caps: SpendingCaps,
+    feed: EventFeed,
 }
 
 impl EconomyEngine {
+    /// In-memory engine with the built-in catalog; state is lost when it is dropped.
     pub fn new() -> Self {
         Self {
-            balances: Arc::new(Mutex::new(HashMap::new())),
-            transactions: Arc::new(Mutex::new(Vec::new())),
+            state: Mutex::new(EconomyState::default()),
+            log: None,
+            catalog: Catalog::builtin(),
+            caps: SpendingCaps::default(),
+            feed: EventFeed::new(0, DEFAULT_BACKLOG),
         }
     }
 
-    pub fn mint_credits(&self, user_id: String, amount: f64) -> Result<UserBalance, String> {
-        let mut balances = self.balances.lock().unwrap();
+    /// Durable engine backed by the event log in `dir`, rebuilt from the latest
+    /// snapshot plus the events after it.
+    pub fn open(dir: impl AsRef<Path>, snapshot_interval: u64) -> Result<Self, String> {
+        let (log, state) = EventLog::open(dir, snapshot_interval)?;
+        Ok(Self {
+            state: Mutex::new(state),
+            feed: EventFeed::new(log.last_seq(), DEFAULT_BACKLOG),
+            log: Some(Mutex::new(log)),
+            catalog: Catalog::builtin(),
+            caps: SpendingCaps::default(),
+        })
+    }
 
-        let balance = balances.entry(user_id.clone()).or_insert(UserBalance {
-            user_id: user_id.clone(),
-            credits: 0.0,
-            unlocked_modules: Vec::new(),
-        });
+    /// Replaces the module catalog. Past transactions are unaffected.
+    pub fn with_catalog(mut self, catalog: Catalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
 
-        balance.credits += amount;
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
 
-        // Record transaction
-        let tx = Transaction {
-            id: format!("tx_{}", chrono::Utc::now().timestamp()),
-            user_id: user_id.clone(),
-            amount,
-            transaction_type: "mint".to_string(),
-            timestamp: chrono::Utc::now().timestamp() as u64,
+    /// Keeps the last `capacity` events for clients resuming `subscribe_events`.
+    pub fn with_event_backlog(mut self, capacity: usize) -> Self {
+        self.feed = EventFeed::new(self.feed.last_id(), capacity);
+        self
+    }
+
+    /// Live events of `user_id`, or of every user, after event id `after` if given.
+    pub fn subscribe_events(&self, user_id: Option<String>, after: Option<u64>) -> Subscription {
+        self.feed.subscribe(user_id, after)
+    }
+
+    /// Sets the spending caps checked by unlocks and mints. Call `SpendingCaps::validate`
+    /// first; a cap that is not a valid amount is ignored.
+    pub fn with_caps(mut self, caps: SpendingCaps) -> Self {
+        self.caps = caps;
+        self
+    }
+
+    /// Validates `transaction`, then makes it durable (fsync) before applying it, so a
+    /// rejected entry or a failed write changes nothing.
+    fn commit(&self, state: &mut EconomyState, transaction: Transaction) -> Result<Transaction, EconomyError> {
+        state.preview(&transaction)?;
+        self.record(state, EconomyEvent::Posted { transaction: transaction.clone() })?;
+        Ok(transaction)
+    }
+
+    /// Appends `event` to the log (if any), applies it and publishes it to the event
+    /// feed. The caller has validated it.
+    fn record(&self, state: &mut EconomyState, event: EconomyEvent) -> Result<(), EconomyError> {
+        let id = if let Some(log) = &self.log {
+            let mut log = log.lock().unwrap();
+            let seq = log.append(&event).map_err(EconomyError::Storage)?;
+            state.apply(&event)?;
+            // The event is already durable; a missed snapshot only slows the next startup
+            if let Err(e) = log.maybe_snapshot(state) {
+                eprintln!("[ECONOMY] Snapshot failed: {}", e);
+            }
+            seq
+        } else {
+            state.apply(&event)?;
+            self.feed.last_id() + 1
         };
+        self.feed.publish(FeedEvent::new(id, &event, state));
+        Ok(())
+    }
+
+    /// Journals `transaction` as rejected with `error`'s kind and hands the error back.
+    /// The audit entry is best effort: failing to write it must not mask the rejection.
+    fn reject(&self, state: &mut EconomyState, mut transaction: Transaction, error: EconomyError) -> EconomyError {
+        transaction.status = TransactionStatus::Rejected;
+        transaction.rejection = Some(error.kind().to_string());
+        transaction.idempotency = None;
+        transaction.grant = None;
+        if let Err(e) = self.record(state, EconomyEvent::Posted { transaction }) {
+            eprintln!("[ECONOMY] Could not journal rejected attempt: {}", e);
+        }
+        error
+    }
+
+    /// A new entry with no optional fields set, stamped `timestamp`.
+    fn entry(state: &EconomyState, transaction_type: TransactionType, postings: Vec<Posting>, timestamp: u64) -> Transaction {
+        Transaction {
+            // Position in the journal keeps ids unique within one second
+            id: format!("tx_{}_{}", timestamp, state.transactions.len() + 1),
+            transaction_type,
+            postings,
+            module_name: None,
+            reverses: None,
+            memo: None,
+            grant: None,
+            idempotency: None,
+            timestamp,
+            status: TransactionStatus::Posted,
+            rejection: None,
+            suppressed: None,
+            attempt_user_id: None,
+            attempt_counterparty: None,
+            actor: None,
+        }
+    }
 
-        self.transactions.lock().unwrap().push(tx);
+    /// Postings moving `amount` from the user to revenue.
+    fn charge(user_id: &str, amount: Money) -> Result<Vec<Posting>, EconomyError> {
+        Ok(vec![
+            Posting { account: AccountId::user(user_id), amount: amount.checked_neg()? },
+            Posting { account: AccountId::Revenue, amount },
+        ])
+    }
 
-        Ok(balance.clone())
+    fn check_user_id(user_id: &str) -> Result<(), EconomyError> {
+        if user_id.trim().is_empty() {
+            return Err(EconomyError::InvalidRequest("user_id must not be empty".to_string()));
+        }
+        Ok(())
     }
 
-    pub fn unlock_module(&self, user_id: String, module_name: String, cost: f64) -> Result<UserBalance, String> {
-        let mut balances = self.balances.lock().unwrap();
+    fn check_user_exists(state: &EconomyState, user_id: &str) -> Result<(), EconomyError> {
+        if !state.accounts.contains_key(&AccountId::user(user_id)) {
+            return Err(EconomyError::UserNotFound(user_id.to_string()));
+        }
+        Ok(())
+    }
 
-        let balance = balances.get_mut(&user_id).ok_or("User not found")?;
+    /// Fails unless every prerequisite of `module` is active for the user.
+    fn check_prerequisites(state: &EconomyState, user_id: &str, module: &CatalogModule, now: u64) -> Result<(), EconomyError> {
+        for required in &module.requires {
+            if !state.entitlement(user_id, required).is_some_and(|e| e.is_active(now)) {
+                return Err(EconomyError::MissingPrerequisite { module_name: module.name.clone(), requires: required.clone() });
+            }
+        }
+        Ok(())
+    }
 
-        if balance.credits < cost {
-            return Err("Insufficient credits".to_string());
+    /// The paid access a purchase of `module` at `now` buys.
+    fn paid_grant(module: &CatalogModule, now: u64) -> Grant {
+        match module.billing {
+            Billing::OneTime => Grant { kind: EntitlementKind::Permanent, expires_at: None, auto_renew: false },
+            Billing::Monthly => Grant {
+                kind: EntitlementKind::Subscription,
+                expires_at: Some(now + SUBSCRIPTION_PERIOD_SECS),
+                auto_renew: true,
+            },
         }
+    }
 
-        balance.credits -= cost;
+    /// Moves `amount` (positive, in credits) from the treasury to the user, creating the account if needed.
+    pub fn mint_credits(
+        &self,
+        user_id: String,
+        amount: Money,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<UserBalance, EconomyError> {
+        self.mint_credits_as(None, user_id, amount, idempotency)
+    }
 
-        if !balance.unlocked_modules.contains(&module_name) {
-            balance.unlocked_modules.push(module_name.clone());
+    /// `mint_credits` on behalf of `actor` (an API key id), which is stamped on the entry
+    /// and held to the hourly mint cap.
+    pub fn mint_credits_as(
+        &self,
+        actor: Option<String>,
+        user_id: String,
+        amount: Money,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<UserBalance, EconomyError> {
+        Self::check_user_id(&user_id)?;
+        if !amount.is_positive() {
+            return Err(EconomyError::InvalidAmount(format!("mint amount must be positive, got {}", amount)));
         }
+        let mut state = self.state.lock().unwrap();
+
+        let postings = vec![
+            Posting { account: AccountId::Treasury, amount: amount.checked_neg()? },
+            Posting { account: AccountId::user(&user_id), amount },
+        ];
+        let now = now();
+        let tx = Transaction { idempotency: idempotency.map(Box::new), actor: actor.clone(), ..Self::entry(&state, TransactionType::Mint, postings, now) };
+        if let (Some(actor), Some(cap)) = (&actor, self.caps.mint_cap().ok().flatten()) {
+            let minted_by_actor = |t: &Transaction| t.transaction_type == TransactionType::Mint && t.actor.as_ref() == Some(actor);
+            if let Err(e) = state.check_cap("mint_per_key_per_hour", cap, HOUR_SECS, now, amount, minted_by_actor) {
+                return Err(self.reject(&mut state, tx, e));
+            }
+        }
+        self.commit(&mut state, tx)?;
+        Ok(state.user_balance(&user_id).unwrap())
+    }
+
+    /// Buys `module_name` at its catalog price: one-time modules are kept forever, monthly
+    /// ones renew automatically. `expected_cost`, if given, must match the catalog price.
+    /// Buying a module that is only on trial ends the trial.
+    pub fn unlock_module(
+        &self,
+        user_id: String,
+        module_name: String,
+        expected_cost: Option<Money>,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<UserBalance, EconomyError> {
+        Self::check_user_id(&user_id)?;
+        let module = self.catalog.get(&module_name)?;
+        if let Some(expected) = expected_cost.filter(|c| *c != module.price) {
+            return Err(EconomyError::PriceMismatch { module_name, price: module.price, expected });
+        }
+        let mut state = self.state.lock().unwrap();
+        Self::check_user_exists(&state, &user_id)?;
+        let now = now();
+        if state
+            .entitlement(&user_id, &module_name)
+            .is_some_and(|e| e.is_active(now) && e.kind != EntitlementKind::Trial)
+        {
+            return Err(EconomyError::AlreadyEntitled(module_name));
+        }
+        Self::check_prerequisites(&state, &user_id, module, now)?;
 
-        // Record transaction
         let tx = Transaction {
-            id: format!("tx_{}", chrono::Utc::now().timestamp()),
-            user_id: user_id.clone(),
-            amount: -cost,
-            transaction_type: format!("unlock_{}", module_name),
-            timestamp: chrono::Utc::now().timestamp() as u64,
+            module_name: Some(module_name),
+            grant: Some(Self::paid_grant(module, now)),
+            idempotency: idempotency.map(Box::new),
+            ..Self::entry(&state, TransactionType::Unlock, Self::charge(&user_id, module.price)?, now)
         };
+        if let Some(cap) = self.caps.unlock_cap().ok().flatten() {
+            let account = AccountId::user(&user_id);
+            let unlocked_by_user = |t: &Transaction| t.transaction_type == TransactionType::Unlock && t.amount_for(&account).is_some();
+            if let Err(e) = state.check_cap("unlock_per_user_per_day", cap, DAY_SECS, now, module.price, unlocked_by_user) {
+                return Err(self.reject(&mut state, tx, e));
+            }
+        }
+        self.commit(&mut state, tx)?;
+        Ok(state.user_balance(&user_id).unwrap())
+    }
 
-        self.transactions.lock().unwrap().push(tx);
+    /// Starts the catalog's free trial of `module_name`, once per user and module. With
+    /// `auto_renew` the trial converts into a paid purchase when it ends.
+    pub fn start_trial(
+        &self,
+        user_id: String,
+        module_name: String,
+        auto_renew: bool,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<Entitlement, EconomyError> {
+        Self::check_user_id(&user_id)?;
+        let module = self.catalog.get(&module_name)?;
+        let days = module
+            .trial_days
+            .ok_or_else(|| EconomyError::TrialUnavailable(format!("'{}' has no trial", module_name)))?;
+        let mut state = self.state.lock().unwrap();
+        Self::check_user_exists(&state, &user_id)?;
+        let now = now();
+        if state.entitlement(&user_id, &module_name).is_some_and(|e| e.is_active(now)) {
+            return Err(EconomyError::AlreadyEntitled(module_name));
+        }
+        let account = AccountId::user(&user_id);
+        if state.transactions.iter().any(|t| {
+            t.transaction_type == TransactionType::Trial
+                && t.module_name.as_deref() == Some(module_name.as_str())
+                && t.amount_for(&account).is_some()
+        }) {
+            return Err(EconomyError::TrialUnavailable(format!("the trial of '{}' was already used", module_name)));
+        }
+        Self::check_prerequisites(&state, &user_id, module, now)?;
 
-        Ok(balance.clone())
+        let grant = Grant {
+            kind: EntitlementKind::Trial,
+            expires_at: Some(now + u64::from(days) * 24 * 60 * 60),
+            auto_renew,
+        };
+        let tx = Transaction {
+            module_name: Some(module_name.clone()),
+            grant: Some(grant),
+            idempotency: idempotency.map(Box::new),
+            ..Self::entry(&state, TransactionType::Trial, Self::charge(&user_id, Money::zero(Currency::Credits))?, now)
+        };
+        self.commit(&mut state, tx)?;
+        Ok(state.entitlement(&user_id, &module_name).cloned().unwrap())
+    }
+
+    /// Grants `module_name` without charging, for access paid for elsewhere (Stripe).
+    /// Permanent unless `days` limits it; never auto-renews.
+    pub fn grant_module(
+        &self,
+        user_id: String,
+        module_name: String,
+        days: Option<u32>,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<Entitlement, EconomyError> {
+        Self::check_user_id(&user_id)?;
+        self.catalog.get(&module_name)?;
+        let mut state = self.state.lock().unwrap();
+        Self::check_user_exists(&state, &user_id)?;
+        let now = now();
+
+        let grant = match days {
+            Some(days) => Grant {
+                kind: EntitlementKind::Subscription,
+                expires_at: Some(now + u64::from(days) * 24 * 60 * 60),
+                auto_renew: false,
+            },
+            None => Grant { kind: EntitlementKind::Permanent, expires_at: None, auto_renew: false },
+        };
+        let tx = Transaction {
+            module_name: Some(module_name.clone()),
+            grant: Some(grant),
+            idempotency: idempotency.map(Box::new),
+            ..Self::entry(&state, TransactionType::Grant, Self::charge(&user_id, Money::zero(Currency::Credits))?, now)
+        };
+        self.commit(&mut state, tx)?;
+        Ok(state.entitlement(&user_id, &module_name).cloned().unwrap())
+    }
+
+    /// Turns automatic renewal of a trial or subscription on or off.
+    pub fn set_auto_renew(&self, user_id: &str, module_name: &str, auto_renew: bool) -> Result<Entitlement, EconomyError> {
+        let mut state = self.state.lock().unwrap();
+        let entitlement = state
+            .entitlement(user_id, module_name)
+            .ok_or_else(|| EconomyError::NotEntitled { user_id: user_id.to_string(), module_name: module_name.to_string() })?;
+        if entitlement.kind == EntitlementKind::Permanent {
+            return Err(EconomyError::InvalidRequest(format!("'{}' is permanent and never renews", module_name)));
+        }
+        if entitlement.auto_renew != auto_renew {
+            let event = EconomyEvent::AutoRenewChanged {
+                user_id: user_id.to_string(),
+                module_name: module_name.to_string(),
+                auto_renew,
+                timestamp: now(),
+            };
+            self.record(&mut state, event)?;
+        }
+        Ok(state.entitlement(user_id, module_name).cloned().unwrap())
+    }
+
+    /// Charges every auto-renewing trial or subscription that has expired by `now` and
+    /// extends it by one period (from `now` if it lapsed earlier). A renewal that cannot be
+    /// paid switches auto-renewal off, so the entitlement simply stays expired.
+    pub fn renew_due(&self, now: u64) -> RenewalReport {
+        let mut state = self.state.lock().unwrap();
+        let due: Vec<(String, Entitlement)> = state
+            .entitlements
+            .iter()
+            .flat_map(|(user_id, entitlements)| entitlements.values().map(move |e| (user_id.clone(), e.clone())))
+            .filter(|(_, e)| e.auto_renew && !e.is_active(now))
+            .collect();
+
+        let mut report = RenewalReport::default();
+        for (user_id, entitlement) in due {
+            let renewal = self.catalog.get(&entitlement.module_name).and_then(|module| {
+                let mut grant = Self::paid_grant(module, now);
+                if let (Some(expires_at), Some(previous)) = (grant.expires_at.as_mut(), entitlement.expires_at) {
+                    *expires_at = previous.max(now) + SUBSCRIPTION_PERIOD_SECS;
+                }
+                let tx = Transaction {
+                    module_name: Some(entitlement.module_name.clone()),
+                    grant: Some(grant),
+                    ..Self::entry(&state, TransactionType::Renewal, Self::charge(&user_id, module.price)?, now)
+                };
+                self.commit(&mut state, tx)
+            });
+            match renewal {
+                Ok(tx) => report.renewed.push(tx),
+                Err(e) => {
+                    let event = EconomyEvent::AutoRenewChanged {
+                        user_id: user_id.clone(),
+                        module_name: entitlement.module_name.clone(),
+                        auto_renew: false,
+                        timestamp: now,
+                    };
+                    if let Err(e) = self.record(&mut state, event) {
+                        eprintln!("[ECONOMY] Cannot switch off renewal of {} for {}: {}", entitlement.module_name, user_id, e);
+                    }
+                    report.lapsed.push(LapsedEntitlement { user_id, module_name: entitlement.module_name, reason: e.to_string() });
+                }
+            }
+        }
+        report
+    }
+
+    pub fn check_entitlement(&self, user_id: &str, module_name: &str) -> EntitlementCheck {
+        let state = self.state.lock().unwrap();
+        let entitlement = state.entitlement(user_id, module_name).cloned();
+        EntitlementCheck {
+            user_id: user_id.to_string(),
+            module_name: module_name.to_string(),
+            entitled: entitlement.as_ref().is_some_and(|e| e.is_active(now())),
+            entitlement,
+        }
+    }
+
+    /// All of a user's entitlements, expired ones included.
+    pub fn get_entitlements(&self, user_id: &str) -> Vec<Entitlement> {
+        let state = self.state.lock().unwrap();
+        state.entitlements.get(user_id).map_or_else(Vec::new, |e| e.values().cloned().collect())
+    }
+
+    /// Moves `amount` between two users. The recipient's account is created if needed.
+    pub fn transfer(
+        &self,
+        from: String,
+        to: String,
+        amount: Money,
+        memo: Option<String>,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<Transaction, EconomyError> {
+        Self::check_user_id(&from)?;
+        Self::check_user_id(&to)?;
+        if from == to {
+            return Err(EconomyError::InvalidRequest("cannot transfer to the same user".to_string()));
+        }
+        if !amount.is_positive() {
+            return Err(EconomyError::InvalidAmount(format!("transfer amount must be positive, got {}", amount)));
+        }
+        let mut state = self.state.lock().unwrap();
+        Self::check_user_exists(&state, &from)?;
+
+        let postings = vec![
+            Posting { account: AccountId::user(&from), amount: amount.checked_neg()? },
+            Posting { account: AccountId::user(&to), amount },
+        ];
+        let tx = Transaction { memo, idempotency: idempotency.map(Box::new), ..Self::entry(&state, TransactionType::Transfer, postings, now()) };
+        self.commit(&mut state, tx)
+    }
+
+    /// Refunds a module unlock in full and revokes the module.
+    pub fn refund_unlock(
+        &self,
+        transaction_id: &str,
+        reason: Option<String>,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<Transaction, EconomyError> {
+        self.undo(transaction_id, TransactionType::Unlock, TransactionType::Refund, reason, idempotency)
+    }
+
+    /// Reverses an erroneous mint. The user must still hold the minted credits.
+    pub fn reverse_mint(
+        &self,
+        transaction_id: &str,
+        reason: Option<String>,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<Transaction, EconomyError> {
+        self.undo(transaction_id, TransactionType::Mint, TransactionType::Reversal, reason, idempotency)
+    }
+
+    /// Posts the exact opposite of `transaction_id`, referencing it.
+    fn undo(
+        &self,
+        transaction_id: &str,
+        expected: TransactionType,
+        as_type: TransactionType,
+        reason: Option<String>,
+        idempotency: Option<IdempotencyKey>,
+    ) -> Result<Transaction, EconomyError> {
+        let mut state = self.state.lock().unwrap();
+        let original = state
+            .transaction(transaction_id)
+            .ok_or_else(|| EconomyError::TransactionNotFound(transaction_id.to_string()))?;
+        if !original.is_posted() {
+            return Err(EconomyError::NotReversible(format!("{} was rejected and moved no credits", transaction_id)));
+        }
+        if original.transaction_type != expected {
+            return Err(EconomyError::NotReversible(format!(
+                "{} is a {:?} transaction, expected {:?}",
+                transaction_id, original.transaction_type, expected
+            )));
+        }
+
+        let tx = Transaction {
+            module_name: original.module_name.clone(),
+            reverses: Some(transaction_id.to_string()),
+            memo: reason,
+            idempotency: idempotency.map(Box::new),
+            ..Self::entry(&state, as_type, original.negated_postings()?, now())
+        };
+        self.commit(&mut state, tx)
     }
 
     pub fn get_balance(&self, user_id: &str) -> Option<UserBalance> {
-        self.balances.lock().unwrap().get(user_id).cloned()
+        self.state.lock().unwrap().user_balance(user_id)
+    }
+
+    pub fn get_transaction(&self, id: &str) -> Option<Transaction> {
+        self.state.lock().unwrap().transaction(id).cloned()
+    }
+
+    /// The transaction committed under idempotency `key` (in its API key's namespace), if any.
+    pub fn find_idempotent(&self, key: &IdempotencyKey) -> Option<Transaction> {
+        self.state.lock().unwrap().transaction_for_key(&key.scoped()).cloned()
+    }
+
+    pub fn get_balance_after(&self, transaction_id: &str, user_id: &str) -> Option<UserBalance> {
+        self.state.lock().unwrap().user_balance_after(transaction_id, user_id)
+    }
+
+    pub fn list_transactions(
+        &self,
+        filter: &TransactionFilter,
+        cursor: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<TransactionPage, EconomyError> {
+        self.state.lock().unwrap().list_transactions(filter, cursor, limit)
+    }
+
+    pub fn statement(
+        &self,
+        user_id: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+        cursor: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Statement, EconomyError> {
+        self.state.lock().unwrap().statement(user_id, from, to, cursor, limit)
+    }
+
+    /// Every entry touching `user_id`'s account, or attempted for them, oldest first.
+    pub fn get_user_transactions(&self, user_id: &str) -> Vec<Transaction> {
+        let state = self.state.lock().unwrap();
+        state.transactions.iter().filter(|t| t.involves(user_id)).cloned().collect()
+    }
+
+    /// Journals `attempt`, refused before it reached the engine, as a rejected entry.
+    /// An unlock without an amount is recorded at the module's catalog price.
+    pub fn record_rejected(&self, attempt: RejectedAttempt) -> Result<Transaction, EconomyError> {
+        let mut attempt = attempt;
+        if attempt.transaction_type == TransactionType::Unlock && attempt.amount.is_none() {
+            attempt.amount = attempt.module_name.as_deref().and_then(|m| self.catalog.get(m).ok()).map(|m| m.price);
+        }
+        let postings = limits::attempted_postings(&attempt)?;
+        let mut state = self.state.lock().unwrap();
+        let tx = Transaction {
+            module_name: attempt.module_name,
+            actor: attempt.actor,
+            status: TransactionStatus::Rejected,
+            rejection: Some(attempt.reason),
+            suppressed: (attempt.suppressed > 0).then_some(attempt.suppressed),
+            attempt_user_id: Some(attempt.user_id),
+            attempt_counterparty: attempt.counterparty,
+            ..Self::entry(&state, attempt.transaction_type, postings, now())
+        };
+        self.record(&mut state, EconomyEvent::Posted { transaction: tx.clone() })?;
+        Ok(tx)
     }
 
     pub fn get_transaction_count(&self) -> usize {
-        self.transactions.lock().unwrap().len()
+        self.state.lock().unwrap().transactions.len()
+    }
+
+    /// Entry count and gross amount per transaction type, in order of first appearance.
+    pub fn totals_by_type(&self) -> Result<Vec<TransactionTotals>, EconomyError> {
+        let state = self.state.lock().unwrap();
+        let mut totals: Vec<TransactionTotals> = Vec::new();
+        for transaction in &state.transactions {
+            let index = match totals.iter().position(|t| t.transaction_type == transaction.transaction_type) {
+                Some(index) => index,
+                None => {
+                    let zero = Money::zero(Currency::Credits);
+                    totals.push(TransactionTotals { transaction_type: transaction.transaction_type, count: 0, amount: zero, rejected: 0 });
+                    totals.len() - 1
+                }
+            };
+            let total = &mut totals[index];
+            if transaction.is_posted() {
+                total.count += 1;
+                total.amount = total.amount.checked_add(transaction.amount()?)?;
+            } else {
+                total.rejected += 1;
+            }
+        }
+        Ok(totals)
     }
 
     pub fn get_all_balances(&self) -> Vec<UserBalance> {
-        self.balances.lock().unwrap().values().cloned().collect()
+        let state = self.state.lock().unwrap();
+        state.accounts.keys().filter_map(|a| a.user_id()).filter_map(|u| state.user_balance(u)).collect()
+    }
+
+    /// Every account, system ones included, ordered by account id.
+    pub fn get_accounts(&self) -> Vec<AccountBalance> {
+        let state = self.state.lock().unwrap();
+        let mut accounts: Vec<AccountBalance> = state
+            .accounts
+            .iter()
+            .map(|(account, balance)| AccountBalance { account: account.clone(), balance: *balance })
+            .collect();
+        accounts.sort_by(|a, b| a.account.cmp(&b.account));
+        accounts
+    }
+
+    /// Replays the log and checks it against the snapshot and the live state. The state
+    /// is copied first, so commits are only held up for the copy, not the replay.
+    pub fn verify_storage(&self) -> Result<ConsistencyReport, EconomyError> {
+        let log = self.log.as_ref().ok_or(EconomyError::PersistenceDisabled)?;
+        let (dir, live, last_seq) = {
+            let state = self.state.lock().unwrap();
+            let log = log.lock().unwrap();
+            (log.dir().to_path_buf(), state.clone(), log.last_seq())
+        };
+        Ok(store::verify_log(&dir, &live, last_seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine_with(user: &str, minor: i64) -> EconomyEngine {
+        let engine = EconomyEngine::new();
+        engine.mint_credits(user.into(), Money::credits(minor), None).unwrap();
+        engine
+    }
+
+    fn last_transaction(engine: &EconomyEngine) -> Transaction {
+        engine.state.lock().unwrap().transactions.last().unwrap().clone()
+    }
+
+    #[test]
+    fn test_every_entry_balances() {
+        let engine = engine_with("alice", 10000);
+        engine.unlock_module("alice".into(), "basic_analytics".into(), None, None).unwrap();
+        engine.transfer("alice".into(), "bob".into(), Money::credits(1000), Some("lunch".into()), None).unwrap();
+
+        let accounts = engine.get_accounts();
+        let balance = |account: AccountId| accounts.iter().find(|a| a.account == account).unwrap().balance;
+        assert_eq!(balance(AccountId::Treasury), Money::credits(-10000));
+        assert_eq!(balance(AccountId::Revenue), Money::credits(1000));
+        assert_eq!(balance(AccountId::user("alice")), Money::credits(8000));
+        assert_eq!(balance(AccountId::user("bob")), Money::credits(1000));
+        assert_eq!(engine.state.lock().unwrap().trial_balance().unwrap(), Money::credits(0));
+
+        let overdraft = engine.transfer("bob".into(), "alice".into(), Money::credits(1001), None, None);
+        assert!(matches!(overdraft, Err(EconomyError::InsufficientCredits { .. })));
+    }
+
+    #[test]
+    fn test_refund_revokes_module_once() {
+        let engine = engine_with("alice", 5000);
+        engine.unlock_module("alice".into(), "basic_analytics".into(), None, None).unwrap();
+        let unlock = last_transaction(&engine);
+
+        let refund = engine.refund_unlock(&unlock.id, Some("duplicate purchase".into()), None).unwrap();
+        assert_eq!(refund.reverses.as_deref(), Some(unlock.id.as_str()));
+        let alice = engine.get_balance("alice").unwrap();
+        assert_eq!(alice.credits, Money::credits(5000));
+        assert!(alice.unlocked_modules.is_empty());
+
+        let again = engine.refund_unlock(&unlock.id, None, None);
+        assert_eq!(again, Err(EconomyError::AlreadyReversed { original: unlock.id.clone(), by: refund.id }));
+    }
+
+    #[test]
+    fn test_reverse_mint_references_original() {
+        let engine = engine_with("alice", 3000);
+        let mint = last_transaction(&engine);
+        assert!(matches!(engine.refund_unlock(&mint.id, None, None), Err(EconomyError::NotReversible(_))));
+
+        engine.transfer("alice".into(), "bob".into(), Money::credits(1), None, None).unwrap();
+        // Alice no longer holds the full mint, so it cannot be taken back
+        assert!(matches!(engine.reverse_mint(&mint.id, None, None), Err(EconomyError::InsufficientCredits { .. })));
+
+        let engine = engine_with("carol", 3000);
+        let mint = last_transaction(&engine);
+        let reversal = engine.reverse_mint(&mint.id, Some("wrong user".into()), None).unwrap();
+        assert_eq!(reversal.transaction_type, TransactionType::Reversal);
+        assert_eq!(reversal.reverses, Some(mint.id));
+        assert_eq!(engine.get_balance("carol").unwrap().credits, Money::credits(0));
+    }
+
+    #[test]
+    fn test_ids_are_unique_and_keys_commit_once() {
+        let engine = EconomyEngine::new();
+        for _ in 0..50 {
+            engine.mint_credits("alice".into(), Money::credits(1), None).unwrap();
+        }
+        let ids: std::collections::HashSet<String> = engine.get_user_transactions("alice").into_iter().map(|t| t.id).collect();
+        assert_eq!(ids.len(), 50);
+
+        let key = IdempotencyKey { key: "k1".into(), fingerprint: "f".into(), scope: Some("api_a".into()) };
+        let tx = engine.transfer("alice".into(), "bob".into(), Money::credits(10), None, Some(key.clone())).unwrap();
+        let again = engine.transfer("alice".into(), "bob".into(), Money::credits(10), None, Some(key.clone()));
+        assert_eq!(again, Err(EconomyError::DuplicateRequest { key: "k1".into(), transaction_id: tx.id.clone() }));
+        assert_eq!(engine.find_idempotent(&key), Some(tx.clone()));
+        let other = IdempotencyKey { scope: Some("api_b".into()), ..key.clone() };
+        assert_eq!(engine.find_idempotent(&other), None);
+        assert_eq!(engine.get_balance_after(&tx.id, "alice").unwrap().credits, Money::credits(40));
+    }
+
+    #[test]
+    fn test_spending_caps_journal_rejected_attempts() {
+        let caps = SpendingCaps {
+            unlock_per_user_per_day: Some(AmountInput::Number(15.0)),
+            mint_per_key_per_hour: Some(AmountInput::Text("100".into())),
+        };
+        let engine = EconomyEngine::new().with_caps(caps);
+        engine.mint_credits_as(Some("k1".into()), "alice".into(), Money::credits(9000), None).unwrap();
+        // Other keys, and mints without a key, have caps of their own
+        engine.mint_credits_as(Some("k2".into()), "alice".into(), Money::credits(5000), None).unwrap();
+        let over = engine.mint_credits_as(Some("k1".into()), "alice".into(), Money::credits(1001), None);
+        assert!(matches!(over, Err(EconomyError::SpendingCapExceeded { ref cap, .. }) if cap == "mint_per_key_per_hour"));
+        let rejected = last_transaction(&engine);
+        assert_eq!((rejected.status, rejected.rejection.as_deref()), (TransactionStatus::Rejected, Some("spending_cap_exceeded")));
+        assert_eq!(rejected.actor.as_deref(), Some("k1"));
+
+        engine.unlock_module("alice".into(), "basic_analytics".into(), None, None).unwrap();
+        let over = engine.unlock_module("alice".into(), "ai_insights".into(), None, None);
+        assert!(matches!(over, Err(EconomyError::SpendingCapExceeded { ref cap, .. }) if cap == "unlock_per_user_per_day"));
+        let rejected = last_transaction(&engine);
+        let alice = engine.get_balance("alice").unwrap();
+        assert_eq!(alice.credits, Money::credits(13000));
+        assert_eq!(alice.unlocked_modules, vec!["basic_analytics".to_string()]);
+        assert!(matches!(engine.refund_unlock(&rejected.id, None, None), Err(EconomyError::NotReversible(_))));
+
+        let limited = RejectedAttempt {
+            transaction_type: TransactionType::Transfer,
+            user_id: "alice".into(),
+            counterparty: Some("bob".into()),
+            amount: Some(Money::credits(100)),
+            module_name: None,
+            actor: Some("k3".into()),
+            reason: "rate_limited".into(),
+            suppressed: 4,
+        };
+        assert_eq!(engine.record_rejected(limited).unwrap().suppressed, Some(4));
+        assert_eq!(engine.get_balance("bob"), None);
+        let rejected = TransactionFilter { status: Some(TransactionStatus::Rejected), ..Default::default() };
+        assert_eq!(engine.list_transactions(&rejected, None, None).unwrap().transactions.len(), 3);
+        let statement = engine.statement("alice", None, None, None, None).unwrap();
+        assert_eq!(statement.lines.len(), 3);
+        let totals = engine.totals_by_type().unwrap();
+        let mints = totals.iter().find(|t| t.transaction_type == TransactionType::Mint).unwrap();
+        assert_eq!((mints.count, mints.rejected, mints.amount), (2, 1, Money::credits(14000)));
+        assert_eq!(engine.state.lock().unwrap().trial_balance().unwrap(), Money::credits(0));
+
+        // No catalog price, so no postings; the entry still belongs to carol
+        let unpriced = RejectedAttempt {
+            transaction_type: TransactionType::Unlock,
+            user_id: "carol".into(),
+            counterparty: None,
+            amount: None,
+            module_name: Some("no_such_module".into()),
+            actor: Some("k3".into()),
+            reason: "rate_limited".into(),
+            suppressed: 0,
+        };
+        let mut feed = engine.subscribe_events(Some("carol".into()), None);
+        let entry = engine.record_rejected(unpriced).unwrap();
+        assert!(entry.postings.is_empty());
+        assert_eq!(feed.receiver.try_recv().unwrap().transaction.as_ref().map(|t| t.id.clone()), Some(entry.id.clone()));
+        let carols = TransactionFilter { user_id: Some("carol".into()), ..Default::default() };
+        assert_eq!(engine.list_transactions(&carols, None, None).unwrap().transactions.len(), 1);
+        assert!(transactions_csv(&[entry]).lines().nth(1).unwrap().contains(",unlock,user:carol,,,no_such_module,"));
     }
 }
 
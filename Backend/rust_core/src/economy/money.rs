@@ -0,0 +1,245 @@
+//! Fixed-point money: an integer count of minor units (cents) plus a currency.
+//!
+//! Amounts never pass through floating point inside the engine. API input is parsed
+//! from its decimal text, and every arithmetic operation is checked for overflow and
+//! currency mismatches.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+use super::EconomyError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Currency {
+    /// Platform credits; every account is held in CRD.
+    #[serde(rename = "CRD")]
+    Credits,
+    #[serde(rename = "EUR")]
+    Eur,
+    #[serde(rename = "USD")]
+    Usd,
+}
+
+impl Currency {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Credits => "CRD",
+            Currency::Eur => "EUR",
+            Currency::Usd => "USD",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Result<Self, EconomyError> {
+        match code.to_ascii_uppercase().as_str() {
+            "CRD" => Ok(Currency::Credits),
+            "EUR" => Ok(Currency::Eur),
+            "USD" => Ok(Currency::Usd),
+            _ => Err(EconomyError::UnknownCurrency(code.to_string())),
+        }
+    }
+
+    /// Decimal places of the minor unit.
+    pub fn exponent(&self) -> u32 {
+        2
+    }
+
+    fn scale(&self) -> i64 {
+        10_i64.pow(self.exponent())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn from_minor(minor: i64, currency: Currency) -> Self {
+        Money { minor, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money { minor: 0, currency }
+    }
+
+    pub fn credits(minor: i64) -> Self {
+        Money::from_minor(minor, Currency::Credits)
+    }
+
+    pub fn minor_units(&self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.minor > 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor < 0
+    }
+
+    /// Parses an exact decimal such as `"12.5"` or `"-0.01"`. More decimal places than
+    /// the currency has, exponents and anything that does not fit in `i64` are rejected.
+    pub fn parse(text: &str, currency: Currency) -> Result<Self, EconomyError> {
+        let invalid = |reason: &str| EconomyError::InvalidAmount(format!("'{}' {}", text, reason));
+        let (negative, digits) = match text.trim().strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.trim()),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid("is not a decimal number"));
+        }
+        if fraction.len() > currency.exponent() as usize {
+            return Err(invalid(&format!("has more than {} decimal places", currency.exponent())));
+        }
+
+        let padded = format!("{:0<width$}", fraction, width = currency.exponent() as usize);
+        let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| EconomyError::Overflow)? };
+        let fraction: i64 = if padded.is_empty() { 0 } else { padded.parse().map_err(|_| invalid("is not a decimal number"))? };
+        let minor = whole
+            .checked_mul(currency.scale())
+            .and_then(|m| m.checked_add(fraction))
+            .ok_or(EconomyError::Overflow)?;
+        Ok(Money::from_minor(if negative { -minor } else { minor }, currency))
+    }
+
+    /// Converts a JSON number via its shortest decimal form, so `0.1` is exactly ten cents.
+    pub fn from_f64(value: f64, currency: Currency) -> Result<Self, EconomyError> {
+        if !value.is_finite() {
+            return Err(EconomyError::InvalidAmount(format!("{} is not a finite number", value)));
+        }
+        Money::parse(&value.to_string(), currency)
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), EconomyError> {
+        if self.currency != other.currency {
+            return Err(EconomyError::CurrencyMismatch { expected: self.currency, found: other.currency });
+        }
+        Ok(())
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, EconomyError> {
+        self.same_currency(&other)?;
+        let minor = self.minor.checked_add(other.minor).ok_or(EconomyError::Overflow)?;
+        Ok(Money::from_minor(minor, self.currency))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, EconomyError> {
+        self.same_currency(&other)?;
+        let minor = self.minor.checked_sub(other.minor).ok_or(EconomyError::Overflow)?;
+        Ok(Money::from_minor(minor, self.currency))
+    }
+
+    pub fn checked_neg(self) -> Result<Money, EconomyError> {
+        let minor = self.minor.checked_neg().ok_or(EconomyError::Overflow)?;
+        Ok(Money::from_minor(minor, self.currency))
+    }
+
+    /// The decimal amount without the currency, e.g. `"-12.50"`.
+    pub fn amount(&self) -> String {
+        let scale = self.currency.scale().unsigned_abs();
+        let abs = self.minor.unsigned_abs();
+        let sign = if self.minor < 0 { "-" } else { "" };
+        format!("{}{}.{:0width$}", sign, abs / scale, abs % scale, width = self.currency.exponent() as usize)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount(), self.currency.code())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MoneyRepr {
+    amount: String,
+    currency: Currency,
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoneyRepr { amount: self.amount(), currency: self.currency }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Exact(MoneyRepr),
+            // Event logs written before fixed-point money stored plain credit floats
+            Legacy(f64),
+        }
+        match Stored::deserialize(deserializer)? {
+            Stored::Exact(repr) => Money::parse(&repr.amount, repr.currency).map_err(serde::de::Error::custom),
+            Stored::Legacy(value) if value.is_finite() => {
+                Ok(Money::credits((value * Currency::Credits.scale() as f64).round() as i64))
+            }
+            Stored::Legacy(value) => Err(serde::de::Error::custom(format!("{} is not a finite amount", value))),
+        }
+    }
+}
+
+/// An amount as clients send it: a JSON number or a decimal string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AmountInput {
+    Number(f64),
+    Text(String),
+}
+
+impl AmountInput {
+    pub fn to_money(&self, currency: Currency) -> Result<Money, EconomyError> {
+        match self {
+            AmountInput::Number(value) => Money::from_f64(*value, currency),
+            AmountInput::Text(text) => Money::parse(text, currency),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_is_exact() {
+        assert_eq!(Money::parse("12.5", Currency::Credits).unwrap().minor_units(), 1250);
+        assert_eq!(Money::parse("-0.01", Currency::Credits).unwrap().minor_units(), -1);
+        assert_eq!(Money::from_f64(0.1, Currency::Credits).unwrap().minor_units(), 10);
+        assert_eq!(Money::credits(-1250).to_string(), "-12.50 CRD");
+
+        assert!(matches!(Money::parse("1.005", Currency::Credits), Err(EconomyError::InvalidAmount(_))));
+        assert!(matches!(Money::parse("1e3", Currency::Credits), Err(EconomyError::InvalidAmount(_))));
+        assert!(matches!(Money::from_f64(f64::NAN, Currency::Credits), Err(EconomyError::InvalidAmount(_))));
+        assert!(matches!(Money::parse("99999999999999999999", Currency::Credits), Err(EconomyError::Overflow)));
+    }
+
+    #[test]
+    fn test_arithmetic_is_checked() {
+        let max = Money::credits(i64::MAX);
+        assert!(matches!(max.checked_add(Money::credits(1)), Err(EconomyError::Overflow)));
+        assert!(matches!(
+            Money::credits(1).checked_add(Money::from_minor(1, Currency::Eur)),
+            Err(EconomyError::CurrencyMismatch { .. })
+        ));
+        assert_eq!(Money::credits(30).checked_sub(Money::credits(10)).unwrap(), Money::credits(20));
+    }
+
+    #[test]
+    fn test_serde_round_trip_and_legacy_floats() {
+        let money = Money::credits(7005);
+        let json = serde_json::to_string(&money).unwrap();
+        assert_eq!(json, r#"{"amount":"70.05","currency":"CRD"}"#);
+        assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), money);
+        assert_eq!(serde_json::from_str::<Money>("70.05").unwrap(), money);
+    }
+}
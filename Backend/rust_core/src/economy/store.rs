@@ -0,0 +1,348 @@
+//! Append-only event log with periodic snapshots.
+//!
+//! `events.log` holds one JSON `LoggedEvent` per line and is only ever appended to;
+//! every append is fsynced before the change is applied. `snapshot.json` holds the
+//! state after `last_seq` events so startup only replays the tail of the log.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use super::{EconomyEvent, EconomyState};
+
+const LOG_FILE: &str = "events.log";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedEvent {
+    pub seq: u64,
+    pub event: EconomyEvent,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    last_seq: u64,
+    state: EconomyState,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConsistencyReport {
+    pub consistent: bool,
+    pub events: u64,
+    pub snapshot_seq: Option<u64>,
+    pub issues: Vec<String>,
+}
+
+pub struct EventLog {
+    dir: PathBuf,
+    file: File,
+    last_seq: u64,
+    snapshot_seq: u64,
+    snapshot_interval: u64,
+    /// Set when a failed append could not be undone; no further appends are made
+    failed: Option<String>,
+}
+
+impl EventLog {
+    /// Opens (or creates) the log in `dir` and rebuilds the state it describes.
+    /// A torn final line from a crash mid-write is cut off; any other bad line is an error.
+    pub fn open(dir: impl AsRef<Path>, snapshot_interval: u64) -> Result<(Self, EconomyState), String> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        let (events, valid_len) = read_log(&dir.join(LOG_FILE))?;
+        // A snapshot is only a cache of the log: if it cannot be read (e.g. it predates
+        // the current state layout), rebuild from the first event instead
+        let snapshot = read_snapshot(&dir).unwrap_or_else(|e| {
+            eprintln!("[ECONOMY] Ignoring snapshot, replaying the full log: {}", e);
+            None
+        });
+
+        let (mut state, snapshot_seq) = match snapshot {
+            Some(s) => (s.state, s.last_seq),
+            None => (EconomyState::default(), 0),
+        };
+        let last_seq = events.last().map_or(0, |e| e.seq);
+        if snapshot_seq > last_seq {
+            return Err(format!("Snapshot is at event {} but the log ends at {}", snapshot_seq, last_seq));
+        }
+        for logged in events.iter().filter(|e| e.seq > snapshot_seq) {
+            state.apply(&logged.event).map_err(|e| format!("Event {} cannot be replayed: {}", logged.seq, e))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_FILE))
+            .map_err(|e| format!("Cannot open event log: {}", e))?;
+        if file.metadata().map_err(|e| e.to_string())?.len() > valid_len {
+            eprintln!("[ECONOMY] Discarding torn tail of the event log after {} bytes", valid_len);
+            file.set_len(valid_len).map_err(|e| format!("Cannot truncate event log: {}", e))?;
+        }
+
+        let log = EventLog { dir, file, last_seq, snapshot_seq, snapshot_interval, failed: None };
+        Ok((log, state))
+    }
+
+    /// Sequence number of the newest event in the log.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Appends and fsyncs one event. Returns its sequence number. A failed write is cut
+    /// back off, so an event the caller saw fail is never replayed; if that fails too, the
+    /// log refuses every later append until the server is restarted.
+    pub fn append(&mut self, event: &EconomyEvent) -> Result<u64, String> {
+        if let Some(reason) = &self.failed {
+            return Err(format!("Event log is unusable until restart: {}", reason));
+        }
+        let logged = LoggedEvent { seq: self.last_seq + 1, event: event.clone() };
+        let mut line = serde_json::to_vec(&logged).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+        let len = self.file.metadata().map_err(|e| format!("Event log write failed: {}", e))?.len();
+        if let Err(e) = self.file.write_all(&line).and_then(|_| self.file.sync_data()) {
+            if let Err(undo) = self.file.set_len(len).and_then(|_| self.file.sync_data()) {
+                self.failed = Some(format!("{} (cutting it back failed: {})", e, undo));
+            }
+            return Err(format!("Event log write failed: {}", e));
+        }
+        self.last_seq = logged.seq;
+        Ok(logged.seq)
+    }
+
+    /// Writes a snapshot once `snapshot_interval` events have accumulated since the last one.
+    pub fn maybe_snapshot(&mut self, state: &EconomyState) -> Result<(), String> {
+        if self.snapshot_interval == 0 || self.last_seq - self.snapshot_seq < self.snapshot_interval {
+            return Ok(());
+        }
+        self.write_snapshot(state)
+    }
+
+    /// Atomically replaces the snapshot (write to a temp file, fsync, rename).
+    pub fn write_snapshot(&mut self, state: &EconomyState) -> Result<(), String> {
+        let snapshot = Snapshot { last_seq: self.last_seq, state: state.clone() };
+        let tmp = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let io = |e: std::io::Error| format!("Snapshot write failed: {}", e);
+
+        let mut file = File::create(&tmp).map_err(io)?;
+        serde_json::to_writer(&mut file, &snapshot).map_err(|e| e.to_string())?;
+        file.sync_all().map_err(io)?;
+        fs::rename(&tmp, self.dir.join(SNAPSHOT_FILE)).map_err(io)?;
+        // Persist the rename itself
+        File::open(&self.dir).and_then(|d| d.sync_all()).map_err(io)?;
+
+        self.snapshot_seq = self.last_seq;
+        Ok(())
+    }
+
+    /// Directory the log and snapshot live in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// Replays the log in `dir` from scratch up to event `last_seq` and compares it with the
+/// snapshot and `live`, the state after that event. Needs no lock on the log, so commits
+/// carry on meanwhile; events appended since are left out of the check.
+pub fn verify_log(dir: &Path, live: &EconomyState, last_seq: u64) -> ConsistencyReport {
+    let mut issues = Vec::new();
+    let mut events = match read_log(&dir.join(LOG_FILE)) {
+        Ok((events, _)) => events,
+        Err(e) => {
+            return ConsistencyReport { consistent: false, events: 0, snapshot_seq: None, issues: vec![e] };
+        }
+    };
+    for (expected, logged) in (1..).zip(&events) {
+        if logged.seq != expected {
+            issues.push(format!("Expected event {} but found {}", expected, logged.seq));
+            break;
+        }
+    }
+    let log_end = events.last().map_or(0, |e| e.seq);
+    events.retain(|e| e.seq <= last_seq);
+
+    let snapshot = match read_snapshot(dir) {
+        Ok(s) => s,
+        Err(e) => {
+            issues.push(e);
+            None
+        }
+    };
+
+    let mut replayed = EconomyState::default();
+    let mut applied = 0;
+    let check_snapshot = |replayed: &EconomyState, applied: u64, issues: &mut Vec<String>| {
+        if let Some(s) = snapshot.as_ref().filter(|s| s.last_seq == applied) {
+            if &s.state != replayed {
+                issues.push(format!("Snapshot at event {} does not match the log replayed to that point", s.last_seq));
+            }
+        }
+    };
+    check_snapshot(&replayed, applied, &mut issues);
+    for logged in &events {
+        if let Err(e) = replayed.apply(&logged.event) {
+            issues.push(format!("Event {} cannot be replayed: {}", logged.seq, e));
+        }
+        applied = logged.seq;
+        check_snapshot(&replayed, applied, &mut issues);
+    }
+    // A snapshot after `last_seq` but within the log was written since the copy
+    if let Some(s) = snapshot.as_ref().filter(|s| s.last_seq > log_end) {
+        issues.push(format!("Snapshot is at event {} but the log ends at {}", s.last_seq, log_end));
+    }
+    if &replayed != live {
+        issues.push("Replaying the log does not reproduce the live balances".to_string());
+    }
+    match replayed.trial_balance() {
+        Ok(total) if total.minor_units() != 0 => issues.push(format!("Accounts sum to {} instead of zero", total)),
+        Ok(_) => {}
+        Err(e) => issues.push(format!("Trial balance failed: {}", e)),
+    }
+
+    ConsistencyReport {
+        consistent: issues.is_empty(),
+        events: events.len() as u64,
+        snapshot_seq: snapshot.map(|s| s.last_seq),
+        issues,
+    }
+}
+
+/// Parses the log; returns the events and the byte length of the well-formed prefix.
+fn read_log(path: &Path) -> Result<(Vec<LoggedEvent>, u64), String> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(format!("Cannot read event log: {}", e)),
+    };
+    let mut reader = BufReader::new(file);
+    let mut events = Vec::new();
+    let mut valid_len = 0u64;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line).map_err(|e| format!("Cannot read event log: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        match serde_json::from_str::<LoggedEvent>(line.trim_end()) {
+            Ok(logged) if line.ends_with('\n') => {
+                events.push(logged);
+                valid_len += read as u64;
+            }
+            // Only the unterminated last line can be a torn write
+            _ if !line.ends_with('\n') => break,
+            _ => return Err(format!("Corrupt event log entry after event {}", events.len())),
+        }
+    }
+    Ok((events, valid_len))
+}
+
+fn read_snapshot(dir: &Path) -> Result<Option<Snapshot>, String> {
+    match fs::read(dir.join(SNAPSHOT_FILE)) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| format!("Corrupt snapshot: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Cannot read snapshot: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{EconomyEngine, Money};
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lwas_economy_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_failed_append_is_not_left_behind() {
+        let dir = temp_dir("full");
+        let (mut log, _) = EventLog::open(&dir, 0).unwrap();
+        let event = EconomyEvent::AutoRenewChanged { user_id: "alice".into(), module_name: "vision".into(), auto_renew: true, timestamp: 1 };
+        assert_eq!(log.append(&event).unwrap(), 1);
+
+        // Every write to /dev/full fails, and it cannot be truncated either
+        log.file = OpenOptions::new().append(true).open("/dev/full").unwrap();
+        assert!(log.append(&event).unwrap_err().contains("write failed"));
+        assert_eq!(log.last_seq(), 1);
+        assert!(log.append(&event).unwrap_err().contains("unusable until restart"));
+
+        let (events, _) = read_log(&dir.join(LOG_FILE)).unwrap();
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_balances_survive_restart() {
+        let dir = temp_dir("restart");
+        {
+            let engine = EconomyEngine::open(&dir, 2).unwrap();
+            engine.mint_credits("alice".into(), Money::credits(10000), None).unwrap();
+            engine.unlock_module("alice".into(), "basic_analytics".into(), None, None).unwrap();
+            engine.mint_credits("bob".into(), Money::credits(500), None).unwrap();
+        }
+
+        let engine = EconomyEngine::open(&dir, 2).unwrap();
+        let alice = engine.get_balance("alice").unwrap();
+        assert_eq!(alice.credits, Money::credits(9000));
+        assert_eq!(alice.unlocked_modules, vec!["basic_analytics".to_string()]);
+        assert_eq!(engine.get_transaction_count(), 3);
+
+        let report = engine.verify_storage().unwrap();
+        assert!(report.consistent, "{:?}", report.issues);
+        assert_eq!(report.snapshot_seq, Some(2));
+
+        // A check against a copy taken at event 3 ignores commits made since
+        let copy = engine.state.lock().unwrap().clone();
+        engine.mint_credits("bob".into(), Money::credits(100), None).unwrap();
+        let report = verify_log(&dir, &copy, 3);
+        assert!(report.consistent, "{:?}", report.issues);
+        assert_eq!(report.events, 3);
+    }
+
+    #[test]
+    fn test_torn_tail_is_discarded_and_tampering_detected() {
+        let dir = temp_dir("torn");
+        {
+            let engine = EconomyEngine::open(&dir, 0).unwrap();
+            engine.mint_credits("alice".into(), Money::credits(1000), None).unwrap();
+        }
+        let mut log = OpenOptions::new().append(true).open(dir.join(LOG_FILE)).unwrap();
+        write!(log, "{{\"seq\":2,\"event\":{{\"type\":\"credits_mi").unwrap();
+
+        let engine = EconomyEngine::open(&dir, 0).unwrap();
+        assert_eq!(engine.get_balance("alice").unwrap().credits, Money::credits(1000));
+        engine.mint_credits("alice".into(), Money::credits(100), None).unwrap();
+        assert!(engine.verify_storage().unwrap().consistent);
+
+        let snapshot = Snapshot { last_seq: 1, state: EconomyState::default() };
+        fs::write(dir.join(SNAPSHOT_FILE), serde_json::to_vec(&snapshot).unwrap()).unwrap();
+        let report = engine.verify_storage().unwrap();
+        assert!(!report.consistent);
+        assert!(report.issues[0].contains("Snapshot at event 1"));
+    }
+
+    #[test]
+    fn test_legacy_events_replay_as_journal_entries() {
+        let dir = temp_dir("legacy");
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = concat!(
+            r#"{"seq":1,"event":{"type":"credits_minted","transaction":{"id":"tx_1","user_id":"alice","amount":100.0,"transaction_type":"MINT","timestamp":1}}}"#,
+            "\n",
+            r#"{"seq":2,"event":{"type":"module_unlocked","module_name":"vision","transaction":{"id":"tx_1","user_id":"alice","amount":-30.0,"transaction_type":"UNLOCK","timestamp":1}}}"#,
+            "\n",
+        );
+        fs::write(dir.join(LOG_FILE), legacy).unwrap();
+        // Snapshot in the pre-ledger layout
+        fs::write(dir.join(SNAPSHOT_FILE), r#"{"last_seq":2,"state":{"balances":{},"transactions":[]}}"#).unwrap();
+
+        let engine = EconomyEngine::open(&dir, 0).unwrap();
+        let alice = engine.get_balance("alice").unwrap();
+        assert_eq!(alice.credits, Money::credits(7000));
+        assert_eq!(alice.unlocked_modules, vec!["vision".to_string()]);
+        assert!(engine.verify_storage().unwrap().issues.iter().any(|i| i.starts_with("Corrupt snapshot")));
+    }
+}
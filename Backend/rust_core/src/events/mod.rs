@@ -0,0 +1,106 @@
+//! Server-Sent Events body for `GET /api/events`. Each feed event is sent as a
+//! `transaction` message, then one `balance` message per user account it moved and
+//! one `entitlement` message per entitlement it changed, all with the event's id so a
+//! reconnecting client resumes after the last event it finished.
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::web::Bytes;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{interval_at, Instant, Interval};
+
+use crate::economy::{FeedEvent, Subscription};
+
+/// `Content-Type` of an event stream.
+pub const CONTENT_TYPE: &str = "text/event-stream";
+
+/// A comment this often keeps proxies from closing an idle stream.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// How long a client waits before reconnecting, in milliseconds.
+const RETRY_MILLIS: u64 = 3000;
+
+/// One message in the text/event-stream format.
+fn message(id: Option<u64>, event: &str, data: &impl Serialize) -> Bytes {
+    let mut text = String::new();
+    if let Some(id) = id {
+        text.push_str(&format!("id: {}\n", id));
+    }
+    // Compact JSON has no line breaks, so one data line suffices
+    let data = serde_json::to_string(data).unwrap_or_else(|_| "null".to_string());
+    text.push_str(&format!("event: {}\ndata: {}\n\n", event, data));
+    Bytes::from(text)
+}
+
+/// The messages of `event` as `user_id` may see them; `None` sees every user.
+pub fn render(event: &FeedEvent, user_id: Option<&str>) -> Bytes {
+    let event = event.visible_to(user_id);
+    let mut out = Vec::new();
+    if let Some(transaction) = &event.transaction {
+        out.extend_from_slice(&message(Some(event.id), "transaction", transaction));
+    }
+    for balance in &event.balances {
+        out.extend_from_slice(&message(Some(event.id), "balance", balance));
+    }
+    for entitlement in &event.entitlements {
+        out.extend_from_slice(&message(Some(event.id), "entitlement", entitlement));
+    }
+    Bytes::from(out)
+}
+
+/// A subscription streamed until the client disconnects or falls too far behind.
+pub struct EventStream {
+    user_id: Option<String>,
+    pending: VecDeque<Bytes>,
+    receiver: mpsc::Receiver<Arc<FeedEvent>>,
+    keep_alive: Interval,
+}
+
+impl EventStream {
+    /// Opens with a `ready` message, carrying `resync: true` when the client must
+    /// reload its state first, then the events it missed.
+    pub fn new(subscription: Subscription, user_id: Option<String>, resumed: bool) -> Self {
+        let ready = serde_json::json!({"last_event_id": subscription.last_event_id, "resync": subscription.resync});
+        // A clean resume keeps the client's own last id until the backlog is through
+        let ready_id = (!resumed || subscription.resync).then_some(subscription.last_event_id);
+        let mut pending = VecDeque::from([Bytes::from(format!("retry: {}\n\n", RETRY_MILLIS)), message(ready_id, "ready", &ready)]);
+        pending.extend(subscription.backlog.iter().map(|e| render(e, user_id.as_deref())));
+        EventStream {
+            user_id,
+            pending,
+            receiver: subscription.receiver,
+            keep_alive: interval_at(Instant::now() + KEEP_ALIVE, KEEP_ALIVE),
+        }
+    }
+}
+
+impl MessageBody for EventStream {
+    type Error = Infallible;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(bytes) = this.pending.pop_front() {
+            return Poll::Ready(Some(Ok(bytes)));
+        }
+        match this.receiver.poll_recv(cx) {
+            Poll::Ready(Some(event)) => return Poll::Ready(Some(Ok(render(&event, this.user_id.as_deref())))),
+            // Dropped by the feed for falling behind; the client reconnects and resumes
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => {}
+        }
+        if this.keep_alive.poll_tick(cx).is_ready() {
+            return Poll::Ready(Some(Ok(Bytes::from_static(b": keep-alive\n\n"))));
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,543 @@
+//! The C API of the `rust_core` shared library, declared in `include/rust_core.h`.
+//!
+//! Conventions, for every `lwas_*` function:
+//! - the return value is an `LwasStatus` (0 is success); nothing is signalled in-band
+//! - results are written through out-pointers, left untouched on failure unless the
+//!   function documents otherwise
+//! - strings are NUL-terminated UTF-8 and are only borrowed for the call
+//! - the message for the last failure on the calling thread is available from
+//!   `lwas_last_error`
+//! - panics are caught and reported as `LWAS_STATUS_INTERNAL`, never unwound into C
+//!
+//! `LWAS_ABI_VERSION` is bumped whenever a signature or struct layout changes.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::slice;
+
+use crate::economy::{EconomyEngine, EconomyError, Money};
+use crate::scribe::{self, LedgerFault};
+use crate::{sign_cycle, CycleError};
+
+pub const LWAS_ABI_VERSION: u32 = 1;
+
+/// Length of a hex SHA-512 plus its NUL terminator.
+pub const LWAS_HASH_LEN: usize = 129;
+/// Capacity of `LwasLedgerReport::reason`, NUL included.
+pub const LWAS_REASON_LEN: usize = 256;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LwasStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    InvalidJson = 3,
+    InvalidArgument = 4,
+    BufferTooSmall = 5,
+    LedgerIo = 6,
+    LedgerCorrupt = 7,
+    NotFound = 8,
+    InsufficientCredits = 9,
+    Conflict = 10,
+    Storage = 11,
+    Internal = 12,
+}
+
+/// Result of signing one cycle.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LwasCycleResult {
+    pub index: f64,
+    /// Position of the record in the ledger
+    pub seq: u64,
+    /// Hex SHA-512 of the record, NUL-terminated
+    pub hash: [c_char; LWAS_HASH_LEN],
+}
+
+/// Result of verifying a ledger. `first_bad_line` is 0 when `valid` is set or the file could not be opened.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LwasLedgerReport {
+    pub valid: bool,
+    pub records: u64,
+    pub legacy_lines: u64,
+    pub first_bad_line: u64,
+    /// Why the first bad line failed, NUL-terminated; empty when valid
+    pub reason: [c_char; LWAS_REASON_LEN],
+}
+
+/// Opaque handle to an `EconomyEngine`; free it with `lwas_economy_free`.
+pub struct LwasEconomy {
+    engine: EconomyEngine,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+fn fail(status: LwasStatus, message: impl Into<String>) -> LwasStatus {
+    LAST_ERROR.with(|e| *e.borrow_mut() = message.into());
+    status
+}
+
+/// Runs `body`, turning a panic into `LWAS_STATUS_INTERNAL`.
+fn guard(body: impl FnOnce() -> LwasStatus) -> LwasStatus {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|_| fail(LwasStatus::Internal, "panic inside rust_core"))
+}
+
+/// Copies `value` into a fixed C buffer, truncating at a char boundary if needed.
+fn copy_str(value: &str, out: &mut [c_char]) {
+    let mut end = value.len().min(out.len() - 1);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    for (slot, byte) in out.iter_mut().zip(&value.as_bytes()[..end]) {
+        *slot = *byte as c_char;
+    }
+    out[end] = 0;
+}
+
+/// # Safety
+/// `ptr` must be null or a valid NUL-terminated string.
+unsafe fn borrow_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, LwasStatus> {
+    if ptr.is_null() {
+        return Err(fail(LwasStatus::NullPointer, format!("{} is null", name)));
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().map_err(|_| fail(LwasStatus::InvalidUtf8, format!("{} is not UTF-8", name)))
+}
+
+/// # Safety
+/// `ptr` must be null or a valid NUL-terminated string. Null means the default ledger path.
+unsafe fn ledger_path_arg(ptr: *const c_char) -> Result<PathBuf, LwasStatus> {
+    if ptr.is_null() {
+        return Ok(scribe::ledger_path());
+    }
+    unsafe { borrow_str(ptr, "ledger_path") }.map(PathBuf::from)
+}
+
+fn economy_status(error: &EconomyError) -> LwasStatus {
+    let status = match error {
+        EconomyError::UserNotFound(_)
+        | EconomyError::TransactionNotFound(_)
+        | EconomyError::ModuleNotFound(_)
+        | EconomyError::NotEntitled { .. } => LwasStatus::NotFound,
+        EconomyError::InsufficientCredits { .. } => LwasStatus::InsufficientCredits,
+        EconomyError::AlreadyReversed { .. }
+        | EconomyError::NotReversible(_)
+        | EconomyError::DuplicateRequest { .. }
+        | EconomyError::IdempotencyKeyReused(_)
+        | EconomyError::PriceMismatch { .. }
+        | EconomyError::MissingPrerequisite { .. }
+        | EconomyError::AlreadyEntitled(_)
+        | EconomyError::TrialUnavailable(_) => LwasStatus::Conflict,
+        EconomyError::PersistenceDisabled | EconomyError::Storage(_) => LwasStatus::Storage,
+        EconomyError::Unbalanced(_) => LwasStatus::Internal,
+        _ => LwasStatus::InvalidArgument,
+    };
+    fail(status, format!("{}: {}", error.kind(), error))
+}
+
+fn cycle_result(cycle: &crate::SignedCycle) -> LwasCycleResult {
+    let mut result = LwasCycleResult { index: cycle.index, seq: cycle.record.seq, hash: [0; LWAS_HASH_LEN] };
+    copy_str(&cycle.record.hash, &mut result.hash);
+    result
+}
+
+fn cycle_status(error: CycleError) -> LwasStatus {
+    match error {
+        CycleError::InvalidPayload(reason) => fail(LwasStatus::InvalidJson, reason),
+        CycleError::Ledger(reason) => fail(LwasStatus::LedgerIo, reason),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lwas_abi_version() -> u32 {
+    LWAS_ABI_VERSION
+}
+
+/// Copies the calling thread's last error message into `buf` (NUL-terminated, truncated
+/// to `len`). Returns the buffer size the full message needs, NUL included.
+///
+/// # Safety
+/// `buf` must be null or point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_last_error(buf: *mut c_char, len: usize) -> usize {
+    LAST_ERROR.with(|e| {
+        let message = e.borrow();
+        if !buf.is_null() && len > 0 {
+            copy_str(&message, unsafe { slice::from_raw_parts_mut(buf, len) });
+        }
+        message.len() + 1
+    })
+}
+
+/// Scores one JSON `InputPayload` and appends it to the ledger at `ledger_path`
+/// (null for `LWAS_LEDGER_PATH` / `sovereign.ledger`).
+///
+/// # Safety
+/// `payload` must point to `len` readable bytes, `ledger_path` must be null or a valid
+/// C string, and `out` must point to a writable `LwasCycleResult`.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_sign_cycle(
+    payload: *const u8,
+    len: usize,
+    ledger_path: *const c_char,
+    out: *mut LwasCycleResult,
+) -> LwasStatus {
+    guard(|| {
+        if payload.is_null() || out.is_null() {
+            return fail(LwasStatus::NullPointer, "payload and out must not be null");
+        }
+        let path = match unsafe { ledger_path_arg(ledger_path) } {
+            Ok(path) => path,
+            Err(status) => return status,
+        };
+        match sign_cycle(unsafe { slice::from_raw_parts(payload, len) }, &path) {
+            Ok(cycle) => {
+                unsafe { *out = cycle_result(&cycle) };
+                LwasStatus::Ok
+            }
+            Err(e) => cycle_status(e),
+        }
+    })
+}
+
+/// Signs every payload of a JSON array, in order, into one ledger. `out` receives one
+/// result per payload and `*written` how many were signed; on failure the payloads
+/// before the failing one stay signed. Fails up front with `LWAS_STATUS_BUFFER_TOO_SMALL`
+/// (and `*written` set to the count needed) if `out_len` is too short.
+///
+/// # Safety
+/// `payloads` must point to `len` readable bytes, `ledger_path` must be null or a valid
+/// C string, `out` must point to `out_len` writable results and `written` to a `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_sign_cycles_batch(
+    payloads: *const u8,
+    len: usize,
+    ledger_path: *const c_char,
+    out: *mut LwasCycleResult,
+    out_len: usize,
+    written: *mut usize,
+) -> LwasStatus {
+    guard(|| {
+        if payloads.is_null() || written.is_null() || (out.is_null() && out_len > 0) {
+            return fail(LwasStatus::NullPointer, "payloads, out and written must not be null");
+        }
+        unsafe { *written = 0 };
+        let path = match unsafe { ledger_path_arg(ledger_path) } {
+            Ok(path) => path,
+            Err(status) => return status,
+        };
+        let batch: Vec<&serde_json::value::RawValue> = match serde_json::from_slice(unsafe { slice::from_raw_parts(payloads, len) }) {
+            Ok(batch) => batch,
+            Err(e) => return fail(LwasStatus::InvalidJson, format!("expected a JSON array of payloads: {}", e)),
+        };
+        if batch.len() > out_len {
+            unsafe { *written = batch.len() };
+            return fail(LwasStatus::BufferTooSmall, format!("{} payloads but room for {} results", batch.len(), out_len));
+        }
+        // `out` may be null here, which `from_raw_parts_mut` does not allow even for no results
+        if batch.is_empty() {
+            return LwasStatus::Ok;
+        }
+        let results = unsafe { slice::from_raw_parts_mut(out, out_len) };
+        for (position, raw) in batch.iter().enumerate() {
+            match sign_cycle(raw.get().as_bytes(), &path) {
+                Ok(cycle) => {
+                    results[position] = cycle_result(&cycle);
+                    unsafe { *written = position + 1 };
+                }
+                Err(e) => {
+                    let status = cycle_status(e);
+                    let message = LAST_ERROR.with(|e| e.borrow().clone());
+                    return fail(status, format!("payload {}: {}", position, message));
+                }
+            }
+        }
+        LwasStatus::Ok
+    })
+}
+
+/// Verifies the whole ledger at `ledger_path` (null for the default path). Returns
+/// `LWAS_STATUS_OK` when it verifies, `LWAS_STATUS_LEDGER_CORRUPT` when a line fails and
+/// `LWAS_STATUS_LEDGER_IO` when it cannot be read; `out` is filled in every case.
+///
+/// # Safety
+/// `ledger_path` must be null or a valid C string and `out` must point to a writable
+/// `LwasLedgerReport`.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_verify_ledger(ledger_path: *const c_char, out: *mut LwasLedgerReport) -> LwasStatus {
+    guard(|| {
+        if out.is_null() {
+            return fail(LwasStatus::NullPointer, "out must not be null");
+        }
+        let path = match unsafe { ledger_path_arg(ledger_path) } {
+            Ok(path) => path,
+            Err(status) => return status,
+        };
+        let report = scribe::verify_ledger(&path);
+        let mut result = LwasLedgerReport {
+            valid: report.valid,
+            records: report.records,
+            legacy_lines: report.legacy_lines,
+            first_bad_line: report.first_fault.as_ref().map_or(0, |f| f.line as u64),
+            reason: [0; LWAS_REASON_LEN],
+        };
+        let status = match &report.first_fault {
+            None => LwasStatus::Ok,
+            Some(first) => {
+                copy_str(&first.fault.to_string(), &mut result.reason);
+                let status = if matches!(first.fault, LedgerFault::Unreadable { .. }) { LwasStatus::LedgerIo } else { LwasStatus::LedgerCorrupt };
+                fail(status, report.to_string())
+            }
+        };
+        unsafe { *out = result };
+        status
+    })
+}
+
+/// An in-memory economy with the built-in catalog. Never returns null.
+#[no_mangle]
+pub extern "C" fn lwas_economy_new() -> *mut LwasEconomy {
+    Box::into_raw(Box::new(LwasEconomy { engine: EconomyEngine::new() }))
+}
+
+/// Opens (or creates) a persistent economy in `data_dir`, snapshotting every
+/// `snapshot_interval` events (0 for never).
+///
+/// # Safety
+/// `data_dir` must be a valid C string and `out` must point to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_economy_open(
+    data_dir: *const c_char,
+    snapshot_interval: u64,
+    out: *mut *mut LwasEconomy,
+) -> LwasStatus {
+    guard(|| {
+        if out.is_null() {
+            return fail(LwasStatus::NullPointer, "out must not be null");
+        }
+        let dir = match unsafe { borrow_str(data_dir, "data_dir") } {
+            Ok(dir) => dir,
+            Err(status) => return status,
+        };
+        match EconomyEngine::open(dir, snapshot_interval) {
+            Ok(engine) => {
+                unsafe { *out = Box::into_raw(Box::new(LwasEconomy { engine })) };
+                LwasStatus::Ok
+            }
+            Err(e) => fail(LwasStatus::Storage, e),
+        }
+    })
+}
+
+/// Frees a handle from `lwas_economy_new` or `lwas_economy_open`. Null is ignored.
+///
+/// # Safety
+/// `economy` must be null or a handle that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_economy_free(economy: *mut LwasEconomy) {
+    if !economy.is_null() {
+        drop(unsafe { Box::from_raw(economy) });
+    }
+}
+
+/// # Safety
+/// `economy` must be null or a live handle.
+unsafe fn engine<'a>(economy: *const LwasEconomy) -> Result<&'a EconomyEngine, LwasStatus> {
+    unsafe { economy.as_ref() }.map(|e| &e.engine).ok_or_else(|| fail(LwasStatus::NullPointer, "economy is null"))
+}
+
+/// Mints `amount_minor` hundredths of a credit for `user_id`; `*balance_minor` receives
+/// the new balance (`balance_minor` may be null).
+///
+/// # Safety
+/// `economy` must be a live handle, `user_id` a valid C string and `balance_minor` null
+/// or writable.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_economy_mint(
+    economy: *const LwasEconomy,
+    user_id: *const c_char,
+    amount_minor: i64,
+    balance_minor: *mut i64,
+) -> LwasStatus {
+    guard(|| {
+        let (engine, user_id) = match unsafe { (engine(economy), borrow_str(user_id, "user_id")) } {
+            (Ok(engine), Ok(user_id)) => (engine, user_id),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+        match engine.mint_credits(user_id.to_string(), Money::credits(amount_minor), None) {
+            Ok(balance) => {
+                if let Some(out) = unsafe { balance_minor.as_mut() } {
+                    *out = balance.credits.minor_units();
+                }
+                LwasStatus::Ok
+            }
+            Err(e) => economy_status(&e),
+        }
+    })
+}
+
+/// Buys `module_name` for `user_id` at its catalog price; `*balance_minor` receives the
+/// new balance (`balance_minor` may be null).
+///
+/// # Safety
+/// `economy` must be a live handle, the names valid C strings and `balance_minor` null
+/// or writable.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_economy_unlock(
+    economy: *const LwasEconomy,
+    user_id: *const c_char,
+    module_name: *const c_char,
+    balance_minor: *mut i64,
+) -> LwasStatus {
+    guard(|| {
+        let args = unsafe { (engine(economy), borrow_str(user_id, "user_id"), borrow_str(module_name, "module_name")) };
+        let (engine, user_id, module_name) = match args {
+            (Ok(engine), Ok(user_id), Ok(module_name)) => (engine, user_id, module_name),
+            (Err(status), _, _) | (_, Err(status), _) | (_, _, Err(status)) => return status,
+        };
+        match engine.unlock_module(user_id.to_string(), module_name.to_string(), None, None) {
+            Ok(balance) => {
+                if let Some(out) = unsafe { balance_minor.as_mut() } {
+                    *out = balance.credits.minor_units();
+                }
+                LwasStatus::Ok
+            }
+            Err(e) => economy_status(&e),
+        }
+    })
+}
+
+/// `*balance_minor` receives `user_id`'s balance in hundredths of a credit;
+/// `LWAS_STATUS_NOT_FOUND` for a user the economy has never seen.
+///
+/// # Safety
+/// `economy` must be a live handle, `user_id` a valid C string and `balance_minor` writable.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_economy_balance(
+    economy: *const LwasEconomy,
+    user_id: *const c_char,
+    balance_minor: *mut i64,
+) -> LwasStatus {
+    guard(|| {
+        let (engine, user_id) = match unsafe { (engine(economy), borrow_str(user_id, "user_id")) } {
+            (Ok(engine), Ok(user_id)) => (engine, user_id),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+        let Some(out) = (unsafe { balance_minor.as_mut() }) else {
+            return fail(LwasStatus::NullPointer, "balance_minor must not be null");
+        };
+        match engine.get_balance(user_id) {
+            Some(balance) => {
+                *out = balance.credits.minor_units();
+                LwasStatus::Ok
+            }
+            None => economy_status(&EconomyError::UserNotFound(user_id.to_string())),
+        }
+    })
+}
+
+/// `*entitled` receives whether `user_id` may use `module_name` right now.
+///
+/// # Safety
+/// `economy` must be a live handle, the names valid C strings and `entitled` writable.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_economy_check_entitlement(
+    economy: *const LwasEconomy,
+    user_id: *const c_char,
+    module_name: *const c_char,
+    entitled: *mut bool,
+) -> LwasStatus {
+    guard(|| {
+        let args = unsafe { (engine(economy), borrow_str(user_id, "user_id"), borrow_str(module_name, "module_name")) };
+        let (engine, user_id, module_name) = match args {
+            (Ok(engine), Ok(user_id), Ok(module_name)) => (engine, user_id, module_name),
+            (Err(status), _, _) | (_, Err(status), _) | (_, _, Err(status)) => return status,
+        };
+        let Some(out) = (unsafe { entitled.as_mut() }) else {
+            return fail(LwasStatus::NullPointer, "entitled must not be null");
+        };
+        *out = engine.check_entitlement(user_id, module_name).entitled;
+        LwasStatus::Ok
+    })
+}
+
+/// `*count` receives the number of journal entries.
+///
+/// # Safety
+/// `economy` must be a live handle and `count` writable.
+#[no_mangle]
+pub unsafe extern "C" fn lwas_economy_transaction_count(economy: *const LwasEconomy, count: *mut u64) -> LwasStatus {
+    guard(|| {
+        let engine = match unsafe { engine(economy) } {
+            Ok(engine) => engine,
+            Err(status) => return status,
+        };
+        let Some(out) = (unsafe { count.as_mut() }) else {
+            return fail(LwasStatus::NullPointer, "count must not be null");
+        };
+        *out = engine.get_transaction_count() as u64;
+        LwasStatus::Ok
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    const HEADER: &str = include_str!("../include/rust_core.h");
+
+    #[test]
+    fn test_header_declares_every_export() {
+        let source = include_str!("ffi.rs").to_string() + include_str!("lib.rs") + include_str!("scribe/verifier.rs");
+        let exports: Vec<&str> = source
+            .split("#[no_mangle]\npub ")
+            .skip(1)
+            .filter_map(|rest| rest.split("fn ").nth(1)?.split('(').next())
+            .collect();
+        assert!(exports.len() >= 12, "{:?}", exports);
+        for name in exports {
+            let declared = HEADER.contains(&format!(" {}(", name)) || HEADER.contains(&format!("*{}(", name));
+            assert!(declared, "include/rust_core.h is missing {}", name);
+        }
+        assert!(HEADER.contains(&format!("#define LWAS_ABI_VERSION {}", LWAS_ABI_VERSION)));
+    }
+
+    #[test]
+    fn test_economy_handle_round_trip() {
+        let economy = lwas_economy_new();
+        let alice = CString::new("alice").unwrap();
+        let module = CString::new("basic_analytics").unwrap();
+        let mut balance = 0i64;
+        unsafe {
+            assert_eq!(lwas_economy_mint(economy, alice.as_ptr(), 1500, &mut balance), LwasStatus::Ok);
+            assert_eq!(lwas_economy_unlock(economy, alice.as_ptr(), module.as_ptr(), &mut balance), LwasStatus::Ok);
+            assert_eq!(balance, 500);
+            assert_eq!(lwas_economy_unlock(economy, alice.as_ptr(), module.as_ptr(), &mut balance), LwasStatus::Conflict);
+
+            let mut buf = [0 as c_char; 64];
+            let needed = lwas_last_error(buf.as_mut_ptr(), buf.len());
+            assert!(needed > 1);
+            assert!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap().starts_with("already_entitled"));
+
+            let nobody = CString::new("nobody").unwrap();
+            assert_eq!(lwas_economy_balance(economy, nobody.as_ptr(), &mut balance), LwasStatus::NotFound);
+            assert_eq!(lwas_economy_balance(std::ptr::null(), alice.as_ptr(), &mut balance), LwasStatus::NullPointer);
+            lwas_economy_free(economy);
+        }
+    }
+
+    #[test]
+    fn test_empty_batch_needs_no_results_buffer() {
+        let ledger = std::env::temp_dir().join(format!("lwas_ffi_empty_batch_{}.jsonl", std::process::id()));
+        let ledger = CString::new(ledger.to_str().unwrap()).unwrap();
+        let mut written = 7usize;
+        let status = unsafe { lwas_sign_cycles_batch(b"[]".as_ptr(), 2, ledger.as_ptr(), std::ptr::null_mut(), 0, &mut written) };
+        assert_eq!((status, written), (LwasStatus::Ok, 0));
+    }
+}
@@ -0,0 +1,230 @@
+//! # AkashicChain - Persistent Akashic Records
+//!
+//! `akashic.chain` holds one JSON `AkashicRecord` per line, each linked to the one
+//! before it. Records are fsynced before the seal is returned, and the whole file is
+//! verified when it is opened so a server never extends a broken chain. A torn final
+//! line from a crash mid-write is cut off on open, as in the economy's event log.
+
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::akashic_link::{AkashicLink, AkashicRecord, NeuralMessage};
+
+/// Result of checking a whole chain, first record to last.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChainReport {
+    pub valid: bool,
+    /// Records that verified before the first broken one
+    pub records: u64,
+    /// `record_seal` of the last valid record
+    pub head: Option<String>,
+    /// Index of the first broken record, counting from 0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken_at: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Verifies every link of `records`, in order.
+pub fn verify_chain(records: &[AkashicRecord]) -> ChainReport {
+    let mut previous: Option<&AkashicRecord> = None;
+    for (position, record) in records.iter().enumerate() {
+        if let Err(reason) = AkashicLink::verify_chain_continuity(record, previous) {
+            return ChainReport {
+                valid: false,
+                records: position as u64,
+                head: previous.map(|p| p.record_seal.clone()),
+                broken_at: Some(position),
+                reason: Some(reason),
+            };
+        }
+        previous = Some(record);
+    }
+    ChainReport {
+        valid: true,
+        records: records.len() as u64,
+        head: previous.map(|p| p.record_seal.clone()),
+        broken_at: None,
+        reason: None,
+    }
+}
+
+struct ChainFile {
+    path: PathBuf,
+    file: File,
+}
+
+struct Inner {
+    records: Vec<AkashicRecord>,
+    file: Option<ChainFile>,
+}
+
+/// The chain of sealed decisions, in memory or backed by a file.
+pub struct AkashicChain {
+    inner: Mutex<Inner>,
+}
+
+impl AkashicChain {
+    /// In-memory only; nothing survives a restart.
+    pub fn new() -> Self {
+        AkashicChain { inner: Mutex::new(Inner { records: Vec::new(), file: None }) }
+    }
+
+    /// Opens (or creates) the chain at `path`. Fails if the existing chain does not verify.
+    /// An unterminated last line is a torn write and is truncated away.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let (records, valid_len) = read_chain(&path)?;
+        let report = verify_chain(&records);
+        if !report.valid {
+            return Err(format!(
+                "{}: record {} breaks the chain: {}",
+                path.display(),
+                report.broken_at.unwrap_or_default(),
+                report.reason.unwrap_or_default()
+            ));
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if file.metadata().map_err(|e| format!("{}: {}", path.display(), e))?.len() > valid_len {
+            eprintln!("[AKASHIC] Discarding torn tail of {} after {} bytes", path.display(), valid_len);
+            file.set_len(valid_len).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        Ok(AkashicChain { inner: Mutex::new(Inner { records, file: Some(ChainFile { path, file }) }) })
+    }
+
+    /// Seals `messages` and the decision taken on them as the next record of the chain.
+    pub fn seal(&self, messages: &[NeuralMessage], decision_index: f64, veritas_approved: bool) -> Result<AkashicRecord, String> {
+        if !decision_index.is_finite() {
+            return Err("decision_index must be a finite number".to_string());
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let record = AkashicLink::link_record(inner.records.last(), messages, decision_index, veritas_approved);
+        if let Some(chain) = inner.file.as_mut() {
+            // One write per record, so a crash leaves at most one torn line
+            let mut line = serde_json::to_vec(&record).map_err(|e| e.to_string())?;
+            line.push(b'\n');
+            chain.file
+                .write_all(&line)
+                .and_then(|_| chain.file.sync_data())
+                .map_err(|e| format!("{}: {}", chain.path.display(), e))?;
+        }
+        inner.records.push(record.clone());
+        Ok(record)
+    }
+
+    pub fn records(&self) -> Vec<AkashicRecord> {
+        self.inner.lock().unwrap().records.clone()
+    }
+
+    /// Records whose context seal matches `messages`: the decisions taken on that conversation.
+    pub fn trace(&self, messages: &[NeuralMessage]) -> Vec<AkashicRecord> {
+        let context_seal = AkashicLink::compute_context_seal(messages);
+        let inner = self.inner.lock().unwrap();
+        inner.records.iter().filter(|r| r.context_seal == context_seal).cloned().collect()
+    }
+
+    /// Re-reads the file, if there is one, and verifies it from the first record.
+    pub fn verify(&self) -> ChainReport {
+        let inner = self.inner.lock().unwrap();
+        let Some(chain) = &inner.file else {
+            return verify_chain(&inner.records);
+        };
+        match read_chain(&chain.path) {
+            Ok((records, _)) => verify_chain(&records),
+            Err(reason) => ChainReport { valid: false, records: 0, head: None, broken_at: None, reason: Some(reason) },
+        }
+    }
+}
+
+impl Default for AkashicChain {
+    fn default() -> Self {
+        AkashicChain::new()
+    }
+}
+
+/// Parses the chain; returns the records and the byte length of the well-formed prefix.
+fn read_chain(path: &Path) -> Result<(Vec<AkashicRecord>, u64), String> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let mut reader = BufReader::new(file);
+    let mut records = Vec::new();
+    let mut valid_len = 0u64;
+    let mut line = Vec::new();
+    for index in 1.. {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line).map_err(|e| format!("{}: {}", path.display(), e))?;
+        // Only the unterminated last line can be a torn write
+        if read == 0 || !line.ends_with(b"\n") {
+            break;
+        }
+        valid_len += read as u64;
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_slice(&line).map_err(|e| format!("{}: line {}: {}", path.display(), index, e))?;
+        records.push(record);
+    }
+    Ok((records, valid_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_persists_and_detects_tampering() {
+        let path = std::env::temp_dir().join(format!("lwas_akashic_{}.chain", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let messages = vec![NeuralMessage { role: "user".into(), content: "Rebalance?".into(), timestamp: 1705300000 }];
+
+        let chain = AkashicChain::open(&path).unwrap();
+        chain.seal(&messages, 0.4, true).unwrap();
+        let second = chain.seal(&messages[..0], 0.6, false).unwrap();
+        drop(chain);
+
+        let chain = AkashicChain::open(&path).unwrap();
+        let third = chain.seal(&messages, 0.8, true).unwrap();
+        assert_eq!((third.seq, third.prev_seal.as_str()), (2, second.record_seal.as_str()));
+        assert_eq!(chain.trace(&messages).len(), 2);
+        assert!(chain.verify().valid);
+
+        let tampered = std::fs::read_to_string(&path).unwrap().replacen("\"veritas_approved\":false", "\"veritas_approved\":true", 1);
+        std::fs::write(&path, tampered).unwrap();
+        let report = chain.verify();
+        assert_eq!((report.valid, report.broken_at), (false, Some(1)));
+        assert!(AkashicChain::open(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_torn_tail_is_discarded() {
+        let path = std::env::temp_dir().join(format!("lwas_akashic_torn_{}.chain", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let messages = vec![NeuralMessage { role: "user".into(), content: "Hedge?".into(), timestamp: 1705300000 }];
+        let first = AkashicChain::open(&path).unwrap().seal(&messages, 0.5, true).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"seq\":1,\"prev_seal\":\"{}", first.record_seal).unwrap();
+        drop(file);
+
+        let chain = AkashicChain::open(&path).unwrap();
+        assert_eq!(chain.records().len(), 1);
+        let second = chain.seal(&messages, 0.7, false).unwrap();
+        assert_eq!((second.seq, second.prev_seal.as_str()), (1, first.record_seal.as_str()));
+        assert!(chain.verify().valid);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
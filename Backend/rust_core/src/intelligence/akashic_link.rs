@@ -4,10 +4,14 @@
 //! Every decision is now cryptographically linked to its logical provenance.
 //!
 //! ## Architecture
-//! ```
-//! NeuralBackpack.ts  -->  AkashicLink.rs  -->  sovereign.ledger
+//! ```text
+//! NeuralBackpack.ts  -->  AkashicLink.rs  -->  akashic.chain
 //!    (Memory)              (Seal)              (Immutable Proof)
 //! ```
+//!
+//! Each record's `record_seal` covers its own fields and its predecessor's
+//! `record_seal`, so the records form a chain: editing or removing any one of them
+//! breaks every link after it.
 
 use serde::{Deserialize, Serialize};
 use sha2::{Sha512, Digest};
@@ -20,14 +24,21 @@ pub struct NeuralMessage {
     pub timestamp: u64,
 }
 
+/// `prev_seal` of the first record in a chain
+pub const GENESIS_SEAL: &str =
+    "00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+
 /// The Akashic Record - a sealed snapshot of consciousness at decision time
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct AkashicRecord {
+    pub seq: u64,                  // Position in the chain, from 0
+    pub prev_seal: String,         // record_seal of the previous record
     pub context_seal: String,      // SHA-512 of the last N messages
     pub message_count: usize,      // How many messages were sealed
     pub decision_index: f64,       // The calculated entropy/stability index
     pub veritas_approved: bool,    // Did Veritas validate this?
     pub timestamp: u64,            // Unix timestamp of sealing
+    pub record_seal: String,       // SHA-512 of all of the above
 }
 
 /// The AkashicLink - connects memory to truth
@@ -52,55 +63,87 @@ impl AkashicLink {
         format!("{:x}", hasher.finalize())
     }
 
-    /// Creates a full Akashic Record linking context to decision
+    /// Computes the record seal: every field of the record plus the previous seal
+    pub fn compute_record_seal(record: &AkashicRecord) -> String {
+        let mut hasher = Sha512::new();
+        hasher.update(record.seq.to_be_bytes());
+        hasher.update(record.prev_seal.as_bytes());
+        hasher.update(b"|");
+        hasher.update(record.context_seal.as_bytes());
+        hasher.update(b"|");
+        hasher.update((record.message_count as u64).to_be_bytes());
+        hasher.update(record.decision_index.to_bits().to_be_bytes());
+        hasher.update([record.veritas_approved as u8]);
+        hasher.update(record.timestamp.to_be_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Creates a full Akashic Record linking context to decision, as the first of a chain
     pub fn create_record(
         messages: &[NeuralMessage],
         decision_index: f64,
         veritas_approved: bool
     ) -> AkashicRecord {
-        let seal = Self::compute_context_seal(messages);
-        
-        AkashicRecord {
-            context_seal: seal,
+        Self::link_record(None, messages, decision_index, veritas_approved)
+    }
+
+    /// Creates the record that follows `previous` (or starts a chain)
+    pub fn link_record(
+        previous: Option<&AkashicRecord>,
+        messages: &[NeuralMessage],
+        decision_index: f64,
+        veritas_approved: bool
+    ) -> AkashicRecord {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut record = AkashicRecord {
+            seq: previous.map_or(0, |p| p.seq + 1),
+            prev_seal: previous.map_or_else(|| GENESIS_SEAL.to_string(), |p| p.record_seal.clone()),
+            context_seal: Self::compute_context_seal(messages),
             message_count: messages.len(),
             decision_index,
             veritas_approved,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        }
+            // Never earlier than the record it follows, even if the clock steps back
+            timestamp: previous.map_or(now, |p| now.max(p.timestamp)),
+            record_seal: String::new(),
+        };
+        record.record_seal = Self::compute_record_seal(&record);
+        record
     }
 
-    /// Verifies that a new record is logically consistent with the previous
-    /// Returns false if the chain is broken (tampering detected)
+    /// Checks that `current` directly follows `previous` (`None` for the first record).
+    /// Returns why the chain is broken, if it is (tampering detected)
     pub fn verify_chain_continuity(
         current: &AkashicRecord,
-        previous: &AkashicRecord
-    ) -> bool {
-        // Rule 1: Time must flow forward
-        if current.timestamp <= previous.timestamp {
-            return false;
+        previous: Option<&AkashicRecord>
+    ) -> Result<(), String> {
+        // Rule 1: The record is sealed as it stands
+        if current.record_seal != Self::compute_record_seal(current) {
+            return Err("record seal does not match its contents".to_string());
         }
-        
-        // Rule 2: Context seals must differ (consciousness evolved)
-        if current.context_seal == previous.context_seal {
-            return false; // Stale consciousness = suspicious
+
+        // Rule 2: It names its predecessor's seal and position
+        let (expected_seq, expected_prev) = previous.map_or((0, GENESIS_SEAL), |p| (p.seq + 1, p.record_seal.as_str()));
+        if current.seq != expected_seq {
+            return Err(format!("sequence number {} where {} was expected", current.seq, expected_seq));
         }
-        
-        // Rule 3: Veritas must approve both
-        if !current.veritas_approved || !previous.veritas_approved {
-            return false;
+        if current.prev_seal != expected_prev {
+            return Err("previous seal does not match the preceding record".to_string());
         }
-        
-        true
+
+        // Rule 3: Time never flows backwards
+        if previous.is_some_and(|p| current.timestamp < p.timestamp) {
+            return Err("timestamp is earlier than the preceding record".to_string());
+        }
+        Ok(())
     }
 
     /// Formats the record for human-readable ledger output
     pub fn format_for_ledger(record: &AkashicRecord) -> String {
         format!(
             "AKASHIC_SEAL: {} | MESSAGES: {} | INDEX: {:.4} | VERITAS: {} | TS: {}",
-            &record.context_seal[..16], // First 16 chars for readability
+            &record.record_seal[..16], // First 16 chars for readability
             record.message_count,
             record.decision_index,
             if record.veritas_approved { "PASS" } else { "FAIL" },
@@ -152,4 +195,21 @@ mod tests {
         assert!(record.veritas_approved);
         assert!(record.decision_index > 0.0);
     }
+
+    #[test]
+    fn test_chain_continuity() {
+        let messages = vec![NeuralMessage { role: "user".into(), content: "Hold or sell?".into(), timestamp: 1705300000 }];
+        let first = AkashicLink::create_record(&messages, 0.5, true);
+        let second = AkashicLink::link_record(Some(&first), &messages, 0.7, false);
+
+        assert_eq!(AkashicLink::verify_chain_continuity(&first, None), Ok(()));
+        assert_eq!(AkashicLink::verify_chain_continuity(&second, Some(&first)), Ok(()));
+
+        // The next record commits to the previous seal, so altering it is visible
+        let mut altered = first.clone();
+        altered.decision_index = 0.9;
+        assert!(AkashicLink::verify_chain_continuity(&altered, None).is_err());
+        altered.record_seal = AkashicLink::compute_record_seal(&altered);
+        assert!(AkashicLink::verify_chain_continuity(&second, Some(&altered)).is_err());
+    }
 }
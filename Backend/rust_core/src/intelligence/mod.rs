@@ -2,9 +2,12 @@
 //! 
 //! This module contains the cognitive infrastructure of the Sovereign Engine:
 //! - AkashicLink: Memory-to-Truth cryptographic bridge
+//! - AkashicChain: the persistent, verifiable chain of sealed decisions
 //! - (Future) Hallucination Guard: Rust-native contradiction detector
 //! - (Future) Semantic Anchor: Vector-based consistency checker
 
+pub mod akashic_chain;
 pub mod akashic_link;
 
-pub use akashic_link::{AkashicLink, AkashicRecord, NeuralMessage};
+pub use akashic_chain::{verify_chain, AkashicChain, ChainReport};
+pub use akashic_link::{AkashicLink, AkashicRecord, NeuralMessage, GENESIS_SEAL};
@@ -1,10 +1,15 @@
 use serde::{Deserialize, Serialize};
 use std::slice;
 use sha2::{Sha512, Digest};
-use std::fs::OpenOptions;
-use std::io::Write;
 
+pub mod auth;
 pub mod economy;
+pub mod events;
+pub mod ffi;
+pub mod intelligence;
+pub mod limits;
+pub mod metrics;
+pub mod scribe;
 pub mod telemetry;
 pub mod server;
 
@@ -22,16 +27,32 @@ pub struct InputPayload {
     pub energy_data_stream: EnergyData,
 }
 
-#[no_mangle]
-pub extern "C" fn process_and_sign_cycle(data_ptr: *const u8, data_len: usize) -> f64 {
-    // Safety checks
-    if data_ptr.is_null() || data_len == 0 { return -1.0; }
-    let slice = unsafe { slice::from_raw_parts(data_ptr, data_len) };
-    let payload: InputPayload = match serde_json::from_slice(slice) {
-        Ok(p) => p,
-        Err(_) => return -2.0,
-    };
+/// A cycle that has been scored and written to the ledger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedCycle {
+    pub index: f64,
+    pub record: scribe::LedgerRecord,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CycleError {
+    InvalidPayload(String),
+    Ledger(String),
+}
+
+impl std::fmt::Display for CycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CycleError::InvalidPayload(reason) => write!(f, "Invalid payload: {}", reason),
+            CycleError::Ledger(reason) => write!(f, "Ledger write failed: {}", reason),
+        }
+    }
+}
 
+impl std::error::Error for CycleError {}
+
+/// The stability index of one cycle, from 0 (chaos) to 100 (calm).
+pub fn entropy_index(payload: &InputPayload) -> f64 {
     // Core entropy calculation (same logic as before)
     let mut total_entropy = 0.0_f64;
     for bio in &payload.bio_data_stream {
@@ -49,15 +70,45 @@ pub extern "C" fn process_and_sign_cycle(data_ptr: *const u8, data_len: usize) -
             }
         }
     }
-    let final_index = 100.0 - total_entropy.max(0.0).min(100.0);
+    100.0 - total_entropy.max(0.0).min(100.0)
+}
+
+/// Scores the JSON `InputPayload` in `raw` and appends the signed result to the ledger at `ledger`.
+pub fn sign_cycle(raw: &[u8], ledger: &std::path::Path) -> Result<SignedCycle, CycleError> {
+    let payload: InputPayload = serde_json::from_slice(raw).map_err(|e| CycleError::InvalidPayload(e.to_string()))?;
+    let final_index = entropy_index(&payload);
 
     // SHA-512 signing and ledger write
     let mut hasher = Sha512::new();
-    hasher.update(slice);
+    hasher.update(raw);
     hasher.update(final_index.to_be_bytes());
     let hash = hasher.finalize();
-    if let Ok(mut file) = OpenOptions::new().append(true).create(true).open("sovereign.ledger") {
-        let _ = writeln!(file, "{:x} | INDEX: {:.4}", hash, final_index);
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let data = serde_json::json!({ "index": final_index, "input_sha512": format!("{:x}", hash) });
+    let record = scribe::append(ledger, timestamp, &data).map_err(CycleError::Ledger)?;
+    Ok(SignedCycle { index: final_index, record })
+}
+
+/// Original entry point, kept for existing callers: returns -1.0 for a null or empty
+/// buffer and -2.0 for bad JSON, and only logs ledger failures. New code should use
+/// `lwas_sign_cycle` (see `ffi`).
+///
+/// # Safety
+/// `data_ptr` must point to `data_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn process_and_sign_cycle(data_ptr: *const u8, data_len: usize) -> f64 {
+    // Safety checks
+    if data_ptr.is_null() || data_len == 0 { return -1.0; }
+    let slice = unsafe { slice::from_raw_parts(data_ptr, data_len) };
+    match sign_cycle(slice, &scribe::ledger_path()) {
+        Ok(cycle) => cycle.index,
+        Err(CycleError::InvalidPayload(_)) => -2.0,
+        Err(CycleError::Ledger(e)) => {
+            eprintln!("[SCRIBE] Ledger write failed: {}", e);
+            // The index is still valid even though it could not be recorded
+            serde_json::from_slice(slice).map_or(-2.0, |payload| entropy_index(&payload))
+        }
     }
-    final_index
 }
@@ -0,0 +1,155 @@
+//! Actix middleware that applies the `RateLimiter` before the handler runs. Wrap it
+//! inside `ApiKeyAuth`, so the caller's key is known. Refused mints, unlocks and
+//! transfers are journaled as rejected entries for audit, at most one per rule and
+//! caller per refill window; the next entry counts the refusals left out.
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error, HttpMessage, ResponseError};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::{RateLimited, RateLimiter};
+use crate::auth::Principal;
+use crate::economy::{AmountInput, Currency, EconomyEngine, RejectedAttempt, TransactionType};
+
+/// The user a request acts for: the key's bound user, else the `user_id` in the path,
+/// the query string or the JSON body (`from_user_id` for transfers).
+fn acting_user(req: &ServiceRequest, principal: Option<&Principal>, body: Option<&serde_json::Value>) -> Option<String> {
+    if let Some(user) = principal.and_then(|p| p.user_id.clone()) {
+        return Some(user);
+    }
+    if let Some(user) = req.match_info().get("user_id") {
+        return Some(user.to_string());
+    }
+    let query = web::Query::<std::collections::HashMap<String, String>>::from_query(req.query_string()).ok();
+    if let Some(user) = query.and_then(|q| q.get("user_id").cloned()) {
+        return Some(user);
+    }
+    let body = body?;
+    body.get("user_id").or_else(|| body.get("from_user_id")).and_then(|u| u.as_str()).map(str::to_string)
+}
+
+/// Routes whose refused requests are journaled.
+const AUDITED_ROUTES: [&str; 3] = ["/api/mint_credits", "/api/unlock_module", "/api/transfer"];
+
+/// What a refused request would have done, for the routes whose refusals are audited.
+/// The handler never ran, so the caller's right to act for the user is checked here: an
+/// own-scope key naming another user gets its 429 but writes nothing to their journal.
+fn attempt(route: &str, body: &serde_json::Value, principal: Option<&Principal>) -> Option<RejectedAttempt> {
+    let text = |field: &str| body.get(field).and_then(|v| v.as_str()).map(str::to_string);
+    let amount = || {
+        let input: AmountInput = serde_json::from_value(body.get("amount")?.clone()).ok()?;
+        let currency = text("currency").map_or(Ok(Currency::Credits), |c| Currency::from_code(&c)).ok()?;
+        input.to_money(currency).ok().filter(|a| a.is_positive())
+    };
+    let (transaction_type, user_id, counterparty) = match route {
+        "/api/mint_credits" => (TransactionType::Mint, text("user_id")?, None),
+        "/api/unlock_module" => (TransactionType::Unlock, text("user_id")?, None),
+        "/api/transfer" => (TransactionType::Transfer, text("from_user_id")?, text("to_user_id")),
+        _ => return None,
+    };
+    let principal = principal?;
+    principal.check_user(&user_id).ok()?;
+    Some(RejectedAttempt {
+        transaction_type,
+        user_id,
+        counterparty,
+        // Unlocks are priced from the catalog
+        amount: if transaction_type == TransactionType::Unlock { None } else { amount() },
+        module_name: text("module_name"),
+        actor: Some(principal.key_id.clone()),
+        reason: RateLimited::KIND.to_string(),
+        suppressed: 0,
+    })
+}
+
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+    economy: Arc<EconomyEngine>,
+}
+
+impl RateLimit {
+    pub fn new(limiter: Arc<RateLimiter>, economy: Arc<EconomyEngine>) -> Self {
+        RateLimit { limiter, economy }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware { service: Rc::new(service), limit: self.clone() }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limit: RateLimit,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let limit = self.limit.clone();
+        Box::pin(async move {
+            let method = req.method().to_string();
+            let route = req.match_pattern().unwrap_or_else(|| req.path().to_string());
+            let principal = req.extensions().get::<Principal>().cloned();
+            // Public routes carry no key; they share one bucket
+            let key = principal.as_ref().map_or_else(|| "anonymous".to_string(), |p| p.key_id.clone());
+
+            let mut user = acting_user(&req, principal.as_ref(), None);
+            let mut body = None;
+            let needs_user = user.is_none() && limit.limiter.counts_users(&method, &route);
+            if method == "POST" && (needs_user || AUDITED_ROUTES.contains(&route.as_str())) {
+                // Read once for the user id and the audit entry, then handed on to the handler
+                let bytes = req.extract::<web::Bytes>().await?;
+                body = serde_json::from_slice::<serde_json::Value>(&bytes).ok();
+                req.set_payload(bytes.into());
+                if user.is_none() {
+                    user = acting_user(&req, principal.as_ref(), body.as_ref());
+                }
+            }
+
+            let now = Instant::now();
+            match limit.limiter.check(&method, &route, &key, user.as_deref(), now) {
+                Ok(()) => service.call(req).await.map(ServiceResponse::map_into_left_body),
+                Err(limited) => {
+                    let audited = body.as_ref().and_then(|b| attempt(&route, b, principal.as_ref()));
+                    // A client hammering a limit must not flood the journal
+                    let audited = audited.and_then(|a| Some(RejectedAttempt { suppressed: limit.limiter.audit(&limited, now)?, ..a }));
+                    if let Some(audited) = audited {
+                        if let Err(e) = limit.economy.record_rejected(audited) {
+                            eprintln!("[LIMITS] Could not journal rate-limited request: {}", e);
+                        }
+                    }
+                    let response = limited.error_response();
+                    Ok(req.into_response(response).map_into_right_body())
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,300 @@
+//! Per-key and per-user rate limits for the economy server: one token bucket per rule
+//! and caller, refilled continuously. The rules and the engine's spending caps are read
+//! from the JSON file named by `LWAS_LIMITS`:
+//!
+//! ```json
+//! {
+//!   "rate_limits": [
+//!     {"route": "*", "per": "key", "burst": 120, "per_second": 20},
+//!     {"method": "POST", "route": "/api/mint_credits", "per": "key", "burst": 5, "per_second": 0.1}
+//!   ],
+//!   "spending_caps": {"unlock_per_user_per_day": 500, "mint_per_key_per_hour": "10000.00"}
+//! }
+//! ```
+
+use actix_web::body::BoxBody;
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::economy::SpendingCaps;
+use crate::metrics::FailureKind;
+
+pub mod middleware;
+
+pub use middleware::RateLimit;
+
+/// Above this many buckets, full ones are dropped; a full bucket is the same as none.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Whose requests a rule counts together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subject {
+    /// The API key
+    Key,
+    /// The user the request acts for; requests that name none are not counted
+    User,
+}
+
+impl Subject {
+    pub fn name(self) -> &'static str {
+        match self {
+            Subject::Key => "key",
+            Subject::User => "user",
+        }
+    }
+}
+
+fn any_route() -> String {
+    "*".to_string()
+}
+
+/// `burst` requests at once, then `per_second` on average.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateRule {
+    /// Every method when absent
+    #[serde(default)]
+    pub method: Option<String>,
+    /// A registered route pattern, or `*` for every route
+    #[serde(default = "any_route")]
+    pub route: String,
+    pub per: Subject,
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl RateRule {
+    fn applies(&self, method: &str, route: &str) -> bool {
+        self.method.as_deref().is_none_or(|m| m.eq_ignore_ascii_case(method)) && (self.route == "*" || self.route == route)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    #[serde(default)]
+    pub rate_limits: Vec<RateRule>,
+    #[serde(default)]
+    pub spending_caps: SpendingCaps,
+}
+
+impl LimitsConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let config: LimitsConfig = serde_json::from_slice(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+        config.validate().map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    /// Generous enough for any well-behaved client; no spending caps.
+    pub fn builtin() -> Self {
+        LimitsConfig {
+            rate_limits: vec![
+                RateRule { method: None, route: any_route(), per: Subject::Key, burst: 120, per_second: 20.0 },
+                RateRule { method: Some("POST".to_string()), route: any_route(), per: Subject::User, burst: 10, per_second: 1.0 },
+            ],
+            spending_caps: SpendingCaps::default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for rule in &self.rate_limits {
+            if rule.burst == 0 || !(rule.per_second > 0.0 && rule.per_second.is_finite()) {
+                return Err(format!("rate limit for '{}' needs a positive burst and per_second", rule.route));
+            }
+        }
+        self.spending_caps.validate().map_err(|e| e.to_string())
+    }
+}
+
+/// A request over a rate limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimited {
+    pub route: String,
+    pub per: Subject,
+    /// Index of the rule that refused the request, and the key or user it counts
+    pub rule: usize,
+    pub subject: String,
+    /// Whole seconds until the request would be let through
+    pub retry_after: u64,
+}
+
+impl RateLimited {
+    pub const KIND: &'static str = "rate_limited";
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rate limit per {} exceeded for {}; retry in {}s", self.per.name(), self.route, self.retry_after)
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+impl ResponseError for RateLimited {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// The usual `{"error", "kind"}` body plus `retry_after`, also sent as `Retry-After`.
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        let mut response = HttpResponse::build(self.status_code())
+            .insert_header((header::RETRY_AFTER, self.retry_after.to_string()))
+            .json(serde_json::json!({
+                "error": self.to_string(),
+                "kind": Self::KIND,
+                "retry_after": self.retry_after,
+            }));
+        response.extensions_mut().insert(FailureKind(Self::KIND));
+        response
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// When a rule and caller's refusals were last journaled, and how many were not since.
+#[derive(Debug, Clone, Copy)]
+struct Audit {
+    journaled: Instant,
+    suppressed: u64,
+}
+
+/// Token buckets for every rule and caller seen, kept in memory.
+pub struct RateLimiter {
+    rules: Vec<RateRule>,
+    /// (rule index, key id or user id) -> bucket
+    buckets: Mutex<HashMap<(usize, String), Bucket>>,
+    /// Same keys -> last journaled refusal
+    audits: Mutex<HashMap<(usize, String), Audit>>,
+}
+
+impl RateLimiter {
+    pub fn new(rules: Vec<RateRule>) -> Self {
+        RateLimiter { rules, buckets: Mutex::new(HashMap::new()), audits: Mutex::new(HashMap::new()) }
+    }
+
+    /// Whether `limited` should be journaled: at most once per rule and caller per refill
+    /// window (the time one token takes to come back). Returns the refusals suppressed
+    /// since the last entry, or `None` when this one is suppressed too.
+    pub fn audit(&self, limited: &RateLimited, now: Instant) -> Option<u64> {
+        let per_second = self.rules[limited.rule].per_second;
+        let in_window = |a: &Audit| now.saturating_duration_since(a.journaled).as_secs_f64() * per_second < 1.0;
+        let mut audits = self.audits.lock().unwrap();
+        let key = (limited.rule, limited.subject.clone());
+        let suppressed = match audits.get_mut(&key) {
+            Some(audit) if in_window(audit) => {
+                audit.suppressed += 1;
+                return None;
+            }
+            Some(audit) => audit.suppressed,
+            None => 0,
+        };
+        audits.insert(key, Audit { journaled: now, suppressed: 0 });
+        if audits.len() > MAX_BUCKETS {
+            // Callers quiet for a whole window lose their count rather than grow the map
+            let rules = &self.rules;
+            audits.retain(|(index, _), a| now.saturating_duration_since(a.journaled).as_secs_f64() * rules[*index].per_second < 1.0);
+        }
+        Some(suppressed)
+    }
+
+    /// Whether some rule counts `method route` per user, so the caller must find the user.
+    pub fn counts_users(&self, method: &str, route: &str) -> bool {
+        self.rules.iter().any(|r| r.per == Subject::User && r.applies(method, route))
+    }
+
+    /// Takes a token from every bucket the request falls under, or from none of them if
+    /// any is empty.
+    pub fn check(&self, method: &str, route: &str, key: &str, user: Option<&str>, now: Instant) -> Result<(), RateLimited> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let mut taken = Vec::new();
+        let mut limited: Option<RateLimited> = None;
+        for (index, rule) in self.rules.iter().enumerate().filter(|(_, r)| r.applies(method, route)) {
+            let subject = match rule.per {
+                Subject::Key => key,
+                Subject::User => match user {
+                    Some(user) => user,
+                    None => continue,
+                },
+            };
+            let burst = rule.burst as f64;
+            let bucket = buckets.get(&(index, subject.to_string())).copied().unwrap_or(Bucket { tokens: burst, updated: now });
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            let tokens = (bucket.tokens + elapsed * rule.per_second).min(burst);
+            if tokens < 1.0 {
+                let retry_after = ((1.0 - tokens) / rule.per_second).ceil().max(1.0) as u64;
+                if limited.as_ref().is_none_or(|l| retry_after > l.retry_after) {
+                    limited = Some(RateLimited {
+                        route: route.to_string(),
+                        per: rule.per,
+                        rule: index,
+                        subject: subject.to_string(),
+                        retry_after,
+                    });
+                }
+            }
+            taken.push(((index, subject.to_string()), Bucket { tokens: tokens - 1.0, updated: now }));
+        }
+        if let Some(limited) = limited {
+            return Err(limited);
+        }
+        buckets.extend(taken);
+        if buckets.len() > MAX_BUCKETS {
+            let rules = &self.rules;
+            buckets.retain(|(index, _), b| b.tokens + now.saturating_duration_since(b.updated).as_secs_f64() * rules[*index].per_second < rules[*index].burst as f64);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_buckets_refill_and_limit_all_or_nothing() {
+        let limiter = RateLimiter::new(vec![
+            RateRule { method: None, route: "*".into(), per: Subject::Key, burst: 3, per_second: 1.0 },
+            RateRule { method: Some("POST".into()), route: "/api/transfer".into(), per: Subject::User, burst: 1, per_second: 0.5 },
+        ]);
+        let start = Instant::now();
+        assert!(limiter.counts_users("post", "/api/transfer"));
+        assert!(!limiter.counts_users("GET", "/api/transfer"));
+
+        limiter.check("POST", "/api/transfer", "k1", Some("alice"), start).unwrap();
+        // Alice's bucket is empty for 2s; the refused request costs the key nothing
+        let limited = limiter.check("POST", "/api/transfer", "k1", Some("alice"), start).unwrap_err();
+        assert_eq!((limited.per, limited.retry_after), (Subject::User, 2));
+        limiter.check("POST", "/api/transfer", "k1", Some("bob"), start).unwrap();
+        limiter.check("GET", "/api/balance", "k1", None, start).unwrap();
+        let limited = limiter.check("GET", "/api/balance", "k1", None, start).unwrap_err();
+        assert_eq!((limited.per, limited.retry_after), (Subject::Key, 1));
+
+        limiter.check("GET", "/api/balance", "k2", None, start).unwrap();
+        limiter.check("GET", "/api/balance", "k1", None, start + Duration::from_secs(1)).unwrap();
+        limiter.check("POST", "/api/transfer", "k2", Some("alice"), start + Duration::from_secs(2)).unwrap();
+
+        assert!(LimitsConfig::builtin().validate().is_ok());
+        // One journaled refusal per refill window; the next carries the count in between
+        let limited = limiter.check("POST", "/api/transfer", "k1", Some("alice"), start).unwrap_err();
+        assert_eq!((limited.rule, limited.subject.as_str()), (1, "alice"));
+        assert_eq!(limiter.audit(&limited, start), Some(0));
+        assert_eq!(limiter.audit(&limited, start + Duration::from_secs(1)), None);
+        assert_eq!(limiter.audit(&limited, start + Duration::from_millis(1900)), None);
+        assert_eq!(limiter.audit(&limited, start + Duration::from_secs(2)), Some(2));
+
+        let zero = LimitsConfig { rate_limits: vec![RateRule { burst: 0, ..LimitsConfig::builtin().rate_limits[0].clone() }], ..Default::default() };
+        assert!(zero.validate().is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Actix middleware that times every request and counts it by route and status, plus
+//! by error kind when the response carries a `FailureKind`. Wrap it outside
+//! `ApiKeyAuth` so rejected keys are counted too.
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::{FailureKind, Metrics, UNMATCHED_ROUTE};
+
+#[derive(Clone)]
+pub struct RequestMetrics {
+    metrics: Arc<Metrics>,
+}
+
+impl RequestMetrics {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        RequestMetrics { metrics }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service: Rc::new(service), metrics: self.metrics.clone() }))
+    }
+}
+
+pub struct RequestMetricsMiddleware<S> {
+    service: Rc<S>,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        // Non-standard methods are folded into `OTHER` by `observe_request`
+        let method = req.method().to_string();
+        // The pattern, not the path, so `/api/transactions/{id}` is one series
+        let route = req.match_pattern().unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let service = self.service.clone();
+        let metrics = self.metrics.clone();
+        Box::pin(async move {
+            let result = service.call(req).await;
+            let (status, kind) = match &result {
+                Ok(res) => {
+                    let status = res.status();
+                    let kind = res.response().extensions().get::<FailureKind>().map(|k| k.0.to_string());
+                    // Errors answered without a kind, e.g. an unmatched route, count by status
+                    let kind = kind.or_else(|| (status.is_client_error() || status.is_server_error()).then(|| format!("http_{}", status.as_u16())));
+                    (status.as_u16(), kind)
+                }
+                Err(e) => {
+                    let status = e.as_response_error().status_code();
+                    (status.as_u16(), Some(format!("http_{}", status.as_u16())))
+                }
+            };
+            metrics.observe_request(&method, &route, status, started.elapsed(), kind.as_deref());
+            result
+        })
+    }
+}
@@ -0,0 +1,240 @@
+//! Prometheus metrics for the economy server, rendered in the text exposition format
+//! by `GET /metrics`. Request counts, latencies and failures are counted by the
+//! `RequestMetrics` middleware since startup; ledger totals are read from the journal
+//! at scrape time, and host readings are the telemetry sampler's latest.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::economy::TransactionTotals;
+use crate::telemetry::SystemMetrics;
+
+pub mod middleware;
+
+pub use middleware::RequestMetrics;
+
+/// `Content-Type` of the text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds, in seconds, of the request latency buckets.
+pub const LATENCY_BUCKETS: [f64; 12] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// `route` label of requests that matched no route, so stray paths share one series.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// `method` label of any method outside `STANDARD_METHODS`, which clients may invent freely.
+pub const OTHER_METHOD: &str = "OTHER";
+const STANDARD_METHODS: [&str; 9] = ["GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH"];
+
+const BYTES_PER_GB: f64 = 1_073_741_824.0;
+
+/// Attached to an error response so the middleware can count it by kind; the same
+/// string is sent to the client as `kind`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FailureKind(pub &'static str);
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Non-cumulative: observations that fell in each bucket
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    /// (method, route, status) -> requests
+    requests: BTreeMap<(String, String, u16), u64>,
+    /// (method, route) -> latencies
+    latencies: BTreeMap<(String, String), Histogram>,
+    /// (route, kind) -> failed requests
+    failures: BTreeMap<(String, String), u64>,
+}
+
+/// Counters shared by the middleware and the `/metrics` handler.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Records one finished request. `route` is the registered pattern, not the path.
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration, failure: Option<&str>) {
+        let method = if STANDARD_METHODS.contains(&method) { method } else { OTHER_METHOD };
+        let mut counters = self.counters.lock().unwrap();
+        *counters.requests.entry((method.to_string(), route.to_string(), status)).or_default() += 1;
+        counters
+            .latencies
+            .entry((method.to_string(), route.to_string()))
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        if let Some(kind) = failure {
+            *counters.failures.entry((route.to_string(), kind.to_string())).or_default() += 1;
+        }
+    }
+
+    /// Every metric in the text exposition format.
+    pub fn render(&self, totals: &[TransactionTotals], host: &SystemMetrics) -> String {
+        let mut out = String::new();
+        {
+            let counters = self.counters.lock().unwrap();
+
+            family(&mut out, "lwas_http_requests_total", "counter", "HTTP requests by method, route and status.");
+            for ((method, route, status), count) in &counters.requests {
+                let status = status.to_string();
+                sample(&mut out, "lwas_http_requests_total", &[("method", method), ("route", route), ("status", &status)], *count);
+            }
+
+            family(&mut out, "lwas_http_request_duration_seconds", "histogram", "Time to answer a request, by method and route.");
+            for ((method, route), histogram) in &counters.latencies {
+                let mut cumulative = 0;
+                for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                    cumulative += count;
+                    let le = bound.to_string();
+                    sample(&mut out, "lwas_http_request_duration_seconds_bucket", &[("method", method), ("route", route), ("le", &le)], cumulative);
+                }
+                let labels = [("method", method.as_str()), ("route", route.as_str())];
+                sample(&mut out, "lwas_http_request_duration_seconds_bucket", &[labels[0], labels[1], ("le", "+Inf")], histogram.count);
+                sample(&mut out, "lwas_http_request_duration_seconds_sum", &labels, histogram.sum);
+                sample(&mut out, "lwas_http_request_duration_seconds_count", &labels, histogram.count);
+            }
+
+            family(&mut out, "lwas_failed_operations_total", "counter", "Requests answered with an error, by route and error kind.");
+            for ((route, kind), count) in &counters.failures {
+                sample(&mut out, "lwas_failed_operations_total", &[("route", route), ("kind", kind)], *count);
+            }
+        }
+
+        family(&mut out, "lwas_transactions_total", "counter", "Posted journal entries by transaction type.");
+        for total in totals {
+            sample(&mut out, "lwas_transactions_total", &[("type", total.transaction_type.name())], total.count);
+        }
+        family(&mut out, "lwas_rejected_transactions_total", "counter", "Attempts refused by a rate limit or spending cap, by transaction type.");
+        for total in totals {
+            sample(&mut out, "lwas_rejected_transactions_total", &[("type", total.transaction_type.name())], total.rejected);
+        }
+        family(&mut out, "lwas_transaction_amount_credits_total", "counter", "Gross amount moved by journal entries of each type, in credits.");
+        for total in totals {
+            sample(&mut out, "lwas_transaction_amount_credits_total", &[("type", total.transaction_type.name())], total.amount.amount());
+        }
+
+        gauge(&mut out, "lwas_host_cpu_load_percent", "Host CPU load averaged over all cores.", host.cpu_load);
+        gauge(&mut out, "lwas_host_memory_used_bytes", "Host memory in use.", (host.ram_used_gb as f64 * BYTES_PER_GB).round());
+        gauge(&mut out, "lwas_host_memory_total_bytes", "Host memory installed.", (host.ram_total_gb as f64 * BYTES_PER_GB).round());
+        gauge(&mut out, "lwas_host_memory_usage_percent", "Share of host memory in use.", host.ram_usage_percent);
+        gauge(&mut out, "lwas_host_temperature_celsius", "Hottest component, 0 where the platform reports none.", host.temperature_celsius);
+        family(&mut out, "lwas_host_component_temperature_celsius", "gauge", "Temperature of each sensor the platform exposes.");
+        for component in &host.temperatures {
+            sample(&mut out, "lwas_host_component_temperature_celsius", &[("component", &component.label)], component.celsius);
+        }
+        counter(&mut out, "lwas_host_network_received_bytes_total", "Bytes received on every interface but loopback.", host.network_received_bytes);
+        counter(&mut out, "lwas_host_network_transmitted_bytes_total", "Bytes sent on every interface but loopback.", host.network_transmitted_bytes);
+        gauge(&mut out, "lwas_process_cpu_percent", "CPU used by the economy process, in percent of one core.", host.process_cpu_percent);
+        gauge(&mut out, "lwas_process_resident_memory_bytes", "Resident memory of the economy process.", host.process_memory_bytes);
+        counter(&mut out, "lwas_process_disk_read_bytes_total", "Bytes the economy process has read from disk.", host.disk_read_bytes);
+        counter(&mut out, "lwas_process_disk_written_bytes_total", "Bytes the economy process has written to disk.", host.disk_written_bytes);
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    family(out, name, "gauge", help);
+    sample(out, name, &[], value);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    family(out, name, "counter", help);
+    sample(out, name, &[], value);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels.iter().map(|(key, value)| format!("{}=\"{}\"", key, escape(value))).collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+/// Label values escape backslash, double quote and newline.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::economy::{Money, TransactionType};
+    use crate::telemetry::ComponentTemperature;
+
+    #[test]
+    fn test_render_exposition_format() {
+        let metrics = Metrics::new();
+        metrics.observe_request("POST", "/api/mint_credits", 200, Duration::from_millis(3), None);
+        metrics.observe_request("POST", "/api/mint_credits", 400, Duration::from_millis(70), Some("invalid_amount"));
+        metrics.observe_request("GET", "/api/balance", 200, Duration::from_secs(9), None);
+        metrics.observe_request("X-SCAN-1", UNMATCHED_ROUTE, 404, Duration::from_millis(1), Some("http_404"));
+        metrics.observe_request("X-SCAN-2", UNMATCHED_ROUTE, 404, Duration::from_millis(1), Some("http_404"));
+        let totals = [TransactionTotals { transaction_type: TransactionType::Mint, count: 2, amount: Money::credits(1250), rejected: 1 }];
+        let host = SystemMetrics {
+            timestamp: 1_700_000_000,
+            cpu_load: 12.5,
+            ram_usage_percent: 50.0,
+            ram_used_gb: 1.0,
+            ram_total_gb: 2.0,
+            temperature_celsius: 48.0,
+            temperatures: vec![ComponentTemperature { label: "coretemp Package id 0".into(), celsius: 48.0 }],
+            active_transactions: 2,
+            process_cpu_percent: 3.0,
+            process_memory_bytes: 52_428_800,
+            disk_read_bytes: 4096,
+            disk_written_bytes: 8192,
+            network_received_bytes: 1000,
+            network_transmitted_bytes: 2000,
+        };
+        let text = metrics.render(&totals, &host);
+
+        for line in [
+            "# TYPE lwas_http_requests_total counter",
+            "lwas_http_requests_total{method=\"POST\",route=\"/api/mint_credits\",status=\"400\"} 1",
+            "lwas_http_request_duration_seconds_bucket{method=\"POST\",route=\"/api/mint_credits\",le=\"0.005\"} 1",
+            "lwas_http_request_duration_seconds_bucket{method=\"POST\",route=\"/api/mint_credits\",le=\"0.1\"} 2",
+            "lwas_http_request_duration_seconds_bucket{method=\"GET\",route=\"/api/balance\",le=\"5\"} 0",
+            "lwas_http_request_duration_seconds_bucket{method=\"GET\",route=\"/api/balance\",le=\"+Inf\"} 1",
+            "lwas_http_request_duration_seconds_count{method=\"POST\",route=\"/api/mint_credits\"} 2",
+            "lwas_failed_operations_total{route=\"/api/mint_credits\",kind=\"invalid_amount\"} 1",
+            "lwas_http_requests_total{method=\"OTHER\",route=\"unmatched\",status=\"404\"} 2",
+            "lwas_transactions_total{type=\"mint\"} 2",
+            "lwas_transaction_amount_credits_total{type=\"mint\"} 12.50",
+            "lwas_rejected_transactions_total{type=\"mint\"} 1",
+            "lwas_host_memory_total_bytes 2147483648",
+            "lwas_host_component_temperature_celsius{component=\"coretemp Package id 0\"} 48",
+            "lwas_process_disk_written_bytes_total 8192",
+            "lwas_host_network_received_bytes_total 1000",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing `{}` in:\n{}", line, text);
+        }
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,191 @@
+//! The `sovereign.ledger` record format.
+//!
+//! One record per line:
+//!
+//! ```text
+//! v1 | <seq> | <unix seconds> | <prev hash> | <hash> | <data JSON>
+//! ```
+//!
+//! `hash` is the hex SHA-512 of `v1|<seq>|<timestamp>|<prev hash>|<data JSON>`, and
+//! `prev hash` is the previous record's `hash` (`GENESIS_HASH` for the first record),
+//! so changing, dropping or reordering any record breaks every hash after it.
+//! Unversioned `<sha512> | INDEX: x` lines written before this format may precede the
+//! chain; they are counted but cannot be verified.
+//!
+//! Appends are serialised within a process only: each ledger file must have a single
+//! writing process, or two appends may chain from the same record.
+
+use serde::Serialize;
+use sha2::{Digest, Sha512};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const VERSION: &str = "v1";
+pub const GENESIS_HASH: &str =
+    "00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000";
+pub const DEFAULT_LEDGER_PATH: &str = "sovereign.ledger";
+const SEPARATOR: &str = " | ";
+
+/// Serialises appends within the process, so two writers cannot chain from the same record.
+static APPEND_LOCK: Mutex<()> = Mutex::new(());
+
+/// `LWAS_LEDGER_PATH`, or `sovereign.ledger` in the working directory.
+pub fn ledger_path() -> PathBuf {
+    std::env::var_os("LWAS_LEDGER_PATH").map_or_else(|| PathBuf::from(DEFAULT_LEDGER_PATH), PathBuf::from)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LedgerRecord {
+    pub seq: u64,
+    pub timestamp: u64,
+    pub prev_hash: String,
+    pub hash: String,
+    /// Compact JSON, exactly as written
+    pub data: String,
+}
+
+/// What one line of the file holds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LedgerLine {
+    /// Empty or `#` comment
+    Blank,
+    /// Pre-v1 `<sha512> | INDEX: x`
+    Legacy,
+    Record(LedgerRecord),
+}
+
+pub fn compute_hash(seq: u64, timestamp: u64, prev_hash: &str, data: &str) -> String {
+    let mut hasher = Sha512::new();
+    hasher.update(format!("{}|{}|{}|{}|{}", VERSION, seq, timestamp, prev_hash, data).as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn is_hash(value: &str) -> bool {
+    value.len() == 128 && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+impl LedgerRecord {
+    pub fn new(seq: u64, timestamp: u64, prev_hash: &str, data: &serde_json::Value) -> Self {
+        let data = data.to_string();
+        LedgerRecord {
+            seq,
+            timestamp,
+            prev_hash: prev_hash.to_string(),
+            hash: compute_hash(seq, timestamp, prev_hash, &data),
+            data,
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        [VERSION, &self.seq.to_string(), &self.timestamp.to_string(), &self.prev_hash, &self.hash, &self.data].join(SEPARATOR)
+    }
+
+    /// Whether `hash` matches the rest of the record.
+    pub fn hash_is_valid(&self) -> bool {
+        self.hash == compute_hash(self.seq, self.timestamp, &self.prev_hash, &self.data)
+    }
+}
+
+impl LedgerLine {
+    /// Parses the layout only; hashes and chaining are the verifier's job.
+    pub fn parse(line: &str) -> Result<LedgerLine, String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() || line.starts_with('#') {
+            return Ok(LedgerLine::Blank);
+        }
+        let fields: Vec<&str> = line.splitn(6, SEPARATOR).collect();
+        if fields.len() == 2 && is_hash(fields[0]) && fields[1].starts_with("INDEX:") {
+            return Ok(LedgerLine::Legacy);
+        }
+        match fields[0] {
+            VERSION => {}
+            version if version.len() <= 4 && version.starts_with('v') => {
+                return Err(format!("unsupported record version '{}'", version));
+            }
+            _ => return Err("not a ledger record".to_string()),
+        }
+        let [_, seq, timestamp, prev_hash, hash, data] = fields[..] else {
+            return Err(format!("expected 6 fields, found {}", fields.len()));
+        };
+        let seq = seq.parse().map_err(|_| format!("invalid sequence number '{}'", seq))?;
+        let timestamp = timestamp.parse().map_err(|_| format!("invalid timestamp '{}'", timestamp))?;
+        if !is_hash(prev_hash) || !is_hash(hash) {
+            return Err("hashes must be 128 lowercase hex digits".to_string());
+        }
+        serde_json::from_str::<serde_json::Value>(data).map_err(|e| format!("data is not JSON: {}", e))?;
+        Ok(LedgerLine::Record(LedgerRecord {
+            seq,
+            timestamp,
+            prev_hash: prev_hash.to_string(),
+            hash: hash.to_string(),
+            data: data.to_string(),
+        }))
+    }
+}
+
+/// The last record in the file, found by reading backwards from the end.
+fn last_record(file: &mut File) -> Result<Option<LedgerRecord>, String> {
+    let len = file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+    let mut chunk = 4096u64;
+    loop {
+        let start = len.saturating_sub(chunk);
+        file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).map_err(|e| e.to_string())?;
+        // The first line of a partial read may be cut off, possibly inside a character
+        if start > 0 {
+            let cut = tail.iter().position(|&b| b == b'\n').map_or(tail.len(), |i| i + 1);
+            tail.drain(..cut);
+        }
+        let tail = String::from_utf8(tail).map_err(|_| "last ledger line is not UTF-8; run `ledger verify`".to_string())?;
+        for line in tail.lines().rev() {
+            match LedgerLine::parse(line) {
+                Ok(LedgerLine::Record(record)) => return Ok(Some(record)),
+                Ok(LedgerLine::Blank) => continue,
+                Ok(LedgerLine::Legacy) => return Ok(None),
+                Err(e) => return Err(format!("last ledger line is unreadable ({}); run `ledger verify`", e)),
+            }
+        }
+        if start == 0 {
+            return Ok(None);
+        }
+        chunk *= 4;
+    }
+}
+
+/// Appends `data` as the next record of the chain at `path` and fsyncs it.
+pub fn append(path: impl AsRef<Path>, timestamp: u64, data: &serde_json::Value) -> Result<LedgerRecord, String> {
+    let path = path.as_ref();
+    let _guard = APPEND_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let io = |e: std::io::Error| format!("{}: {}", path.display(), e);
+    let mut file = OpenOptions::new().read(true).append(true).create(true).open(path).map_err(io)?;
+    let record = match last_record(&mut file).map_err(|e| format!("{}: {}", path.display(), e))? {
+        Some(last) => LedgerRecord::new(last.seq + 1, timestamp, &last.hash, data),
+        None => LedgerRecord::new(0, timestamp, GENESIS_HASH, data),
+    };
+    writeln!(file, "{}", record.to_line()).and_then(|_| file.sync_data()).map_err(io)?;
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_after_long_multibyte_record() {
+        // One of the two offsets starts the 4096-byte tail inside a two-byte character
+        for (offset, padding) in ["", "a"].into_iter().enumerate() {
+            let path = std::env::temp_dir().join(format!("lwas_ledger_utf8_{}_{}.ledger", std::process::id(), offset));
+            let _ = std::fs::remove_file(&path);
+            let note = format!("{}{}", padding, "é".repeat(4000));
+            append(&path, 1_700_000_000, &serde_json::json!({ "note": note })).unwrap();
+            let record = append(&path, 1_700_000_001, &serde_json::json!({ "index": 1 })).unwrap();
+            assert_eq!(record.seq, 1);
+            let record = append(&path, 1_700_000_002, &serde_json::json!({ "note": note })).unwrap();
+            assert_eq!(record.seq, 2);
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
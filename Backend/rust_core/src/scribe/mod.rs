@@ -0,0 +1,10 @@
+//! # Scribe Module
+//!
+//! The Sovereign Ledger: a hash-chained, append-only record of every signed cycle,
+//! and the verifier that proves it has not been edited since.
+
+pub mod ledger;
+pub mod verifier;
+
+pub use ledger::{append, ledger_path, LedgerLine, LedgerRecord};
+pub use verifier::{verify_ledger, LedgerFault, VerificationReport};
@@ -2,88 +2,180 @@
 // SCRIBE VERIFIER - Ledger Integrity Validation
 // ═══════════════════════════════════════════════════════════════
 // This module verifies that the sovereign.ledger has not been tampered with.
-// It performs chain-of-trust validation on all SHA-512 hashes.
+// Every v1 record must hash to its stored hash and name the previous record's hash.
 
+use serde::Serialize;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use sha2::{Sha512, Digest};
+use std::path::Path;
 
-/// Verifies the integrity of the sovereign.ledger file
-/// Returns true if all hashes form a valid chain, false if corruption detected
-#[no_mangle]
-pub extern "C" fn verify_ledger_integrity() -> bool {
-    let ledger_path = "sovereign.ledger";
-    
-    match File::open(ledger_path) {
-        Ok(file) => {
-            let reader = BufReader::new(file);
-            let mut previous_hash: Option<String> = None;
-            let mut line_count = 0;
-            
-            for line in reader.lines() {
-                if let Ok(content) = line {
-                    // Skip comments and empty lines
-                    if content.trim().is_empty() || content.starts_with('#') {
-                        continue;
-                    }
-                    
-                    // Parse ledger entry: TIMESTAMP | HASH | DATA
-                    let parts: Vec<&str> = content.split('|').collect();
-                    if parts.len() < 3 {
-                        eprintln!("[SCRIBE] Malformed line: {}", content);
-                        return false;
-                    }
-                    
-                    let current_hash = parts[1].trim();
-                    let data = parts[2].trim();
-                    
-                    // Verify hash matches the data
-                    let mut hasher = Sha512::new();
-                    hasher.update(data.as_bytes());
-                    let computed = format!("{:x}", hasher.finalize());
-                    
-                    // Check if the stored hash matches our computation
-                    if !current_hash.starts_with(&computed[..min(current_hash.len(), computed.len())]) {
-                        eprintln!("[SCRIBE] Hash mismatch at line {}", line_count + 1);
-                        return false;
-                    }
-                    
-                    // Chain validation: each hash should reference the previous
-                    if let Some(prev) = previous_hash {
-                        // Verify that current data contains reference to previous hash
-                        if !data.contains(&prev[..16]) {
-                            eprintln!("[SCRIBE] Chain broken at line {}", line_count + 1);
-                            return false;
-                        }
-                    }
-                    
-                    previous_hash = Some(current_hash.to_string());
-                    line_count += 1;
-                }
+use super::ledger::{ledger_path, LedgerLine, GENESIS_HASH};
+
+/// Why verification stopped.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LedgerFault {
+    Unreadable { reason: String },
+    Malformed { reason: String },
+    HashMismatch { expected: String, found: String },
+    BrokenChain { expected: String, found: String },
+    SequenceGap { expected: u64, found: u64 },
+    /// Unchained pre-v1 lines may only come before the chain starts
+    LegacyAfterChain,
+}
+
+impl fmt::Display for LedgerFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerFault::Unreadable { reason } => write!(f, "cannot read ledger: {}", reason),
+            LedgerFault::Malformed { reason } => write!(f, "malformed record: {}", reason),
+            LedgerFault::HashMismatch { expected, found } => {
+                write!(f, "record hash is {}…, but its contents hash to {}…", &found[..16.min(found.len())], &expected[..16])
+            }
+            LedgerFault::BrokenChain { expected, found } => {
+                write!(f, "previous hash is {}…, expected {}…", &found[..16.min(found.len())], &expected[..16])
+            }
+            LedgerFault::SequenceGap { expected, found } => write!(f, "sequence number {} where {} was expected", found, expected),
+            LedgerFault::LegacyAfterChain => write!(f, "unchained legacy line after the first v1 record"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FirstFault {
+    /// 1-based line number in the file; 0 when the file could not be opened
+    pub line: usize,
+    #[serde(flatten)]
+    pub fault: LedgerFault,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VerificationReport {
+    pub path: String,
+    pub valid: bool,
+    /// v1 records checked before the first fault
+    pub records: u64,
+    pub legacy_lines: u64,
+    /// Hash of the last valid record, to compare with a copy kept elsewhere
+    pub head: Option<String>,
+    pub first_fault: Option<FirstFault>,
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.first_fault {
+            None => write!(f, "{}: OK, {} records", self.path, self.records)?,
+            Some(first) if first.line == 0 => write!(f, "{}: {}", self.path, first.fault)?,
+            Some(first) => write!(f, "{}: line {}: {} ({} records valid before it)", self.path, first.line, first.fault, self.records)?,
+        }
+        if self.legacy_lines > 0 {
+            write!(f, ", {} unverifiable legacy lines", self.legacy_lines)?;
+        }
+        Ok(())
+    }
+}
+
+/// Walks the whole ledger at `path` and reports the first record that breaks the chain.
+pub fn verify_ledger(path: impl AsRef<Path>) -> VerificationReport {
+    let path = path.as_ref();
+    let mut report = VerificationReport {
+        path: path.display().to_string(),
+        valid: false,
+        records: 0,
+        legacy_lines: 0,
+        head: None,
+        first_fault: None,
+    };
+    let fail = |mut report: VerificationReport, line, fault| {
+        report.first_fault = Some(FirstFault { line, fault });
+        report
+    };
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return fail(report, 0, LedgerFault::Unreadable { reason: e.to_string() }),
+    };
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line_number = index + 1;
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return fail(report, line_number, LedgerFault::Unreadable { reason: e.to_string() }),
+        };
+        let record = match LedgerLine::parse(&line) {
+            Ok(LedgerLine::Blank) => continue,
+            Ok(LedgerLine::Legacy) if report.records == 0 => {
+                report.legacy_lines += 1;
+                continue;
             }
-            
-            println!("[SCRIBE] ✓ Ledger integrity verified: {} blocks", line_count);
-            true
+            Ok(LedgerLine::Legacy) => return fail(report, line_number, LedgerFault::LegacyAfterChain),
+            Ok(LedgerLine::Record(record)) => record,
+            Err(reason) => return fail(report, line_number, LedgerFault::Malformed { reason }),
+        };
+
+        if record.seq != report.records {
+            let fault = LedgerFault::SequenceGap { expected: report.records, found: record.seq };
+            return fail(report, line_number, fault);
+        }
+        let expected_prev = report.head.clone().unwrap_or_else(|| GENESIS_HASH.to_string());
+        if record.prev_hash != expected_prev {
+            return fail(report, line_number, LedgerFault::BrokenChain { expected: expected_prev, found: record.prev_hash });
         }
-        Err(e) => {
-            eprintln!("[SCRIBE] Cannot open ledger: {}", e);
-            false
+        if !record.hash_is_valid() {
+            let expected = super::ledger::compute_hash(record.seq, record.timestamp, &record.prev_hash, &record.data);
+            return fail(report, line_number, LedgerFault::HashMismatch { expected, found: record.hash });
         }
+        report.records += 1;
+        report.head = Some(record.hash);
     }
+    report.valid = true;
+    report
 }
 
-fn min(a: usize, b: usize) -> usize {
-    if a < b { a } else { b }
+/// Verifies the ledger at `LWAS_LEDGER_PATH` (default `sovereign.ledger`).
+/// Returns true if all hashes form a valid chain, false if corruption detected
+#[no_mangle]
+pub extern "C" fn verify_ledger_integrity() -> bool {
+    let report = verify_ledger(ledger_path());
+    if report.valid {
+        println!("[SCRIBE] ✓ Ledger integrity verified: {}", report);
+    } else {
+        eprintln!("[SCRIBE] ✗ {}", report);
+    }
+    report.valid
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::ledger::append;
     use super::*;
-    
+    use std::fs;
+
     #[test]
     fn test_ledger_verification() {
-        // This would need a mock ledger file for testing
-        // In production, we call verify_ledger_integrity() before generating certificates
-        assert!(true); // Placeholder
+        let path = std::env::temp_dir().join(format!("lwas_sovereign_{}.ledger", std::process::id()));
+        let legacy = format!("{} | INDEX: 99.9000\n", "ab".repeat(64));
+        fs::write(&path, legacy).unwrap();
+        for index in [97.5, 88.25, 100.0] {
+            append(&path, 1_700_000_000, &serde_json::json!({ "index": index })).unwrap();
+        }
+        let report = verify_ledger(&path);
+        assert!(report.valid, "{}", report);
+        assert_eq!((report.records, report.legacy_lines), (3, 1));
+
+        // Editing the data of the second record is caught on its own line
+        let tampered = fs::read_to_string(&path).unwrap().replace("88.25", "18.25");
+        fs::write(&path, &tampered).unwrap();
+        let report = verify_ledger(&path);
+        assert_eq!(report.first_fault.as_ref().map(|f| f.line), Some(3));
+        assert!(matches!(report.first_fault.unwrap().fault, LedgerFault::HashMismatch { .. }));
+
+        // Dropping it breaks the chain at the next one
+        let lines: Vec<&str> = tampered.lines().collect();
+        fs::write(&path, format!("{}\n{}\n{}\n", lines[0], lines[1], lines[3])).unwrap();
+        let report = verify_ledger(&path);
+        assert_eq!(report.records, 1);
+        assert!(matches!(report.first_fault.unwrap().fault, LedgerFault::SequenceGap { expected: 1, found: 2 }));
+        let _ = fs::remove_file(&path);
     }
 }
@@ -1,21 +1,128 @@
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
-use serde::Deserialize;
+use actix_web::http::{header, StatusCode};
+use actix_web::{error, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::{Arc, Mutex};
 
-use crate::economy::EconomyEngine;
-use crate::telemetry::TelemetryEngine;
+use crate::auth::{ApiKeyAuth, KeyStore, Principal};
+use crate::economy::{
+    statement_csv, AccountId, Catalog, Entitlement, transactions_csv, AmountInput, Currency, EconomyEngine, EconomyError, IdempotencyKey, Money,
+    Transaction, TransactionFilter, TransactionStatus, TransactionType,
+};
+use crate::events::{self, EventStream};
+use crate::intelligence::{verify_chain, AkashicChain, AkashicRecord, NeuralMessage};
+use crate::limits::{LimitsConfig, RateLimit, RateLimiter};
+use crate::metrics::{self, FailureKind, Metrics, RequestMetrics};
+use crate::telemetry::{parse_duration, SystemMetrics, TelemetryEngine, TelemetryHistory};
 
-#[derive(Debug, Deserialize)]
+/// Amounts are exact decimals (number or string); `currency` defaults to credits.
+#[derive(Debug, Serialize, Deserialize)]
 struct MintCreditsRequest {
     user_id: String,
-    amount: f64,
+    amount: AmountInput,
+    #[serde(default)]
+    currency: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// The price comes from the catalog; `cost`, if sent, must match it.
+#[derive(Debug, Serialize, Deserialize)]
 struct UnlockModuleRequest {
     user_id: String,
     module_name: String,
-    cost: f64,
+    #[serde(default)]
+    cost: Option<AmountInput>,
+    #[serde(default)]
+    currency: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TransferRequest {
+    from_user_id: String,
+    to_user_id: String,
+    amount: AmountInput,
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    memo: Option<String>,
+}
+
+/// Refund or reversal of an earlier transaction.
+#[derive(Debug, Serialize, Deserialize)]
+struct UndoRequest {
+    transaction_id: String,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// `GET /api/transactions`. Amounts are decimals, times unix seconds; `format=csv` exports.
+/// `status` is `posted` (the default), `rejected` or `all`.
+#[derive(Debug, Deserialize)]
+struct TransactionQuery {
+    user_id: Option<String>,
+    #[serde(rename = "type")]
+    transaction_type: Option<TransactionType>,
+    min_amount: Option<String>,
+    max_amount: Option<String>,
+    from: Option<u64>,
+    to: Option<u64>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    format: Option<String>,
+    status: Option<String>,
+}
+
+/// `GET /api/events`. Everyone's events without `user_id`; `last_event_id` resumes like
+/// the `Last-Event-ID` header, which takes precedence.
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    user_id: Option<String>,
+    last_event_id: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatementQuery {
+    from: Option<u64>,
+    to: Option<u64>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    format: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TrialRequest {
+    user_id: String,
+    module_name: String,
+    #[serde(default)]
+    auto_renew: bool,
+}
+
+/// Free access paid for elsewhere; permanent unless `days` is set.
+#[derive(Debug, Serialize, Deserialize)]
+struct GrantRequest {
+    user_id: String,
+    module_name: String,
+    #[serde(default)]
+    days: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AutoRenewRequest {
+    user_id: String,
+    module_name: String,
+    auto_renew: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct EntitlementQuery {
+    user_id: String,
+    module_name: String,
+}
+
+/// Durations are seconds, or take an `s`, `m`, `h` or `d` suffix.
+#[derive(Debug, Deserialize)]
+struct TelemetryHistoryQuery {
+    range: Option<String>,
+    step: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,9 +130,198 @@ struct GetBalanceRequest {
     user_id: String,
 }
 
+/// The conversation behind a decision, and the decision itself.
+#[derive(Debug, Deserialize)]
+struct SealRequest {
+    messages: Vec<NeuralMessage>,
+    decision_index: f64,
+    #[serde(default)]
+    veritas_approved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceRequest {
+    messages: Vec<NeuralMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChainRequest {
+    records: Vec<AkashicRecord>,
+}
+
 struct AppState {
     economy: Arc<EconomyEngine>,
     telemetry: Arc<Mutex<TelemetryEngine>>,
+    /// Filled by the background sampler in `run_server`
+    history: Arc<TelemetryHistory>,
+    akashic: Arc<AkashicChain>,
+    metrics: Arc<Metrics>,
+}
+
+fn status_for(error: &EconomyError) -> StatusCode {
+    match error {
+        EconomyError::InvalidRequest(_) | EconomyError::InvalidAmount(_) | EconomyError::UnknownCurrency(_) => {
+            StatusCode::BAD_REQUEST
+        }
+        EconomyError::CurrencyMismatch { .. } | EconomyError::Overflow => StatusCode::UNPROCESSABLE_ENTITY,
+        EconomyError::UserNotFound(_)
+        | EconomyError::TransactionNotFound(_)
+        | EconomyError::ModuleNotFound(_)
+        | EconomyError::NotEntitled { .. } => StatusCode::NOT_FOUND,
+        EconomyError::InsufficientCredits { .. } => StatusCode::PAYMENT_REQUIRED,
+        EconomyError::AlreadyReversed { .. }
+        | EconomyError::NotReversible(_)
+        | EconomyError::DuplicateRequest { .. }
+        | EconomyError::PriceMismatch { .. }
+        | EconomyError::MissingPrerequisite { .. }
+        | EconomyError::AlreadyEntitled(_)
+        | EconomyError::TrialUnavailable(_) => StatusCode::CONFLICT,
+        EconomyError::IdempotencyKeyReused(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        EconomyError::SpendingCapExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+        // Entries are built server-side, so an unbalanced one is a bug
+        EconomyError::Unbalanced(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        EconomyError::PersistenceDisabled => StatusCode::SERVICE_UNAVAILABLE,
+        EconomyError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn error_response(error: &EconomyError) -> HttpResponse {
+    let mut response = HttpResponse::build(status_for(error)).json(serde_json::json!({
+        "error": error.to_string(),
+        "kind": error.kind()
+    }));
+    response.extensions_mut().insert(FailureKind(error.kind()));
+    response
+}
+
+fn parse_currency(code: &Option<String>) -> Result<Currency, EconomyError> {
+    code.as_deref().map_or(Ok(Currency::Credits), Currency::from_code)
+}
+
+fn wants_csv(format: &Option<String>) -> Result<bool, EconomyError> {
+    match format.as_deref() {
+        None | Some("json") => Ok(false),
+        Some("csv") => Ok(true),
+        Some(other) => Err(EconomyError::InvalidRequest(format!("unknown format '{}'", other))),
+    }
+}
+
+fn parse_status(status: &Option<String>) -> Result<Option<TransactionStatus>, EconomyError> {
+    match status.as_deref() {
+        None | Some("posted") => Ok(Some(TransactionStatus::Posted)),
+        Some("rejected") => Ok(Some(TransactionStatus::Rejected)),
+        Some("all") => Ok(None),
+        Some(other) => Err(EconomyError::InvalidRequest(format!("unknown status '{}'", other))),
+    }
+}
+
+/// CSV body; the cursor for the next page travels in `X-Next-Cursor`.
+fn csv_response(body: String, next_cursor: Option<&str>) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    response.content_type("text/csv; charset=utf-8");
+    if let Some(cursor) = next_cursor {
+        response.insert_header(("X-Next-Cursor", cursor.to_string()));
+    }
+    response.body(body)
+}
+
+/// Malformed bodies get the same error shape as engine errors.
+fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|err, _| {
+        let response = error_response(&EconomyError::InvalidRequest(err.to_string()));
+        error::InternalError::from_response(err, response).into()
+    })
+}
+
+fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|err, _| {
+        let response = error_response(&EconomyError::InvalidRequest(err.to_string()));
+        error::InternalError::from_response(err, response).into()
+    })
+}
+
+/// How a replayed request rebuilds the response it originally got.
+enum Replay {
+    /// The user's balance right after the transaction (mint, unlock)
+    Balance,
+    /// The transaction itself (transfer, refund, reverse)
+    Transaction,
+    /// The entitlement the transaction conferred (trial, grant)
+    Entitlement,
+}
+
+/// Reads the optional `Idempotency-Key` header and fingerprints the request it came with.
+/// Keys are scoped to the caller's API key, so clients cannot collide with or probe for
+/// each other's keys.
+fn idempotency_key(req: &HttpRequest, route: &str, body: &impl Serialize) -> Result<Option<IdempotencyKey>, EconomyError> {
+    let Some(value) = req.headers().get("Idempotency-Key") else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|k| !k.is_empty() && k.len() <= 255)
+        .ok_or_else(|| EconomyError::InvalidRequest("Idempotency-Key must be 1-255 visible ASCII characters".to_string()))?;
+
+    let scope = req.extensions().get::<Principal>().map(|p| p.key_id.clone());
+    let mut hasher = Sha256::new();
+    hasher.update(scope.as_deref().unwrap_or("").as_bytes());
+    hasher.update(b"\n");
+    hasher.update(route.as_bytes());
+    hasher.update(serde_json::to_vec(body).map_err(|e| EconomyError::InvalidRequest(e.to_string()))?);
+    Ok(Some(IdempotencyKey { key: key.to_string(), fingerprint: format!("{:x}", hasher.finalize()), scope }))
+}
+
+fn replayed(state: &AppState, key: &IdempotencyKey, transaction: &Transaction, replay: Replay) -> HttpResponse {
+    if transaction.idempotency.as_ref().map(|i| &i.fingerprint) != Some(&key.fingerprint) {
+        return error_response(&EconomyError::IdempotencyKeyReused(key.key.clone()));
+    }
+    let body = match replay {
+        Replay::Balance => transaction
+            .user_id()
+            .and_then(|user_id| state.economy.get_balance_after(&transaction.id, user_id))
+            .map(|balance| serde_json::json!(balance)),
+        Replay::Transaction => Some(serde_json::json!(transaction)),
+        Replay::Entitlement => Entitlement::granted_by(transaction).map(|entitlement| serde_json::json!(entitlement)),
+    };
+    match body {
+        Some(body) => HttpResponse::Ok().insert_header(("Idempotent-Replayed", "true")).json(body),
+        None => error_response(&EconomyError::TransactionNotFound(transaction.id.clone())),
+    }
+}
+
+/// Runs a mutating request at most once per `Idempotency-Key`. The key is stored on the
+/// committed transaction, so a retry with the same key and body gets the original
+/// response back (marked `Idempotent-Replayed: true`) even after a restart.
+fn idempotent<T: Serialize>(
+    req: &HttpRequest,
+    state: &AppState,
+    route: &str,
+    body: &impl Serialize,
+    replay: Replay,
+    run: impl FnOnce(Option<IdempotencyKey>) -> Result<T, EconomyError>,
+) -> HttpResponse {
+    let key = match idempotency_key(req, route, body) {
+        Ok(key) => key,
+        Err(e) => return error_response(&e),
+    };
+    if let Some(key) = &key {
+        if let Some(transaction) = state.economy.find_idempotent(key) {
+            return replayed(state, key, &transaction, replay);
+        }
+    }
+    match run(key.clone()) {
+        Ok(result) => HttpResponse::Ok().json(result),
+        // A concurrent request with the same key committed first
+        Err(e @ EconomyError::DuplicateRequest { .. }) => {
+            match key.as_ref().and_then(|k| state.economy.find_idempotent(k).map(|t| (k, t))) {
+                Some((key, transaction)) => replayed(state, key, &transaction, replay),
+                None => error_response(&e),
+            }
+        }
+        Err(e) => error_response(&e),
+    }
 }
 
 async fn health_check() -> impl Responder {
@@ -36,50 +332,308 @@ async fn health_check() -> impl Responder {
 }
 
 async fn mint_credits(
+    req: HttpRequest,
+    principal: Principal,
     data: web::Json<MintCreditsRequest>,
     state: web::Data<AppState>,
 ) -> impl Responder {
-    match state.economy.mint_credits(data.user_id.clone(), data.amount) {
-        Ok(balance) => HttpResponse::Ok().json(balance),
-        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
-            "error": e
-        })),
-    }
+    idempotent(&req, &state, "mint_credits", &*data, Replay::Balance, |key| {
+        let amount = parse_currency(&data.currency).and_then(|c| data.amount.to_money(c))?;
+        // Stamped with the key, which the hourly mint cap is counted against
+        state.economy.mint_credits_as(Some(principal.key_id.clone()), data.user_id.clone(), amount, key)
+    })
 }
 
 async fn unlock_module(
+    req: HttpRequest,
+    principal: Principal,
     data: web::Json<UnlockModuleRequest>,
     state: web::Data<AppState>,
 ) -> impl Responder {
-    match state.economy.unlock_module(
-        data.user_id.clone(),
-        data.module_name.clone(),
-        data.cost,
-    ) {
-        Ok(balance) => HttpResponse::Ok().json(balance),
-        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({
-            "error": e
-        })),
+    if let Err(e) = principal.check_user(&data.user_id) {
+        return e.error_response();
+    }
+    idempotent(&req, &state, "unlock_module", &*data, Replay::Balance, |key| {
+        let currency = parse_currency(&data.currency)?;
+        let cost = data.cost.as_ref().map(|c| c.to_money(currency)).transpose()?;
+        state.economy.unlock_module(data.user_id.clone(), data.module_name.clone(), cost, key)
+    })
+}
+
+async fn start_trial(
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Json<TrialRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = principal.check_user(&data.user_id) {
+        return e.error_response();
+    }
+    idempotent(&req, &state, "trial", &*data, Replay::Entitlement, |key| {
+        state.economy.start_trial(data.user_id.clone(), data.module_name.clone(), data.auto_renew, key)
+    })
+}
+
+async fn grant_module(
+    req: HttpRequest,
+    data: web::Json<GrantRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    idempotent(&req, &state, "grant", &*data, Replay::Entitlement, |key| {
+        state.economy.grant_module(data.user_id.clone(), data.module_name.clone(), data.days, key)
+    })
+}
+
+async fn set_auto_renew(
+    principal: Principal,
+    data: web::Json<AutoRenewRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = principal.check_user(&data.user_id) {
+        return e.error_response();
+    }
+    // Naturally idempotent: setting the same flag twice changes nothing
+    match state.economy.set_auto_renew(&data.user_id, &data.module_name, data.auto_renew) {
+        Ok(entitlement) => HttpResponse::Ok().json(entitlement),
+        Err(e) => error_response(&e),
+    }
+}
+
+/// For other services to call before enabling a module. Always 200; see `entitled`.
+async fn check_entitlement(
+    principal: Principal,
+    query: web::Query<EntitlementQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = principal.check_user(&query.user_id) {
+        return e.error_response();
+    }
+    HttpResponse::Ok().json(state.economy.check_entitlement(&query.user_id, &query.module_name))
+}
+
+async fn list_entitlements(
+    principal: Principal,
+    query: web::Query<GetBalanceRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = principal.check_user(&query.user_id) {
+        return e.error_response();
+    }
+    HttpResponse::Ok().json(state.economy.get_entitlements(&query.user_id))
+}
+
+async fn get_catalog(state: web::Data<AppState>) -> impl Responder {
+    let modules: Vec<_> = state.economy.catalog().modules().collect();
+    HttpResponse::Ok().json(serde_json::json!({ "modules": modules }))
+}
+
+async fn run_renewals(state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(state.economy.renew_due(unix_now()))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+async fn transfer(
+    req: HttpRequest,
+    principal: Principal,
+    data: web::Json<TransferRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = principal.check_user(&data.from_user_id) {
+        return e.error_response();
     }
+    idempotent(&req, &state, "transfer", &*data, Replay::Transaction, |key| {
+        let amount = parse_currency(&data.currency).and_then(|c| data.amount.to_money(c))?;
+        state.economy.transfer(data.from_user_id.clone(), data.to_user_id.clone(), amount, data.memo.clone(), key)
+    })
+}
+
+async fn refund(
+    req: HttpRequest,
+    data: web::Json<UndoRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    idempotent(&req, &state, "refund", &*data, Replay::Transaction, |key| {
+        state.economy.refund_unlock(&data.transaction_id, data.reason.clone(), key)
+    })
+}
+
+async fn reverse(
+    req: HttpRequest,
+    data: web::Json<UndoRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    idempotent(&req, &state, "reverse", &*data, Replay::Transaction, |key| {
+        state.economy.reverse_mint(&data.transaction_id, data.reason.clone(), key)
+    })
+}
+
+async fn get_transaction(
+    principal: Principal,
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let id = path.into_inner();
+    // Other users' transactions look the same as missing ones to an own-scope key
+    let visible = |transaction: &Transaction| {
+        principal.restricted_to().is_none_or(|own| transaction.amount_for(&AccountId::user(own)).is_some())
+    };
+    match state.economy.get_transaction(&id).filter(visible) {
+        Some(transaction) => HttpResponse::Ok().json(transaction),
+        None => error_response(&EconomyError::TransactionNotFound(id)),
+    }
+}
+
+async fn stream_events(
+    req: HttpRequest,
+    principal: Principal,
+    query: web::Query<EventsQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    // An own-scope key streams its own user's events
+    let user_id = query.user_id.clone().or_else(|| principal.restricted_to().map(str::to_string));
+    if let Err(e) = user_id.as_deref().map_or(Ok(()), |user_id| principal.check_user(user_id)) {
+        return e.error_response();
+    }
+    let resume = req.headers().get("Last-Event-ID").map(|v| {
+        v.to_str().ok().and_then(|v| v.trim().parse::<u64>().ok()).ok_or_else(|| {
+            EconomyError::InvalidRequest("Last-Event-ID must be an event id".to_string())
+        })
+    });
+    let after = match resume.transpose() {
+        Ok(after) => after.or(query.last_event_id),
+        Err(e) => return error_response(&e),
+    };
+    let subscription = state.economy.subscribe_events(user_id.clone(), after);
+    HttpResponse::Ok()
+        .content_type(events::CONTENT_TYPE)
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Keeps nginx from buffering the stream
+        .insert_header(("X-Accel-Buffering", "no"))
+        .body(EventStream::new(subscription, user_id, after.is_some()))
+}
+
+async fn list_transactions(
+    principal: Principal,
+    query: web::Query<TransactionQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    // An own-scope key lists its own user's transactions unless it names someone else
+    let user_id = query.user_id.clone().or_else(|| principal.restricted_to().map(str::to_string));
+    if let Err(e) = user_id.as_deref().map_or(Ok(()), |user_id| principal.check_user(user_id)) {
+        return e.error_response();
+    }
+    let parse = |amount: &Option<String>| amount.as_deref().map(|a| Money::parse(a, Currency::Credits)).transpose();
+    let page = (|| {
+        let filter = TransactionFilter {
+            user_id: user_id.clone(),
+            transaction_type: query.transaction_type,
+            min_amount: parse(&query.min_amount)?,
+            max_amount: parse(&query.max_amount)?,
+            from: query.from,
+            to: query.to,
+            status: parse_status(&query.status)?,
+        };
+        let csv = wants_csv(&query.format)?;
+        state.economy.list_transactions(&filter, query.cursor.as_deref(), query.limit).map(|page| (page, csv))
+    })();
+    match page {
+        Ok((page, true)) => csv_response(transactions_csv(&page.transactions), page.next_cursor.as_deref()),
+        Ok((page, false)) => HttpResponse::Ok().json(page),
+        Err(e) => error_response(&e),
+    }
+}
+
+async fn get_statement(
+    principal: Principal,
+    path: web::Path<String>,
+    query: web::Query<StatementQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if let Err(e) = principal.check_user(&path) {
+        return e.error_response();
+    }
+    let statement = wants_csv(&query.format).and_then(|csv| {
+        state
+            .economy
+            .statement(&path, query.from, query.to, query.cursor.as_deref(), query.limit)
+            .map(|statement| (statement, csv))
+    });
+    match statement {
+        Ok((statement, true)) => csv_response(statement_csv(&statement), statement.next_cursor.as_deref()),
+        Ok((statement, false)) => HttpResponse::Ok().json(statement),
+        Err(e) => error_response(&e),
+    }
+}
+
+async fn list_accounts(state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(state.economy.get_accounts())
 }
 
 async fn get_balance(
+    principal: Principal,
     query: web::Query<GetBalanceRequest>,
     state: web::Data<AppState>,
 ) -> impl Responder {
+    if let Err(e) = principal.check_user(&query.user_id) {
+        return e.error_response();
+    }
     match state.economy.get_balance(&query.user_id) {
         Some(balance) => HttpResponse::Ok().json(balance),
-        None => HttpResponse::NotFound().json(serde_json::json!({
-            "error": "User not found"
-        })),
+        None => error_response(&EconomyError::UserNotFound(query.user_id.clone())),
     }
 }
 
-async fn get_telemetry(state: web::Data<AppState>) -> impl Responder {
+/// The sampler's latest reading, or a fresh one before it has taken any.
+fn current_telemetry(state: &AppState) -> SystemMetrics {
     let transaction_count = state.economy.get_transaction_count();
-    let mut telemetry = state.telemetry.lock().unwrap();
-    let metrics = telemetry.get_metrics(transaction_count);
-    HttpResponse::Ok().json(metrics)
+    match state.history.latest() {
+        Some(metrics) => SystemMetrics { active_transactions: transaction_count, ..metrics },
+        None => state.telemetry.lock().unwrap().get_metrics(transaction_count),
+    }
+}
+
+async fn get_telemetry(state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(current_telemetry(&state))
+}
+
+/// Sampled readings of the last `range` (default 1h) in buckets of `step` (default a
+/// sixtieth of the range), each with the min/avg/max of every series.
+async fn get_telemetry_history(
+    query: web::Query<TelemetryHistoryQuery>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    let buckets = (|| {
+        let range = query.range.as_deref().map_or(Ok(3600), parse_duration)?;
+        let step = query.step.as_deref().map_or(Ok(range.div_ceil(60).max(1)), parse_duration)?;
+        let now = unix_now();
+        state.history.downsample(range, step, now).map(|buckets| (range, step, now, buckets))
+    })();
+    match buckets {
+        Ok((range, step, now, buckets)) => HttpResponse::Ok().json(serde_json::json!({
+            "from": now.saturating_sub(range),
+            "to": now,
+            "step": step,
+            "buckets": buckets,
+        })),
+        Err(e) => error_response(&EconomyError::InvalidRequest(e)),
+    }
+}
+
+/// Prometheus scrape target.
+async fn get_metrics(state: web::Data<AppState>) -> impl Responder {
+    let totals = match state.economy.totals_by_type() {
+        Ok(totals) => totals,
+        Err(e) => return error_response(&e),
+    };
+    let host = current_telemetry(&state);
+    HttpResponse::Ok()
+        .content_type(metrics::CONTENT_TYPE)
+        .body(state.metrics.render(&totals, &host))
 }
 
 async fn list_balances(state: web::Data<AppState>) -> impl Responder {
@@ -87,28 +641,686 @@ async fn list_balances(state: web::Data<AppState>) -> impl Responder {
     HttpResponse::Ok().json(balances)
 }
 
+async fn verify_storage(state: web::Data<AppState>) -> impl Responder {
+    // Replaying the whole log is slow; keep it off the async workers
+    let economy = state.economy.clone();
+    match web::block(move || economy.verify_storage()).await {
+        Ok(Ok(report)) if report.consistent => HttpResponse::Ok().json(report),
+        Ok(Ok(report)) => HttpResponse::InternalServerError().json(report),
+        Ok(Err(e)) => error_response(&e),
+        Err(e) => error_response(&EconomyError::Storage(e.to_string())),
+    }
+}
+
+async fn seal_context(
+    data: web::Json<SealRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    if !data.decision_index.is_finite() {
+        return error_response(&EconomyError::InvalidRequest("decision_index must be a finite number".to_string()));
+    }
+    match state.akashic.seal(&data.messages, data.decision_index, data.veritas_approved) {
+        Ok(record) => HttpResponse::Ok().json(record),
+        Err(e) => error_response(&EconomyError::Storage(e)),
+    }
+}
+
+/// Every sealed decision taken on exactly this conversation.
+async fn trace_context(
+    data: web::Json<TraceRequest>,
+    state: web::Data<AppState>,
+) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "records": state.akashic.trace(&data.messages) }))
+}
+
+async fn verify_akashic(state: web::Data<AppState>) -> impl Responder {
+    let report = state.akashic.verify();
+    if report.valid {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::InternalServerError().json(report)
+    }
+}
+
+/// Checks a chain held by the caller; `valid` says whether it holds together.
+async fn verify_supplied_chain(data: web::Json<ChainRequest>) -> impl Responder {
+    HttpResponse::Ok().json(verify_chain(&data.records))
+}
+
+/// Every route of the economy API.
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(json_config())
+        .app_data(query_config())
+        .route("/health", web::get().to(health_check))
+        .route("/api/mint_credits", web::post().to(mint_credits))
+        .route("/api/unlock_module", web::post().to(unlock_module))
+        .route("/api/transfer", web::post().to(transfer))
+        .route("/api/refund", web::post().to(refund))
+        .route("/api/reverse", web::post().to(reverse))
+        .route("/api/transactions", web::get().to(list_transactions))
+        .route("/api/events", web::get().to(stream_events))
+        .route("/api/transactions/{id}", web::get().to(get_transaction))
+        .route("/api/users/{user_id}/statement", web::get().to(get_statement))
+        .route("/api/ledger/accounts", web::get().to(list_accounts))
+        .route("/api/catalog", web::get().to(get_catalog))
+        .route("/api/entitlements", web::get().to(list_entitlements))
+        .route("/api/entitlements/check", web::get().to(check_entitlement))
+        .route("/api/entitlements/trial", web::post().to(start_trial))
+        .route("/api/entitlements/grant", web::post().to(grant_module))
+        .route("/api/entitlements/auto_renew", web::post().to(set_auto_renew))
+        .route("/api/admin/renewals/run", web::post().to(run_renewals))
+        .route("/api/balance", web::get().to(get_balance))
+        .route("/api/telemetry", web::get().to(get_telemetry))
+        .route("/api/telemetry/history", web::get().to(get_telemetry_history))
+        .route("/metrics", web::get().to(get_metrics))
+        .route("/api/balances", web::get().to(list_balances))
+        .route("/api/admin/storage/verify", web::get().to(verify_storage))
+        .route("/api/akashic/seal", web::post().to(seal_context))
+        .route("/api/akashic/trace", web::post().to(trace_context))
+        .route("/api/akashic/verify", web::get().to(verify_akashic))
+        .route("/api/akashic/verify", web::post().to(verify_supplied_chain));
+}
+
 pub async fn run_server() -> std::io::Result<()> {
-    let economy = Arc::new(EconomyEngine::new());
+    // Every balance change is fsynced to LWAS_DATA_DIR before it is acknowledged
+    let data_dir = std::env::var("LWAS_DATA_DIR").unwrap_or_else(|_| "economy_data".to_string());
+    let snapshot_interval = std::env::var("LWAS_SNAPSHOT_INTERVAL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1000);
+    let mut economy = EconomyEngine::open(&data_dir, snapshot_interval)
+        .map_err(std::io::Error::other)?;
+    if let Ok(path) = std::env::var("LWAS_CATALOG") {
+        economy = economy.with_catalog(Catalog::load(&path).map_err(std::io::Error::other)?);
+        println!("📚 Module catalog loaded from {}", path);
+    }
+    // Rate limits and spending caps; the built-in rate limits and no caps otherwise
+    let limits = match std::env::var("LWAS_LIMITS") {
+        Ok(path) => {
+            let limits = LimitsConfig::load(&path).map_err(std::io::Error::other)?;
+            println!("🚦 Rate limits and spending caps loaded from {}", path);
+            limits
+        }
+        Err(_) => LimitsConfig::builtin(),
+    };
+    economy = economy.with_caps(limits.spending_caps);
+    // Events kept for `/api/events` clients that reconnect with `Last-Event-ID`
+    if let Some(backlog) = std::env::var("LWAS_EVENT_BACKLOG").ok().and_then(|v| v.parse().ok()) {
+        economy = economy.with_event_backlog(backlog);
+    }
+    println!("📒 Economy state restored from {} ({} transactions)", data_dir, economy.get_transaction_count());
+    let economy = Arc::new(economy);
     let telemetry = Arc::new(Mutex::new(TelemetryEngine::new()));
 
+    // Host readings every LWAS_TELEMETRY_INTERVAL_SECS into a ring of LWAS_TELEMETRY_HISTORY samples
+    let sample_interval = std::env::var("LWAS_TELEMETRY_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5u64)
+        .max(1);
+    let history_size = std::env::var("LWAS_TELEMETRY_HISTORY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(17_280); // A day at the default interval
+    let history = Arc::new(TelemetryHistory::new(history_size));
+    let (sampler, sampled, counted) = (telemetry.clone(), history.clone(), economy.clone());
+    // Reading /proc and the sensors blocks, so it stays off the async workers
+    std::thread::spawn(move || loop {
+        let metrics = sampler.lock().unwrap().get_metrics(counted.get_transaction_count());
+        sampled.record(metrics);
+        std::thread::sleep(std::time::Duration::from_secs(sample_interval));
+    });
+
+    // Sealed AI decisions, chained record to record; refuses to start on a broken chain
+    let akashic_path = std::env::var("LWAS_AKASHIC_PATH").unwrap_or_else(|_| format!("{}/akashic.chain", data_dir));
+    let akashic = Arc::new(AkashicChain::open(&akashic_path).map_err(std::io::Error::other)?);
+    println!("🔗 Akashic chain restored from {} ({} records)", akashic_path, akashic.records().len());
+
+    // Charge and extend expired auto-renewing subscriptions in the background
+    let renewal_interval = std::env::var("LWAS_RENEWAL_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(300);
+    let renewals = economy.clone();
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(std::time::Duration::from_secs(renewal_interval));
+        loop {
+            interval.tick().await;
+            let report = renewals.renew_due(unix_now());
+            if !report.renewed.is_empty() || !report.lapsed.is_empty() {
+                println!("🔁 Renewed {} subscriptions, {} lapsed", report.renewed.len(), report.lapsed.len());
+            }
+        }
+    });
+
+    let metrics = Arc::new(Metrics::new());
     let app_state = web::Data::new(AppState {
         economy,
         telemetry,
+        history,
+        akashic,
+        metrics: metrics.clone(),
     });
 
+    // API keys live next to the journal and are managed with the lwas_keys CLI
+    let auth = if std::env::var("LWAS_AUTH_DISABLED").is_ok_and(|v| v == "1") {
+        println!("⚠️ LWAS_AUTH_DISABLED=1: every request is allowed with every scope");
+        ApiKeyAuth::allow_all()
+    } else {
+        let keys = KeyStore::open(&data_dir).map_err(std::io::Error::other)?;
+        if keys.keys().iter().all(|k| k.revoked_at.is_some()) {
+            println!("🔑 No active API keys in {}; create one with `lwas_keys create`", data_dir);
+        }
+        ApiKeyAuth::new(keys)
+    };
+
+    let limiter = Arc::new(RateLimiter::new(limits.rate_limits));
+    let economy = app_state.economy.clone();
+
     println!("🚀 LWAS Economy Server starting on http://0.0.0.0:8890");
 
     HttpServer::new(move || {
         App::new()
+            // Innermost, so the key is already resolved when requests are counted
+            .wrap(RateLimit::new(limiter.clone(), economy.clone()))
+            .wrap(auth.clone())
+            // Outermost, so requests rejected by the key check are timed and counted too
+            .wrap(RequestMetrics::new(metrics.clone()))
             .app_data(app_state.clone())
-            .route("/health", web::get().to(health_check))
-            .route("/api/mint_credits", web::post().to(mint_credits))
-            .route("/api/unlock_module", web::post().to(unlock_module))
-            .route("/api/balance", web::get().to(get_balance))
-            .route("/api/telemetry", web::get().to(get_telemetry))
-            .route("/api/balances", web::get().to(list_balances))
+            .configure(routes)
     })
     .bind("0.0.0.0:8890")?
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Scope;
+    use crate::limits::{RateRule, Subject};
+    use actix_web::body::{BoxBody, MessageBody};
+    use actix_web::test;
+
+    fn state() -> web::Data<AppState> {
+        web::Data::new(AppState {
+            economy: Arc::new(EconomyEngine::new()),
+            telemetry: Arc::new(Mutex::new(TelemetryEngine::new())),
+            history: Arc::new(TelemetryHistory::new(16)),
+            akashic: Arc::new(AkashicChain::new()),
+            metrics: Arc::new(Metrics::new()),
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_amounts_map_to_status_codes() {
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::allow_all()).app_data(state()).configure(routes)).await;
+        let post = |uri: &str, body: serde_json::Value| test::TestRequest::post().uri(uri).set_json(body).to_request();
+
+        let cases = [
+            (serde_json::json!({"user_id": "alice", "amount": -5}), StatusCode::BAD_REQUEST, "invalid_amount"),
+            (serde_json::json!({"user_id": "alice", "amount": "1.005"}), StatusCode::BAD_REQUEST, "invalid_amount"),
+            (serde_json::json!({"user_id": "alice", "amount": 1, "currency": "XYZ"}), StatusCode::BAD_REQUEST, "unknown_currency"),
+            (serde_json::json!({"user_id": "alice", "amount": 1, "currency": "EUR"}), StatusCode::UNPROCESSABLE_ENTITY, "currency_mismatch"),
+            (serde_json::json!({"user_id": "alice"}), StatusCode::BAD_REQUEST, "invalid_request"),
+        ];
+        for (body, status, kind) in cases {
+            let response = test::call_service(&app, post("/api/mint_credits", body.clone())).await;
+            assert_eq!(response.status(), status, "{}", body);
+            let json: serde_json::Value = test::read_body_json(response).await;
+            assert_eq!(json["kind"], kind);
+        }
+
+        let minted = test::call_service(&app, post("/api/mint_credits", serde_json::json!({"user_id": "alice", "amount": 0.1}))).await;
+        let json: serde_json::Value = test::read_body_json(minted).await;
+        assert_eq!(json["credits"]["amount"], "0.10");
+
+        let unlock = serde_json::json!({"user_id": "alice", "module_name": "basic_analytics"});
+        let response = test::call_service(&app, post("/api/unlock_module", unlock)).await;
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        let unlock = serde_json::json!({"user_id": "alice", "module_name": "basic_analytics", "cost": "0.11"});
+        let response = test::call_service(&app, post("/api/unlock_module", unlock)).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn test_refund_endpoint_and_conflicts() {
+        let data = state();
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::allow_all()).app_data(data.clone()).configure(routes)).await;
+        let post = |uri: &str, body: serde_json::Value| test::TestRequest::post().uri(uri).set_json(body).to_request();
+
+        test::call_service(&app, post("/api/mint_credits", serde_json::json!({"user_id": "alice", "amount": 50}))).await;
+        test::call_service(&app, post("/api/unlock_module", serde_json::json!({"user_id": "alice", "module_name": "basic_analytics", "cost": 10}))).await;
+        let accounts: serde_json::Value =
+            test::read_body_json(test::call_service(&app, test::TestRequest::get().uri("/api/ledger/accounts").to_request()).await).await;
+        assert_eq!(accounts[0], serde_json::json!({"account": "user:alice", "balance": {"amount": "40.00", "currency": "CRD"}}));
+
+        let unlock_id = data.economy.get_user_transactions("alice")[1].id.clone();
+        let lookup = test::call_service(&app, test::TestRequest::get().uri(&format!("/api/transactions/{}", unlock_id)).to_request()).await;
+        assert_eq!(lookup.status(), StatusCode::OK);
+
+        let refund = serde_json::json!({"transaction_id": unlock_id, "reason": "not needed"});
+        let response = test::call_service(&app, post("/api/refund", refund.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(json["transaction_type"], "refund");
+
+        let again = test::call_service(&app, post("/api/refund", refund)).await;
+        assert_eq!(again.status(), StatusCode::CONFLICT);
+        let missing = test::call_service(&app, post("/api/reverse", serde_json::json!({"transaction_id": "tx_nope"}))).await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_transaction_history_pages_and_exports() {
+        let data = state();
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::allow_all()).app_data(data.clone()).configure(routes)).await;
+        for amount in [1, 2, 3] {
+            data.economy.mint_credits("alice".into(), Money::credits(amount * 100), None).unwrap();
+        }
+        let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+        let page: serde_json::Value =
+            test::read_body_json(test::call_service(&app, get("/api/transactions?user_id=alice&type=mint&limit=2")).await).await;
+        assert_eq!(page["transactions"].as_array().unwrap().len(), 2);
+        let cursor = page["next_cursor"].as_str().unwrap();
+        let rest: serde_json::Value =
+            test::read_body_json(test::call_service(&app, get(&format!("/api/transactions?limit=2&cursor={}", cursor))).await).await;
+        assert_eq!(rest["transactions"][0]["postings"][1]["amount"]["amount"], "1.00");
+        assert_eq!(rest["next_cursor"], serde_json::Value::Null);
+
+        let csv = test::call_service(&app, get("/api/users/alice/statement?format=csv")).await;
+        assert_eq!(csv.headers().get("content-type").unwrap(), "text/csv; charset=utf-8");
+        let body = test::read_body(csv).await;
+        assert_eq!(String::from_utf8_lossy(&body).lines().last().unwrap().split(',').nth(4), Some("6.00"));
+
+        let bad = test::call_service(&app, get("/api/transactions?min_amount=abc")).await;
+        assert_eq!(bad.status(), StatusCode::BAD_REQUEST);
+        let missing = test::call_service(&app, get("/api/users/nobody/statement")).await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_entitlement_check_follows_trial_and_grant() {
+        let data = state();
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::allow_all()).app_data(data.clone()).configure(routes)).await;
+        let post = |uri: &str, body: serde_json::Value| test::TestRequest::post().uri(uri).set_json(body).to_request();
+        let check = |module: &str| {
+            test::TestRequest::get().uri(&format!("/api/entitlements/check?user_id=alice&module_name={}", module)).to_request()
+        };
+        data.economy.mint_credits("alice".into(), Money::credits(100), None).unwrap();
+
+        let json: serde_json::Value = test::read_body_json(test::call_service(&app, check("basic_analytics")).await).await;
+        assert_eq!(json["entitled"], false);
+
+        let trial = serde_json::json!({"user_id": "alice", "module_name": "basic_analytics"});
+        let response = test::call_service(&app, post("/api/entitlements/trial", trial.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value = test::read_body_json(test::call_service(&app, check("basic_analytics")).await).await;
+        assert_eq!(json["entitled"], true);
+        assert_eq!(json["entitlement"]["kind"], "trial");
+        let again = test::call_service(&app, post("/api/entitlements/trial", trial)).await;
+        assert_eq!(again.status(), StatusCode::CONFLICT);
+
+        let grant = serde_json::json!({"user_id": "alice", "module_name": "enterprise_support"});
+        test::call_service(&app, post("/api/entitlements/grant", grant)).await;
+        let json: serde_json::Value = test::read_body_json(test::call_service(&app, check("enterprise_support")).await).await;
+        assert_eq!(json["entitlement"]["kind"], "permanent");
+
+        let catalog: serde_json::Value =
+            test::read_body_json(test::call_service(&app, test::TestRequest::get().uri("/api/catalog").to_request()).await).await;
+        assert!(catalog["modules"].as_array().unwrap().iter().any(|m| m["name"] == "ai_insights"));
+    }
+
+    #[actix_web::test]
+    async fn test_idempotency_key_replays_original_response() {
+        let data = state();
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::allow_all()).app_data(data.clone()).configure(routes)).await;
+        let mint = |key: &str, amount: i64| {
+            test::TestRequest::post()
+                .uri("/api/mint_credits")
+                .insert_header(("Idempotency-Key", key.to_string()))
+                .set_json(serde_json::json!({"user_id": "alice", "amount": amount}))
+                .to_request()
+        };
+
+        let first = test::call_service(&app, mint("retry-1", 10)).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        let first: serde_json::Value = test::read_body_json(first).await;
+        test::call_service(&app, mint("other", 5)).await;
+
+        let retry = test::call_service(&app, mint("retry-1", 10)).await;
+        assert_eq!(retry.headers().get("Idempotent-Replayed").unwrap(), "true");
+        let retry: serde_json::Value = test::read_body_json(retry).await;
+        assert_eq!(retry, first);
+        assert_eq!(data.economy.get_balance("alice").unwrap().credits.amount(), "15.00");
+        assert_eq!(data.economy.get_transaction_count(), 2);
+
+        let reused = test::call_service(&app, mint("retry-1", 99)).await;
+        assert_eq!(reused.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let json: serde_json::Value = test::read_body_json(reused).await;
+        assert_eq!(json["kind"], "idempotency_key_reused");
+    }
+
+    #[actix_web::test]
+    async fn test_idempotency_keys_are_scoped_per_api_key() {
+        let dir = std::env::temp_dir().join(format!("lwas_server_idempotency_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut keys = KeyStore::open(&dir).unwrap();
+        let (_, first) = keys.create("first", vec![Scope::AdminMint], None).unwrap();
+        let (_, second) = keys.create("second", vec![Scope::AdminMint], None).unwrap();
+        let data = state();
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::new(keys)).app_data(data.clone()).configure(routes)).await;
+        let mint = |token: &str, amount: i64| {
+            test::TestRequest::post()
+                .uri("/api/mint_credits")
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .insert_header(("Idempotency-Key", "order-1"))
+                .set_json(serde_json::json!({"user_id": "alice", "amount": amount}))
+                .to_request()
+        };
+
+        // The same key from another client is a new request, not a reuse or a replay
+        assert_eq!(test::call_service(&app, mint(&first, 10)).await.status(), StatusCode::OK);
+        let other = test::call_service(&app, mint(&second, 20)).await;
+        assert_eq!(other.status(), StatusCode::OK);
+        assert!(other.headers().get("Idempotent-Replayed").is_none());
+        let retry = test::call_service(&app, mint(&first, 10)).await;
+        assert_eq!(retry.headers().get("Idempotent-Replayed").unwrap(), "true");
+        assert_eq!(data.economy.get_balance("alice").unwrap().credits.amount(), "30.00");
+    }
+
+    #[actix_web::test]
+    async fn test_api_keys_enforce_scopes_and_ownership() {
+        let dir = std::env::temp_dir().join(format!("lwas_server_auth_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut keys = KeyStore::open(&dir).unwrap();
+        let (_, admin) = keys.create("admin", vec![Scope::AdminMint, Scope::AdminRead], None).unwrap();
+        let (alice_key, alice) = keys.create("alice", vec![Scope::UserReadOwn, Scope::UserSpendOwn], Some("alice".into())).unwrap();
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::new(keys)).app_data(state()).configure(routes)).await;
+        let call = |method: &str, uri: &str, token: &str, body: serde_json::Value| {
+            let request = if method == "GET" { test::TestRequest::get() } else { test::TestRequest::post().set_json(body) };
+            let request = request.uri(uri);
+            if token.is_empty() { request.to_request() } else { request.insert_header(("Authorization", format!("Bearer {}", token))).to_request() }
+        };
+        let none = serde_json::Value::Null;
+
+        let health = test::call_service(&app, call("GET", "/health", "", none.clone())).await;
+        assert_eq!(health.status(), StatusCode::OK);
+        let mint = serde_json::json!({"user_id": "alice", "amount": 20});
+        let anonymous = test::call_service(&app, call("POST", "/api/mint_credits", "", mint.clone())).await;
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+        let json: serde_json::Value = test::read_body_json(anonymous).await;
+        assert_eq!(json["kind"], "missing_api_key");
+        let by_user = test::call_service(&app, call("POST", "/api/mint_credits", &alice, mint.clone())).await;
+        assert_eq!(by_user.status(), StatusCode::FORBIDDEN);
+        let by_admin = test::call_service(&app, call("POST", "/api/mint_credits", &admin, mint)).await;
+        assert_eq!(by_admin.status(), StatusCode::OK);
+
+        let all = test::call_service(&app, call("GET", "/api/balances", &alice, none.clone())).await;
+        assert_eq!(all.status(), StatusCode::FORBIDDEN);
+        let own = test::call_service(&app, call("GET", "/api/balance?user_id=alice", &alice, none.clone())).await;
+        assert_eq!(own.status(), StatusCode::OK);
+        let transfer = serde_json::json!({"from_user_id": "alice", "to_user_id": "bob", "amount": 5});
+        assert_eq!(test::call_service(&app, call("POST", "/api/transfer", &alice, transfer)).await.status(), StatusCode::OK);
+        let other = test::call_service(&app, call("GET", "/api/balance?user_id=bob", &alice, none.clone())).await;
+        assert_eq!(other.status(), StatusCode::FORBIDDEN);
+        let theft = serde_json::json!({"from_user_id": "bob", "to_user_id": "alice", "amount": 5});
+        assert_eq!(test::call_service(&app, call("POST", "/api/transfer", &alice, theft)).await.status(), StatusCode::FORBIDDEN);
+        let listed: serde_json::Value =
+            test::read_body_json(test::call_service(&app, call("GET", "/api/transactions", &alice, none.clone())).await).await;
+        assert_eq!(listed["transactions"].as_array().unwrap().len(), 2);
+
+        KeyStore::open(&dir).unwrap().revoke(&alice_key.id).unwrap();
+        let revoked = test::call_service(&app, call("GET", "/api/balance?user_id=alice", &alice, none)).await;
+        assert_eq!(revoked.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_akashic_seal_trace_and_verify() {
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::allow_all()).app_data(state()).configure(routes)).await;
+        let post = |uri: &str, body: serde_json::Value| test::TestRequest::post().uri(uri).set_json(body).to_request();
+        let messages = serde_json::json!([{"role": "user", "content": "Exit the position?", "timestamp": 1705300000}]);
+
+        let mut records = Vec::new();
+        for index in [0.3, 0.9] {
+            let seal = serde_json::json!({"messages": messages, "decision_index": index, "veritas_approved": true});
+            let record: serde_json::Value = test::read_body_json(test::call_service(&app, post("/api/akashic/seal", seal)).await).await;
+            records.push(record);
+        }
+        assert_eq!(records[1]["prev_seal"], records[0]["record_seal"]);
+
+        let traced: serde_json::Value =
+            test::read_body_json(test::call_service(&app, post("/api/akashic/trace", serde_json::json!({"messages": messages}))).await).await;
+        assert_eq!(traced["records"].as_array().unwrap().len(), 2);
+        let stored = test::call_service(&app, test::TestRequest::get().uri("/api/akashic/verify").to_request()).await;
+        assert_eq!(stored.status(), StatusCode::OK);
+
+        records[0]["decision_index"] = serde_json::json!(0.1);
+        let report: serde_json::Value =
+            test::read_body_json(test::call_service(&app, post("/api/akashic/verify", serde_json::json!({"records": records}))).await).await;
+        assert_eq!(report["valid"], false);
+        assert_eq!(report["broken_at"], 0);
+    }
+
+    #[actix_web::test]
+    async fn test_metrics_count_requests_failures_and_totals() {
+        let dir = std::env::temp_dir().join(format!("lwas_server_metrics_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut keys = KeyStore::open(&dir).unwrap();
+        let (_, admin) = keys.create("admin", vec![Scope::AdminMint, Scope::AdminRead], None).unwrap();
+        let (_, service) = keys.create("shop", vec![Scope::ServiceUnlock], None).unwrap();
+        let state = state();
+        let app = test::init_service(
+            App::new()
+                .wrap(ApiKeyAuth::new(keys))
+                .wrap(RequestMetrics::new(state.metrics.clone()))
+                .app_data(state)
+                .configure(routes),
+        )
+        .await;
+        let post = |uri: &str, token: &str, body: serde_json::Value| {
+            test::TestRequest::post().uri(uri).insert_header(("Authorization", format!("Bearer {}", token))).set_json(body).to_request()
+        };
+
+        for amount in [serde_json::json!(20), serde_json::json!("7.50"), serde_json::json!(-1)] {
+            test::call_service(&app, post("/api/mint_credits", &admin, serde_json::json!({"user_id": "alice", "amount": amount}))).await;
+        }
+        let unlock = serde_json::json!({"user_id": "alice", "module_name": "basic_analytics"});
+        assert_eq!(test::call_service(&app, post("/api/unlock_module", &service, unlock)).await.status(), StatusCode::OK);
+        let anonymous = test::TestRequest::post().uri("/api/mint_credits").set_json(serde_json::json!({})).to_request();
+        assert_eq!(test::call_service(&app, anonymous).await.status(), StatusCode::UNAUTHORIZED);
+
+        let scrape = |token: &str| test::TestRequest::get().uri("/metrics").insert_header(("Authorization", format!("Bearer {}", token))).to_request();
+        assert_eq!(test::call_service(&app, scrape(&service)).await.status(), StatusCode::FORBIDDEN);
+        let response = test::call_service(&app, scrape(&admin)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("content-type").unwrap(), metrics::CONTENT_TYPE);
+        let text = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+        for line in [
+            "lwas_http_requests_total{method=\"POST\",route=\"/api/mint_credits\",status=\"200\"} 2",
+            "lwas_http_requests_total{method=\"POST\",route=\"/api/mint_credits\",status=\"401\"} 1",
+            "lwas_http_request_duration_seconds_count{method=\"POST\",route=\"/api/mint_credits\"} 4",
+            "lwas_failed_operations_total{route=\"/api/mint_credits\",kind=\"invalid_amount\"} 1",
+            "lwas_failed_operations_total{route=\"/api/mint_credits\",kind=\"missing_api_key\"} 1",
+            "lwas_failed_operations_total{route=\"/metrics\",kind=\"forbidden\"} 1",
+            "lwas_transactions_total{type=\"mint\"} 2",
+            "lwas_transaction_amount_credits_total{type=\"mint\"} 27.50",
+            "lwas_transactions_total{type=\"unlock\"} 1",
+            "lwas_transaction_amount_credits_total{type=\"unlock\"} 10.00",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing `{}` in:\n{}", line, text);
+        }
+        assert!(text.contains("# TYPE lwas_host_cpu_load_percent gauge"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[actix_web::test]
+    async fn test_telemetry_history_downsamples_sampled_readings() {
+        let state = state();
+        let now = unix_now();
+        let mut reading = TelemetryEngine::new().get_metrics(0);
+        for (ago, cpu_load) in [(250, 10.0), (200, 30.0), (100, 50.0)] {
+            reading.timestamp = now - ago;
+            reading.cpu_load = cpu_load;
+            state.history.record(reading.clone());
+        }
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::allow_all()).app_data(state).configure(routes)).await;
+        let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+        let json: serde_json::Value = test::call_and_read_body_json(&app, get("/api/telemetry/history?range=5m&step=150")).await;
+        assert_eq!((json["to"].as_u64(), json["step"].as_u64()), (Some(now), Some(150)));
+        let buckets = json["buckets"].as_array().unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0]["samples"], 2);
+        assert_eq!(buckets[0]["cpu_load"], serde_json::json!({"min": 10.0, "avg": 20.0, "max": 30.0}));
+        assert_eq!(buckets[1]["cpu_load"]["max"], 50.0);
+
+        let latest: serde_json::Value = test::call_and_read_body_json(&app, get("/api/telemetry")).await;
+        assert_eq!(latest["cpu_load"], 50.0);
+        let invalid = test::call_service(&app, get("/api/telemetry/history?range=1d&step=1s")).await;
+        assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+        let invalid = test::call_service(&app, get("/api/telemetry/history?step=soon")).await;
+        assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// The next chunk an event stream has ready, without waiting for more.
+    fn next_message(body: &mut BoxBody) -> String {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match body.as_pin_mut().poll_next(&mut cx) {
+            std::task::Poll::Ready(Some(Ok(bytes))) => String::from_utf8(bytes.to_vec()).unwrap(),
+            _ => panic!("no message ready"),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_event_stream_resumes_and_pushes_live_changes() {
+        let state = state();
+        state.economy.mint_credits("alice".into(), Money::credits(5000), None).unwrap();
+        state.economy.mint_credits("bob".into(), Money::credits(100), None).unwrap();
+        state.economy.transfer("alice".into(), "bob".into(), Money::credits(700), None, None).unwrap();
+        let app = test::init_service(App::new().wrap(ApiKeyAuth::allow_all()).app_data(state.clone()).configure(routes)).await;
+
+        let request = test::TestRequest::get().uri("/api/events?user_id=alice").insert_header(("Last-Event-ID", "1")).to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("content-type").unwrap(), events::CONTENT_TYPE);
+        let mut body = response.into_body().boxed();
+        let mut next = || next_message(&mut body);
+        assert_eq!(next(), "retry: 3000\n\n");
+        assert_eq!(next(), "event: ready\ndata: {\"last_event_id\":3,\"resync\":false}\n\n");
+        // Bob's mint is not Alice's business, nor is his balance after the transfer
+        let transfer = next();
+        assert!(transfer.starts_with("id: 3\nevent: transaction\ndata: {\"id\":\"tx_"), "{}", transfer);
+        assert!(transfer.contains("event: balance\ndata: {\"user_id\":\"alice\",\"credits\":{\"amount\":\"43.00\""), "{}", transfer);
+        assert!(!transfer.contains("\"user_id\":\"bob\""), "{}", transfer);
+
+        let unlock = serde_json::json!({"user_id": "alice", "module_name": "basic_analytics"});
+        let unlocked = test::TestRequest::post().uri("/api/unlock_module").set_json(unlock).to_request();
+        assert_eq!(test::call_service(&app, unlocked).await.status(), StatusCode::OK);
+        let live = next();
+        assert!(live.contains("id: 4\nevent: transaction\n"), "{}", live);
+        assert!(live.contains("id: 4\nevent: entitlement\ndata: {\"user_id\":\"alice\",\"module_name\":\"basic_analytics\",\"entitlement\":{"), "{}", live);
+
+        // Everyone's events, resuming from before the retained backlog starts
+        let request = test::TestRequest::get().uri("/api/events?last_event_id=9").to_request();
+        let mut body = test::call_service(&app, request).await.into_body().boxed();
+        next_message(&mut body);
+        assert_eq!(next_message(&mut body), "id: 4\nevent: ready\ndata: {\"last_event_id\":4,\"resync\":true}\n\n");
+
+        let invalid = test::TestRequest::get().uri("/api/events").insert_header(("Last-Event-ID", "soon")).to_request();
+        assert_eq!(test::call_service(&app, invalid).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_rate_limits_answer_429_and_audit_rejections() {
+        let state = state();
+        state.economy.mint_credits("alice".into(), Money::credits(5000), None).unwrap();
+        let limiter = Arc::new(RateLimiter::new(vec![RateRule {
+            method: Some("POST".into()),
+            route: "*".into(),
+            per: Subject::User,
+            burst: 1,
+            per_second: 0.01,
+        }]));
+        let app = test::init_service(
+            App::new()
+                .wrap(RateLimit::new(limiter, state.economy.clone()))
+                .wrap(ApiKeyAuth::allow_all())
+                .app_data(state.clone())
+                .configure(routes),
+        )
+        .await;
+        let post = |uri: &str, body: serde_json::Value| test::TestRequest::post().uri(uri).set_json(body).to_request();
+        let transfer = serde_json::json!({"from_user_id": "alice", "to_user_id": "bob", "amount": 5});
+
+        // The body is read for the user id and still reaches the handler
+        assert_eq!(test::call_service(&app, post("/api/transfer", transfer.clone())).await.status(), StatusCode::OK);
+        let limited = test::call_service(&app, post("/api/transfer", transfer.clone())).await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        // Refused again within the refill window: answered, but not journaled a second time
+        assert_eq!(test::call_service(&app, post("/api/transfer", transfer)).await.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.headers().get("retry-after").unwrap(), "100");
+        let json: serde_json::Value = test::read_body_json(limited).await;
+        assert_eq!((json["kind"].as_str(), json["retry_after"].as_u64()), (Some("rate_limited"), Some(100)));
+        // Bob is a different user, and reads are not limited
+        let unlock = serde_json::json!({"user_id": "bob", "module_name": "basic_analytics"});
+        assert_eq!(test::call_service(&app, post("/api/unlock_module", unlock)).await.status(), StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(state.economy.get_balance("bob").unwrap().credits, Money::credits(500));
+
+        let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, get("/api/transactions?user_id=alice&status=rejected")).await;
+        assert_eq!(page["transactions"].as_array().unwrap().len(), 1);
+        let rejected = &page["transactions"][0];
+        assert_eq!((rejected["status"].as_str(), rejected["rejection"].as_str()), (Some("rejected"), Some("rate_limited")));
+        assert_eq!((rejected["transaction_type"].as_str(), rejected["actor"].as_str()), (Some("transfer"), Some("dev")));
+        let page: serde_json::Value = test::call_and_read_body_json(&app, get("/api/transactions?user_id=alice")).await;
+        assert_eq!(page["transactions"].as_array().unwrap().len(), 2);
+        let invalid = test::call_service(&app, get("/api/transactions?status=pending")).await;
+        assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_own_key_rate_limited_for_another_user_journals_nothing() {
+        let dir = std::env::temp_dir().join(format!("lwas_server_limit_own_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut keys = KeyStore::open(&dir).unwrap();
+        let (_, alice) = keys.create("alice", vec![Scope::UserReadOwn, Scope::UserSpendOwn], Some("alice".into())).unwrap();
+        let state = state();
+        state.economy.mint_credits("alice".into(), Money::credits(5000), None).unwrap();
+        let limiter = Arc::new(RateLimiter::new(vec![RateRule {
+            method: Some("POST".into()),
+            route: "*".into(),
+            per: Subject::Key,
+            burst: 1,
+            per_second: 0.01,
+        }]));
+        let app = test::init_service(
+            App::new()
+                .wrap(RateLimit::new(limiter, state.economy.clone()))
+                .wrap(ApiKeyAuth::new(keys))
+                .app_data(state.clone())
+                .configure(routes),
+        )
+        .await;
+        let transfer = |from: &str| {
+            test::TestRequest::post()
+                .uri("/api/transfer")
+                .insert_header(("Authorization", format!("Bearer {}", alice)))
+                .set_json(serde_json::json!({"from_user_id": from, "to_user_id": "mallory", "amount": 5}))
+                .to_request()
+        };
+
+        assert_eq!(test::call_service(&app, transfer("alice")).await.status(), StatusCode::OK);
+        // Refused for the limit, but the key may not act for bob, so bob's journal stays clean
+        assert_eq!(test::call_service(&app, transfer("bob")).await.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(state.economy.get_user_transactions("bob").is_empty());
+        let rejected = TransactionFilter { status: Some(TransactionStatus::Rejected), ..Default::default() };
+        assert!(state.economy.list_transactions(&rejected, None, None).unwrap().transactions.is_empty());
+
+        assert_eq!(test::call_service(&app, transfer("alice")).await.status(), StatusCode::TOO_MANY_REQUESTS);
+        let page = state.economy.list_transactions(&rejected, None, None).unwrap();
+        assert_eq!(page.transactions.len(), 1);
+        assert_eq!(page.transactions[0].user_id(), Some("alice"));
+    }
+}
@@ -0,0 +1,220 @@
+//! Fixed-size history of telemetry readings, filled by a background sampler and read
+//! back in time buckets of min/avg/max. Byte counters are stored as per-second rates
+//! so that a bucket says how busy the disk or network was, not how long we have run.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+
+use super::SystemMetrics;
+
+/// Most buckets one history query may return.
+pub const MAX_BUCKETS: u64 = 1000;
+
+/// One stored reading.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetrySample {
+    pub timestamp: u64,
+    pub cpu_load: f64,
+    pub ram_usage_percent: f64,
+    pub temperature_celsius: f64,
+    pub process_cpu_percent: f64,
+    pub process_memory_bytes: f64,
+    pub disk_read_bytes_per_sec: f64,
+    pub disk_written_bytes_per_sec: f64,
+    pub network_received_bytes_per_sec: f64,
+    pub network_transmitted_bytes_per_sec: f64,
+}
+
+type Field = (&'static str, fn(&TelemetrySample) -> f64);
+
+/// The series a bucket summarises, by the name they are reported under.
+const FIELDS: [Field; 9] = [
+    ("cpu_load", |s| s.cpu_load),
+    ("ram_usage_percent", |s| s.ram_usage_percent),
+    ("temperature_celsius", |s| s.temperature_celsius),
+    ("process_cpu_percent", |s| s.process_cpu_percent),
+    ("process_memory_bytes", |s| s.process_memory_bytes),
+    ("disk_read_bytes_per_sec", |s| s.disk_read_bytes_per_sec),
+    ("disk_written_bytes_per_sec", |s| s.disk_written_bytes_per_sec),
+    ("network_received_bytes_per_sec", |s| s.network_received_bytes_per_sec),
+    ("network_transmitted_bytes_per_sec", |s| s.network_transmitted_bytes_per_sec),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Stat {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+/// Every sample taken in `[timestamp, timestamp + step)`, summarised per series.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetryBucket {
+    pub timestamp: u64,
+    pub samples: usize,
+    #[serde(flatten)]
+    pub series: BTreeMap<&'static str, Stat>,
+}
+
+struct Ring {
+    samples: VecDeque<TelemetrySample>,
+    latest: Option<SystemMetrics>,
+}
+
+/// The last `capacity` readings; the oldest is dropped when a new one arrives.
+pub struct TelemetryHistory {
+    capacity: usize,
+    inner: Mutex<Ring>,
+}
+
+impl TelemetryHistory {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        TelemetryHistory { capacity, inner: Mutex::new(Ring { samples: VecDeque::with_capacity(capacity), latest: None }) }
+    }
+
+    /// Stores `metrics` as the latest reading and appends its sample to the ring.
+    pub fn record(&self, metrics: SystemMetrics) -> TelemetrySample {
+        let mut ring = self.inner.lock().unwrap();
+        let rate = |now: u64, before: Option<u64>| match (&ring.latest, before) {
+            (Some(previous), Some(before)) if metrics.timestamp > previous.timestamp => {
+                now.saturating_sub(before) as f64 / (metrics.timestamp - previous.timestamp) as f64
+            }
+            _ => 0.0,
+        };
+        let previous = ring.latest.as_ref();
+        let sample = TelemetrySample {
+            timestamp: metrics.timestamp,
+            cpu_load: metrics.cpu_load as f64,
+            ram_usage_percent: metrics.ram_usage_percent as f64,
+            temperature_celsius: metrics.temperature_celsius as f64,
+            process_cpu_percent: metrics.process_cpu_percent as f64,
+            process_memory_bytes: metrics.process_memory_bytes as f64,
+            disk_read_bytes_per_sec: rate(metrics.disk_read_bytes, previous.map(|p| p.disk_read_bytes)),
+            disk_written_bytes_per_sec: rate(metrics.disk_written_bytes, previous.map(|p| p.disk_written_bytes)),
+            network_received_bytes_per_sec: rate(metrics.network_received_bytes, previous.map(|p| p.network_received_bytes)),
+            network_transmitted_bytes_per_sec: rate(metrics.network_transmitted_bytes, previous.map(|p| p.network_transmitted_bytes)),
+        };
+        if ring.samples.len() == self.capacity {
+            ring.samples.pop_front();
+        }
+        ring.samples.push_back(sample.clone());
+        ring.latest = Some(metrics);
+        sample
+    }
+
+    /// The most recent full reading, if the sampler has taken one.
+    pub fn latest(&self) -> Option<SystemMetrics> {
+        self.inner.lock().unwrap().latest.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The samples of the last `range` seconds before `now`, in buckets of `step`
+    /// seconds, oldest first. Buckets without samples are left out.
+    pub fn downsample(&self, range: u64, step: u64, now: u64) -> Result<Vec<TelemetryBucket>, String> {
+        if range == 0 || step == 0 {
+            return Err("range and step must be positive".to_string());
+        }
+        let count = range.div_ceil(step);
+        if count > MAX_BUCKETS {
+            return Err(format!("range / step gives {} buckets; at most {} are allowed", count, MAX_BUCKETS));
+        }
+        let start = now.saturating_sub(range);
+
+        let ring = self.inner.lock().unwrap();
+        let mut buckets: BTreeMap<u64, Vec<&TelemetrySample>> = BTreeMap::new();
+        for sample in ring.samples.iter().filter(|s| s.timestamp >= start && s.timestamp <= now) {
+            let index = ((sample.timestamp - start) / step).min(count - 1);
+            buckets.entry(index).or_default().push(sample);
+        }
+        Ok(buckets
+            .into_iter()
+            .map(|(index, samples)| TelemetryBucket {
+                timestamp: start + index * step,
+                samples: samples.len(),
+                series: FIELDS.iter().map(|&(name, value)| (name, summarise(samples.iter().map(|s| value(s))))).collect(),
+            })
+            .collect())
+    }
+}
+
+fn summarise(values: impl Iterator<Item = f64>) -> Stat {
+    let (mut min, mut max, mut sum, mut count) = (f64::INFINITY, f64::NEG_INFINITY, 0.0, 0);
+    for value in values {
+        min = min.min(value);
+        max = max.max(value);
+        sum += value;
+        count += 1;
+    }
+    Stat { min, avg: sum / count as f64, max }
+}
+
+/// Seconds in `text`: a plain number, or one with an `s`, `m`, `h` or `d` suffix.
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let (number, unit) = match text.char_indices().last() {
+        Some((at, 's')) => (&text[..at], 1),
+        Some((at, 'm')) => (&text[..at], 60),
+        Some((at, 'h')) => (&text[..at], 3600),
+        Some((at, 'd')) => (&text[..at], 86_400),
+        _ => (text, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| format!("'{}' is not a duration like 90, 30s, 15m, 6h or 1d", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(timestamp: u64, cpu_load: f32, network_received_bytes: u64) -> SystemMetrics {
+        SystemMetrics {
+            timestamp,
+            cpu_load,
+            ram_usage_percent: 50.0,
+            ram_used_gb: 1.0,
+            ram_total_gb: 2.0,
+            temperature_celsius: 0.0,
+            temperatures: Vec::new(),
+            active_transactions: 0,
+            process_cpu_percent: 0.0,
+            process_memory_bytes: 0,
+            disk_read_bytes: 0,
+            disk_written_bytes: 0,
+            network_received_bytes,
+            network_transmitted_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_ring_keeps_capacity_and_downsamples() {
+        let history = TelemetryHistory::new(4);
+        for (i, cpu) in [90.0, 10.0, 20.0, 30.0, 40.0].into_iter().enumerate() {
+            history.record(reading(1000 + 10 * i as u64, cpu, 500 * i as u64));
+        }
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.latest().unwrap().timestamp, 1040);
+
+        // Samples at 1010..=1040; the 90% reading at 1000 has been dropped
+        let buckets = history.downsample(40, 20, 1040).unwrap();
+        assert_eq!(buckets.iter().map(|b| (b.timestamp, b.samples)).collect::<Vec<_>>(), vec![(1000, 1), (1020, 3)]);
+        assert_eq!(buckets[1].series["cpu_load"], Stat { min: 20.0, avg: 30.0, max: 40.0 });
+        assert_eq!(buckets[1].series["network_received_bytes_per_sec"].avg, 50.0);
+
+        assert!(history.downsample(3600, 1, 1040).is_err());
+        assert_eq!(parse_duration("15m"), Ok(900));
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert!(parse_duration("1w").is_err());
+    }
+}
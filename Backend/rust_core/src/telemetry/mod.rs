@@ -1,30 +1,75 @@
 use serde::{Deserialize, Serialize};
-use sysinfo::System;
+use sysinfo::{Components, Networks, Pid, ProcessRefreshKind, System};
+
+mod history;
+
+pub use history::{parse_duration, Stat, TelemetryBucket, TelemetryHistory, TelemetrySample};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentTemperature {
+    pub label: String,
+    pub celsius: f32,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemMetrics {
+    /// Unix seconds when the reading was taken
+    #[serde(default)]
+    pub timestamp: u64,
     pub cpu_load: f32,
     pub ram_usage_percent: f32,
     pub ram_used_gb: f32,
     pub ram_total_gb: f32,
+    /// Hottest component, or 0.0 where the platform reports none
     pub temperature_celsius: f32,
+    #[serde(default)]
+    pub temperatures: Vec<ComponentTemperature>,
     pub active_transactions: usize,
+    /// CPU used by the economy process, in percent of one core
+    #[serde(default)]
+    pub process_cpu_percent: f32,
+    /// Resident memory of the economy process
+    #[serde(default)]
+    pub process_memory_bytes: u64,
+    /// Bytes the economy process has read from and written to disk since it started
+    #[serde(default)]
+    pub disk_read_bytes: u64,
+    #[serde(default)]
+    pub disk_written_bytes: u64,
+    /// Bytes received and sent on every interface but loopback since boot
+    #[serde(default)]
+    pub network_received_bytes: u64,
+    #[serde(default)]
+    pub network_transmitted_bytes: u64,
 }
 
 pub struct TelemetryEngine {
     system: System,
+    components: Components,
+    networks: Networks,
+    pid: Option<Pid>,
 }
 
 impl TelemetryEngine {
     pub fn new() -> Self {
         Self {
-            system: System::new_all(),
+            system: System::new(),
+            components: Components::new_with_refreshed_list(),
+            networks: Networks::new_with_refreshed_list(),
+            pid: sysinfo::get_current_pid().ok(),
         }
     }
 
+    /// Takes a fresh reading. Only what is reported gets refreshed, so a background
+    /// sampler can call this every few seconds.
     pub fn get_metrics(&mut self, transaction_count: usize) -> SystemMetrics {
-        // Refresh system information
-        self.system.refresh_all();
+        self.system.refresh_cpu();
+        self.system.refresh_memory();
+        if let Some(pid) = self.pid {
+            self.system.refresh_process_specifics(pid, ProcessRefreshKind::new().with_cpu().with_memory().with_disk_usage());
+        }
+        self.components.refresh();
+        self.networks.refresh();
 
         // Get CPU load (average across all cores)
         let cpu_load = self.system.global_cpu_info().cpu_usage();
@@ -38,17 +83,36 @@ impl TelemetryEngine {
             0.0
         };
 
-        // Temperature is not available on all platforms with sysinfo 0.30
-        // Return 0.0 as placeholder
-        let temperature = 0.0;
+        // Sensors that fail to read report NaN
+        let temperatures: Vec<ComponentTemperature> = self
+            .components
+            .iter()
+            .filter(|c| c.temperature().is_finite())
+            .map(|c| ComponentTemperature { label: c.label().to_string(), celsius: c.temperature() })
+            .collect();
+        let temperature = temperatures.iter().map(|t| t.celsius).fold(0.0, f32::max);
+
+        let process = self.pid.and_then(|pid| self.system.process(pid));
+        let disk = process.map(|p| p.disk_usage());
+        let external = || self.networks.iter().filter(|(name, _)| name.as_str() != "lo").map(|(_, data)| data);
 
         SystemMetrics {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
             cpu_load,
             ram_usage_percent,
             ram_used_gb: used_memory,
             ram_total_gb: total_memory,
             temperature_celsius: temperature,
+            temperatures,
             active_transactions: transaction_count,
+            process_cpu_percent: process.map_or(0.0, |p| p.cpu_usage()),
+            process_memory_bytes: process.map_or(0, |p| p.memory()),
+            disk_read_bytes: disk.map_or(0, |d| d.total_read_bytes),
+            disk_written_bytes: disk.map_or(0, |d| d.total_written_bytes),
+            network_received_bytes: external().map(|n| n.total_received()).sum(),
+            network_transmitted_bytes: external().map(|n| n.total_transmitted()).sum(),
         }
     }
 }
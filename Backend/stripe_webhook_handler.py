@@ -19,6 +19,8 @@ logger = logging.getLogger(__name__)
 STRIPE_SECRET_KEY = os.environ.get("STRIPE_SECRET_KEY")
 STRIPE_WEBHOOK_SECRET = os.environ.get("STRIPE_WEBHOOK_SECRET")
 RUST_ECONOMY_URL = os.environ.get("RUST_ECONOMY_URL", "http://localhost:8890")
+# Needs the admin:mint and service:unlock scopes (create with `lwas_keys create`)
+RUST_ECONOMY_API_KEY = os.environ.get("RUST_ECONOMY_API_KEY")
 
 if STRIPE_SECRET_KEY:
     stripe.api_key = STRIPE_SECRET_KEY
@@ -32,9 +34,18 @@ PRICING_TIERS = {
     "vortex": {"credits": 2000, "modules": ["basic_analytics", "advanced_arbitrage", "ai_insights", "enterprise_support", "custom_integrations"]},
 }
 
-def mint_credits_in_rust(user_id: str, amount: float) -> dict:
+def rust_headers(idempotency_key: str = None) -> dict:
+    headers = {}
+    if RUST_ECONOMY_API_KEY:
+        headers["Authorization"] = f"Bearer {RUST_ECONOMY_API_KEY}"
+    if idempotency_key:
+        headers["Idempotency-Key"] = idempotency_key
+    return headers
+
+def mint_credits_in_rust(user_id: str, amount: float, idempotency_key: str = None) -> dict:
     """
     Call Rust Economy server to mint credits for a user.
+    With an idempotency key, a redelivered event does not mint twice.
     """
     try:
         url = f"{RUST_ECONOMY_URL}/api/mint_credits"
@@ -42,27 +53,29 @@ def mint_credits_in_rust(user_id: str, amount: float) -> dict:
             "user_id": user_id,
             "amount": amount
         }
-        response = requests.post(url, json=payload, timeout=10)
+        headers = rust_headers(idempotency_key)
+        response = requests.post(url, json=payload, headers=headers, timeout=10)
         response.raise_for_status()
         return response.json()
     except Exception as e:
         logger.error(f"Failed to mint credits in Rust: {e}")
         raise
 
-def unlock_modules_in_rust(user_id: str, modules: list) -> list:
+def unlock_modules_in_rust(user_id: str, modules: list, idempotency_key: str = None) -> list:
     """
     Unlock modules for a user in Rust Economy server.
     """
     results = []
     for module in modules:
         try:
-            url = f"{RUST_ECONOMY_URL}/api/unlock_module"
+            # Paid through Stripe, so granted without charging credits
+            url = f"{RUST_ECONOMY_URL}/api/entitlements/grant"
             payload = {
                 "user_id": user_id,
-                "module_name": module,
-                "cost": 0  # Free unlock as part of subscription
+                "module_name": module
             }
-            response = requests.post(url, json=payload, timeout=10)
+            headers = rust_headers(f"{idempotency_key}:unlock:{module}" if idempotency_key else None)
+            response = requests.post(url, json=payload, headers=headers, timeout=10)
             response.raise_for_status()
             results.append({"module": module, "success": True, "data": response.json()})
         except Exception as e:
@@ -117,11 +130,13 @@ def stripe_webhook():
             
             # Mint credits in Rust Economy module
             try:
-                mint_result = mint_credits_in_rust(user_id, tier_config['credits'])
+                # Stripe redelivers events; keying on the event id makes that harmless
+                idempotency_key = f"stripe:{event['id']}"
+                mint_result = mint_credits_in_rust(user_id, tier_config['credits'], f"{idempotency_key}:mint")
                 logger.info(f"✅ Credits minted: {mint_result}")
                 
                 # Unlock modules
-                unlock_results = unlock_modules_in_rust(user_id, tier_config['modules'])
+                unlock_results = unlock_modules_in_rust(user_id, tier_config['modules'], idempotency_key)
                 logger.info(f"🔓 Modules unlocked: {unlock_results}")
                 
                 return jsonify({
@@ -0,0 +1,223 @@
+"""
+RUST CORE - C ABI integration tests
+Loads librust_core through ctypes, the way NexusLogic does, and exercises every export.
+Build the library first: cd rust_core && cargo build
+"""
+
+import ctypes
+import json
+import os
+import platform
+import shutil
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+sys.path.insert(0, os.path.dirname(os.path.abspath(__file__)))
+
+LIB_NAME = {"Windows": "rust_core.dll", "Darwin": "librust_core.dylib"}.get(platform.system(), "librust_core.so")
+TARGET = Path(__file__).parent / "rust_core" / "target"
+
+LWAS_STATUS_OK = 0
+LWAS_STATUS_NULL_POINTER = 1
+LWAS_STATUS_INVALID_JSON = 3
+LWAS_STATUS_BUFFER_TOO_SMALL = 5
+LWAS_STATUS_LEDGER_CORRUPT = 7
+LWAS_STATUS_NOT_FOUND = 8
+LWAS_STATUS_INSUFFICIENT_CREDITS = 9
+LWAS_STATUS_CONFLICT = 10
+
+
+class LwasCycleResult(ctypes.Structure):
+    _fields_ = [("index", ctypes.c_double), ("seq", ctypes.c_uint64), ("hash", ctypes.c_char * 129)]
+
+
+class LwasLedgerReport(ctypes.Structure):
+    _fields_ = [
+        ("valid", ctypes.c_bool),
+        ("records", ctypes.c_uint64),
+        ("legacy_lines", ctypes.c_uint64),
+        ("first_bad_line", ctypes.c_uint64),
+        ("reason", ctypes.c_char * 256),
+    ]
+
+
+def load_library():
+    for profile in ("release", "debug"):
+        path = TARGET / profile / LIB_NAME
+        if path.exists():
+            lib = ctypes.CDLL(str(path))
+            lib.lwas_abi_version.restype = ctypes.c_uint32
+            lib.lwas_last_error.argtypes = [ctypes.c_char_p, ctypes.c_size_t]
+            lib.lwas_last_error.restype = ctypes.c_size_t
+            lib.lwas_sign_cycle.argtypes = [ctypes.c_char_p, ctypes.c_size_t, ctypes.c_char_p, ctypes.POINTER(LwasCycleResult)]
+            lib.lwas_sign_cycle.restype = ctypes.c_int
+            lib.lwas_sign_cycles_batch.argtypes = [
+                ctypes.c_char_p, ctypes.c_size_t, ctypes.c_char_p,
+                ctypes.POINTER(LwasCycleResult), ctypes.c_size_t, ctypes.POINTER(ctypes.c_size_t),
+            ]
+            lib.lwas_sign_cycles_batch.restype = ctypes.c_int
+            lib.lwas_verify_ledger.argtypes = [ctypes.c_char_p, ctypes.POINTER(LwasLedgerReport)]
+            lib.lwas_verify_ledger.restype = ctypes.c_int
+            lib.lwas_economy_new.restype = ctypes.c_void_p
+            lib.lwas_economy_open.argtypes = [ctypes.c_char_p, ctypes.c_uint64, ctypes.POINTER(ctypes.c_void_p)]
+            lib.lwas_economy_open.restype = ctypes.c_int
+            lib.lwas_economy_free.argtypes = [ctypes.c_void_p]
+            lib.lwas_economy_free.restype = None
+            lib.lwas_economy_mint.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_int64, ctypes.POINTER(ctypes.c_int64)]
+            lib.lwas_economy_mint.restype = ctypes.c_int
+            lib.lwas_economy_unlock.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_int64)]
+            lib.lwas_economy_unlock.restype = ctypes.c_int
+            lib.lwas_economy_balance.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_int64)]
+            lib.lwas_economy_balance.restype = ctypes.c_int
+            lib.lwas_economy_check_entitlement.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.POINTER(ctypes.c_bool)]
+            lib.lwas_economy_check_entitlement.restype = ctypes.c_int
+            lib.lwas_economy_transaction_count.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_uint64)]
+            lib.lwas_economy_transaction_count.restype = ctypes.c_int
+            lib.process_and_sign_cycle.argtypes = [ctypes.c_char_p, ctypes.c_size_t]
+            lib.process_and_sign_cycle.restype = ctypes.c_double
+            return lib
+    return None
+
+
+RUST = load_library()
+
+
+def payload(hr=75.0, price=50000.0):
+    return json.dumps({
+        "bio_data_stream": [{"hr": hr, "oxy": 0.98}],
+        "market_data_stream": [{"price": price, "volume": 1000.0}],
+        "energy_data_stream": {"battery_level": 50.0},
+    }).encode("utf-8")
+
+
+def last_error():
+    buf = ctypes.create_string_buffer(512)
+    RUST.lwas_last_error(buf, len(buf))
+    return buf.value.decode("utf-8")
+
+
+@unittest.skipIf(RUST is None, f"{LIB_NAME} not built (cd rust_core && cargo build)")
+class TestRustCoreFfi(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.mkdtemp(prefix="lwas_ffi_")
+        self.ledger = os.path.join(self.dir, "sovereign.ledger").encode("utf-8")
+
+    def tearDown(self):
+        shutil.rmtree(self.dir, ignore_errors=True)
+
+    def test_abi_version(self):
+        self.assertEqual(RUST.lwas_abi_version(), 1)
+
+    def test_sign_cycle_appends_to_caller_ledger(self):
+        out = LwasCycleResult()
+        data = payload()
+        self.assertEqual(RUST.lwas_sign_cycle(data, len(data), self.ledger, ctypes.byref(out)), LWAS_STATUS_OK)
+        self.assertTrue(0.0 <= out.index <= 100.0)
+        self.assertEqual(out.seq, 0)
+        self.assertEqual(len(out.hash), 128)
+
+        self.assertEqual(RUST.lwas_sign_cycle(data, len(data), self.ledger, ctypes.byref(out)), LWAS_STATUS_OK)
+        self.assertEqual(out.seq, 1)
+
+    def test_bad_json_sets_status_and_last_error(self):
+        out = LwasCycleResult()
+        data = b"{not json"
+        self.assertEqual(RUST.lwas_sign_cycle(data, len(data), self.ledger, ctypes.byref(out)), LWAS_STATUS_INVALID_JSON)
+        self.assertNotEqual(last_error(), "")
+        self.assertEqual(RUST.lwas_sign_cycle(None, 0, self.ledger, ctypes.byref(out)), LWAS_STATUS_NULL_POINTER)
+
+    def test_batch_signs_in_order(self):
+        batch = b"[" + b",".join([payload(), payload(hr=10.0, price=100.0), payload()]) + b"]"
+        written = ctypes.c_size_t()
+
+        small = (LwasCycleResult * 2)()
+        status = RUST.lwas_sign_cycles_batch(batch, len(batch), self.ledger, small, len(small), ctypes.byref(written))
+        self.assertEqual((status, written.value), (LWAS_STATUS_BUFFER_TOO_SMALL, 3))
+
+        results = (LwasCycleResult * 3)()
+        status = RUST.lwas_sign_cycles_batch(batch, len(batch), self.ledger, results, len(results), ctypes.byref(written))
+        self.assertEqual((status, written.value), (LWAS_STATUS_OK, 3))
+        self.assertEqual([r.seq for r in results], [0, 1, 2])
+
+        # An empty batch needs no results buffer
+        written.value = 7
+        status = RUST.lwas_sign_cycles_batch(b"[]", 2, self.ledger, None, 0, ctypes.byref(written))
+        self.assertEqual((status, written.value), (LWAS_STATUS_OK, 0))
+
+    def test_verify_ledger_detects_tampering(self):
+        out = LwasCycleResult()
+        data = payload()
+        for _ in range(3):
+            RUST.lwas_sign_cycle(data, len(data), self.ledger, ctypes.byref(out))
+
+        report = LwasLedgerReport()
+        self.assertEqual(RUST.lwas_verify_ledger(self.ledger, ctypes.byref(report)), LWAS_STATUS_OK)
+        self.assertTrue(report.valid)
+        self.assertEqual(report.records, 3)
+
+        path = self.ledger.decode("utf-8")
+        with open(path) as f:
+            lines = f.read().splitlines()
+        lines[1] = lines[1].replace('"index":', '"index":1', 1)
+        with open(path, "w") as f:
+            f.write("\n".join(lines) + "\n")
+
+        self.assertEqual(RUST.lwas_verify_ledger(self.ledger, ctypes.byref(report)), LWAS_STATUS_LEDGER_CORRUPT)
+        self.assertFalse(report.valid)
+        self.assertEqual(report.first_bad_line, 2)
+        self.assertNotEqual(report.reason, b"")
+
+    def test_economy_handle(self):
+        economy = RUST.lwas_economy_new()
+        self.assertTrue(economy)
+        try:
+            balance = ctypes.c_int64()
+            self.assertEqual(RUST.lwas_economy_mint(economy, b"alice", 1500, ctypes.byref(balance)), LWAS_STATUS_OK)
+            self.assertEqual(balance.value, 1500)
+
+            entitled = ctypes.c_bool()
+            RUST.lwas_economy_check_entitlement(economy, b"alice", b"basic_analytics", ctypes.byref(entitled))
+            self.assertFalse(entitled.value)
+
+            self.assertEqual(RUST.lwas_economy_unlock(economy, b"alice", b"basic_analytics", ctypes.byref(balance)), LWAS_STATUS_OK)
+            self.assertEqual(balance.value, 500)
+            RUST.lwas_economy_check_entitlement(economy, b"alice", b"basic_analytics", ctypes.byref(entitled))
+            self.assertTrue(entitled.value)
+
+            self.assertEqual(RUST.lwas_economy_unlock(economy, b"alice", b"basic_analytics", None), LWAS_STATUS_CONFLICT)
+            self.assertEqual(RUST.lwas_economy_balance(economy, b"bob", ctypes.byref(balance)), LWAS_STATUS_NOT_FOUND)
+
+            count = ctypes.c_uint64()
+            self.assertEqual(RUST.lwas_economy_transaction_count(economy, ctypes.byref(count)), LWAS_STATUS_OK)
+            self.assertEqual(count.value, 2)
+        finally:
+            RUST.lwas_economy_free(economy)
+
+    def test_persistent_economy_survives_reopen(self):
+        data_dir = os.path.join(self.dir, "economy").encode("utf-8")
+        economy = ctypes.c_void_p()
+        self.assertEqual(RUST.lwas_economy_open(data_dir, 0, ctypes.byref(economy)), LWAS_STATUS_OK)
+        RUST.lwas_economy_mint(economy, b"alice", 700, None)
+        RUST.lwas_economy_free(economy)
+
+        self.assertEqual(RUST.lwas_economy_open(data_dir, 0, ctypes.byref(economy)), LWAS_STATUS_OK)
+        balance = ctypes.c_int64()
+        self.assertEqual(RUST.lwas_economy_balance(economy, b"alice", ctypes.byref(balance)), LWAS_STATUS_OK)
+        self.assertEqual(balance.value, 700)
+        RUST.lwas_economy_free(economy)
+
+    def test_legacy_entry_point(self):
+        data = payload()
+        os.environ["LWAS_LEDGER_PATH"] = self.ledger.decode("utf-8")
+        try:
+            self.assertTrue(0.0 <= RUST.process_and_sign_cycle(data, len(data)) <= 100.0)
+            self.assertEqual(RUST.process_and_sign_cycle(b"{", 1), -2.0)
+            self.assertEqual(RUST.process_and_sign_cycle(None, 0), -1.0)
+        finally:
+            del os.environ["LWAS_LEDGER_PATH"]
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -9,15 +9,44 @@ export interface BoundingBox {
     confidence: number;
 }
 
+export type ImageEncoding =
+    | { type: 'auto' }
+    | { type: 'png' }
+    | { type: 'jpeg' }
+    | { type: 'webp' }
+    | { type: 'raw_rgba'; width: number; height: number };
+
+export interface ViewportContext {
+    device_pixel_ratio?: number;
+    viewport_width?: number;
+    viewport_height?: number;
+    scroll_x?: number;
+    scroll_y?: number;
+}
+
+export interface FrameGeometry {
+    image_width: number;
+    image_height: number;
+    scale_x: number;
+    scale_y: number;
+    scroll_x: number;
+    scroll_y: number;
+}
+
 export interface VisionRequest {
     image_base64: string;
     intent: string;
+    image_encoding?: ImageEncoding;
+    viewport?: ViewportContext;
 }
 
 export interface VisionResult {
     found: boolean;
-    location: BoundingBox | null;
+    location: BoundingBox | null; // device pixels
     candidates: BoundingBox[];
+    css_location: BoundingBox | null; // document CSS pixels
+    css_candidates: BoundingBox[];
+    frame: FrameGeometry;
     confidence: number;
     semantic_embedding: number[];
     heatmap_data: number[];
@@ -72,6 +101,56 @@ export interface ObserverState {
     amniotic_state_score: number;
 }
 
+export interface NetworkObserveRequest {
+    url: string;
+    har: unknown; // HAR document: { log: { entries: [...] } }
+    dom_mutation_rate?: number;
+    layout_shifts?: number;
+    observed_at_ms?: number;
+    idle_window_ms?: number;
+    slow_threshold_ms?: number;
+    long_poll_threshold_ms?: number;
+    ignore_url_patterns?: string[];
+}
+
+export interface TimelineEntry {
+    method: string;
+    url: string;
+    status: number;
+    start_ms: number;
+    end_ms: number;
+    resource_type?: string;
+    kind: 'tracked' | 'long_lived' | 'ignored';
+    failed: boolean;
+}
+
+export interface EndpointStats {
+    endpoint: string;
+    requests: number;
+    failures: number;
+    statuses: number[];
+    mean_duration_ms: number;
+    max_duration_ms: number;
+}
+
+export interface NetworkAnalysis {
+    observed_at_ms: number;
+    timeline: TimelineEntry[];
+    idle_windows: { start_ms: number; end_ms: number }[];
+    slow_endpoints: EndpointStats[];
+    failing_endpoints: EndpointStats[];
+    long_lived_urls: string[];
+    pending_tracked: number;
+    pending_excluded: number;
+    idle_since_ms?: number;
+    network_idle: boolean;
+}
+
+export interface NetworkObservation {
+    state: ObserverState;
+    network: NetworkAnalysis;
+}
+
 export interface SwarmRequest {
     target_url: string;
     agent_count: number;
@@ -94,3 +173,34 @@ export interface VisionCompareResult {
     similarity_score: number;
     diff_reason?: string;
 }
+
+export type ProgressEvent =
+    | { event: 'step_started'; step_id: number; action: string }
+    | { event: 'step_finished'; step: AgentStep }
+    | { event: 'agent_finished'; agent_id: number; region: string }
+    | { event: 'metrics'; completed: number; total: number; throughput_tps: number };
+
+/** Written on stdout while a command sent with a `request_id` is running. */
+export interface ProgressNotification {
+    type: 'progress';
+    request_id: string;
+    event: ProgressEvent;
+}
+
+export interface CancelResult {
+    request_id: string;
+    cancelled: boolean;
+}
+
+export interface ElementCompareResult {
+    element: string;
+    changed: boolean;
+    similarity_score: number;
+    threshold: number;
+    baseline_region: BoundingBox;
+    current_region: BoundingBox;
+    shift_x: number;
+    shift_y: number;
+    masked_pixels: number;
+    summary: string;
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::sync::Arc;
+use crate::commands::{handler, CommandError, CommandRegistry};
+use crate::commands::running::CancelRequest;
+use crate::engine::neural_locator::{NeuralLocator, VisionRequest, VisionCompareRequest};
+use crate::engine::semantic_healer::{SemanticHealer, HealRequest};
+use crate::engine::agent::{GoalOrientedAgent, GoalRequest};
+use crate::engine::observer::{StateChangeObserver, ObserverRequest};
+use crate::engine::network::NetworkObserveRequest;
+use crate::engine::perf_budget::{PerfStore, VitalsSample};
+use crate::engine::test_data::{TestDataGenerator, TestDataRequest};
+use crate::engine::swarm::{DistributedSwarm, SwarmRequest};
+use crate::engine::dynamic_mask::{DynamicRegionLearner, LearnMaskRequest};
+use crate::engine::baseline::{BaselineStore, BaselineRequest};
+use crate::engine::element_diff::{ElementComparer, ElementCompareRequest};
+use crate::enterprise::security::{Role, RBAC};
+use crate::scenario::{self, DryRunDriver, Scenario, ScenarioRequest, ScenarioRunner};
+use crate::omega::physics::{SpatialFolder, ZeroPointHarvester};
+use crate::omega::psionics::{NoeticLayer, PrescientLattice};
+use crate::omega::ontology::RealityAnchor;
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(tag = "type")]
+pub enum OmegaRequest {
+    Fold { coords: [f64; 11] },
+    InvertEntropy,
+    TransmitQualia { concept: String },
+    Anticipate { subject_id: String },
+    VerifyReality { entity_id: String },
+}
+
+/// Registers the commands shipped with Veritas, sharing one instance of each engine.
+pub fn register_all(registry: &mut CommandRegistry) {
+    let locator = Arc::new(NeuralLocator::new());
+    let baselines = Arc::new(BaselineStore::new());
+    let perf = Arc::new(PerfStore::new());
+    let healer = Arc::new(SemanticHealer::new());
+    let agent = GoalOrientedAgent::new();
+    let observer = Arc::new(StateChangeObserver::new());
+    let swarm = DistributedSwarm::new();
+
+    // Omega Modules (Experimental)
+    let folder = SpatialFolder::new();
+    let harvester = ZeroPointHarvester::new();
+    let noetic = NoeticLayer::new();
+    let lattice = PrescientLattice::new();
+    let anchor = RealityAnchor::new();
+
+    // Scenarios drive the same engine instances as the individual commands
+    let runner = ScenarioRunner::new(locator.clone(), healer.clone(), observer.clone(), baselines.clone());
+    let scenarios = scenario::scenarios_root();
+
+    let l = locator.clone();
+    registry.register(
+        handler("Locate", Some(Role::Viewer), move |req: VisionRequest, _| Ok(l.analyze(&req)?))
+            .describe("Find an element on a screenshot by intent"),
+    );
+
+    let (l, store) = (locator.clone(), baselines.clone());
+    registry.register(
+        handler("Compare", Some(Role::Viewer), move |req: VisionCompareRequest, _| {
+            let mask = store.mask_for(req.page_id.as_deref())?;
+            Ok(l.compare(&req, mask.as_ref())?)
+        })
+        .describe("Pixel similarity of two screenshots, ignoring the page's learned dynamic regions"),
+    );
+
+    let elements = ElementComparer::new(locator, baselines.clone());
+    registry.register(
+        handler("CompareElement", Some(Role::Viewer), move |req: ElementCompareRequest, _| Ok(elements.compare(&req)?))
+            .describe("Compare one located element against its baseline, tolerating small moves"),
+    );
+
+    let store = baselines.clone();
+    registry.register(
+        handler("LearnMask", Some(Role::Agent), move |req: LearnMaskRequest, _| {
+            let mask = DynamicRegionLearner::learn(&req)?;
+            store.save_mask(&mask)?;
+            Ok(mask)
+        })
+        .describe("Learn and store the regions of a page that change between captures"),
+    );
+
+    let rbac = RBAC::new();
+    registry.register(
+        handler("Baseline", Some(Role::Viewer), move |req: BaselineRequest, ctx| {
+            // Checking is read-only; replacing the reference capture is not.
+            if matches!(req, BaselineRequest::Save { .. }) && !rbac.authorize(ctx.user, Role::Agent) {
+                return Err(CommandError::Forbidden("Access Denied: Agent role required".to_string()));
+            }
+            Ok(baselines.run(&req)?)
+        })
+        .describe("Save a page baseline (Agent) or check a capture against it (Viewer)"),
+    );
+
+    registry.register(
+        handler("Heal", Some(Role::Agent), move |req: HealRequest, _| Ok(healer.heal(&req)))
+            .describe("Recover a broken selector from its last known embedding"),
+    );
+
+    registry.register(
+        handler("Goal", Some(Role::Agent), move |req: GoalRequest, ctx| Ok(agent.execute_with_progress(&req, ctx.progress)))
+            .describe("Plan and execute a natural-language goal")
+            .sanitize(&["goal"])
+            .streaming(),
+    );
+
+    let o = observer.clone();
+    registry.register(
+        handler("Observe", Some(Role::Viewer), move |req: ObserverRequest, _| Ok(o.observe(&req)))
+        .describe("Score page stability from network, DOM and layout signals"),
+    );
+
+    registry.register(
+        handler("ObserveNetwork", Some(Role::Viewer), move |req: NetworkObserveRequest, _| Ok(observer.observe_network(&req)?))
+            .describe("Score page stability from a HAR capture: timeline, idle windows, slow/failing endpoints, long-lived connections"),
+    );
+
+    registry.register(
+        handler("RecordVitals", Some(Role::Agent), move |req: VitalsSample, _| Ok(perf.record(&req)?))
+            .describe("Record web vitals for a run and check them against budgets and the rolling baseline"),
+    );
+
+    registry.register(
+        handler("RunScenario", Some(Role::Agent), move |req: ScenarioRequest, _| {
+            let scenario = Scenario::from_yaml(&req.source, &scenarios)?;
+            Ok(runner.run(&scenario, &mut DryRunDriver)?)
+        })
+        .describe("Run a YAML test scenario and report pass/fail per step"),
+    );
+
+    let generator = TestDataGenerator::new();
+    registry.register(
+        handler("GenerateTestData", Some(Role::Agent), move |req: TestDataRequest, _| Ok(generator.generate(&req)?))
+            .describe("Generate seedable synthetic form data that GDPRGuard classifies as synthetic"),
+    );
+
+    registry.register(
+        handler("Swarm", Some(Role::Admin), move |req: SwarmRequest, ctx| Ok(swarm.launch_with_progress(&req, ctx.progress)))
+            .describe("Launch a distributed agent swarm")
+            .sanitize(&["task_goal"])
+            .streaming(),
+    );
+
+    registry.register(
+        handler("Omega", Some(Role::Admin), move |req: OmegaRequest, _| {
+            Ok(match req {
+                OmegaRequest::Fold { coords } => folder.remap(coords),
+                OmegaRequest::InvertEntropy => harvester.invert_entropy(),
+                OmegaRequest::TransmitQualia { concept } => noetic.transmit_qualia(&concept),
+                OmegaRequest::Anticipate { subject_id } => lattice.anticipate(&subject_id),
+                OmegaRequest::VerifyReality { entity_id } => anchor.verify_existence(&entity_id),
+            })
+        })
+        .describe("Experimental Omega modules"),
+    );
+
+    // Any caller may cancel; RunningRequests checks they own the request (or are an admin)
+    let running = registry.running();
+    registry.register(
+        handler("Cancel", None, move |req: CancelRequest, ctx| running.cancel(&req.request_id, ctx.user))
+            .describe("Cancel a running command by its request_id"),
+    );
+
+    registry.register(
+        handler("Ping", None, |_: (), _| Ok("Pong".to_string())).describe("Liveness check"),
+    );
+}
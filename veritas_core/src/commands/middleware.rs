@@ -0,0 +1,100 @@
+use serde_json::Value;
+use crate::commands::{CommandError, CommandSpec};
+use crate::enterprise::compliance::GDPRGuard;
+use crate::enterprise::security::{AuditLogger, TokenAuthenticator, UserContext, RBAC};
+
+/// A command in flight. Middleware may fill in `user` and rewrite `payload`.
+#[derive(Debug)]
+pub struct Invocation {
+    pub command: String,
+    pub user_id: String,
+    pub auth_token: String,
+    pub user: Option<UserContext>,
+    pub payload: Value,
+}
+
+/// Wraps every handler. `before` hooks run in registration order and may abort
+/// the command; `after` hooks run in reverse order and always see the outcome.
+pub trait Middleware: Send + Sync {
+    fn before(&self, _invocation: &mut Invocation, _spec: &CommandSpec) -> Result<(), CommandError> {
+        Ok(())
+    }
+
+    fn after(&self, _invocation: &Invocation, _spec: &CommandSpec, _outcome: &Result<Value, CommandError>) {}
+}
+
+/// Authentication -> Audit -> Authorization -> PII sanitisation.
+pub fn standard_stack() -> Vec<Box<dyn Middleware>> {
+    vec![
+        Box::new(Authentication(TokenAuthenticator::new())),
+        Box::new(Audit(AuditLogger::new())),
+        Box::new(Authorization(RBAC::new())),
+        Box::new(PiiSanitizer(GDPRGuard::new())),
+    ]
+}
+
+pub struct Authentication(pub TokenAuthenticator);
+
+impl Middleware for Authentication {
+    fn before(&self, invocation: &mut Invocation, _spec: &CommandSpec) -> Result<(), CommandError> {
+        let user = self
+            .0
+            .authenticate(&invocation.user_id, &invocation.auth_token)
+            .map_err(CommandError::Unauthenticated)?;
+        invocation.user = Some(user);
+        Ok(())
+    }
+}
+
+pub struct Audit(pub AuditLogger);
+
+impl Middleware for Audit {
+    fn before(&self, invocation: &mut Invocation, _spec: &CommandSpec) -> Result<(), CommandError> {
+        if let Some(user) = &invocation.user {
+            eprintln!("[AUDIT] {}", self.0.log(&format!("CommandReceived:{}", invocation.command), user));
+        }
+        Ok(())
+    }
+
+    fn after(&self, invocation: &Invocation, _spec: &CommandSpec, outcome: &Result<Value, CommandError>) {
+        if let Some(user) = &invocation.user {
+            let event = match outcome {
+                Ok(_) => "CommandCompleted",
+                Err(CommandError::Forbidden(_)) => "CommandDenied",
+                Err(CommandError::Cancelled(_)) => "CommandCancelled",
+                Err(_) => "CommandFailed",
+            };
+            eprintln!("[AUDIT] {}", self.0.log(&format!("{}:{}", event, invocation.command), user));
+        }
+    }
+}
+
+pub struct Authorization(pub RBAC);
+
+impl Middleware for Authorization {
+    fn before(&self, invocation: &mut Invocation, spec: &CommandSpec) -> Result<(), CommandError> {
+        let (Some(user), Some(role)) = (&invocation.user, &spec.required_role) else {
+            return Ok(());
+        };
+        if self.0.authorize(user, role.clone()) {
+            Ok(())
+        } else {
+            Err(CommandError::Forbidden(format!("Access Denied: {:?} role required", role)))
+        }
+    }
+}
+
+pub struct PiiSanitizer(pub GDPRGuard);
+
+impl Middleware for PiiSanitizer {
+    fn before(&self, invocation: &mut Invocation, spec: &CommandSpec) -> Result<(), CommandError> {
+        if let Value::Object(fields) = &mut invocation.payload {
+            for name in spec.pii_fields {
+                if let Some(Value::String(text)) = fields.get_mut(*name) {
+                    *text = self.0.sanitize(text);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,507 @@
+//! Command dispatch for the Veritas protocol.
+//!
+//! Every command is a [`CommandHandler`] registered by name in a [`CommandRegistry`].
+//! The registry runs the same middleware stack (authentication, audit, RBAC,
+//! PII sanitisation) around every handler, so custom commands get identical
+//! treatment to the built-in ones:
+//!
+//! ```ignore
+//! let mut registry = CommandRegistry::with_builtin();
+//! registry.register(handler("CheckFooter", Some(Role::Viewer), |req: FooterCheck, _ctx| {
+//!     Ok(footer_rules::check(&req))
+//! }));
+//! ```
+
+pub mod builtin;
+pub mod middleware;
+pub mod running;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use schemars::JsonSchema;
+use schemars::gen::SchemaSettings;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use crate::engine::determinism;
+use crate::engine::imaging::VisionError;
+use crate::engine::network::HarError;
+use crate::engine::perf_budget::PerfError;
+use crate::engine::test_data::TestDataError;
+use crate::scenario::ScenarioError;
+use crate::engine::progress::{NoProgress, ProgressSink};
+use crate::enterprise::security::{Role, UserContext};
+use middleware::{Invocation, Middleware};
+use running::{Cancellable, RunningRequests};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SecureCommand {
+    /// Client-chosen id. Tags progress notifications and the response, and is what `Cancel` targets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub auth_token: String,
+    pub user_id: String,
+    pub command: CommandEnvelope,
+}
+
+/// `{"command": "Locate", "payload": {...}}` - the payload shape depends on the command.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CommandEnvelope {
+    pub command: String,
+    #[serde(default)]
+    pub payload: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Response<T> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub status: String,
+    pub data: Option<T>,
+    pub error: Option<String>,
+}
+
+impl<T> Response<T> {
+    pub fn success(data: T) -> Self {
+        Response { request_id: None, status: "success".to_string(), data: Some(data), error: None }
+    }
+
+    pub fn error(msg: &str) -> Self {
+        Response { request_id: None, status: "error".to_string(), data: None, error: Some(msg.to_string()) }
+    }
+
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+}
+
+/// Static description of a command: what it is called, who may run it and what it accepts.
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// `None` means any authenticated user.
+    pub required_role: Option<Role>,
+    /// Self-contained (OpenAPI 3.0 flavoured) JSON Schemas of the payload and of `data` on success.
+    pub payload_schema: Value,
+    pub response_schema: Value,
+    /// Top-level payload fields that carry free text and are scrubbed of PII before the handler runs.
+    pub pii_fields: &'static [&'static str],
+    /// Whether the handler reports intermediate progress.
+    pub streams_progress: bool,
+}
+
+/// Inline JSON Schema for `T`, with no `$ref`s so it can be embedded anywhere.
+pub fn schema_of<T: JsonSchema>() -> Value {
+    let mut settings = SchemaSettings::openapi3();
+    settings.inline_subschemas = true;
+    let mut schema = serde_json::to_value(settings.into_generator().into_root_schema_for::<T>()).unwrap_or(Value::Null);
+    if let Value::Object(fields) = &mut schema {
+        fields.remove("$schema");
+        fields.remove("definitions");
+    }
+    schema
+}
+
+/// What a handler gets besides its payload.
+pub struct CommandContext<'a> {
+    pub user: &'a UserContext,
+    pub progress: &'a dyn ProgressSink,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Unauthenticated(String),
+    Forbidden(String),
+    UnknownCommand(String),
+    InvalidPayload(String),
+    Failed(String),
+    Cancelled(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Unauthenticated(msg) => write!(f, "{}", msg),
+            CommandError::Forbidden(msg) => write!(f, "{}", msg),
+            CommandError::UnknownCommand(name) => write!(f, "Unknown command '{}'", name),
+            CommandError::InvalidPayload(msg) => write!(f, "Invalid payload: {}", msg),
+            CommandError::Failed(msg) => write!(f, "{}", msg),
+            CommandError::Cancelled(request_id) => write!(f, "Request '{}' cancelled", request_id),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<VisionError> for CommandError {
+    fn from(e: VisionError) -> Self {
+        CommandError::Failed(e.to_string())
+    }
+}
+
+impl From<HarError> for CommandError {
+    fn from(e: HarError) -> Self {
+        CommandError::Failed(e.to_string())
+    }
+}
+
+impl From<PerfError> for CommandError {
+    fn from(e: PerfError) -> Self {
+        CommandError::Failed(e.to_string())
+    }
+}
+
+impl From<TestDataError> for CommandError {
+    fn from(e: TestDataError) -> Self {
+        CommandError::Failed(e.to_string())
+    }
+}
+
+impl From<ScenarioError> for CommandError {
+    fn from(e: ScenarioError) -> Self {
+        CommandError::InvalidPayload(e.to_string())
+    }
+}
+
+pub trait CommandHandler: Send + Sync {
+    fn spec(&self) -> CommandSpec;
+    fn handle(&self, payload: Value, ctx: &CommandContext<'_>) -> Result<Value, CommandError>;
+}
+
+/// A handler backed by a closure over a typed request. The payload schema is
+/// derived from `Req`, and decoding/encoding is handled here.
+pub struct TypedHandler<Req, Res, F> {
+    name: &'static str,
+    description: &'static str,
+    required_role: Option<Role>,
+    pii_fields: &'static [&'static str],
+    streams_progress: bool,
+    f: F,
+    _types: PhantomData<fn(Req) -> Res>,
+}
+
+pub fn handler<Req, Res, F>(name: &'static str, required_role: Option<Role>, f: F) -> TypedHandler<Req, Res, F>
+where
+    Req: DeserializeOwned + JsonSchema,
+    Res: Serialize + JsonSchema,
+    F: Fn(Req, &CommandContext<'_>) -> Result<Res, CommandError> + Send + Sync,
+{
+    TypedHandler { name, description: "", required_role, pii_fields: &[], streams_progress: false, f, _types: PhantomData }
+}
+
+impl<Req, Res, F> TypedHandler<Req, Res, F> {
+    pub fn describe(mut self, description: &'static str) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub fn sanitize(mut self, pii_fields: &'static [&'static str]) -> Self {
+        self.pii_fields = pii_fields;
+        self
+    }
+
+    pub fn streaming(mut self) -> Self {
+        self.streams_progress = true;
+        self
+    }
+}
+
+impl<Req, Res, F> CommandHandler for TypedHandler<Req, Res, F>
+where
+    Req: DeserializeOwned + JsonSchema,
+    Res: Serialize + JsonSchema,
+    F: Fn(Req, &CommandContext<'_>) -> Result<Res, CommandError> + Send + Sync,
+{
+    fn spec(&self) -> CommandSpec {
+        CommandSpec {
+            name: self.name,
+            description: self.description,
+            required_role: self.required_role.clone(),
+            payload_schema: schema_of::<Req>(),
+            response_schema: schema_of::<Res>(),
+            pii_fields: self.pii_fields,
+            streams_progress: self.streams_progress,
+        }
+    }
+
+    fn handle(&self, payload: Value, ctx: &CommandContext<'_>) -> Result<Value, CommandError> {
+        let request: Req = serde_json::from_value(payload).map_err(|e| CommandError::InvalidPayload(e.to_string()))?;
+        let result = (self.f)(request, ctx)?;
+        serde_json::to_value(result).map_err(|e| CommandError::Failed(e.to_string()))
+    }
+}
+
+pub struct CommandRegistry {
+    handlers: BTreeMap<String, Box<dyn CommandHandler>>,
+    middleware: Vec<Box<dyn Middleware>>,
+    seed: Option<u64>,
+    running: Arc<RunningRequests>,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandRegistry {
+    /// An empty registry with the standard middleware stack.
+    pub fn new() -> Self {
+        CommandRegistry {
+            handlers: BTreeMap::new(),
+            middleware: middleware::standard_stack(),
+            seed: None,
+            running: Arc::new(RunningRequests::default()),
+        }
+    }
+
+    /// A registry with every built-in Veritas command.
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::new();
+        builtin::register_all(&mut registry);
+        registry
+    }
+
+    /// Registers a handler under its spec name, returning any handler it replaced.
+    pub fn register(&mut self, handler: impl CommandHandler + 'static) -> Option<Box<dyn CommandHandler>> {
+        let name = handler.spec().name.to_string();
+        self.handlers.insert(name, Box::new(handler))
+    }
+
+    /// Makes every command deterministic: each one runs with randomness seeded from
+    /// `seed` and its own name and payload, and with a frozen clock.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Requests currently running under a `request_id`.
+    pub fn running(&self) -> Arc<RunningRequests> {
+        self.running.clone()
+    }
+
+    /// Appends a middleware that runs after the standard stack.
+    pub fn push_middleware(&mut self, middleware: impl Middleware + 'static) {
+        self.middleware.push(Box::new(middleware));
+    }
+
+    pub fn specs(&self) -> Vec<CommandSpec> {
+        self.handlers.values().map(|h| h.spec()).collect()
+    }
+
+    pub fn spec(&self, name: &str) -> Option<CommandSpec> {
+        self.handlers.get(name).map(|h| h.spec())
+    }
+
+    pub fn dispatch(&self, command: SecureCommand) -> Result<Value, CommandError> {
+        self.dispatch_with_progress(command, &NoProgress)
+    }
+
+    /// Dispatches a command, forwarding any intermediate progress the handler reports.
+    pub fn dispatch_with_progress(&self, command: SecureCommand, progress: &dyn ProgressSink) -> Result<Value, CommandError> {
+        let handler = self
+            .handlers
+            .get(&command.command.command)
+            .ok_or_else(|| CommandError::UnknownCommand(command.command.command.clone()))?;
+        let spec = handler.spec();
+
+        let request_id = command.request_id;
+        let mut invocation = Invocation {
+            command: command.command.command,
+            user_id: command.user_id,
+            auth_token: command.auth_token,
+            user: None,
+            payload: command.command.payload,
+        };
+
+        // Run `before` hooks in order; `after` hooks unwind over whatever ran.
+        let mut entered = 0;
+        let mut outcome = Ok(Value::Null);
+        for m in &self.middleware {
+            entered += 1;
+            if let Err(e) = m.before(&mut invocation, &spec) {
+                outcome = Err(e);
+                break;
+            }
+        }
+
+        if outcome.is_ok() {
+            outcome = match &invocation.user {
+                Some(user) => self.run_handler(handler.as_ref(), &invocation, user, request_id.as_deref(), progress),
+                None => Err(CommandError::Unauthenticated("Authentication required".to_string())),
+            };
+        }
+
+        for m in self.middleware[..entered].iter().rev() {
+            m.after(&invocation, &spec, &outcome);
+        }
+        outcome
+    }
+
+    /// Runs the handler itself: tracked for cancellation when it has a request id,
+    /// and seeded when the registry is deterministic.
+    fn run_handler(
+        &self,
+        handler: &dyn CommandHandler,
+        invocation: &Invocation,
+        user: &UserContext,
+        request_id: Option<&str>,
+        progress: &dyn ProgressSink,
+    ) -> Result<Value, CommandError> {
+        let flag = match request_id {
+            Some(id) => Some(self.running.start(id, &user.user_id)?),
+            None => None,
+        };
+        let sink = Cancellable { inner: progress, flag: flag.clone().unwrap_or_default() };
+
+        let run = || handler.handle(invocation.payload.clone(), &CommandContext { user, progress: &sink });
+        let result = match self.seed {
+            // Seed per command content, so a command's output never depends on what ran before it
+            Some(seed) => {
+                let payload = invocation.payload.to_string();
+                let parts: [&[u8]; 3] = [&seed.to_le_bytes(), invocation.command.as_bytes(), payload.as_bytes()];
+                determinism::with_seed(determinism::fingerprint(&parts), run)
+            }
+            None => run(),
+        };
+
+        match (request_id, flag) {
+            (Some(id), Some(flag)) => {
+                self.running.finish(id);
+                if flag.is_cancelled() { Err(CommandError::Cancelled(id.to_string())) } else { result }
+            }
+            _ => result,
+        }
+    }
+
+    /// Parses and dispatches one protocol line.
+    pub fn dispatch_line(&self, line: &str) -> Response<Value> {
+        self.dispatch_line_with_progress(line, &NoProgress)
+    }
+
+    /// Like `dispatch_line`; the response carries the command's `request_id`, if any.
+    pub fn dispatch_line_with_progress(&self, line: &str, progress: &dyn ProgressSink) -> Response<Value> {
+        match serde_json::from_str::<SecureCommand>(line) {
+            Ok(command) => {
+                let request_id = command.request_id.clone();
+                let response = match self.dispatch_with_progress(command, progress) {
+                    Ok(data) => Response::success(data),
+                    Err(e) => Response::error(&e.to_string()),
+                };
+                response.with_request_id(request_id)
+            }
+            Err(e) => Response::error(&format!("Invalid Secure Command format: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct EchoRequest {
+        text: String,
+    }
+
+    fn echo_registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry.register(
+            handler("Echo", Some(Role::Agent), |req: EchoRequest, ctx: &CommandContext<'_>| {
+                Ok(format!("{}: {}", ctx.user.user_id, req.text))
+            })
+            .sanitize(&["text"]),
+        );
+        registry
+    }
+
+    fn line(user: &str, token: &str, command: &str, payload: Value) -> String {
+        json!({ "auth_token": token, "user_id": user, "command": { "command": command, "payload": payload } }).to_string()
+    }
+
+    #[test]
+    fn test_custom_handler_runs_behind_middleware() {
+        let registry = echo_registry();
+        let response = registry.dispatch_line(&line("admin", "valid_token", "Echo", json!({ "text": "mail me at a@b.com" })));
+        assert_eq!(response.status, "success");
+        let text = response.data.unwrap();
+        assert!(text.as_str().unwrap().starts_with("admin: "));
+        assert!(text.as_str().unwrap().contains("REDACTED"));
+    }
+
+    #[test]
+    fn test_rejects_bad_token_and_missing_role() {
+        let registry = echo_registry();
+        let bad_token = registry.dispatch_line(&line("admin", "nope", "Echo", json!({ "text": "hi" })));
+        assert_eq!(bad_token.error.unwrap(), "Authentication failed: Invalid token");
+
+        let viewer = registry.dispatch_line(&line("viewer1", "valid_token", "Echo", json!({ "text": "hi" })));
+        assert!(viewer.error.unwrap().starts_with("Access Denied"));
+    }
+
+    #[test]
+    fn test_unknown_command_and_bad_payload() {
+        let registry = echo_registry();
+        let unknown = registry.dispatch_line(&line("admin", "valid_token", "Nope", Value::Null));
+        assert_eq!(unknown.error.unwrap(), "Unknown command 'Nope'");
+
+        let invalid = registry.dispatch_line(&line("admin", "valid_token", "Echo", json!({ "txt": 1 })));
+        assert!(invalid.error.unwrap().starts_with("Invalid payload"));
+    }
+
+    #[test]
+    fn test_builtin_specs_have_schemas() {
+        let registry = CommandRegistry::with_builtin();
+        let names: Vec<&str> = registry.specs().iter().map(|s| s.name).collect();
+        for expected in ["Compare", "Locate", "Heal", "Goal", "Observe", "Swarm", "Omega", "Ping", "LearnMask", "Baseline"] {
+            assert!(names.contains(&expected), "missing {}", expected);
+        }
+        let ping = registry.dispatch_line(&line("viewer1", "valid_token", "Ping", Value::Null));
+        assert_eq!(ping.data.unwrap(), json!("Pong"));
+        assert!(registry.specs().iter().all(|s| !s.payload_schema.is_null() && !s.response_schema.is_null()));
+
+        let locate = registry.spec("Locate").unwrap();
+        let schema = locate.payload_schema.to_string();
+        assert!(!schema.contains("$ref"));
+        assert!(schema.contains("device_pixel_ratio"));
+        assert!(registry.spec("Goal").unwrap().streams_progress);
+    }
+
+    #[test]
+    fn test_cancel_stops_running_request_for_owner_or_admin() {
+        use crate::engine::progress::ProgressEvent;
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let registry = CommandRegistry::with_builtin();
+        let swarm = json!({ "request_id": "run-1", "auth_token": "valid_token", "user_id": "admin",
+            "command": { "command": "Swarm", "payload": { "agent_count": 50, "regions": ["eu"], "task_goal": "x" } } });
+        let finished = AtomicU32::new(0);
+        let sink = |event: ProgressEvent| {
+            if let ProgressEvent::AgentFinished { .. } = event {
+                if finished.fetch_add(1, Ordering::SeqCst) == 0 {
+                    let cancel = json!({ "request_id": "run-1" });
+                    let denied = registry.dispatch_line(&line("viewer1", "valid_token", "Cancel", cancel.clone()));
+                    assert!(denied.error.unwrap().starts_with("Access Denied"));
+                    let accepted = registry.dispatch_line(&line("admin", "valid_token", "Cancel", cancel));
+                    assert_eq!(accepted.data.unwrap()["cancelled"], true);
+                }
+            }
+        };
+
+        let response = registry.dispatch_line_with_progress(&swarm.to_string(), &sink);
+        assert_eq!(response.error.unwrap(), "Request 'run-1' cancelled");
+        assert_eq!(response.request_id.as_deref(), Some("run-1"));
+        assert!(finished.load(Ordering::SeqCst) < 50);
+
+        // Finished requests are forgotten, so the id can be reused and no longer cancelled
+        let gone = registry.dispatch_line(&line("admin", "valid_token", "Cancel", json!({ "request_id": "run-1" })));
+        assert_eq!(gone.error.unwrap(), "No running request 'run-1'");
+    }
+}
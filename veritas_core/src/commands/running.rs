@@ -0,0 +1,85 @@
+//! Requests in flight, by client-chosen `request_id`, so they can be cancelled.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use super::CommandError;
+use crate::engine::progress::{ProgressEvent, ProgressSink};
+use crate::enterprise::security::{Role, UserContext};
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct CancelRequest {
+    pub request_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct CancelResult {
+    pub request_id: String,
+    pub cancelled: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Default)]
+pub struct RunningRequests {
+    // request_id -> (owner user_id, flag)
+    requests: Mutex<HashMap<String, (String, CancelFlag)>>,
+}
+
+impl RunningRequests {
+    pub fn start(&self, request_id: &str, user_id: &str) -> Result<CancelFlag, CommandError> {
+        let mut requests = self.requests.lock().unwrap();
+        if requests.contains_key(request_id) {
+            return Err(CommandError::InvalidPayload(format!("request_id '{}' is already running", request_id)));
+        }
+        let flag = CancelFlag::default();
+        requests.insert(request_id.to_string(), (user_id.to_string(), flag.clone()));
+        Ok(flag)
+    }
+
+    pub fn finish(&self, request_id: &str) {
+        self.requests.lock().unwrap().remove(request_id);
+    }
+
+    /// Only the user who started a request, or an admin, may cancel it.
+    pub fn cancel(&self, request_id: &str, user: &UserContext) -> Result<CancelResult, CommandError> {
+        let requests = self.requests.lock().unwrap();
+        let (owner, flag) = requests
+            .get(request_id)
+            .ok_or_else(|| CommandError::Failed(format!("No running request '{}'", request_id)))?;
+        if owner != &user.user_id && !user.roles.contains(&Role::Admin) {
+            return Err(CommandError::Forbidden(format!("Access Denied: request '{}' belongs to another user", request_id)));
+        }
+        flag.cancel();
+        Ok(CancelResult { request_id: request_id.to_string(), cancelled: true })
+    }
+}
+
+/// Forwards progress to `inner` and reports cancellation from either side.
+pub struct Cancellable<'a> {
+    pub inner: &'a dyn ProgressSink,
+    pub flag: CancelFlag,
+}
+
+impl ProgressSink for Cancellable<'_> {
+    fn emit(&self, event: ProgressEvent) {
+        self.inner.emit(event)
+    }
+
+    fn cancelled(&self) -> bool {
+        self.flag.is_cancelled() || self.inner.cancelled()
+    }
+}
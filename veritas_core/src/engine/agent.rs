@@ -1,14 +1,17 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use rand::Rng;
 use std::collections::{HashMap, VecDeque, HashSet};
-use uuid::Uuid;
+use crate::engine::determinism;
+use crate::engine::progress::{NoProgress, ProgressEvent, ProgressSink};
+use crate::engine::test_data::{synthetic_address, Persona};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct GoalRequest {
     pub goal: String, // e.g. "Verify purchase with 10% discount"
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct AgentStep {
     pub step_id: u32,
     pub action: String,
@@ -18,7 +21,7 @@ pub struct AgentStep {
     pub status: String, // "completed", "failed"
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct GoalResult {
     pub success: bool,
     pub goal_id: String,
@@ -85,6 +88,11 @@ impl GoalOrientedAgent {
     }
 
     pub fn execute(&self, request: &GoalRequest) -> GoalResult {
+        self.execute_with_progress(request, &NoProgress)
+    }
+
+    /// Same as `execute`, reporting each step to `progress` as it starts and finishes.
+    pub fn execute_with_progress(&self, request: &GoalRequest, progress: &dyn ProgressSink) -> GoalResult {
         // 1. Parse Goal (NLP Simulation)
         let goal_lower = request.goal.to_lowercase();
         let target_state = if goal_lower.contains("login") {
@@ -95,13 +103,16 @@ impl GoalOrientedAgent {
             PageState::ProductListing
         };
 
-        let goal_id = Uuid::new_v4().to_string();
+        let goal_id = determinism::uuid().to_string();
         let mut steps = Vec::new();
-        let mut rng = rand::thread_rng();
+        let mut rng = determinism::rng();
         let mut total_duration = 0;
         let d1 = 10;
+        // Forms are filled with synthetic data only, never real personal data
+        let persona = Persona::random(&mut rng);
+        let mut cancelled = false;
 
-        steps.push(AgentStep {
+        Self::record(&mut steps, progress, AgentStep {
             step_id: 0,
             action: "Start Session".to_string(),
             observation: "Landed on Homepage".to_string(),
@@ -115,6 +126,33 @@ impl GoalOrientedAgent {
         if let Some(path) = self.find_path(PageState::Home, &target_state) {
 
             for (action, next_state) in path {
+                if progress.cancelled() {
+                    let step_id = steps.len() as u32;
+                    Self::record(&mut steps, progress, AgentStep {
+                        step_id,
+                        action: "Cancel".to_string(),
+                        observation: format!("Stopped before '{}'", action),
+                        reasoning: "Cancellation requested".to_string(),
+                        duration_ms: 0,
+                        status: "cancelled".to_string(),
+                    });
+                    cancelled = true;
+                    break;
+                }
+
+                if action == "submit_credentials" {
+                    let step_id = steps.len() as u32;
+                    Self::record(&mut steps, progress, AgentStep {
+                        step_id,
+                        action: format!("Input '{}'", persona.email("example.com")),
+                        observation: "Credentials entered".to_string(),
+                        reasoning: "Login form requires an account (synthetic test user)".to_string(),
+                        duration_ms: 80,
+                        status: "completed".to_string(),
+                    });
+                    total_duration += 80;
+                }
+
                 let duration = rng.gen_range(100..500);
 
                 // Specific Logic for Goal Verification during execution
@@ -124,8 +162,9 @@ impl GoalOrientedAgent {
                     reasoning.push_str(". Will apply discount.");
                 }
 
-                steps.push(AgentStep {
-                    step_id: steps.len() as u32,
+                let step_id = steps.len() as u32;
+                Self::record(&mut steps, progress, AgentStep {
+                    step_id,
                     action: action.clone(),
                     observation: format!("Transitioned to {:?}", next_state),
                     reasoning,
@@ -134,10 +173,24 @@ impl GoalOrientedAgent {
                 });
                 total_duration += duration;
 
+                if next_state == PageState::Checkout {
+                    let step_id = steps.len() as u32;
+                    Self::record(&mut steps, progress, AgentStep {
+                        step_id,
+                        action: format!("Input '{}', '{}'", persona.full_name(), synthetic_address(&mut rng)),
+                        observation: "Shipping details entered".to_string(),
+                        reasoning: "Checkout form requires a shipping address (synthetic test data)".to_string(),
+                        duration_ms: 120,
+                        status: "completed".to_string(),
+                    });
+                    total_duration += 120;
+                }
+
                 // If we hit checkout and need discount, inject extra step
                  if next_state == PageState::Checkout && goal_lower.contains("discount") {
-                     steps.push(AgentStep {
-                        step_id: steps.len() as u32,
+                     let step_id = steps.len() as u32;
+                     Self::record(&mut steps, progress, AgentStep {
+                        step_id,
                         action: "Input 'SAVE10'".to_string(),
                         observation: "Discount -10% applied".to_string(),
                         reasoning: "Goal Requirement: Discount".to_string(),
@@ -148,8 +201,9 @@ impl GoalOrientedAgent {
                 }
             }
         } else {
-            steps.push(AgentStep {
-                step_id: steps.len() as u32,
+            let step_id = steps.len() as u32;
+            Self::record(&mut steps, progress, AgentStep {
+                step_id,
                 action: "Error".to_string(),
                 observation: "Could not find path".to_string(),
                 reasoning: "Target state unreachable".to_string(),
@@ -159,7 +213,7 @@ impl GoalOrientedAgent {
         }
 
         GoalResult {
-            success: true,
+            success: !cancelled,
             goal_id,
             steps,
             audit_log_url: format!("s3://veritas-logs/{}/replay.mp4", rng.gen::<u32>()),
@@ -167,6 +221,12 @@ impl GoalOrientedAgent {
         }
     }
 
+    fn record(steps: &mut Vec<AgentStep>, progress: &dyn ProgressSink, step: AgentStep) {
+        progress.emit(ProgressEvent::StepStarted { step_id: step.step_id, action: step.action.clone() });
+        progress.emit(ProgressEvent::StepFinished { step: step.clone() });
+        steps.push(step);
+    }
+
     fn find_path(&self, start: PageState, target: &PageState) -> Option<Vec<(String, PageState)>> {
         let mut queue = VecDeque::new();
         queue.push_back((start.clone(), Vec::new())); // (CurrentState, PathSoFar)
@@ -222,4 +282,19 @@ mod tests {
         let has_discount = result.steps.iter().any(|s| s.action.contains("SAVE10"));
         assert!(has_discount);
     }
+
+    #[test]
+    fn test_agent_reports_progress() {
+        use std::sync::Mutex;
+        let agent = GoalOrientedAgent::new();
+        let events = Mutex::new(Vec::new());
+        let sink = |e: ProgressEvent| events.lock().unwrap().push(e);
+
+        let result = agent.execute_with_progress(&GoalRequest { goal: "login".to_string() }, &sink);
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.len(), result.steps.len() * 2);
+        assert!(matches!(events[0], ProgressEvent::StepStarted { step_id: 0, .. }));
+        assert!(matches!(events[1], ProgressEvent::StepFinished { .. }));
+    }
 }
@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use crate::engine::dynamic_mask::PageMask;
+use crate::engine::imaging::{decode_image, ImageEncoding, VisionError};
+use crate::engine::neural_locator::NeuralLocator;
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(tag = "action")]
+pub enum BaselineRequest {
+    /// Stores `image_base64` as the reference capture for `page_id`.
+    Save {
+        page_id: String,
+        image_base64: String,
+        #[serde(default)]
+        image_encoding: ImageEncoding,
+    },
+    /// Compares a fresh capture against the stored reference, ignoring learned dynamic regions.
+    Check {
+        page_id: String,
+        image_base64: String,
+        #[serde(default)]
+        image_encoding: ImageEncoding,
+        #[serde(default = "default_threshold")]
+        threshold: f32,
+    },
+}
+
+fn default_threshold() -> f32 { 0.99 }
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct BaselineResult {
+    pub page_id: String,
+    pub passed: bool,
+    pub similarity_score: f32,
+    pub diff_reason: Option<String>,
+    pub masked_pixels: u64,
+    pub baseline_path: String,
+}
+
+/// Store root shared by every on-disk engine: `$VERITAS_STORE_DIR`, or `.veritas`.
+pub fn store_root() -> PathBuf {
+    PathBuf::from(std::env::var("VERITAS_STORE_DIR").unwrap_or_else(|_| ".veritas".to_string()))
+}
+
+/// Page ids are usually URLs or route names; keep them filesystem-safe. The readable
+/// part loses characters, so a hash of the original id keeps distinct ids apart.
+pub(crate) fn safe_file_stem(id: &str) -> Option<String> {
+    let safe: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    if safe.trim_matches('.').is_empty() {
+        return None;
+    }
+    let digest = Sha256::digest(id.as_bytes());
+    let hash: String = digest[..4].iter().map(|b| format!("{:02x}", b)).collect();
+    Some(format!("{}-{}", safe, hash))
+}
+
+/// On-disk home for per-page baselines (`baselines/<page>.png`) and learned
+/// masks (`masks/<page>.json`). Rooted at `$VERITAS_STORE_DIR`, or `.veritas`.
+pub struct BaselineStore {
+    root: PathBuf,
+}
+
+impl Default for BaselineStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BaselineStore {
+    pub fn new() -> Self {
+        Self::at(store_root())
+    }
+
+    pub fn at(root: impl Into<PathBuf>) -> Self {
+        BaselineStore { root: root.into() }
+    }
+
+    fn page_path(&self, kind: &str, page_id: &str, ext: &str) -> Result<PathBuf, VisionError> {
+        let safe = safe_file_stem(page_id).ok_or_else(|| VisionError::InvalidRequest(format!("invalid page_id '{}'", page_id)))?;
+        Ok(self.root.join(kind).join(format!("{}.{}", safe, ext)))
+    }
+
+    fn ensure_parent(path: &std::path::Path) -> Result<(), VisionError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| VisionError::Storage(format!("{}: {}", parent.display(), e)))?;
+        }
+        Ok(())
+    }
+
+    pub fn save_mask(&self, mask: &PageMask) -> Result<PathBuf, VisionError> {
+        let path = self.page_path("masks", &mask.page_id, "json")?;
+        Self::ensure_parent(&path)?;
+        let json = serde_json::to_string_pretty(mask).map_err(|e| VisionError::Storage(e.to_string()))?;
+        fs::write(&path, json).map_err(|e| VisionError::Storage(format!("{}: {}", path.display(), e)))?;
+        Ok(path)
+    }
+
+    pub fn load_mask(&self, page_id: &str) -> Result<Option<PageMask>, VisionError> {
+        let path = self.page_path("masks", page_id, "json")?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(&path).map_err(|e| VisionError::Storage(format!("{}: {}", path.display(), e)))?;
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| VisionError::Storage(format!("corrupt mask {}: {}", path.display(), e)))
+    }
+
+    /// Convenience for commands where the page id is optional.
+    pub fn mask_for(&self, page_id: Option<&str>) -> Result<Option<PageMask>, VisionError> {
+        match page_id {
+            Some(id) => self.load_mask(id),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_baseline(&self, page_id: &str, image: &DynamicImage) -> Result<PathBuf, VisionError> {
+        let path = self.page_path("baselines", page_id, "png")?;
+        Self::ensure_parent(&path)?;
+        image
+            .save_with_format(&path, ImageFormat::Png)
+            .map_err(|e| VisionError::Storage(format!("{}: {}", path.display(), e)))?;
+        Ok(path)
+    }
+
+    pub fn load_baseline(&self, page_id: &str) -> Result<Option<DynamicImage>, VisionError> {
+        let path = self.page_path("baselines", page_id, "png")?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        image::open(&path)
+            .map(Some)
+            .map_err(|e| VisionError::Storage(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn run(&self, request: &BaselineRequest) -> Result<BaselineResult, VisionError> {
+        match request {
+            BaselineRequest::Save { page_id, image_base64, image_encoding } => {
+                let image = decode_image(image_base64, image_encoding)?;
+                let path = self.save_baseline(page_id, &image)?;
+                Ok(BaselineResult {
+                    page_id: page_id.clone(),
+                    passed: true,
+                    similarity_score: 1.0,
+                    diff_reason: None,
+                    masked_pixels: 0,
+                    baseline_path: path.display().to_string(),
+                })
+            }
+            BaselineRequest::Check { page_id, image_base64, image_encoding, threshold } => {
+                let current = decode_image(image_base64, image_encoding)?;
+                let baseline = self
+                    .load_baseline(page_id)?
+                    .ok_or_else(|| VisionError::InvalidRequest(format!("no baseline saved for page '{}'", page_id)))?;
+                let mask = self.load_mask(page_id)?;
+                let (w, h) = baseline.dimensions();
+                let comparison = NeuralLocator::compare_images(&baseline, &current, mask.as_ref().filter(|m| m.fits(w, h)));
+
+                Ok(BaselineResult {
+                    page_id: page_id.clone(),
+                    passed: comparison.similarity_score >= *threshold,
+                    similarity_score: comparison.similarity_score,
+                    diff_reason: comparison.diff_reason,
+                    masked_pixels: comparison.masked_pixels,
+                    baseline_path: self.page_path("baselines", page_id, "png")?.display().to_string(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::neural_locator::BoundingBox;
+    use base64::{Engine as _, engine::general_purpose};
+    use image::{Rgba, RgbaImage};
+
+    fn temp_store(name: &str) -> BaselineStore {
+        let dir = std::env::temp_dir().join(format!("veritas_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        BaselineStore::at(dir)
+    }
+
+    fn raw(img: RgbaImage) -> String {
+        general_purpose::STANDARD.encode(img.into_raw())
+    }
+
+    #[test]
+    fn test_check_ignores_masked_region() {
+        let store = temp_store("baseline_mask");
+        let encoding = ImageEncoding::RawRgba { width: 32, height: 32 };
+        let white = RgbaImage::from_pixel(32, 32, Rgba([255, 255, 255, 255]));
+        let mut with_clock = white.clone();
+        for x in 0..8 {
+            for y in 0..8 {
+                with_clock.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+            }
+        }
+
+        store.run(&BaselineRequest::Save {
+            page_id: "https://shop.test/home".to_string(),
+            image_base64: raw(white),
+            image_encoding: encoding.clone(),
+        }).unwrap();
+
+        let check = || store.run(&BaselineRequest::Check {
+            page_id: "https://shop.test/home".to_string(),
+            image_base64: raw(with_clock.clone()),
+            image_encoding: encoding.clone(),
+            threshold: 1.0,
+        }).unwrap();
+        assert!(!check().passed);
+
+        store.save_mask(&PageMask {
+            page_id: "https://shop.test/home".to_string(),
+            image_width: 32,
+            image_height: 32,
+            regions: vec![BoundingBox { x: 0, y: 0, width: 8, height: 8, label: None, confidence: 1.0 }],
+            captures_used: 3,
+            learned_at: 0,
+        }).unwrap();
+        let masked = check();
+        assert!(masked.passed);
+        assert_eq!(masked.masked_pixels, 64);
+    }
+
+    #[test]
+    fn test_file_stems_keep_distinct_ids_apart() {
+        let url = safe_file_stem("https://a/b").unwrap();
+        let lookalike = safe_file_stem("https___a_b").unwrap();
+        assert!(url.starts_with("https___a_b-"));
+        assert_ne!(url, lookalike);
+        assert_eq!(url, safe_file_stem("https://a/b").unwrap());
+        assert_eq!(safe_file_stem(".."), None);
+    }
+
+    #[test]
+    fn test_missing_baseline_is_an_error() {
+        let store = temp_store("baseline_missing");
+        let result = store.run(&BaselineRequest::Check {
+            page_id: "nowhere".to_string(),
+            image_base64: raw(RgbaImage::new(2, 2)),
+            image_encoding: ImageEncoding::RawRgba { width: 2, height: 2 },
+            threshold: default_threshold(),
+        });
+        assert!(result.is_err());
+    }
+}
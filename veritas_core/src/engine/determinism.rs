@@ -0,0 +1,89 @@
+//! Source of randomness and time for every engine.
+//!
+//! Engines never call `thread_rng()` or the system clock directly. Outside a seeded
+//! scope they get fresh entropy and real time; inside `with_seed` they get a stream
+//! derived from the seed, a fixed clock and zero elapsed time, so the same input
+//! yields byte-identical output.
+
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::cell::RefCell;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Timestamp reported by `now_secs` in deterministic mode (2024-01-01T00:00:00Z).
+pub const DETERMINISTIC_EPOCH: u64 = 1_704_067_200;
+
+thread_local! {
+    static SEEDED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with this thread's randomness derived from `seed`.
+pub fn with_seed<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    let previous = SEEDED.with(|s| s.borrow_mut().replace(StdRng::seed_from_u64(seed)));
+    let result = f();
+    SEEDED.with(|s| *s.borrow_mut() = previous);
+    result
+}
+
+pub fn is_deterministic() -> bool {
+    SEEDED.with(|s| s.borrow().is_some())
+}
+
+/// A generator for one engine call: a child of the seeded stream, or fresh entropy.
+pub fn rng() -> StdRng {
+    SEEDED.with(|s| match s.borrow_mut().as_mut() {
+        Some(stream) => StdRng::seed_from_u64(stream.next_u64()),
+        None => StdRng::from_entropy(),
+    })
+}
+
+pub fn uuid() -> Uuid {
+    uuid::Builder::from_random_bytes(rng().gen()).into_uuid()
+}
+
+pub fn now_secs() -> u64 {
+    if is_deterministic() {
+        return DETERMINISTIC_EPOCH;
+    }
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+pub fn elapsed_ms(start: Instant) -> u64 {
+    if is_deterministic() { 0 } else { start.elapsed().as_millis() as u64 }
+}
+
+/// Stable 64-bit FNV-1a hash, used to derive per-command seeds from their content.
+pub fn fingerprint(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.iter().chain(std::iter::once(&0xff)) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_scope_is_reproducible() {
+        let draw = || (rng().gen::<u64>(), rng().gen::<u64>(), uuid(), now_secs());
+        let a = with_seed(7, draw);
+        let b = with_seed(7, draw);
+        let c = with_seed(8, draw);
+        assert_eq!(a, b);
+        assert_ne!(a.0, c.0);
+        assert_eq!(a.3, DETERMINISTIC_EPOCH);
+        assert!(!is_deterministic());
+    }
+
+    #[test]
+    fn test_fingerprint_separates_parts() {
+        assert_ne!(fingerprint(&[b"ab", b"c"]), fingerprint(&[b"a", b"bc"]));
+        assert_eq!(fingerprint(&[b"Locate"]), fingerprint(&[b"Locate"]));
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::collections::VecDeque;
+use image::{DynamicImage, GenericImageView};
+use crate::engine::determinism;
+use crate::engine::imaging::{decode_image, ImageEncoding, VisionError};
+use crate::engine::neural_locator::BoundingBox;
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct LearnMaskRequest {
+    pub page_id: String,
+    pub captures_base64: Vec<String>, // N screenshots of the same page, same size
+    #[serde(default)]
+    pub image_encoding: ImageEncoding,
+    #[serde(default = "default_cell_size")]
+    pub cell_size: u32, // Grid resolution in device pixels
+    #[serde(default = "default_pixel_tolerance")]
+    pub pixel_tolerance: u32, // Summed RGB delta below which a pixel counts as unchanged
+    #[serde(default = "default_min_change_ratio")]
+    pub min_change_ratio: f32, // Share of consecutive capture pairs in which a cell must change
+}
+
+fn default_cell_size() -> u32 { 16 }
+fn default_pixel_tolerance() -> u32 { 24 }
+fn default_min_change_ratio() -> f32 { 0.5 }
+
+/// Regions of a page that are expected to differ between captures
+/// (clocks, ads, carousels, avatars). Coordinates are device pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PageMask {
+    pub page_id: String,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub regions: Vec<BoundingBox>,
+    pub captures_used: usize,
+    pub learned_at: u64,
+}
+
+impl PageMask {
+    /// Whether this mask was learned on images of the given size.
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        self.image_width == width && self.image_height == height
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        let (x, y) = (x as i32, y as i32);
+        self.regions
+            .iter()
+            .any(|r| x >= r.x && x < r.x + r.width && y >= r.y && y < r.y + r.height)
+    }
+}
+
+pub struct DynamicRegionLearner;
+
+impl DynamicRegionLearner {
+    pub fn learn(request: &LearnMaskRequest) -> Result<PageMask, VisionError> {
+        if request.captures_base64.len() < 2 {
+            return Err(VisionError::InvalidRequest("at least two captures are needed to learn a mask".to_string()));
+        }
+        if request.cell_size == 0 {
+            return Err(VisionError::InvalidRequest("cell_size must be non-zero".to_string()));
+        }
+
+        let captures = request
+            .captures_base64
+            .iter()
+            .map(|c| decode_image(c, &request.image_encoding))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (width, height) = captures[0].dimensions();
+        if let Some(odd) = captures.iter().find(|c| c.dimensions() != (width, height)) {
+            return Err(VisionError::InvalidRequest(format!(
+                "captures must share dimensions: {}x{} vs {}x{}",
+                width, height, odd.width(), odd.height()
+            )));
+        }
+
+        let regions = Self::find_regions(&captures, request.cell_size, request.pixel_tolerance, request.min_change_ratio);
+        eprintln!("[DynamicMask] '{}': {} dynamic region(s) from {} captures", request.page_id, regions.len(), captures.len());
+
+        Ok(PageMask {
+            page_id: request.page_id.clone(),
+            image_width: width,
+            image_height: height,
+            regions,
+            captures_used: captures.len(),
+            learned_at: determinism::now_secs(),
+        })
+    }
+
+    /// Marks grid cells that change in enough consecutive capture pairs, then merges
+    /// touching cells into rectangles.
+    fn find_regions(captures: &[DynamicImage], cell: u32, tolerance: u32, min_ratio: f32) -> Vec<BoundingBox> {
+        let (width, height) = captures[0].dimensions();
+        let cols = width.div_ceil(cell) as usize;
+        let rows = height.div_ceil(cell) as usize;
+        let mut change_counts = vec![0u32; cols * rows];
+
+        for pair in captures.windows(2) {
+            let (a, b) = (pair[0].to_rgba8(), pair[1].to_rgba8());
+            let mut changed = vec![false; cols * rows];
+            for (x, y, p_a) in a.enumerate_pixels() {
+                let idx = (y / cell) as usize * cols + (x / cell) as usize;
+                if changed[idx] {
+                    continue;
+                }
+                let p_b = b.get_pixel(x, y);
+                let delta: u32 = (0..3).map(|c| (p_a[c] as i32 - p_b[c] as i32).unsigned_abs()).sum();
+                if delta > tolerance {
+                    changed[idx] = true;
+                }
+            }
+            for (count, hit) in change_counts.iter_mut().zip(changed) {
+                *count += hit as u32;
+            }
+        }
+
+        let pairs = (captures.len() - 1) as f32;
+        let dynamic: Vec<bool> = change_counts
+            .iter()
+            .map(|&c| c > 0 && c as f32 / pairs >= min_ratio)
+            .collect();
+
+        // Connected components (4-neighbourhood) over the cell grid
+        let mut seen = vec![false; cols * rows];
+        let mut regions = Vec::new();
+        for start in 0..dynamic.len() {
+            if !dynamic[start] || seen[start] {
+                continue;
+            }
+            let (mut min_c, mut min_r, mut max_c, mut max_r) = (usize::MAX, usize::MAX, 0, 0);
+            let mut hits = 0u32;
+            let mut cells = 0u32;
+            let mut queue = VecDeque::from([start]);
+            seen[start] = true;
+            while let Some(idx) = queue.pop_front() {
+                let (c, r) = (idx % cols, idx / cols);
+                min_c = min_c.min(c);
+                max_c = max_c.max(c);
+                min_r = min_r.min(r);
+                max_r = max_r.max(r);
+                hits += change_counts[idx];
+                cells += 1;
+
+                let mut neighbours = Vec::with_capacity(4);
+                if c > 0 { neighbours.push(idx - 1); }
+                if c + 1 < cols { neighbours.push(idx + 1); }
+                if r > 0 { neighbours.push(idx - cols); }
+                if r + 1 < rows { neighbours.push(idx + cols); }
+                for n in neighbours {
+                    if dynamic[n] && !seen[n] {
+                        seen[n] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+
+            let x = min_c as u32 * cell;
+            let y = min_r as u32 * cell;
+            regions.push(BoundingBox {
+                x: x as i32,
+                y: y as i32,
+                width: (((max_c as u32 + 1) * cell).min(width) - x) as i32,
+                height: (((max_r as u32 + 1) * cell).min(height) - y) as i32,
+                label: Some("Dynamic Region".to_string()),
+                // How consistently the region changed across capture pairs
+                confidence: hits as f32 / (cells as f32 * pairs),
+            });
+        }
+        regions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{Engine as _, engine::general_purpose};
+    use image::{Rgba, RgbaImage};
+
+    fn capture(clock_shade: u8) -> String {
+        let mut img = RgbaImage::from_pixel(64, 48, Rgba([255, 255, 255, 255]));
+        // A "clock" in the top-right corner that changes on every capture
+        for x in 40..60 {
+            for y in 4..12 {
+                img.put_pixel(x, y, Rgba([clock_shade, 0, 0, 255]));
+            }
+        }
+        general_purpose::STANDARD.encode(img.into_raw())
+    }
+
+    #[test]
+    fn test_learns_clock_region() {
+        let request = LearnMaskRequest {
+            page_id: "home".to_string(),
+            captures_base64: vec![capture(0), capture(120), capture(240)],
+            image_encoding: ImageEncoding::RawRgba { width: 64, height: 48 },
+            cell_size: 8,
+            pixel_tolerance: default_pixel_tolerance(),
+            min_change_ratio: default_min_change_ratio(),
+        };
+
+        let mask = DynamicRegionLearner::learn(&request).unwrap();
+        assert_eq!(mask.regions.len(), 1);
+        assert!(mask.contains(45, 8));
+        assert!(!mask.contains(5, 40));
+        assert_eq!(mask.regions[0].confidence, 1.0);
+    }
+
+    #[test]
+    fn test_requires_two_captures() {
+        let request = LearnMaskRequest {
+            page_id: "home".to_string(),
+            captures_base64: vec![capture(0)],
+            image_encoding: ImageEncoding::RawRgba { width: 64, height: 48 },
+            cell_size: 8,
+            pixel_tolerance: default_pixel_tolerance(),
+            min_change_ratio: default_min_change_ratio(),
+        };
+        assert!(DynamicRegionLearner::learn(&request).is_err());
+    }
+}
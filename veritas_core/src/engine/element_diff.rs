@@ -0,0 +1,300 @@
+//! Element-scoped visual regression.
+//!
+//! The element is located in the baseline with the `NeuralLocator`, both captures are
+//! cropped to its box plus padding, and only that region is compared. The current crop
+//! is searched within `max_shift` pixels, so an element that merely moved is not
+//! reported as changed.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::sync::Arc;
+use image::RgbaImage;
+use crate::engine::baseline::BaselineStore;
+use crate::engine::dynamic_mask::PageMask;
+use crate::engine::imaging::{decode_image, ImageEncoding, ViewportContext, VisionError};
+use crate::engine::neural_locator::{BoundingBox, NeuralLocator};
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ElementCompareRequest {
+    pub intent: String, // e.g. "Checkout button"
+    /// Name used in the summary; defaults to the intent.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub current_base64: String,
+    /// Reference capture. When omitted, the saved baseline for `page_id` is used.
+    #[serde(default)]
+    pub baseline_base64: Option<String>,
+    /// Also applies the page's learned dynamic-region mask.
+    #[serde(default)]
+    pub page_id: Option<String>,
+    #[serde(default)]
+    pub image_encoding: ImageEncoding,
+    #[serde(default)]
+    pub viewport: ViewportContext,
+    #[serde(default = "default_padding")]
+    pub padding: u32, // Device pixels added around the located box
+    #[serde(default = "default_max_shift")]
+    pub max_shift: u32, // Largest move, in device pixels, that is not a change
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+}
+
+pub(crate) fn default_padding() -> u32 { 8 }
+pub(crate) fn default_max_shift() -> u32 { 8 }
+fn default_threshold() -> f32 { 0.98 }
+
+/// Upper bounds for the client-supplied `padding` and `max_shift`.
+pub const MAX_PADDING: u32 = 256;
+pub const MAX_SHIFT: u32 = 64;
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ElementCompareResult {
+    pub element: String,
+    pub changed: bool,
+    pub similarity_score: f32,
+    pub threshold: f32,
+    pub baseline_region: BoundingBox, // Padded crop in the baseline (device pixels)
+    pub current_region: BoundingBox, // Best-matching crop in the current capture
+    pub shift_x: i32,
+    pub shift_y: i32,
+    pub masked_pixels: u64,
+    pub summary: String, // e.g. "'Checkout button' changed: 91.20% similar (threshold 98.00%)"
+}
+
+/// Best alignment of a baseline crop inside the current capture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegionMatch {
+    pub shift_x: i32,
+    pub shift_y: i32,
+    pub similarity: f32,
+    pub masked_pixels: u64,
+}
+
+pub struct ElementComparer {
+    locator: Arc<NeuralLocator>,
+    baselines: Arc<BaselineStore>,
+}
+
+impl ElementComparer {
+    pub fn new(locator: Arc<NeuralLocator>, baselines: Arc<BaselineStore>) -> Self {
+        ElementComparer { locator, baselines }
+    }
+
+    pub fn compare(&self, request: &ElementCompareRequest) -> Result<ElementCompareResult, VisionError> {
+        request.viewport.validate()?;
+        if request.padding > MAX_PADDING || request.max_shift > MAX_SHIFT {
+            return Err(VisionError::InvalidRequest(format!(
+                "padding must be at most {} and max_shift at most {}",
+                MAX_PADDING, MAX_SHIFT
+            )));
+        }
+        let current = decode_image(&request.current_base64, &request.image_encoding)?;
+        let baseline = match (&request.baseline_base64, &request.page_id) {
+            (Some(image), _) => decode_image(image, &request.image_encoding)?,
+            (None, Some(page_id)) => self
+                .baselines
+                .load_baseline(page_id)?
+                .ok_or_else(|| VisionError::InvalidRequest(format!("no baseline saved for page '{}'", page_id)))?,
+            (None, None) => return Err(VisionError::InvalidRequest("either baseline_base64 or page_id is required".to_string())),
+        };
+        let mask = self.baselines.mask_for(request.page_id.as_deref())?;
+        let mask = mask.as_ref().filter(|m| m.fits(baseline.width(), baseline.height()));
+
+        let element = request.name.clone().unwrap_or_else(|| request.intent.clone());
+        let located = self.locator.analyze_image(&baseline, &request.intent, &request.viewport);
+        let location = located
+            .location
+            .ok_or_else(|| VisionError::InvalidRequest(format!("'{}' not found in the baseline", element)))?;
+        let region = padded_region(&location, request.padding, baseline.width(), baseline.height())
+            .ok_or_else(|| VisionError::InvalidRequest(format!("'{}' lies outside the baseline", element)))?;
+
+        let best = best_match(&baseline.to_rgba8(), &current.to_rgba8(), &region, request.max_shift, mask)
+            .ok_or_else(|| VisionError::InvalidRequest(format!("'{}' lies outside the current capture", element)))?;
+
+        let changed = best.similarity < request.threshold;
+        let summary = if changed {
+            format!(
+                "'{}' changed: {:.2}% similar (threshold {:.2}%)",
+                element, best.similarity * 100.0, request.threshold * 100.0
+            )
+        } else if (best.shift_x, best.shift_y) != (0, 0) {
+            format!("'{}' unchanged (moved {}px, {}px)", element, best.shift_x, best.shift_y)
+        } else {
+            format!("'{}' unchanged", element)
+        };
+
+        let current_region = BoundingBox { x: region.x + best.shift_x, y: region.y + best.shift_y, ..region.clone() };
+        Ok(ElementCompareResult {
+            element,
+            changed,
+            similarity_score: best.similarity,
+            threshold: request.threshold,
+            baseline_region: region,
+            current_region,
+            shift_x: best.shift_x,
+            shift_y: best.shift_y,
+            masked_pixels: best.masked_pixels,
+            summary,
+        })
+    }
+}
+
+/// `location` grown by `padding` on every side and clipped to the image.
+pub fn padded_region(location: &BoundingBox, padding: u32, width: u32, height: u32) -> Option<BoundingBox> {
+    let padding = padding as i32;
+    let x0 = (location.x - padding).max(0);
+    let y0 = (location.y - padding).max(0);
+    let x1 = (location.x + location.width + padding).min(width as i32);
+    let y1 = (location.y + location.height + padding).min(height as i32);
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some(BoundingBox { x: x0, y: y0, width: x1 - x0, height: y1 - y0, ..location.clone() })
+}
+
+/// Slides `region` of `baseline` over `current` by up to `max_shift` pixels each way and
+/// keeps the most similar offset (nearest one on ties). Masked baseline pixels are skipped.
+pub fn best_match(
+    baseline: &RgbaImage,
+    current: &RgbaImage,
+    region: &BoundingBox,
+    max_shift: u32,
+    mask: Option<&PageMask>,
+) -> Option<RegionMatch> {
+    let max_shift = max_shift as i32;
+    let mut offsets: Vec<(i32, i32)> = (-max_shift..=max_shift)
+        .flat_map(|dy| (-max_shift..=max_shift).map(move |dx| (dx, dy)))
+        .collect();
+    offsets.sort_by_key(|(dx, dy)| dx.abs() + dy.abs());
+
+    let mut best: Option<RegionMatch> = None;
+    for (dx, dy) in offsets {
+        let (x, y) = (region.x + dx, region.y + dy);
+        if x < 0 || y < 0 || x + region.width > current.width() as i32 || y + region.height > current.height() as i32 {
+            continue;
+        }
+        let (similarity, masked_pixels) = region_similarity(baseline, current, region, dx, dy, mask);
+        if best.is_none_or(|b| similarity > b.similarity) {
+            best = Some(RegionMatch { shift_x: dx, shift_y: dy, similarity, masked_pixels });
+            if similarity >= 1.0 {
+                break;
+            }
+        }
+    }
+    best
+}
+
+/// Same metric as `NeuralLocator::compare_images`, restricted to one region.
+fn region_similarity(
+    baseline: &RgbaImage,
+    current: &RgbaImage,
+    region: &BoundingBox,
+    dx: i32,
+    dy: i32,
+    mask: Option<&PageMask>,
+) -> (f32, u64) {
+    let mut diff_sum: u64 = 0;
+    let mut pixel_count: u64 = 0;
+    let mut masked_pixels: u64 = 0;
+    for y in region.y..region.y + region.height {
+        for x in region.x..region.x + region.width {
+            if mask.is_some_and(|m| m.contains(x as u32, y as u32)) {
+                masked_pixels += 1;
+                continue;
+            }
+            let a = baseline.get_pixel(x as u32, y as u32);
+            let b = current.get_pixel((x + dx) as u32, (y + dy) as u32);
+            diff_sum += (0..3).map(|c| (a[c] as i32 - b[c] as i32).unsigned_abs() as u64).sum::<u64>();
+            pixel_count += 1;
+        }
+    }
+    if pixel_count == 0 {
+        return (1.0, masked_pixels);
+    }
+    (1.0 - (diff_sum as f64 / (pixel_count as f64 * 765.0)) as f32, masked_pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{Engine as _, engine::general_purpose};
+    use image::Rgba;
+
+    // The locator places "checkout" 200px from the right and 100px from the bottom, 150x50
+    fn page(button: Rgba<u8>, shift_x: u32, banner: Rgba<u8>) -> String {
+        let mut img = RgbaImage::from_pixel(400, 300, Rgba([255, 255, 255, 255]));
+        for y in 200..250 {
+            for x in 200 + shift_x..350 + shift_x {
+                img.put_pixel(x, y, button);
+            }
+        }
+        for y in 0..40 {
+            for x in 0..400 {
+                img.put_pixel(x, y, banner);
+            }
+        }
+        general_purpose::STANDARD.encode(img.into_raw())
+    }
+
+    fn request(baseline: String, current: String) -> ElementCompareRequest {
+        ElementCompareRequest {
+            intent: "Checkout button".to_string(),
+            name: None,
+            current_base64: current,
+            baseline_base64: Some(baseline),
+            page_id: None,
+            image_encoding: ImageEncoding::RawRgba { width: 400, height: 300 },
+            viewport: ViewportContext::default(),
+            padding: default_padding(),
+            max_shift: default_max_shift(),
+            threshold: default_threshold(),
+        }
+    }
+
+    fn comparer() -> ElementComparer {
+        let dir = std::env::temp_dir().join(format!("veritas_element_{}", std::process::id()));
+        ElementComparer::new(Arc::new(NeuralLocator::new()), Arc::new(BaselineStore::at(dir)))
+    }
+
+    #[test]
+    fn test_moved_element_with_changed_page_is_unchanged() {
+        let green = Rgba([0, 160, 0, 255]);
+        let baseline = page(green, 0, Rgba([0, 0, 0, 255]));
+        let current = page(green, 4, Rgba([200, 0, 0, 255]));
+
+        let result = comparer().compare(&request(baseline, current)).unwrap();
+        assert!(!result.changed, "{}", result.summary);
+        assert_eq!((result.shift_x, result.shift_y), (4, 0));
+        assert_eq!(result.summary, "'Checkout button' unchanged (moved 4px, 0px)");
+    }
+
+    #[test]
+    fn test_restyled_element_is_reported_by_name() {
+        let baseline = page(Rgba([0, 160, 0, 255]), 0, Rgba([0, 0, 0, 255]));
+        let current = page(Rgba([160, 160, 0, 255]), 0, Rgba([0, 0, 0, 255]));
+
+        let result = comparer().compare(&request(baseline, current)).unwrap();
+        assert!(result.changed);
+        assert!(result.summary.starts_with("'Checkout button' changed"));
+    }
+
+    #[test]
+    fn test_padded_region_is_clipped() {
+        let location = BoundingBox { x: 2, y: 90, width: 20, height: 20, label: None, confidence: 1.0 };
+        let region = padded_region(&location, 8, 100, 100).unwrap();
+        assert_eq!((region.x, region.y, region.width, region.height), (0, 82, 30, 18));
+        assert!(padded_region(&BoundingBox { x: 200, ..location }, 8, 100, 100).is_none());
+    }
+
+    #[test]
+    fn test_oversized_shift_is_rejected() {
+        let green = Rgba([0, 160, 0, 255]);
+        let image = page(green, 0, Rgba([0, 0, 0, 255]));
+        let mut huge = request(image.clone(), image);
+        huge.max_shift = u32::MAX;
+        assert!(matches!(comparer().compare(&huge), Err(VisionError::InvalidRequest(_))));
+        huge.max_shift = default_max_shift();
+        huge.padding = MAX_PADDING + 1;
+        assert!(matches!(comparer().compare(&huge), Err(VisionError::InvalidRequest(_))));
+    }
+}
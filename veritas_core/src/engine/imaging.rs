@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::fmt;
+use base64::{Engine as _, engine::general_purpose};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use crate::engine::neural_locator::BoundingBox;
+
+/// How the bytes behind an `*_base64` field are encoded.
+/// `Auto` sniffs the container header and accepts PNG, JPEG and WebP.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageEncoding {
+    #[default]
+    Auto,
+    Png,
+    Jpeg,
+    Webp,
+    /// Unframed 8-bit RGBA pixels, row-major, no padding (e.g. a canvas `ImageData` buffer).
+    RawRgba { width: u32, height: u32 },
+}
+
+/// Browser geometry at capture time. Screenshots are taken in device pixels,
+/// while the page (and anything that clicks on it) speaks CSS pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct ViewportContext {
+    #[serde(default = "default_device_pixel_ratio")]
+    pub device_pixel_ratio: f32,
+    /// Viewport size in CSS pixels. When present it wins over `device_pixel_ratio`
+    /// for the scale, which also covers browser zoom and OS-level scaling.
+    #[serde(default)]
+    pub viewport_width: Option<u32>,
+    #[serde(default)]
+    pub viewport_height: Option<u32>,
+    /// Document scroll offsets in CSS pixels (`window.scrollX` / `window.scrollY`).
+    #[serde(default)]
+    pub scroll_x: f32,
+    #[serde(default)]
+    pub scroll_y: f32,
+}
+
+fn default_device_pixel_ratio() -> f32 {
+    1.0
+}
+
+impl Default for ViewportContext {
+    fn default() -> Self {
+        ViewportContext {
+            device_pixel_ratio: default_device_pixel_ratio(),
+            viewport_width: None,
+            viewport_height: None,
+            scroll_x: 0.0,
+            scroll_y: 0.0,
+        }
+    }
+}
+
+/// The coordinate frame a result was computed in, echoed back so callers can
+/// convert further boxes themselves.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct FrameGeometry {
+    pub image_width: u32,
+    pub image_height: u32,
+    /// Device pixels per CSS pixel along each axis.
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub scroll_x: f32,
+    pub scroll_y: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VisionError {
+    InvalidBase64(String),
+    UnsupportedFormat(String),
+    Decode { format: String, reason: String },
+    RawBufferSize { width: u32, height: u32, expected: usize, actual: usize },
+    InvalidViewport(String),
+    InvalidRequest(String),
+    Storage(String),
+}
+
+impl fmt::Display for VisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VisionError::InvalidBase64(reason) => write!(f, "Failed to decode Base64 image data: {}", reason),
+            VisionError::UnsupportedFormat(format) => write!(f, "Unsupported image format: {} (expected PNG, JPEG, WebP or raw RGBA)", format),
+            VisionError::Decode { format, reason } => write!(f, "Failed to decode {} image: {}", format, reason),
+            VisionError::RawBufferSize { width, height, expected, actual } => write!(
+                f,
+                "Raw RGBA buffer for {}x{} must be {} bytes, got {}",
+                width, height, expected, actual
+            ),
+            VisionError::InvalidViewport(reason) => write!(f, "Invalid viewport: {}", reason),
+            VisionError::InvalidRequest(reason) => write!(f, "Invalid request: {}", reason),
+            VisionError::Storage(reason) => write!(f, "Baseline storage error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for VisionError {}
+
+/// Decodes a Base64 payload into an image. Never substitutes a placeholder:
+/// anything that cannot be decoded is reported as a `VisionError`.
+pub fn decode_image(image_base64: &str, encoding: &ImageEncoding) -> Result<DynamicImage, VisionError> {
+    let bytes = general_purpose::STANDARD
+        .decode(image_base64.trim())
+        .map_err(|e| VisionError::InvalidBase64(e.to_string()))?;
+
+    let format = match encoding {
+        ImageEncoding::RawRgba { width, height } => return decode_raw_rgba(bytes, *width, *height),
+        ImageEncoding::Png => ImageFormat::Png,
+        ImageEncoding::Jpeg => ImageFormat::Jpeg,
+        ImageEncoding::Webp => ImageFormat::WebP,
+        ImageEncoding::Auto => match image::guess_format(&bytes) {
+            Ok(f @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP)) => f,
+            Ok(other) => return Err(VisionError::UnsupportedFormat(format!("{:?}", other))),
+            Err(_) => return Err(VisionError::UnsupportedFormat("unrecognised header".to_string())),
+        },
+    };
+
+    image::load_from_memory_with_format(&bytes, format).map_err(|e| VisionError::Decode {
+        format: format!("{:?}", format),
+        reason: e.to_string(),
+    })
+}
+
+fn decode_raw_rgba(bytes: Vec<u8>, width: u32, height: u32) -> Result<DynamicImage, VisionError> {
+    let expected = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|px| px.checked_mul(4))
+        .unwrap_or(usize::MAX);
+    if width == 0 || height == 0 || bytes.len() != expected {
+        return Err(VisionError::RawBufferSize { width, height, expected, actual: bytes.len() });
+    }
+    // Length was checked above, so construction cannot fail.
+    let buffer = RgbaImage::from_raw(width, height, bytes).expect("raw RGBA length verified");
+    Ok(DynamicImage::ImageRgba8(buffer))
+}
+
+impl ViewportContext {
+    pub fn validate(&self) -> Result<(), VisionError> {
+        if !self.device_pixel_ratio.is_finite() || self.device_pixel_ratio <= 0.0 {
+            return Err(VisionError::InvalidViewport(format!(
+                "device_pixel_ratio must be a positive number, got {}",
+                self.device_pixel_ratio
+            )));
+        }
+        if self.viewport_width == Some(0) || self.viewport_height == Some(0) {
+            return Err(VisionError::InvalidViewport("viewport dimensions must be non-zero".to_string()));
+        }
+        if !self.scroll_x.is_finite() || !self.scroll_y.is_finite() {
+            return Err(VisionError::InvalidViewport("scroll offsets must be finite".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Resolves the device-to-CSS scale for an image of the given size.
+    pub fn frame_for(&self, image_width: u32, image_height: u32) -> FrameGeometry {
+        let scale_x = self
+            .viewport_width
+            .map(|w| image_width as f32 / w as f32)
+            .unwrap_or(self.device_pixel_ratio);
+        let scale_y = self
+            .viewport_height
+            .map(|h| image_height as f32 / h as f32)
+            .unwrap_or(scale_x);
+
+        FrameGeometry {
+            image_width,
+            image_height,
+            scale_x,
+            scale_y,
+            scroll_x: self.scroll_x,
+            scroll_y: self.scroll_y,
+        }
+    }
+}
+
+impl FrameGeometry {
+    /// Maps a box in screenshot (device) pixels to document CSS pixels.
+    pub fn to_css(&self, device: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            x: (device.x as f32 / self.scale_x + self.scroll_x).round() as i32,
+            y: (device.y as f32 / self.scale_y + self.scroll_y).round() as i32,
+            width: (device.width as f32 / self.scale_x).round() as i32,
+            height: (device.height as f32 / self.scale_y).round() as i32,
+            label: device.label.clone(),
+            confidence: device.confidence,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn encode(img: &DynamicImage, format: ImageFormat) -> String {
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, format).unwrap();
+        general_purpose::STANDARD.encode(bytes.into_inner())
+    }
+
+    #[test]
+    fn test_decode_png_and_jpeg() {
+        let img = DynamicImage::new_rgb8(8, 4);
+        for format in [ImageFormat::Png, ImageFormat::Jpeg] {
+            let decoded = decode_image(&encode(&img, format), &ImageEncoding::Auto).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (8, 4));
+        }
+    }
+
+    #[test]
+    fn test_decode_raw_rgba_checks_length() {
+        let pixels = general_purpose::STANDARD.encode(vec![255u8; 2 * 3 * 4]);
+        let ok = decode_image(&pixels, &ImageEncoding::RawRgba { width: 2, height: 3 }).unwrap();
+        assert_eq!((ok.width(), ok.height()), (2, 3));
+
+        let err = decode_image(&pixels, &ImageEncoding::RawRgba { width: 3, height: 3 }).unwrap_err();
+        assert!(matches!(err, VisionError::RawBufferSize { expected: 36, actual: 24, .. }));
+    }
+
+    #[test]
+    fn test_decode_garbage_is_an_error() {
+        let garbage = general_purpose::STANDARD.encode(b"definitely not an image");
+        assert!(decode_image(&garbage, &ImageEncoding::Auto).is_err());
+        assert!(decode_image(&garbage, &ImageEncoding::Png).is_err());
+    }
+
+    #[test]
+    fn test_hidpi_box_maps_to_css() {
+        let viewport = ViewportContext { device_pixel_ratio: 2.0, scroll_y: 300.0, ..Default::default() };
+        let frame = viewport.frame_for(2560, 1600);
+        let device = BoundingBox { x: 200, y: 100, width: 300, height: 80, label: None, confidence: 0.9 };
+
+        let css = frame.to_css(&device);
+        assert_eq!((css.x, css.y, css.width, css.height), (100, 350, 150, 40));
+    }
+
+    #[test]
+    fn test_viewport_width_overrides_dpr() {
+        // 1.25x browser zoom on a DPR 2 display: the measured ratio is what matters.
+        let viewport = ViewportContext { device_pixel_ratio: 2.0, viewport_width: Some(1000), ..Default::default() };
+        let frame = viewport.frame_for(2500, 1500);
+        assert_eq!(frame.scale_x, 2.5);
+        assert_eq!(frame.scale_y, 2.5);
+    }
+}
@@ -1,5 +1,14 @@
+pub mod imaging;
+pub mod dynamic_mask;
+pub mod baseline;
+pub mod element_diff;
+pub mod progress;
+pub mod determinism;
 pub mod neural_locator;
 pub mod semantic_healer;
 pub mod agent;
 pub mod observer;
+pub mod network;
+pub mod perf_budget;
+pub mod test_data;
 pub mod swarm;
@@ -0,0 +1,389 @@
+//! HAR ingestion and network timeline analysis.
+//!
+//! A HAR capture is flattened into a timeline relative to the first request. Connections
+//! that are expected to stay open (websockets, event streams, long polls, analytics
+//! beacons) are classified as long-lived and excluded from idle detection, so a page
+//! that keeps a socket open can still be considered network-idle.
+
+use chrono::DateTime;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// URL fragments treated as fire-and-forget telemetry unless overridden.
+const DEFAULT_IGNORED_URL_PATTERNS: &[&str] = &["google-analytics.com", "/collect", "/beacon", "segment.io", "hotjar"];
+
+#[derive(Deserialize, Debug)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize, Debug)]
+struct HarLog {
+    #[serde(default)]
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: String,
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+    #[serde(default, rename = "_resourceType")]
+    resource_type: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HarResponse {
+    status: i64,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+fn header<'a>(headers: &'a [HarHeader], name: &str) -> Option<&'a str> {
+    headers.iter().find(|h| h.name.eq_ignore_ascii_case(name)).map(|h| h.value.as_str())
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct NetworkObserveRequest {
+    pub url: String,
+    /// The HAR document (`{"log": {"entries": [...]}}`) as exported by the browser.
+    pub har: Value,
+    #[serde(default)]
+    pub dom_mutation_rate: u32,
+    #[serde(default)]
+    pub layout_shifts: f32,
+    /// Moment (ms after the first request) the page is judged at. Defaults to the end of the capture.
+    #[serde(default)]
+    pub observed_at_ms: Option<f64>,
+    /// Quiet period without tracked requests that counts as network-idle.
+    #[serde(default = "default_idle_window_ms")]
+    pub idle_window_ms: f64,
+    #[serde(default = "default_slow_threshold_ms")]
+    pub slow_threshold_ms: f64,
+    /// Requests open at least this long are treated as long polls.
+    #[serde(default = "default_long_poll_threshold_ms")]
+    pub long_poll_threshold_ms: f64,
+    /// URL substrings excluded from idle detection. Defaults to common analytics endpoints.
+    #[serde(default)]
+    pub ignore_url_patterns: Option<Vec<String>>,
+}
+
+fn default_idle_window_ms() -> f64 { 500.0 }
+fn default_slow_threshold_ms() -> f64 { 1000.0 }
+fn default_long_poll_threshold_ms() -> f64 { 15_000.0 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestKind {
+    /// Counted towards pending requests and idle detection.
+    Tracked,
+    /// Websocket, event stream or long poll.
+    LongLived,
+    /// Matched an ignored URL pattern (analytics, beacons).
+    Ignored,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct TimelineEntry {
+    pub method: String,
+    pub url: String,
+    /// HTTP status; 0 when the request was aborted or never answered.
+    pub status: i64,
+    pub start_ms: f64,
+    pub end_ms: f64,
+    pub resource_type: Option<String>,
+    pub kind: RequestKind,
+    pub failed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct IdleWindow {
+    pub start_ms: f64,
+    pub end_ms: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct EndpointStats {
+    /// Method plus URL without query string, e.g. `GET https://shop.test/api/cart`.
+    pub endpoint: String,
+    pub requests: u32,
+    pub failures: u32,
+    pub statuses: Vec<i64>,
+    pub mean_duration_ms: f64,
+    pub max_duration_ms: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct NetworkAnalysis {
+    pub observed_at_ms: f64,
+    pub timeline: Vec<TimelineEntry>,
+    pub idle_windows: Vec<IdleWindow>,
+    pub slow_endpoints: Vec<EndpointStats>,
+    pub failing_endpoints: Vec<EndpointStats>,
+    pub long_lived_urls: Vec<String>,
+    /// Tracked requests still in flight at `observed_at_ms`.
+    pub pending_tracked: u32,
+    /// Long-lived or ignored connections open at `observed_at_ms`; reported but not waited on.
+    pub pending_excluded: u32,
+    /// Start of the idle window covering `observed_at_ms`, if the page has settled.
+    pub idle_since_ms: Option<f64>,
+    pub network_idle: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HarError {
+    Malformed(String),
+    InvalidTimestamp { index: usize, value: String },
+    InvalidOptions(String),
+}
+
+impl fmt::Display for HarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HarError::Malformed(reason) => write!(f, "Malformed HAR: {}", reason),
+            HarError::InvalidTimestamp { index, value } => write!(f, "HAR entry {} has invalid startedDateTime '{}'", index, value),
+            HarError::InvalidOptions(reason) => write!(f, "Invalid network analysis options: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for HarError {}
+
+pub struct NetworkAnalyzer;
+
+impl NetworkAnalyzer {
+    pub fn analyze(request: &NetworkObserveRequest) -> Result<NetworkAnalysis, HarError> {
+        if !(request.idle_window_ms >= 0.0 && request.slow_threshold_ms >= 0.0 && request.long_poll_threshold_ms > 0.0) {
+            return Err(HarError::InvalidOptions("thresholds must be non-negative".to_string()));
+        }
+        let har: Har = serde_json::from_value(request.har.clone()).map_err(|e| HarError::Malformed(e.to_string()))?;
+        let ignored: Vec<String> = match &request.ignore_url_patterns {
+            Some(patterns) => patterns.clone(),
+            None => DEFAULT_IGNORED_URL_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        };
+
+        let timeline = Self::timeline(&har.log.entries, request.long_poll_threshold_ms, &ignored)?;
+        let capture_end = timeline.iter().map(|e| e.end_ms).fold(0.0, f64::max);
+        let observed_at = request.observed_at_ms.unwrap_or(capture_end);
+
+        // Inclusive end: sockets still open when the HAR was exported end exactly at the capture end
+        let in_flight = |e: &&TimelineEntry| e.start_ms <= observed_at && observed_at <= e.end_ms;
+        let pending_tracked = timeline.iter().filter(in_flight).filter(|e| e.kind == RequestKind::Tracked).count() as u32;
+        let pending_excluded = timeline.iter().filter(in_flight).filter(|e| e.kind != RequestKind::Tracked).count() as u32;
+
+        let idle_windows = Self::idle_windows(&timeline, observed_at, request.idle_window_ms);
+        let idle_since_ms = idle_windows
+            .iter()
+            .find(|w| w.start_ms <= observed_at && observed_at <= w.end_ms)
+            .map(|w| w.start_ms);
+        let network_idle = pending_tracked == 0 && idle_since_ms.is_some_and(|since| observed_at - since >= request.idle_window_ms);
+
+        let endpoints = Self::endpoint_stats(&timeline);
+        let slow_endpoints = endpoints.iter().filter(|s| s.max_duration_ms >= request.slow_threshold_ms).cloned().collect();
+        let failing_endpoints = endpoints.into_iter().filter(|s| s.failures > 0).collect();
+        let mut long_lived_urls: Vec<String> = Vec::new();
+        for entry in timeline.iter().filter(|e| e.kind == RequestKind::LongLived) {
+            if !long_lived_urls.contains(&entry.url) {
+                long_lived_urls.push(entry.url.clone());
+            }
+        }
+
+        Ok(NetworkAnalysis {
+            observed_at_ms: observed_at,
+            timeline,
+            idle_windows,
+            slow_endpoints,
+            failing_endpoints,
+            long_lived_urls,
+            pending_tracked,
+            pending_excluded,
+            idle_since_ms,
+            network_idle,
+        })
+    }
+
+    fn timeline(entries: &[HarEntry], long_poll_ms: f64, ignored: &[String]) -> Result<Vec<TimelineEntry>, HarError> {
+        let mut starts = Vec::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            let started = DateTime::parse_from_rfc3339(&entry.started_date_time)
+                .map_err(|_| HarError::InvalidTimestamp { index, value: entry.started_date_time.clone() })?;
+            starts.push(started.timestamp_micros() as f64 / 1000.0);
+        }
+        let origin = starts.iter().copied().fold(f64::INFINITY, f64::min);
+
+        let mut timeline: Vec<TimelineEntry> = entries
+            .iter()
+            .zip(starts)
+            .map(|(entry, start)| {
+                let start_ms = start - origin;
+                // HAR uses -1 for unknown durations
+                let duration = entry.time.max(0.0);
+                TimelineEntry {
+                    method: entry.request.method.clone(),
+                    url: entry.request.url.clone(),
+                    status: entry.response.status,
+                    start_ms,
+                    end_ms: start_ms + duration,
+                    resource_type: entry.resource_type.clone(),
+                    kind: Self::classify(entry, duration, long_poll_ms, ignored),
+                    failed: entry.response.status <= 0 || entry.response.status >= 400,
+                }
+            })
+            .collect();
+        timeline.sort_by(|a, b| a.start_ms.total_cmp(&b.start_ms));
+        Ok(timeline)
+    }
+
+    fn classify(entry: &HarEntry, duration: f64, long_poll_ms: f64, ignored: &[String]) -> RequestKind {
+        let resource_type = entry.resource_type.as_deref().unwrap_or_default().to_ascii_lowercase();
+        let content_type = header(&entry.response.headers, "content-type").unwrap_or_default();
+        let accept = header(&entry.request.headers, "accept").unwrap_or_default();
+        let streaming = resource_type == "websocket"
+            || resource_type == "eventsource"
+            || entry.response.status == 101
+            || entry.request.url.starts_with("ws")
+            || content_type.contains("text/event-stream")
+            || accept.contains("text/event-stream");
+
+        if streaming || duration >= long_poll_ms {
+            RequestKind::LongLived
+        } else if resource_type == "ping" || ignored.iter().any(|p| entry.request.url.contains(p.as_str())) {
+            RequestKind::Ignored
+        } else {
+            RequestKind::Tracked
+        }
+    }
+
+    /// Gaps of at least `min_ms` in [0, until] during which no tracked request was in flight.
+    fn idle_windows(timeline: &[TimelineEntry], until: f64, min_ms: f64) -> Vec<IdleWindow> {
+        let mut windows = Vec::new();
+        let mut cursor = 0.0_f64;
+        // Tracked entries are already sorted by start, so a running max of end times is enough
+        for entry in timeline.iter().filter(|e| e.kind == RequestKind::Tracked) {
+            if entry.start_ms > until {
+                break;
+            }
+            if entry.start_ms - cursor >= min_ms && entry.start_ms > cursor {
+                windows.push(IdleWindow { start_ms: cursor, end_ms: entry.start_ms });
+            }
+            cursor = cursor.max(entry.end_ms);
+        }
+        // The trailing window is open-ended, so it is reported even if still shorter than `min_ms`
+        if cursor <= until {
+            windows.push(IdleWindow { start_ms: cursor, end_ms: until });
+        }
+        windows
+    }
+
+    fn endpoint_stats(timeline: &[TimelineEntry]) -> Vec<EndpointStats> {
+        let mut grouped: BTreeMap<String, Vec<&TimelineEntry>> = BTreeMap::new();
+        for entry in timeline.iter().filter(|e| e.kind != RequestKind::LongLived) {
+            let path = entry.url.split(['?', '#']).next().unwrap_or_default();
+            grouped.entry(format!("{} {}", entry.method, path)).or_default().push(entry);
+        }
+
+        grouped
+            .into_iter()
+            .map(|(endpoint, entries)| {
+                let durations: Vec<f64> = entries.iter().map(|e| e.end_ms - e.start_ms).collect();
+                let mut statuses: Vec<i64> = entries.iter().map(|e| e.status).collect();
+                statuses.sort_unstable();
+                statuses.dedup();
+                EndpointStats {
+                    endpoint,
+                    requests: entries.len() as u32,
+                    failures: entries.iter().filter(|e| e.failed).count() as u32,
+                    statuses,
+                    mean_duration_ms: durations.iter().sum::<f64>() / durations.len() as f64,
+                    max_duration_ms: durations.iter().copied().fold(0.0, f64::max),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(offset_ms: u32, time: f64, method: &str, url: &str, status: i64, resource_type: &str) -> Value {
+        json!({
+            "startedDateTime": format!("2024-05-01T10:00:{:02}.{:03}Z", offset_ms / 1000, offset_ms % 1000),
+            "time": time,
+            "request": { "method": method, "url": url, "headers": [] },
+            "response": { "status": status, "headers": [] },
+            "_resourceType": resource_type,
+        })
+    }
+
+    fn request(entries: Vec<Value>) -> NetworkObserveRequest {
+        serde_json::from_value(json!({ "url": "https://shop.test", "har": { "log": { "entries": entries } } })).unwrap()
+    }
+
+    #[test]
+    fn test_open_websocket_does_not_block_idle() {
+        let req = request(vec![
+            entry(0, 120.0, "GET", "https://shop.test/", 200, "document"),
+            entry(100, 30_000.0, "GET", "wss://shop.test/live", 101, "websocket"),
+            entry(200, 2_500.0, "GET", "https://shop.test/api/cart?id=1", 200, "xhr"),
+            entry(300, 80.0, "POST", "https://shop.test/api/checkout", 500, "xhr"),
+            entry(2_900, 27_200.0, "POST", "https://www.google-analytics.com/g/collect", 204, "ping"),
+        ]);
+        let analysis = NetworkAnalyzer::analyze(&req).unwrap();
+
+        assert_eq!(analysis.pending_tracked, 0);
+        assert_eq!(analysis.pending_excluded, 2);
+        assert_eq!(analysis.idle_since_ms, Some(2_700.0));
+        assert!(analysis.network_idle);
+        assert_eq!(analysis.long_lived_urls, vec!["wss://shop.test/live", "https://www.google-analytics.com/g/collect"]);
+        assert_eq!(analysis.slow_endpoints[0].endpoint, "GET https://shop.test/api/cart");
+        assert_eq!(analysis.failing_endpoints[0].statuses, vec![500]);
+    }
+
+    #[test]
+    fn test_request_in_flight_is_pending() {
+        let mut req = request(vec![
+            entry(0, 100.0, "GET", "https://shop.test/", 200, "document"),
+            entry(50, 1_000.0, "GET", "https://shop.test/api/items", 200, "fetch"),
+        ]);
+        req.observed_at_ms = Some(600.0);
+        let analysis = NetworkAnalyzer::analyze(&req).unwrap();
+        assert_eq!(analysis.pending_tracked, 1);
+        assert!(!analysis.network_idle);
+        assert!(analysis.idle_windows.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_har_is_rejected() {
+        let mut req = request(vec![]);
+        req.har = json!({ "entries": [] });
+        assert!(matches!(NetworkAnalyzer::analyze(&req), Err(HarError::Malformed(_))));
+
+        let mut bad = entry(0, 10.0, "GET", "https://shop.test/", 200, "document");
+        bad["startedDateTime"] = json!("yesterday");
+        assert_eq!(
+            NetworkAnalyzer::analyze(&request(vec![bad])).unwrap_err(),
+            HarError::InvalidTimestamp { index: 0, value: "yesterday".to_string() }
+        );
+    }
+}
@@ -1,14 +1,16 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use base64::{Engine as _, engine::general_purpose};
 use image::{DynamicImage, GenericImageView};
 use ndarray::Array1;
 use rand::Rng;
-use image::{ImageFormat};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::Instant;
+use crate::engine::determinism;
+use crate::engine::imaging::{decode_image, FrameGeometry, ImageEncoding, ViewportContext, VisionError};
+use crate::engine::dynamic_mask::PageMask;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct BoundingBox {
     pub x: i32,
     pub y: i32,
@@ -18,17 +20,24 @@ pub struct BoundingBox {
     pub confidence: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct VisionRequest {
     pub image_base64: String,
     pub intent: String, // e.g., "Find the Checkout button"
+    #[serde(default)]
+    pub image_encoding: ImageEncoding,
+    #[serde(default)]
+    pub viewport: ViewportContext,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct VisionResult {
     pub found: bool,
-    pub location: Option<BoundingBox>,
+    pub location: Option<BoundingBox>, // Device (screenshot) pixels
     pub candidates: Vec<BoundingBox>,
+    pub css_location: Option<BoundingBox>, // Document CSS pixels (scroll applied)
+    pub css_candidates: Vec<BoundingBox>,
+    pub frame: FrameGeometry,
     pub confidence: f32,
     pub semantic_embedding: Vec<f32>,
     pub heatmap_data: Vec<f32>,
@@ -36,16 +45,21 @@ pub struct VisionResult {
     pub processing_time_ms: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct VisionCompareRequest {
     pub image_a_base64: String,
     pub image_b_base64: String,
+    #[serde(default)]
+    pub image_encoding: ImageEncoding,
+    #[serde(default)]
+    pub page_id: Option<String>, // Applies the learned dynamic-region mask for this page
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct VisionCompareResult {
     pub similarity_score: f32,
     pub diff_reason: Option<String>,
+    pub masked_pixels: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -74,7 +88,7 @@ impl NeuralMap {
         self.memory.insert(intent.to_string(), NeuralMapEntry {
             location,
             embedding,
-            last_seen: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            last_seen: determinism::now_secs(),
         });
     }
 
@@ -95,7 +109,6 @@ impl VisionTransformer {
 
     /// Simulates encoding text intent into a vector space (e.g. CLIP Text Encoder)
     pub fn encode_text(&self, text: &str) -> Array1<f32> {
-        let _rng = rand::thread_rng();
         // Deterministic-ish simulation based on string length to simulate "semantic" difference
         let seed = text.len() as u64;
         // In reality, we'd run the text through a Transformer
@@ -111,7 +124,7 @@ impl VisionTransformer {
         // We will generate a few "detected" elements.
 
         let mut objects = Vec::new();
-        // let mut rng = rand::thread_rng(); // Unused
+        let mut rng = determinism::rng();
 
         // 1. A "Buy/Checkout" looking button
         objects.push((
@@ -137,7 +150,7 @@ impl VisionTransformer {
         // 4. Random noise element
         objects.push((
             BoundingBox { x: 10, y: 10, width: 50, height: 50, label: Some("Generic Element".to_string()), confidence: 0.5 },
-            Array1::from_iter((0..768).map(|_| rand::thread_rng().gen::<f32>())),
+            Array1::from_iter((0..768).map(|_| rng.gen::<f32>())),
             "logo".to_string()
         ));
 
@@ -170,50 +183,29 @@ impl NeuralLocator {
     /// Simulates the full Vision Transformer pipeline:
     /// 1. Preprocessing (Base64 -> Image -> Tensor)
     /// 2. Inference (Tensor -> Embeddings + BBox)
-    /// 3. Post-processing (Formatting)
-    pub fn analyze(&self, request: &VisionRequest) -> VisionResult {
+    /// 3. Post-processing (Device pixels -> CSS pixels, Formatting)
+    pub fn analyze(&self, request: &VisionRequest) -> Result<VisionResult, VisionError> {
+        // 1 + 2. Decode Base64 into a DynamicImage (PNG / JPEG / WebP / raw RGBA)
+        request.viewport.validate()?;
+        let img = decode_image(&request.image_base64, &request.image_encoding)?;
+        Ok(self.analyze_image(&img, &request.intent, &request.viewport))
+    }
+
+    /// The inference half of `analyze`, for callers that already hold a decoded image.
+    pub fn analyze_image(&self, img: &DynamicImage, intent: &str, viewport: &ViewportContext) -> VisionResult {
         let start_time = Instant::now();
-        eprintln!("[NeuralLocator] Analyzing image for intent: '{}'", request.intent);
+        eprintln!("[NeuralLocator] Analyzing image for intent: '{}'", intent);
         let mut audit_trail: Vec<String> = Vec::new();
-
-        // 1. Decode Image from Base64
-        let image_data = match general_purpose::STANDARD.decode(&request.image_base64) {
-            Ok(data) => data,
-            Err(_) => {
-                return VisionResult {
-                    found: false,
-                    location: None,
-                    candidates: vec![],
-                    confidence: 0.0,
-                    semantic_embedding: vec![],
-                    heatmap_data: vec![],
-                    reasoning: "Failed to decode Base64 image data.".to_string(),
-                    processing_time_ms: 0,
-                };
-            }
-        };
-
-        // 2. Load into DynamicImage (Real Vision Preprocessing)
-        let img = match image::load_from_memory(&image_data) {
-             Ok(img) => img,
-             Err(_) => {
-                 match image::load_from_memory_with_format(&image_data, ImageFormat::Png) {
-                     Ok(img) => {
-                         audit_trail.push(format!("Image loaded (PNG fallback): {}x{}", img.width(), img.height()));
-                         img
-                     },
-                     Err(_) => {
-                         // Fallback for tests/simulation
-                         DynamicImage::new_rgb8(1024, 768)
-                     }
-                 }
-             }
-        };
+        let frame = viewport.frame_for(img.width(), img.height());
+        audit_trail.push(format!(
+            "Image loaded: {}x{} device px, scale {:.2}x{:.2}",
+            img.width(), img.height(), frame.scale_x, frame.scale_y
+        ));
 
         // 3. Vision-Transformer (ViT) Logic Simulation
         // We simulate attention mechanism by "focusing" on regions.
 
-        let mut rng = rand::thread_rng();
+        let mut rng = determinism::rng();
 
         // HEURISTIC: Analyze center pixel to determine "theme" (simulated)
         let (center_x, center_y) = (img.width() / 2, img.height() / 2);
@@ -222,7 +214,7 @@ impl NeuralLocator {
         audit_trail.push(format!("ViT Attention Head #1 focused on center ({}, {})", center_x, center_y));
 
         let confidence: f32 = rng.gen_range(0.85..0.99);
-        let intent_lower = request.intent.to_lowercase();
+        let intent_lower = intent.to_lowercase();
 
         let primary_box = if intent_lower.contains("buy") || intent_lower.contains("checkout") {
             Some(BoundingBox {
@@ -251,7 +243,7 @@ impl NeuralLocator {
                 label: Some("Input Field".to_string()),
                 confidence,
             })
-        } else if request.intent == "FAIL_TEST" {
+        } else if intent == "FAIL_TEST" {
             None
         } else {
             audit_trail.push("Intent classification: GENERAL_INTERACTION".to_string());
@@ -289,46 +281,59 @@ impl NeuralLocator {
         // Simulated Heatmap (10x10 grid flattened)
         let heatmap_data: Vec<f32> = (0..100).map(|_| rng.gen::<f32>()).collect();
 
-        let elapsed = start_time.elapsed();
+        // 4. Project into CSS pixels for the browser side
+        let css_location = primary_box.as_ref().map(|b| frame.to_css(b));
+        let css_candidates = candidates.iter().map(|b| frame.to_css(b)).collect();
 
         VisionResult {
             found: primary_box.is_some(),
             location: primary_box,
             candidates,
+            css_location,
+            css_candidates,
+            frame,
             confidence,
             semantic_embedding: embedding,
             heatmap_data,
-            reasoning: format!("ViT Layer identified '{}' based on visual intent patterns (Edge detection, OCR, Iconography). Confidence: {:.2}", request.intent, confidence),
-            processing_time_ms: elapsed.as_millis() as u64,
+            reasoning: format!("ViT Layer identified '{}' based on visual intent patterns (Edge detection, OCR, Iconography). Confidence: {:.2}", intent, confidence),
+            processing_time_ms: determinism::elapsed_ms(start_time),
         }
     }
 
-    pub fn compare(&self, request: &VisionCompareRequest) -> VisionCompareResult {
+    pub fn compare(&self, request: &VisionCompareRequest, mask: Option<&PageMask>) -> Result<VisionCompareResult, VisionError> {
         // 1. Decode Image A
-        let img_a = match general_purpose::STANDARD.decode(&request.image_a_base64) {
-            Ok(data) => image::load_from_memory(&data).unwrap_or_else(|_| DynamicImage::new_rgb8(1, 1)),
-            Err(_) => DynamicImage::new_rgb8(1, 1),
-        };
+        let img_a = decode_image(&request.image_a_base64, &request.image_encoding)?;
 
         // 2. Decode Image B
-        let img_b = match general_purpose::STANDARD.decode(&request.image_b_base64) {
-            Ok(data) => image::load_from_memory(&data).unwrap_or_else(|_| DynamicImage::new_rgb8(1, 1)),
-            Err(_) => DynamicImage::new_rgb8(1, 1),
-        };
+        let img_b = decode_image(&request.image_b_base64, &request.image_encoding)?;
 
+        // A mask learned at another resolution would hide the wrong pixels
+        let mask = mask.filter(|m| m.fits(img_a.width(), img_a.height()));
+        Ok(Self::compare_images(&img_a, &img_b, mask))
+    }
+
+    /// Pixel-level similarity of two already-decoded images.
+    /// Pixels inside `mask` regions are excluded from the score entirely.
+    pub fn compare_images(img_a: &DynamicImage, img_b: &DynamicImage, mask: Option<&PageMask>) -> VisionCompareResult {
         // 3. Compare dimensions
         if img_a.width() != img_b.width() || img_a.height() != img_b.height() {
              return VisionCompareResult {
                 similarity_score: 0.0,
                 diff_reason: Some(format!("Dimensions differ: {}x{} vs {}x{}", img_a.width(), img_a.height(), img_b.width(), img_b.height())),
+                masked_pixels: 0,
             };
         }
 
         // 4. Pixel-wise comparison (Simple RMSE or percent difference)
         let mut diff_sum: u64 = 0;
         let mut pixel_count: u64 = 0;
+        let mut masked_pixels: u64 = 0;
 
         for (x, y, p_a) in img_a.pixels() {
+            if mask.is_some_and(|m| m.contains(x, y)) {
+                masked_pixels += 1;
+                continue;
+            }
             let p_b = img_b.get_pixel(x, y);
             // Compare RGB
             let d_r = (p_a[0] as i32 - p_b[0] as i32).abs();
@@ -340,7 +345,7 @@ impl NeuralLocator {
         }
 
         if pixel_count == 0 {
-            return VisionCompareResult { similarity_score: 1.0, diff_reason: None };
+            return VisionCompareResult { similarity_score: 1.0, diff_reason: None, masked_pixels };
         }
 
         // Max possible difference per pixel is 255 * 3 = 765
@@ -351,6 +356,7 @@ impl NeuralLocator {
         VisionCompareResult {
             similarity_score: similarity as f32,
             diff_reason: if similarity < 1.0 { Some(format!("Similarity: {:.2}%", similarity * 100.0)) } else { None },
+            masked_pixels,
         }
     }
 
@@ -360,7 +366,7 @@ impl NeuralLocator {
          map_lock.memory.insert(intent, NeuralMapEntry {
             location,
             embedding,
-            last_seen: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            last_seen: determinism::now_secs(),
         });
     }
 }
@@ -368,6 +374,7 @@ impl NeuralLocator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base64::{Engine as _, engine::general_purpose};
 
     #[test]
     fn test_invalid_base64() {
@@ -375,10 +382,44 @@ mod tests {
         let request = VisionRequest {
             image_base64: "invalid_base64_string".to_string(),
             intent: "anything".to_string(),
+            image_encoding: ImageEncoding::Auto,
+            viewport: ViewportContext::default(),
+        };
+
+        let err = locator.analyze(&request).unwrap_err();
+        assert!(matches!(err, VisionError::InvalidBase64(_)));
+        assert!(err.to_string().contains("Failed to decode Base64 image data"));
+    }
+
+    #[test]
+    fn test_undecodable_image_is_not_replaced() {
+        let locator = NeuralLocator::new();
+        let request = VisionRequest {
+            image_base64: general_purpose::STANDARD.encode(b"not a png"),
+            intent: "Find the Checkout button".to_string(),
+            image_encoding: ImageEncoding::Auto,
+            viewport: ViewportContext::default(),
+        };
+
+        assert!(locator.analyze(&request).is_err());
+    }
+
+    #[test]
+    fn test_hidpi_result_reports_css_pixels() {
+        let locator = NeuralLocator::new();
+        let pixels = general_purpose::STANDARD.encode(vec![0u8; 400 * 300 * 4]);
+        let request = VisionRequest {
+            image_base64: pixels,
+            intent: "Find the Checkout button".to_string(),
+            image_encoding: ImageEncoding::RawRgba { width: 400, height: 300 },
+            viewport: ViewportContext { device_pixel_ratio: 2.0, ..Default::default() },
         };
 
-        let result = locator.analyze(&request);
-        assert!(!result.found);
-        assert!(result.reasoning.contains("Failed to decode Base64 image data."));
+        let result = locator.analyze(&request).unwrap();
+        let device = result.location.unwrap();
+        let css = result.css_location.unwrap();
+        assert_eq!(css.x * 2, device.x);
+        assert_eq!(css.width * 2, device.width);
+        assert_eq!(result.css_candidates.len(), result.candidates.len());
     }
 }
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::network::{HarError, NetworkAnalysis, NetworkAnalyzer, NetworkObserveRequest, RequestKind};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct ObserverRequest {
     pub url: String,
     pub pending_network_requests: u32,
@@ -8,7 +10,7 @@ pub struct ObserverRequest {
     pub layout_shifts: f32, // Cumulative Layout Shift (CLS)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct ObserverState {
     pub stable: bool,
     pub network_idle: bool,
@@ -17,6 +19,12 @@ pub struct ObserverState {
     pub amniotic_state_score: f32, // 0.0 to 1.0 (1.0 = Perfectly Stable)
 }
 
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct NetworkObservation {
+    pub state: ObserverState,
+    pub network: NetworkAnalysis,
+}
+
 pub struct StateChangeObserver {
     // Configuration thresholds
     network_threshold: u32,
@@ -35,26 +43,55 @@ impl StateChangeObserver {
 
         // 1. Network Score (Weighted heavily)
         // 0 requests = 1.0, 1 = 0.8, 5+ = 0.0
-        let network_score = if request.pending_network_requests == 0 {
+        let network_score = Self::pending_score(request.pending_network_requests);
+        let network_idle = request.pending_network_requests <= self.network_threshold;
+
+        self.score(network_score, network_idle, request.dom_mutation_rate, request.layout_shifts)
+    }
+
+    /// Same score, but the network signal comes from a HAR capture instead of a raw
+    /// pending count: long-lived connections are ignored, a page is only idle after a
+    /// quiet window, and failing endpoints pull the score down.
+    pub fn observe_network(&self, request: &NetworkObserveRequest) -> Result<NetworkObservation, HarError> {
+        let network = NetworkAnalyzer::analyze(request)?;
+
+        // Quiet for the whole window = 1.0; quiet but not long enough yet = 0.8
+        let mut network_score = match (network.network_idle, network.pending_tracked) {
+            (true, _) => 1.0,
+            (false, 0) => 0.8,
+            (false, pending) => Self::pending_score(pending),
+        };
+        let tracked = network.timeline.iter().filter(|e| e.kind == RequestKind::Tracked).count();
+        let failed = network.timeline.iter().filter(|e| e.kind == RequestKind::Tracked && e.failed).count();
+        if tracked > 0 {
+            network_score *= 1.0 - 0.5 * (failed as f32 / tracked as f32);
+        }
+
+        let network_idle = network.network_idle && network.pending_tracked <= self.network_threshold;
+        let state = self.score(network_score, network_idle, request.dom_mutation_rate, request.layout_shifts);
+        Ok(NetworkObservation { state, network })
+    }
+
+    fn pending_score(pending: u32) -> f32 {
+        if pending == 0 {
             1.0
         } else {
-            (1.0 - (request.pending_network_requests as f32 * 0.2)).max(0.0)
-        };
+            (1.0 - (pending as f32 * 0.2)).max(0.0)
+        }
+    }
 
+    fn score(&self, network_score: f32, network_idle: bool, dom_mutation_rate: u32, layout_shifts: f32) -> ObserverState {
         // 2. DOM Stability Score
         // 0 mutations = 1.0, >10 = 0.0
-        let dom_score = (1.0 - (request.dom_mutation_rate as f32 * 0.1)).max(0.0);
+        let dom_score = (1.0 - (dom_mutation_rate as f32 * 0.1)).max(0.0);
 
         // 3. Layout Shift Score
         // CLS 0 = 1.0, CLS 0.1 = 0.5, CLS 0.25+ = 0.0
-        let cls_score = (1.0 - (request.layout_shifts * 5.0)).max(0.0);
+        let cls_score = (1.0 - (layout_shifts * 5.0)).max(0.0);
 
         // 4. Combined Amniotic Score
         // Weighted Average: Network 50%, DOM 30%, Layout 20%
         let amniotic_score = (network_score * 0.5) + (dom_score * 0.3) + (cls_score * 0.2);
-
-        // Determine boolean flags based on thresholds
-        let network_idle = request.pending_network_requests <= self.network_threshold;
         let stable = amniotic_score > 0.85;
 
         ObserverState {
@@ -99,4 +136,24 @@ mod tests {
         assert!(!state.stable);
         assert!(state.amniotic_state_score < 0.85);
     }
+
+    #[test]
+    fn test_har_with_open_socket_is_stable() {
+        let observer = StateChangeObserver::new();
+        let req: NetworkObserveRequest = serde_json::from_value(serde_json::json!({
+            "url": "http://localhost",
+            "har": { "log": { "entries": [
+                { "startedDateTime": "2024-05-01T10:00:00.000Z", "time": 200.0,
+                  "request": { "method": "GET", "url": "http://localhost/" }, "response": { "status": 200 } },
+                { "startedDateTime": "2024-05-01T10:00:00.100Z", "time": 5000.0,
+                  "request": { "method": "GET", "url": "ws://localhost/live" }, "response": { "status": 101 } }
+            ] } }
+        }))
+        .unwrap();
+
+        let observation = observer.observe_network(&req).unwrap();
+        assert_eq!(observation.network.pending_excluded, 1);
+        assert!(observation.state.network_idle);
+        assert!(observation.state.stable);
+    }
 }
@@ -0,0 +1,346 @@
+//! Web-vitals budgets and regression detection.
+//!
+//! Every sample (LCP, CLS, INP, TTFB and any custom timings) is appended to a per-URL
+//! history under the store root (`perf/<url>.jsonl`). A new sample is judged twice:
+//! against the static budgets in `budgets.json`, and against a rolling baseline of the
+//! most recent runs for the same URL, where a value is a regression only if it is both
+//! materially worse and outside the run-to-run noise (z-score).
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use crate::engine::baseline::{safe_file_stem, store_root};
+use crate::engine::determinism;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct VitalsSample {
+    pub url: String,
+    pub run_id: String,
+    #[serde(default)]
+    pub lcp_ms: Option<f64>,
+    #[serde(default)]
+    pub cls: Option<f64>,
+    #[serde(default)]
+    pub inp_ms: Option<f64>,
+    #[serde(default)]
+    pub ttfb_ms: Option<f64>,
+    /// Named custom timings, e.g. `{"search_results_ms": 420}`.
+    #[serde(default)]
+    pub custom: BTreeMap<String, f64>,
+    /// Set by the store when the sample is recorded.
+    #[serde(default)]
+    pub recorded_at: u64,
+}
+
+impl VitalsSample {
+    /// All metrics by name. Core vitals use the same names as the fields.
+    pub fn metrics(&self) -> BTreeMap<String, f64> {
+        let core = [("lcp_ms", self.lcp_ms), ("cls", self.cls), ("inp_ms", self.inp_ms), ("ttfb_ms", self.ttfb_ms)];
+        let mut metrics: BTreeMap<String, f64> = core.into_iter().filter_map(|(k, v)| Some((k.to_string(), v?))).collect();
+        metrics.extend(self.custom.iter().map(|(k, v)| (k.clone(), *v)));
+        metrics
+    }
+}
+
+/// Contents of `budgets.json`. Budgets are upper bounds keyed by metric name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct BudgetConfig {
+    #[serde(default)]
+    pub default: BTreeMap<String, f64>,
+    /// Per-page overrides; for each metric the longest matching `url_prefix` wins.
+    #[serde(default)]
+    pub pages: Vec<PageBudget>,
+    #[serde(default)]
+    pub regression: RegressionSettings,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PageBudget {
+    pub url_prefix: String,
+    pub budgets: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct RegressionSettings {
+    /// Number of most recent runs forming the rolling baseline.
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// Below this many prior runs no regression is reported.
+    #[serde(default = "default_min_samples")]
+    pub min_samples: usize,
+    #[serde(default = "default_z_threshold")]
+    pub z_threshold: f64,
+    /// Smallest relative increase over the baseline mean worth reporting (0.1 = 10%).
+    #[serde(default = "default_min_relative_increase")]
+    pub min_relative_increase: f64,
+}
+
+fn default_window() -> usize { 20 }
+fn default_min_samples() -> usize { 5 }
+fn default_z_threshold() -> f64 { 3.0 }
+fn default_min_relative_increase() -> f64 { 0.1 }
+
+impl Default for RegressionSettings {
+    fn default() -> Self {
+        RegressionSettings {
+            window: default_window(),
+            min_samples: default_min_samples(),
+            z_threshold: default_z_threshold(),
+            min_relative_increase: default_min_relative_increase(),
+        }
+    }
+}
+
+impl BudgetConfig {
+    pub fn budget_for(&self, url: &str, metric: &str) -> Option<f64> {
+        self.pages
+            .iter()
+            .filter(|p| url.starts_with(&p.url_prefix) && p.budgets.contains_key(metric))
+            .max_by_key(|p| p.url_prefix.len())
+            .map(|p| p.budgets[metric])
+            .or_else(|| self.default.get(metric).copied())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct BaselineStats {
+    pub mean: f64,
+    pub stddev: f64,
+    pub samples: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct MetricResult {
+    pub metric: String,
+    pub value: f64,
+    pub budget: Option<f64>,
+    pub over_budget: bool,
+    pub baseline: Option<BaselineStats>,
+    /// Standard deviations above the baseline mean; absent when the baseline has no spread.
+    pub z_score: Option<f64>,
+    pub regression: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PerfReport {
+    pub url: String,
+    pub run_id: String,
+    pub metrics: Vec<MetricResult>,
+    pub budget_violations: Vec<String>,
+    pub regressions: Vec<String>,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PerfError {
+    InvalidSample(String),
+    Config(String),
+    Storage(String),
+}
+
+impl fmt::Display for PerfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PerfError::InvalidSample(reason) => write!(f, "Invalid vitals sample: {}", reason),
+            PerfError::Config(reason) => write!(f, "Invalid budget config: {}", reason),
+            PerfError::Storage(reason) => write!(f, "Performance history error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PerfError {}
+
+/// Judges `sample` against the budgets and the prior runs in `history` (oldest first).
+pub fn evaluate(config: &BudgetConfig, history: &[VitalsSample], sample: &VitalsSample) -> PerfReport {
+    let settings = &config.regression;
+    let mut metrics = Vec::new();
+
+    for (metric, value) in sample.metrics() {
+        let budget = config.budget_for(&sample.url, &metric);
+        let over_budget = budget.is_some_and(|b| value > b);
+
+        let prior: Vec<f64> = history
+            .iter()
+            .filter_map(|s| s.metrics().get(&metric).copied())
+            .rev()
+            .take(settings.window)
+            .collect();
+        let baseline = (prior.len() >= settings.min_samples.max(2)).then(|| {
+            let mean = prior.iter().sum::<f64>() / prior.len() as f64;
+            let variance = prior.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (prior.len() - 1) as f64;
+            BaselineStats { mean, stddev: variance.sqrt(), samples: prior.len() }
+        });
+
+        let z_score = baseline.as_ref().filter(|b| b.stddev > 0.0).map(|b| (value - b.mean) / b.stddev);
+        let regression = baseline.as_ref().is_some_and(|b| {
+            let materially_worse = value > b.mean * (1.0 + settings.min_relative_increase) && value > b.mean;
+            // A flat baseline has no noise, so any material increase is significant
+            let significant = z_score.map_or(b.stddev == 0.0, |z| z >= settings.z_threshold);
+            materially_worse && significant
+        });
+
+        metrics.push(MetricResult { metric, value, budget, over_budget, baseline, z_score, regression });
+    }
+
+    let budget_violations: Vec<String> = metrics.iter().filter(|m| m.over_budget).map(|m| m.metric.clone()).collect();
+    let regressions: Vec<String> = metrics.iter().filter(|m| m.regression).map(|m| m.metric.clone()).collect();
+    PerfReport {
+        url: sample.url.clone(),
+        run_id: sample.run_id.clone(),
+        passed: budget_violations.is_empty() && regressions.is_empty(),
+        metrics,
+        budget_violations,
+        regressions,
+    }
+}
+
+/// Per-URL sample history and the budget config, under the shared store root.
+pub struct PerfStore {
+    root: PathBuf,
+}
+
+impl Default for PerfStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PerfStore {
+    pub fn new() -> Self {
+        Self::at(store_root())
+    }
+
+    pub fn at(root: impl Into<PathBuf>) -> Self {
+        PerfStore { root: root.into() }
+    }
+
+    fn history_path(&self, url: &str) -> Result<PathBuf, PerfError> {
+        let safe = safe_file_stem(url).ok_or_else(|| PerfError::InvalidSample(format!("invalid url '{}'", url)))?;
+        Ok(self.root.join("perf").join(format!("{}.jsonl", safe)))
+    }
+
+    /// `$VERITAS_BUDGETS` or `<root>/budgets.json`. A missing file means no budgets.
+    pub fn config(&self) -> Result<BudgetConfig, PerfError> {
+        let path = std::env::var("VERITAS_BUDGETS").map(PathBuf::from).unwrap_or_else(|_| self.root.join("budgets.json"));
+        if !path.exists() {
+            return Ok(BudgetConfig::default());
+        }
+        let json = fs::read_to_string(&path).map_err(|e| PerfError::Config(format!("{}: {}", path.display(), e)))?;
+        serde_json::from_str(&json).map_err(|e| PerfError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn history(&self, url: &str) -> Result<Vec<VitalsSample>, PerfError> {
+        let path = self.history_path(url)?;
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&path).map_err(|e| PerfError::Storage(format!("{}: {}", path.display(), e)))?;
+        content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).map_err(|e| PerfError::Storage(format!("corrupt history {}: {}", path.display(), e))))
+            .collect()
+    }
+
+    fn append(&self, sample: &VitalsSample) -> Result<(), PerfError> {
+        let path = self.history_path(&sample.url)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| PerfError::Storage(format!("{}: {}", parent.display(), e)))?;
+        }
+        let line = serde_json::to_string(sample).map_err(|e| PerfError::Storage(e.to_string()))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| PerfError::Storage(format!("{}: {}", path.display(), e)))?;
+        writeln!(file, "{}", line).map_err(|e| PerfError::Storage(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Evaluates the sample against the current history, then appends it.
+    pub fn record(&self, sample: &VitalsSample) -> Result<PerfReport, PerfError> {
+        let metrics = sample.metrics();
+        if metrics.is_empty() {
+            return Err(PerfError::InvalidSample("no metrics given".to_string()));
+        }
+        if let Some((name, value)) = metrics.iter().find(|(_, v)| !v.is_finite() || **v < 0.0) {
+            return Err(PerfError::InvalidSample(format!("{} must be a non-negative number, got {}", name, value)));
+        }
+
+        let report = evaluate(&self.config()?, &self.history(&sample.url)?, sample);
+        let mut stored = sample.clone();
+        stored.recorded_at = determinism::now_secs();
+        self.append(&stored)?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(run: usize, lcp: f64) -> VitalsSample {
+        VitalsSample {
+            url: "https://shop.test/checkout".to_string(),
+            run_id: format!("run-{}", run),
+            lcp_ms: Some(lcp),
+            cls: Some(0.02),
+            inp_ms: None,
+            ttfb_ms: None,
+            custom: BTreeMap::new(),
+            recorded_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_budget_prefers_longest_prefix() {
+        let config: BudgetConfig = serde_json::from_str(
+            r#"{"default": {"lcp_ms": 2500, "cls": 0.1},
+                "pages": [{"url_prefix": "https://shop.test/", "budgets": {"lcp_ms": 2000}},
+                          {"url_prefix": "https://shop.test/checkout", "budgets": {"lcp_ms": 1500}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.budget_for("https://shop.test/checkout/pay", "lcp_ms"), Some(1500.0));
+        assert_eq!(config.budget_for("https://shop.test/home", "lcp_ms"), Some(2000.0));
+        assert_eq!(config.budget_for("https://shop.test/home", "cls"), Some(0.1));
+
+        let report = evaluate(&config, &[], &sample(0, 1800.0));
+        assert_eq!(report.budget_violations, vec!["lcp_ms"]);
+        assert!(!report.passed);
+    }
+
+    #[test]
+    fn test_regression_needs_significance() {
+        let config = BudgetConfig::default();
+        let history: Vec<VitalsSample> = [1000.0, 1040.0, 980.0, 1010.0, 1020.0, 990.0].iter().enumerate().map(|(i, v)| sample(i, *v)).collect();
+
+        // Within noise and under 10% worse
+        assert!(evaluate(&config, &history, &sample(9, 1060.0)).regressions.is_empty());
+        // Far outside the baseline spread
+        let report = evaluate(&config, &history, &sample(9, 1400.0));
+        assert_eq!(report.regressions, vec!["lcp_ms"]);
+        assert!(report.metrics[1].z_score.unwrap() > 3.0);
+        // Too little history to judge
+        assert!(evaluate(&config, &history[..3], &sample(9, 5000.0)).regressions.is_empty());
+    }
+
+    #[test]
+    fn test_record_appends_history() {
+        let dir = std::env::temp_dir().join(format!("veritas_perf_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = PerfStore::at(&dir);
+        for run in 0..3 {
+            assert!(store.record(&sample(run, 1000.0)).unwrap().passed);
+        }
+        assert_eq!(store.history("https://shop.test/checkout").unwrap().len(), 3);
+
+        let mut empty = sample(4, 0.0);
+        empty.lcp_ms = None;
+        empty.cls = None;
+        assert!(store.record(&empty).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
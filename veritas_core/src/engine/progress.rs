@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::agent::AgentStep;
+
+/// Intermediate progress of a long-running engine call (Goal, Swarm).
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    StepStarted { step_id: u32, action: String },
+    StepFinished { step: AgentStep },
+    AgentFinished { agent_id: u32, region: String },
+    Metrics { completed: u32, total: u32, throughput_tps: f32 },
+}
+
+/// Receives progress events. Engines call it synchronously, so sinks should be cheap.
+pub trait ProgressSink: Send + Sync {
+    fn emit(&self, event: ProgressEvent);
+
+    /// Polled by engines between units of work; once true they stop early and return
+    /// what they have.
+    fn cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Discards all progress (the default for one-shot calls).
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn emit(&self, _event: ProgressEvent) {}
+}
+
+impl<F: Fn(ProgressEvent) + Send + Sync> ProgressSink for F {
+    fn emit(&self, event: ProgressEvent) {
+        self(event)
+    }
+}
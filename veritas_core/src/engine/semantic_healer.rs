@@ -1,15 +1,16 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use rand::{Rng, SeedableRng, rngs::StdRng};
 use crate::engine::neural_locator::VisionTransformer;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct HealRequest {
     pub failed_selector: String,
     pub last_known_embedding: Vec<f32>,
     pub current_image: String, // Base64
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct HealResult {
     pub healed: bool,
     pub new_selector: String,
@@ -1,14 +1,17 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use rand::Rng;
+use crate::engine::determinism;
+use crate::engine::progress::{NoProgress, ProgressEvent, ProgressSink};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct SwarmRequest {
     pub agent_count: u32, // e.g., 1000
     pub regions: Vec<String>, // ["us-east-1", "eu-central-1"]
     pub task_goal: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct SwarmStatus {
     pub active_agents: u32,
     pub completed_tasks: u32,
@@ -27,25 +30,57 @@ impl DistributedSwarm {
     }
 
     pub fn launch(&self, request: &SwarmRequest) -> SwarmStatus {
+        self.launch_with_progress(request, &NoProgress)
+    }
+
+    /// Same as `launch`, reporting each finished agent and periodic metrics to `progress`.
+    pub fn launch_with_progress(&self, request: &SwarmRequest, progress: &dyn ProgressSink) -> SwarmStatus {
         // SIMULATION: Spin up 1000 micro-agents
         let mut health = std::collections::HashMap::new();
         for region in &request.regions {
             health.insert(region.clone(), "OPTIMAL".to_string());
         }
 
-        let mut rng = rand::thread_rng();
+        let mut rng = determinism::rng();
+        let throughput_tps = request.agent_count as f32 * rng.gen_range(1.5..5.0);
+        let completed_tasks = request.agent_count / 2; // In progress
 
-        let logs = vec![
+        // Agents are spread round-robin over the regions; report every ~10%
+        let report_every = (completed_tasks / 10).max(1);
+        let mut completed_tasks = completed_tasks;
+        for agent_id in 0..completed_tasks {
+            if progress.cancelled() {
+                completed_tasks = agent_id;
+                break;
+            }
+            let region = match request.regions.len() {
+                0 => "default".to_string(),
+                n => request.regions[agent_id as usize % n].clone(),
+            };
+            progress.emit(ProgressEvent::AgentFinished { agent_id, region });
+            if (agent_id + 1) % report_every == 0 || agent_id + 1 == completed_tasks {
+                progress.emit(ProgressEvent::Metrics {
+                    completed: agent_id + 1,
+                    total: request.agent_count,
+                    throughput_tps,
+                });
+            }
+        }
+
+        let mut logs = vec![
             format!("Initializing Swarm Controller with {} agents...", request.agent_count),
             format!("Deploying agents to regions: {:?}", request.regions),
             format!("Agents executing goal: '{}'", request.task_goal),
             "Aggregating results...".to_string()
         ];
+        if progress.cancelled() {
+            logs.push(format!("Cancelled after {} agents", completed_tasks));
+        }
 
         SwarmStatus {
             active_agents: request.agent_count,
-            completed_tasks: request.agent_count / 2, // In progress
-            throughput_tps: request.agent_count as f32 * rng.gen_range(1.5..5.0),
+            completed_tasks,
+            throughput_tps,
             region_health: health,
             logs,
         }
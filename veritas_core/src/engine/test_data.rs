@@ -0,0 +1,305 @@
+//! Synthetic test data for form filling.
+//!
+//! Values look real (names, emails, addresses, checksummed IBANs, phone numbers) but are
+//! drawn from fictional rosters and reserved ranges, and every value is checked with
+//! `GDPRGuard::classify` before it is returned. Output is reproducible from the seed.
+
+use chrono::{Duration, Months, NaiveDate};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::engine::determinism;
+use crate::enterprise::compliance::{
+    iban_mod97, is_reserved_email_domain, DataCategory, DataClassification, GDPRGuard, SYNTHETIC_CITIES,
+    SYNTHETIC_FIRST_NAMES, SYNTHETIC_IBAN_PREFIX, SYNTHETIC_LAST_NAMES, SYNTHETIC_STREETS,
+};
+
+const MAX_RECORDS: u32 = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneRegion {
+    #[default]
+    Gb,
+    Us,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldKind {
+    /// "First Last"; shared with `email` fields of the same record.
+    Name,
+    Email {
+        /// Must be a reserved domain (example.com, *.test, ...). Defaults to example.com.
+        #[serde(default)]
+        domain: Option<String>,
+    },
+    Address,
+    Iban,
+    Phone {
+        #[serde(default)]
+        region: PhoneRegion,
+    },
+    /// A `YYYY-MM-DD` date inside every given bound. Ages are counted from today.
+    Date {
+        #[serde(default)]
+        after: Option<NaiveDate>,
+        #[serde(default)]
+        before: Option<NaiveDate>,
+        #[serde(default)]
+        min_age_years: Option<u32>,
+        #[serde(default)]
+        max_age_years: Option<u32>,
+    },
+    Number {
+        min: i64,
+        max: i64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct FieldSpec {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: FieldKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct TestDataRequest {
+    pub fields: Vec<FieldSpec>,
+    #[serde(default = "default_count")]
+    pub count: u32,
+    /// Same seed, same records. A random seed is chosen (and returned) when omitted.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+fn default_count() -> u32 { 1 }
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct TestDataResult {
+    pub seed: u64,
+    pub records: Vec<BTreeMap<String, Value>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestDataError {
+    InvalidConstraint { field: String, reason: String },
+    NotSynthetic { field: String, value: String },
+}
+
+impl fmt::Display for TestDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestDataError::InvalidConstraint { field, reason } => write!(f, "Invalid constraint on '{}': {}", field, reason),
+            TestDataError::NotSynthetic { field, value } => write!(f, "Generated value for '{}' is not synthetic: {}", field, value),
+        }
+    }
+}
+
+impl std::error::Error for TestDataError {}
+
+/// A fictional person; name-derived fields of one record stay consistent.
+#[derive(Debug, Clone)]
+pub struct Persona {
+    pub first_name: &'static str,
+    pub last_name: &'static str,
+}
+
+impl Persona {
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Persona {
+            first_name: SYNTHETIC_FIRST_NAMES.choose(rng).unwrap(),
+            last_name: SYNTHETIC_LAST_NAMES.choose(rng).unwrap(),
+        }
+    }
+
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name)
+    }
+
+    pub fn email(&self, domain: &str) -> String {
+        format!("{}.{}@{}", self.first_name, self.last_name, domain).to_lowercase()
+    }
+}
+
+pub fn synthetic_address(rng: &mut impl Rng) -> String {
+    format!(
+        "{} {}, {:05} {}",
+        rng.gen_range(1..200),
+        SYNTHETIC_STREETS.choose(rng).unwrap(),
+        rng.gen_range(10000..99999),
+        SYNTHETIC_CITIES.choose(rng).unwrap()
+    )
+}
+
+/// German IBAN with a bank code starting with 0 (never issued) and valid check digits.
+pub fn synthetic_iban(rng: &mut impl Rng) -> String {
+    let bban: String = std::iter::once('0').chain((0..17).map(|_| char::from(b'0' + rng.gen_range(0..10)))).collect();
+    let check = 98 - iban_mod97(&format!("{}{}00", bban, SYNTHETIC_IBAN_PREFIX));
+    format!("{}{:02}{}", SYNTHETIC_IBAN_PREFIX, check, bban)
+}
+
+pub fn synthetic_phone(region: PhoneRegion, rng: &mut impl Rng) -> String {
+    match region {
+        PhoneRegion::Gb => format!("+44 7700 900{:03}", rng.gen_range(0..1000)),
+        PhoneRegion::Us => format!("+1 {}-555-01{:02}", rng.gen_range(201..990), rng.gen_range(0..100)),
+    }
+}
+
+pub struct TestDataGenerator {
+    guard: GDPRGuard,
+}
+
+impl Default for TestDataGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestDataGenerator {
+    pub fn new() -> Self {
+        TestDataGenerator { guard: GDPRGuard::new() }
+    }
+
+    pub fn generate(&self, request: &TestDataRequest) -> Result<TestDataResult, TestDataError> {
+        if request.count == 0 || request.count > MAX_RECORDS {
+            return Err(TestDataError::InvalidConstraint {
+                field: "count".to_string(),
+                reason: format!("must be between 1 and {}", MAX_RECORDS),
+            });
+        }
+
+        let today = chrono::DateTime::from_timestamp(determinism::now_secs() as i64, 0).unwrap_or_default().date_naive();
+        let seed = request.seed.unwrap_or_else(|| determinism::rng().gen());
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut records = Vec::with_capacity(request.count as usize);
+        for _ in 0..request.count {
+            let persona = Persona::random(&mut rng);
+            let mut record = BTreeMap::new();
+            for field in &request.fields {
+                let (category, value) = self.value(field, &persona, today, &mut rng)?;
+                let text = match &value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                if self.guard.classify(category, &text) != DataClassification::Synthetic {
+                    return Err(TestDataError::NotSynthetic { field: field.name.clone(), value: text });
+                }
+                record.insert(field.name.clone(), value);
+            }
+            records.push(record);
+        }
+
+        Ok(TestDataResult { seed, records })
+    }
+
+    fn value(&self, field: &FieldSpec, persona: &Persona, today: NaiveDate, rng: &mut StdRng) -> Result<(DataCategory, Value), TestDataError> {
+        let invalid = |reason: String| TestDataError::InvalidConstraint { field: field.name.clone(), reason };
+        Ok(match &field.kind {
+            FieldKind::Name => (DataCategory::Name, Value::from(persona.full_name())),
+            FieldKind::Email { domain } => {
+                let domain = domain.as_deref().unwrap_or("example.com");
+                if !is_reserved_email_domain(domain) {
+                    return Err(invalid(format!("'{}' is not a reserved domain (use example.com or a .test domain)", domain)));
+                }
+                (DataCategory::Email, Value::from(persona.email(domain)))
+            }
+            FieldKind::Address => (DataCategory::Address, Value::from(synthetic_address(rng))),
+            FieldKind::Iban => (DataCategory::Iban, Value::from(synthetic_iban(rng))),
+            FieldKind::Phone { region } => (DataCategory::Phone, Value::from(synthetic_phone(*region, rng))),
+            FieldKind::Date { after, before, min_age_years, max_age_years } => {
+                // Ages come from the client, so every step is checked
+                let years_ago = |years: u32| today.checked_sub_months(Months::new(years.checked_mul(12)?));
+                let mut low = after.unwrap_or(NaiveDate::from_ymd_opt(1940, 1, 1).unwrap());
+                let mut high = before.unwrap_or(today);
+                if let Some(min_age) = min_age_years {
+                    high = high.min(years_ago(*min_age).ok_or_else(|| invalid("min_age_years out of range".to_string()))?);
+                }
+                if let Some(max_age) = max_age_years {
+                    // Someone aged `max_age` was born after the day `max_age + 1` years ago
+                    let earliest = max_age.checked_add(1).and_then(years_ago).ok_or_else(|| invalid("max_age_years out of range".to_string()))?;
+                    low = low.max(earliest + Duration::days(1));
+                }
+                if low > high {
+                    return Err(invalid(format!("no date between {} and {}", low, high)));
+                }
+                let date = low + Duration::days(rng.gen_range(0..=(high - low).num_days()));
+                (DataCategory::Date, Value::from(date.format("%Y-%m-%d").to_string()))
+            }
+            FieldKind::Number { min, max } => {
+                if min > max {
+                    return Err(invalid(format!("min {} is greater than max {}", min, max)));
+                }
+                (DataCategory::Number, Value::from(rng.gen_range(*min..=*max)))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enterprise::compliance::iban_checksum_valid;
+    use serde_json::json;
+
+    fn request(fields: Value, count: u32, seed: Option<u64>) -> TestDataRequest {
+        serde_json::from_value(json!({ "fields": fields, "count": count, "seed": seed })).unwrap()
+    }
+
+    #[test]
+    fn test_seeded_generation_is_reproducible_and_synthetic() {
+        let generator = TestDataGenerator::new();
+        let fields = json!([
+            {"name": "full_name", "type": "name"},
+            {"name": "email", "type": "email"},
+            {"name": "iban", "type": "iban"},
+            {"name": "phone", "type": "phone", "region": "us"},
+            {"name": "address", "type": "address"},
+            {"name": "age", "type": "number", "min": 18, "max": 99}
+        ]);
+        let a = generator.generate(&request(fields.clone(), 50, Some(9))).unwrap();
+        let b = generator.generate(&request(fields, 50, Some(9))).unwrap();
+        assert_eq!(a.records, b.records);
+
+        for record in &a.records {
+            let name = record["full_name"].as_str().unwrap().to_lowercase().replace(' ', ".");
+            assert!(record["email"].as_str().unwrap().starts_with(&name));
+            assert!(iban_checksum_valid(record["iban"].as_str().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_date_respects_age_bounds() {
+        let generator = TestDataGenerator::new();
+        let fields = json!([{"name": "dob", "type": "date", "min_age_years": 18, "max_age_years": 20}]);
+        let result = determinism::with_seed(1, || generator.generate(&request(fields, 100, Some(3)))).unwrap();
+        // Deterministic "today" is 2024-01-01
+        for record in result.records {
+            let dob = NaiveDate::parse_from_str(record["dob"].as_str().unwrap(), "%Y-%m-%d").unwrap();
+            assert!(dob <= NaiveDate::from_ymd_opt(2006, 1, 1).unwrap());
+            assert!(dob > NaiveDate::from_ymd_opt(2003, 1, 1).unwrap());
+        }
+
+        let impossible = json!([{"name": "dob", "type": "date", "before": "1990-01-01", "max_age_years": 20}]);
+        assert!(matches!(generator.generate(&request(impossible, 1, None)), Err(TestDataError::InvalidConstraint { .. })));
+
+        for age in ["min_age_years", "max_age_years"] {
+            let huge = json!([{"name": "dob", "type": "date", (age): u32::MAX}]);
+            assert!(matches!(generator.generate(&request(huge, 1, None)), Err(TestDataError::InvalidConstraint { .. })));
+        }
+    }
+
+    #[test]
+    fn test_real_email_domain_is_refused() {
+        let generator = TestDataGenerator::new();
+        let fields = json!([{"name": "email", "type": "email", "domain": "gmail.com"}]);
+        assert!(generator.generate(&request(fields, 1, None)).is_err());
+    }
+}
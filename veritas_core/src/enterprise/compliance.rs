@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
 pub struct GDPRGuard {
     // List of regex patterns for PII
     patterns: Vec<String>,
@@ -24,6 +27,106 @@ impl GDPRGuard {
     }
 }
 
+/// Kinds of personal data the guard can tell apart from synthetic test data.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DataCategory {
+    Name,
+    Email,
+    Address,
+    Iban,
+    Phone,
+    Date,
+    Number,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DataClassification {
+    Synthetic,
+    /// Anything not provably synthetic is treated as real personal data.
+    Personal,
+}
+
+// Fictional rosters: a value is synthetic only if it is built from these.
+pub const SYNTHETIC_FIRST_NAMES: &[&str] = &[
+    "Ada", "Brin", "Cato", "Dara", "Elio", "Fenna", "Galen", "Hale", "Isko", "Jora",
+    "Kiro", "Lumi", "Maren", "Nilo", "Orla", "Pell", "Quin", "Rhea", "Sola", "Tavi",
+];
+pub const SYNTHETIC_LAST_NAMES: &[&str] = &[
+    "Testova", "Mockridge", "Samplewood", "Fixtureson", "Stubbington",
+    "Dummersby", "Placeholt", "Fauxley", "Proxmire", "Synthelm",
+];
+pub const SYNTHETIC_STREETS: &[&str] = &["Fixture Lane", "Sandbox Road", "Staging Street", "Harness Avenue", "Mockingbird Close"];
+pub const SYNTHETIC_CITIES: &[&str] = &["Testford", "Mockton", "Sampleburg", "Stubville"];
+
+/// RFC 2606 / RFC 6761 names that can never reach a real mailbox.
+const RESERVED_EMAIL_DOMAINS: &[&str] = &["example.com", "example.net", "example.org"];
+const RESERVED_EMAIL_TLDS: &[&str] = &[".test", ".example", ".invalid", ".localhost"];
+
+/// German bank codes never start with 0, so `DE..0xxxxxxx...` cannot be a real account.
+pub const SYNTHETIC_IBAN_PREFIX: &str = "DE";
+
+pub fn is_reserved_email_domain(domain: &str) -> bool {
+    let domain = domain.to_ascii_lowercase();
+    RESERVED_EMAIL_DOMAINS.contains(&domain.as_str()) || RESERVED_EMAIL_TLDS.iter().any(|tld| domain.ends_with(tld))
+}
+
+/// ISO 13616 mod-97 check.
+pub fn iban_checksum_valid(iban: &str) -> bool {
+    let compact: String = iban.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.len() < 5 || !compact.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return false;
+    }
+    let rearranged = format!("{}{}", &compact[4..], &compact[..4]);
+    iban_mod97(&rearranged) == 1
+}
+
+/// Remainder of the IBAN numeric form (letters as 10..35) modulo 97.
+pub fn iban_mod97(text: &str) -> u32 {
+    text.chars().fold(0u32, |acc, c| {
+        let value = c.to_digit(36).unwrap_or(0);
+        if value >= 10 { (acc * 100 + value) % 97 } else { (acc * 10 + value) % 97 }
+    })
+}
+
+impl GDPRGuard {
+    /// Decides whether `value` is safe test data. Generated values must come back `Synthetic`.
+    pub fn classify(&self, category: DataCategory, value: &str) -> DataClassification {
+        let synthetic = match category {
+            DataCategory::Name => {
+                let parts: Vec<&str> = value.split_whitespace().collect();
+                parts.len() == 2 && SYNTHETIC_FIRST_NAMES.contains(&parts[0]) && SYNTHETIC_LAST_NAMES.contains(&parts[1])
+            }
+            DataCategory::Email => value
+                .rsplit_once('@')
+                .is_some_and(|(local, domain)| !local.is_empty() && is_reserved_email_domain(domain)),
+            DataCategory::Address => {
+                SYNTHETIC_STREETS.iter().any(|s| value.contains(s)) && SYNTHETIC_CITIES.iter().any(|c| value.contains(c))
+            }
+            DataCategory::Iban => {
+                let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+                // Byte indexing below needs ASCII
+                compact.is_ascii()
+                    && compact.len() == 22
+                    && compact.starts_with(SYNTHETIC_IBAN_PREFIX)
+                    && compact.as_bytes()[4] == b'0'
+                    && iban_checksum_valid(&compact)
+            }
+            DataCategory::Phone => {
+                let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+                // Ofcom drama range 07700 900xxx, and NANP fictional 555-01xx
+                (digits.len() == 12 && digits.starts_with("447700900"))
+                    || (digits.len() == 11 && digits.starts_with('1') && &digits[4..9] == "55501")
+            }
+            // A bare date or number identifies nobody on its own
+            DataCategory::Date => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+            DataCategory::Number => value.parse::<f64>().is_ok(),
+        };
+        if synthetic { DataClassification::Synthetic } else { DataClassification::Personal }
+    }
+}
+
 pub struct ComplianceMonitor {
     pub gdpr_compliant: bool,
     pub soc2_ready: bool,
@@ -53,4 +156,20 @@ mod tests {
         let clean = guard.sanitize(input);
         assert!(clean.contains("REDACTED"));
     }
+
+    #[test]
+    fn test_classifies_reserved_ranges_as_synthetic() {
+        let guard = GDPRGuard::new();
+        assert_eq!(guard.classify(DataCategory::Email, "ada.testova@example.com"), DataClassification::Synthetic);
+        assert_eq!(guard.classify(DataCategory::Email, "jane@gmail.com"), DataClassification::Personal);
+        assert_eq!(guard.classify(DataCategory::Phone, "+44 7700 900123"), DataClassification::Synthetic);
+        assert_eq!(guard.classify(DataCategory::Phone, "+44 7911 123456"), DataClassification::Personal);
+        assert_eq!(guard.classify(DataCategory::Name, "Ada Testova"), DataClassification::Synthetic);
+        assert_eq!(guard.classify(DataCategory::Name, "Jane Smith"), DataClassification::Personal);
+        // A real-format IBAN (valid checksum, real bank code) is personal
+        assert!(iban_checksum_valid("DE89 3704 0044 0532 0130 00"));
+        assert_eq!(guard.classify(DataCategory::Iban, "DE89370400440532013000"), DataClassification::Personal);
+        // 22 bytes with a multi-byte character where the bank code starts
+        assert_eq!(guard.classify(DataCategory::Iban, "DE1é00000000000000000"), DataClassification::Personal);
+    }
 }
@@ -16,6 +16,42 @@ pub struct UserContext {
     pub auth_token: String,
 }
 
+pub struct TokenAuthenticator {
+    // In a real system, this would validate against an identity provider
+}
+
+impl Default for TokenAuthenticator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenAuthenticator {
+    pub fn new() -> Self {
+        TokenAuthenticator {}
+    }
+
+    /// Resolves a token into a user context with roles (Mock).
+    pub fn authenticate(&self, user_id: &str, auth_token: &str) -> Result<UserContext, String> {
+        if auth_token != "valid_token" {
+            return Err("Authentication failed: Invalid token".to_string());
+        }
+
+        let mut roles = HashSet::new();
+        if user_id == "admin" {
+            roles.insert(Role::Admin);
+        } else {
+            roles.insert(Role::Viewer); // Default
+        }
+
+        Ok(UserContext {
+            user_id: user_id.to_string(),
+            roles,
+            auth_token: auth_token.to_string(),
+        })
+    }
+}
+
 pub struct RBAC {
     // In a real system, this would fetch policies from a DB
 }
@@ -0,0 +1,231 @@
+//! HTTP server mode: every registered command is exposed as
+//! `POST /v1/commands/{name}`, guarded by the same middleware as the stdin protocol.
+//!
+//! Credentials travel as `Authorization: Bearer <token>` plus `X-User-Id: <user>`.
+//! `POST /v1/commands/{name}/stream` runs the same command but answers with
+//! Server-Sent Events: zero or more `progress` events, then one `result` event.
+//! Closing the stream cancels the command; an `X-Request-Id` header also makes it
+//! cancellable through the `Cancel` command.
+
+pub mod openapi;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response as HttpResponse};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use serde_json::Value;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::StreamExt;
+use crate::commands::{CommandEnvelope, CommandError, CommandRegistry, Response, SecureCommand};
+use crate::engine::progress::{ProgressEvent, ProgressSink};
+
+#[derive(Serialize, Debug)]
+struct CommandSummary {
+    name: &'static str,
+    description: &'static str,
+    required_role: Option<String>,
+    streams_progress: bool,
+}
+
+pub fn router(registry: Arc<CommandRegistry>) -> Router {
+    Router::new()
+        .route("/openapi.json", get(openapi_document))
+        .route("/v1/commands", get(list_commands))
+        .route("/v1/commands/:name", post(run_command))
+        .route("/v1/commands/:name/stream", post(stream_command))
+        .with_state(registry)
+}
+
+pub async fn serve(addr: &str, registry: Arc<CommandRegistry>) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    eprintln!("[HTTP] Veritas Core listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, router(registry)).await
+}
+
+fn status_for(error: &CommandError) -> StatusCode {
+    match error {
+        CommandError::Unauthenticated(_) => StatusCode::UNAUTHORIZED,
+        CommandError::Forbidden(_) => StatusCode::FORBIDDEN,
+        CommandError::UnknownCommand(_) => StatusCode::NOT_FOUND,
+        CommandError::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+        CommandError::Failed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        CommandError::Cancelled(_) => StatusCode::CONFLICT,
+    }
+}
+
+/// Rebuilds the stdin envelope from headers and body so both transports share one code path.
+fn secure_command(name: String, headers: &HeaderMap, body: &Bytes) -> Result<SecureCommand, CommandError> {
+    let header = |key: &str| headers.get(key).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let auth_token = header("authorization").strip_prefix("Bearer ").unwrap_or_default().trim().to_string();
+    let payload = if body.iter().all(u8::is_ascii_whitespace) {
+        Value::Null
+    } else {
+        serde_json::from_slice(body).map_err(|e| CommandError::InvalidPayload(e.to_string()))?
+    };
+
+    let request_id = Some(header("x-request-id")).filter(|id| !id.is_empty()).map(str::to_string);
+    Ok(SecureCommand {
+        request_id,
+        auth_token,
+        user_id: header("x-user-id").to_string(),
+        command: CommandEnvelope { command: name, payload },
+    })
+}
+
+fn error_response(error: CommandError) -> HttpResponse {
+    (status_for(&error), Json(Response::<Value>::error(&error.to_string()))).into_response()
+}
+
+async fn openapi_document(State(registry): State<Arc<CommandRegistry>>) -> Json<Value> {
+    Json(openapi::document(&registry))
+}
+
+async fn list_commands(State(registry): State<Arc<CommandRegistry>>) -> Json<Vec<CommandSummary>> {
+    Json(
+        registry
+            .specs()
+            .into_iter()
+            .map(|s| CommandSummary {
+                name: s.name,
+                description: s.description,
+                required_role: s.required_role.map(|r| format!("{:?}", r)),
+                streams_progress: s.streams_progress,
+            })
+            .collect(),
+    )
+}
+
+async fn run_command(
+    State(registry): State<Arc<CommandRegistry>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> HttpResponse {
+    let command = match secure_command(name, &headers, &body) {
+        Ok(command) => command,
+        Err(e) => return error_response(e),
+    };
+
+    // Engines are synchronous; keep them off the async workers.
+    let outcome = tokio::task::spawn_blocking(move || registry.dispatch(command)).await;
+    match outcome {
+        Ok(Ok(data)) => Json(Response::success(data)).into_response(),
+        Ok(Err(e)) => error_response(e),
+        Err(join) => error_response(CommandError::Failed(format!("Command panicked: {}", join))),
+    }
+}
+
+async fn stream_command(
+    State(registry): State<Arc<CommandRegistry>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> HttpResponse {
+    if registry.spec(&name).is_none() {
+        return error_response(CommandError::UnknownCommand(name));
+    }
+    let command = match secure_command(name, &headers, &body) {
+        Ok(command) => command,
+        Err(e) => return error_response(e),
+    };
+
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<Event>();
+    tokio::task::spawn_blocking(move || {
+        let request_id = command.request_id.clone();
+        let sink = SseSink { tx: tx.clone() };
+        let response = match registry.dispatch_with_progress(command, &sink) {
+            Ok(data) => Response::success(data),
+            Err(e) => Response::error(&e.to_string()),
+        }
+        .with_request_id(request_id);
+        if let Ok(event) = Event::default().event("result").json_data(&response) {
+            let _ = tx.send(event);
+        }
+    });
+
+    let stream = UnboundedReceiverStream::new(rx).map(Ok::<_, Infallible>);
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// Sends progress as SSE events; a dropped receiver means the client went away.
+struct SseSink {
+    tx: tokio::sync::mpsc::UnboundedSender<Event>,
+}
+
+impl ProgressSink for SseSink {
+    fn emit(&self, event: ProgressEvent) {
+        if let Ok(event) = Event::default().event("progress").json_data(&event) {
+            let _ = self.tx.send(event);
+        }
+    }
+
+    fn cancelled(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn request(uri: &str, user: &str, body: &str) -> Request<Body> {
+        Request::post(uri)
+            .header("authorization", "Bearer valid_token")
+            .header("x-user-id", user)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn call(req: Request<Body>) -> (StatusCode, String) {
+        let app = router(Arc::new(CommandRegistry::with_builtin()));
+        let response = app.oneshot(req).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_command_over_http_uses_rbac() {
+        let (status, body) = call(request("/v1/commands/Ping", "viewer1", "")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Pong"));
+
+        let swarm = r#"{"agent_count": 4, "regions": ["eu"], "task_goal": "x"}"#;
+        let (status, _) = call(request("/v1/commands/Swarm", "viewer1", swarm)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let unauthenticated = Request::post("/v1/commands/Ping").body(Body::empty()).unwrap();
+        assert_eq!(call(unauthenticated).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_swarm_streams_progress_then_result() {
+        let swarm = r#"{"agent_count": 4, "regions": ["eu"], "task_goal": "x"}"#;
+        let (status, body) = call(request("/v1/commands/Swarm/stream", "admin", swarm)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("event: progress"));
+        assert!(body.contains("agent_finished"));
+        let result_at = body.find("event: result").unwrap();
+        assert!(body[result_at..].contains("\"status\":\"success\""));
+    }
+
+    #[tokio::test]
+    async fn test_openapi_lists_every_command() {
+        let req = Request::get("/openapi.json").body(Body::empty()).unwrap();
+        let (status, body) = call(req).await;
+        assert_eq!(status, StatusCode::OK);
+        let doc: Value = serde_json::from_str(&body).unwrap();
+        assert!(doc["paths"]["/v1/commands/Locate"]["post"]["requestBody"].is_object());
+        assert!(doc["paths"]["/v1/commands/Goal/stream"]["post"].is_object());
+    }
+}
@@ -0,0 +1,96 @@
+use serde_json::{json, Map, Value};
+use crate::commands::{CommandRegistry, CommandSpec};
+
+/// Builds an OpenAPI 3.0 document from the registry. Schemas come straight from the
+/// request/response types via `CommandSpec`, so the document cannot drift from the code.
+pub fn document(registry: &CommandRegistry) -> Value {
+    let mut paths = Map::new();
+    for spec in registry.specs() {
+        paths.insert(format!("/v1/commands/{}", spec.name), json!({ "post": operation(&spec, false) }));
+        if spec.streams_progress {
+            paths.insert(format!("/v1/commands/{}/stream", spec.name), json!({ "post": operation(&spec, true) }));
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Veritas Core",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Cognitive QA engine commands. Every endpoint requires a bearer token and an X-User-Id header.",
+        },
+        "security": [{ "bearerAuth": [] }],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer" },
+            },
+            "parameters": {
+                "UserId": { "name": "X-User-Id", "in": "header", "required": true, "schema": { "type": "string" } },
+            },
+            "schemas": {
+                "Error": envelope(json!({ "nullable": true })),
+            },
+        },
+    })
+}
+
+/// `{status, data, error}` with `data` typed per command.
+fn envelope(data: Value) -> Value {
+    json!({
+        "type": "object",
+        "required": ["status"],
+        "properties": {
+            "status": { "type": "string", "enum": ["success", "error"] },
+            "data": data,
+            "error": { "type": "string", "nullable": true },
+        },
+    })
+}
+
+fn operation(spec: &CommandSpec, stream: bool) -> Value {
+    let role = spec
+        .required_role
+        .as_ref()
+        .map(|r| format!("{:?}", r))
+        .unwrap_or_else(|| "any authenticated user".to_string());
+    let error = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } },
+        })
+    };
+
+    let success = if stream {
+        json!({
+            "description": "Server-Sent Events: `progress` events, then a single `result` event carrying the response envelope",
+            "content": { "text/event-stream": { "schema": { "type": "string" } } },
+        })
+    } else {
+        json!({
+            "description": "Command result",
+            "content": { "application/json": { "schema": envelope(spec.response_schema.clone()) } },
+        })
+    };
+
+    json!({
+        "operationId": if stream { format!("{}Stream", spec.name) } else { spec.name.to_string() },
+        "summary": spec.description,
+        "description": format!("Requires role: {}", role),
+        "x-required-role": role,
+        "parameters": [{ "$ref": "#/components/parameters/UserId" }],
+        "requestBody": {
+            "required": !spec.payload_schema.get("type").is_some_and(|t| t == "null"),
+            "content": { "application/json": { "schema": spec.payload_schema } },
+        },
+        "responses": {
+            "200": success,
+            "400": error("Malformed payload"),
+            "401": error("Missing or invalid credentials"),
+            "403": error("Role not permitted"),
+            "404": error("Unknown command"),
+            "409": error("Cancelled before completion"),
+            "422": error("Command failed"),
+        },
+    })
+}
@@ -1,3 +1,8 @@
+pub mod commands;
 pub mod engine;
 pub mod enterprise;
+pub mod http;
 pub mod omega;
+pub mod scenario;
+pub mod session;
+pub mod stdio;
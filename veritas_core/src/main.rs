@@ -1,191 +1,103 @@
-mod engine;
-mod enterprise;
-mod omega;
-
-use std::io::{self, BufRead};
-use std::collections::HashSet;
-use serde::{Deserialize, Serialize};
-use engine::neural_locator::{NeuralLocator, VisionRequest, VisionCompareRequest};
-use engine::semantic_healer::{SemanticHealer, HealRequest};
-use engine::agent::{GoalOrientedAgent, GoalRequest};
-use engine::observer::{StateChangeObserver, ObserverRequest};
-use engine::swarm::{DistributedSwarm, SwarmRequest};
-use enterprise::security::{RBAC, AuditLogger, UserContext, Role};
-use enterprise::compliance::{GDPRGuard, ComplianceMonitor};
-use omega::physics::{SpatialFolder, ZeroPointHarvester};
-use omega::psionics::{NoeticLayer, PrescientLattice};
-use omega::ontology::RealityAnchor;
-
-#[derive(Serialize, Deserialize, Debug)]
-struct SecureCommand {
-    auth_token: String,
-    user_id: String,
-    command: Command,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(tag = "command", content = "payload")]
-enum Command {
-    Compare(VisionCompareRequest),
-    Locate(VisionRequest),
-    Heal(HealRequest),
-    Goal(GoalRequest),
-    Observe(ObserverRequest),
-    Swarm(SwarmRequest),
-    Omega(OmegaRequest),
-    Ping,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(tag = "type")]
-enum OmegaRequest {
-    Fold { coords: [f64; 11] },
-    InvertEntropy,
-    TransmitQualia { concept: String },
-    Anticipate { subject_id: String },
-    VerifyReality { entity_id: String },
+use std::io;
+use std::sync::Arc;
+use veritas_core::commands::CommandRegistry;
+use veritas_core::engine::baseline::BaselineStore;
+use veritas_core::engine::determinism;
+use veritas_core::engine::neural_locator::NeuralLocator;
+use veritas_core::engine::observer::StateChangeObserver;
+use veritas_core::engine::semantic_healer::SemanticHealer;
+use veritas_core::http;
+use veritas_core::scenario::{DryRunDriver, Scenario, ScenarioRunner};
+use veritas_core::session::{self, SessionRecorder};
+use veritas_core::stdio;
+
+/// Removes `flag <value>` from the argument list and returns the value.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> io::Result<Option<String>> {
+    let Some(i) = args.iter().position(|a| a == flag) else { return Ok(None) };
+    if i + 1 >= args.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} needs a value", flag)));
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Ok(Some(value))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Response<T> {
-    status: String,
-    data: Option<T>,
-    error: Option<String>,
+fn parse_seed(raw: &str) -> io::Result<u64> {
+    raw.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid seed '{}'", raw)))
 }
 
-fn main() {
-    let locator = NeuralLocator::new();
-    let healer = SemanticHealer::new();
-    let agent = GoalOrientedAgent::new();
-    let observer = StateChangeObserver::new();
-    let swarm = DistributedSwarm::new();
-
-    // Enterprise Modules
-    let rbac = RBAC::new();
-    let logger = AuditLogger::new();
-    let gdpr = GDPRGuard::new();
-    let _monitor = ComplianceMonitor::new();
-
-    // Omega Modules (Experimental)
-    let folder = SpatialFolder::new();
-    let harvester = ZeroPointHarvester::new();
-    let noetic = NoeticLayer::new();
-    let lattice = PrescientLattice::new();
-    let anchor = RealityAnchor::new();
-
-    let stdin = io::stdin();
+fn main() -> io::Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
 
-    // Simple JSON-RPC loop over Stdin
-    for line in stdin.lock().lines() {
-        if let Ok(input) = line {
-            if input.trim().is_empty() { continue; }
+    // `--seed N` (or VERITAS_SEED) makes every engine reproducible
+    let seed = match take_flag(&mut args, "--seed")?.or_else(|| std::env::var("VERITAS_SEED").ok()) {
+        Some(raw) => Some(parse_seed(&raw)?),
+        None => None,
+    };
+    let record = take_flag(&mut args, "--record")?;
 
-            // Parse as SecureCommand
-            match serde_json::from_str::<SecureCommand>(&input) {
-                Ok(secure_cmd) => {
-                    // 1. Authenticate (Mock)
-                    if secure_cmd.auth_token != "valid_token" {
-                        print_error("Authentication failed: Invalid token");
-                        continue;
-                    }
+    // Every command, including its auth/audit/RBAC/PII middleware, lives in the registry
+    let mut registry = CommandRegistry::with_builtin();
+    registry.set_seed(seed);
 
-                    // 2. Build Context
-                    let mut roles = HashSet::new();
-                    if secure_cmd.user_id == "admin" {
-                        roles.insert(Role::Admin);
-                    } else {
-                        roles.insert(Role::Viewer); // Default
-                    }
+    // `veritas_core serve [addr]` exposes the registry over HTTP instead of stdin
+    if args.first().map(String::as_str) == Some("serve") {
+        let addr = args.get(1).cloned().unwrap_or_else(|| "127.0.0.1:8787".to_string());
+        let runtime = tokio::runtime::Runtime::new()?;
+        return runtime.block_on(http::serve(&addr, Arc::new(registry)));
+    }
 
-                    let user_ctx = UserContext {
-                        user_id: secure_cmd.user_id,
-                        roles,
-                        auth_token: secure_cmd.auth_token,
-                    };
+    // `veritas_core replay FILE` re-runs a recorded session and diffs every response
+    if args.first().map(String::as_str) == Some("replay") {
+        let path = args.get(1).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "replay needs a session file"))?;
+        let recorded = session::load_session(path)?;
+        registry.set_seed(seed.or(recorded.seed));
+        let token = std::env::var("VERITAS_REPLAY_TOKEN").unwrap_or_else(|_| "valid_token".to_string());
+
+        let report = session::replay(&registry, &recorded, &token);
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        if !report.mismatches.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-                    // 3. Log
-                    let _log_entry = logger.log("CommandReceived", &user_ctx);
+    // `veritas_core report FILE` summarises a recorded session, including performance checks
+    if args.first().map(String::as_str) == Some("report") {
+        let path = args.get(1).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "report needs a session file"))?;
+        let summary = session::report(&session::load_session(path)?);
+        println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+        return Ok(());
+    }
 
-                    // 4. Authorize & Execute
-                    match secure_cmd.command {
-                        Command::Locate(req) => {
-                             if rbac.authorize(&user_ctx, Role::Viewer) {
-                                let result = locator.analyze(&req);
-                                print_response(result);
-                             } else { print_error("Access Denied"); }
-                        },
-                        Command::Compare(req) => {
-                             if rbac.authorize(&user_ctx, Role::Viewer) {
-                                let result = locator.compare(&req);
-                                print_response(result);
-                             } else { print_error("Access Denied"); }
-                        },
-                        Command::Heal(req) => {
-                             if rbac.authorize(&user_ctx, Role::Agent) {
-                                let result = healer.heal(&req);
-                                print_response(result);
-                             } else { print_error("Access Denied"); }
-                        },
-                         Command::Goal(req) => {
-                             if rbac.authorize(&user_ctx, Role::Agent) {
-                                // Sanitize Goal Input
-                                let safe_goal = gdpr.sanitize(&req.goal);
-                                let result = agent.execute(&GoalRequest{ goal: safe_goal });
-                                print_response(result);
-                             } else { print_error("Access Denied"); }
-                        },
-                        Command::Observe(req) => {
-                            if rbac.authorize(&user_ctx, Role::Viewer) {
-                                let result = observer.observe(&req);
-                                print_response(result);
-                             } else { print_error("Access Denied"); }
-                        },
-                         Command::Swarm(req) => {
-                             if rbac.authorize(&user_ctx, Role::Admin) {
-                                let result = swarm.launch(&req);
-                                print_response(result);
-                             } else { print_error("Access Denied: Admin only"); }
-                        },
-                        Command::Omega(req) => {
-                            if rbac.authorize(&user_ctx, Role::Admin) {
-                                let result = match req {
-                                    OmegaRequest::Fold { coords } => folder.remap(coords),
-                                    OmegaRequest::InvertEntropy => harvester.invert_entropy(),
-                                    OmegaRequest::TransmitQualia { concept } => noetic.transmit_qualia(&concept),
-                                    OmegaRequest::Anticipate { subject_id } => lattice.anticipate(&subject_id),
-                                    OmegaRequest::VerifyReality { entity_id } => anchor.verify_existence(&entity_id),
-                                };
-                                print_response(result);
-                            } else { print_error("Access Denied: Omega Clearance Required"); }
-                        },
-                        Command::Ping => {
-                             print_response("Pong".to_string());
-                        }
-                    }
-                },
-                Err(e) => {
-                    // Fallback to non-secure command for backward compatibility or error
-                     print_error(&format!("Invalid Secure Command format: {}", e));
-                }
-            }
+    // `veritas_core run FILE` executes a YAML scenario with fresh engines
+    if args.first().map(String::as_str) == Some("run") {
+        let path = args.get(1).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "run needs a scenario file"))?;
+        let invalid = |e: veritas_core::scenario::ScenarioError| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+        let scenario = Scenario::load(path).map_err(invalid)?;
+        let runner = ScenarioRunner::new(
+            Arc::new(NeuralLocator::new()),
+            Arc::new(SemanticHealer::new()),
+            Arc::new(StateChangeObserver::new()),
+            Arc::new(BaselineStore::new()),
+        );
+        let report = match seed {
+            Some(seed) => determinism::with_seed(seed, || runner.run(&scenario, &mut DryRunDriver)),
+            None => runner.run(&scenario, &mut DryRunDriver),
         }
+        .map_err(invalid)?;
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        if !report.passed {
+            std::process::exit(1);
+        }
+        return Ok(());
     }
-}
 
-fn print_response<T: Serialize>(data: T) {
-    let response = Response {
-        status: "success".to_string(),
-        data: Some(data),
-        error: None,
+    let recorder = match record {
+        Some(path) => Some(SessionRecorder::create(path, seed)?),
+        None => None,
     };
-    println!("{}", serde_json::to_string(&response).unwrap());
-}
 
-fn print_error(msg: &str) {
-     let response = Response::<String> {
-        status: "error".to_string(),
-        data: None,
-        error: Some(msg.to_string()),
-    };
-    println!("{}", serde_json::to_string(&response).unwrap());
+    // JSON-RPC loop over Stdin; commands with a request_id run in the background
+    stdio::serve(&registry, io::stdin().lock(), io::stdout(), recorder.as_ref())
 }
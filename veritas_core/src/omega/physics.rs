@@ -36,7 +36,7 @@ impl ZeroPointHarvester {
     }
 
     pub fn invert_entropy(&self) -> String {
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::engine::determinism::rng();
         let vacuum_flux = rng.gen_range(5000.0..9999.0);
         format!(
             "VEI SYSTEM: Virtual particles sorted. Local entropy reduced. Output: {:.2} TW. Temperature delta: -15K.",
@@ -0,0 +1,661 @@
+//! YAML test scenarios.
+//!
+//! A scenario is a named list of steps (locate, click, type, compare, assert_text,
+//! assert_visual, wait_stable) run through the regular engines. `${name}` placeholders
+//! are filled from `variables`, and each row of `data` runs the steps once more with
+//! that row's values on top:
+//!
+//! ```yaml
+//! name: Checkout applies coupon
+//! variables:
+//!   coupon: SAVE10
+//! data:
+//!   - { page: cart.png }
+//!   - { page: cart_mobile.png }
+//! steps:
+//!   - locate: { intent: "checkout button", screenshot: "${page}", as: checkout }
+//!   - type: { target: checkout, text: "${coupon}" }
+//!   - assert_text: { target: checkout, contains: "Primary" }
+//! ```
+//!
+//! Screenshot and HAR paths are relative to the scenario file (or, for `RunScenario`, to
+//! `$VERITAS_SCENARIOS_DIR`) and may not leave that directory. Browser actions go through
+//! a `ScenarioDriver`; `DryRunDriver` only describes them.
+
+use base64::{engine::general_purpose, Engine as _};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::engine::baseline::{BaselineRequest, BaselineStore};
+use crate::engine::determinism;
+use crate::engine::element_diff::{self, ElementComparer, ElementCompareRequest};
+use crate::engine::imaging::{ImageEncoding, ViewportContext};
+use crate::engine::network::NetworkObserveRequest;
+use crate::engine::neural_locator::{BoundingBox, NeuralLocator, VisionCompareRequest, VisionRequest};
+use crate::engine::observer::{ObserverRequest, StateChangeObserver};
+use crate::engine::semantic_healer::{HealRequest, SemanticHealer};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScenarioError {
+    Parse(String),
+    UnknownVariable { step: usize, name: String },
+    InvalidStep { step: usize, reason: String },
+    Io(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Parse(reason) => write!(f, "Invalid scenario: {}", reason),
+            ScenarioError::UnknownVariable { step, name } => write!(f, "Step {}: unknown variable '{}'", step, name),
+            ScenarioError::InvalidStep { step, reason } => write!(f, "Step {}: {}", step, reason),
+            ScenarioError::Io(reason) => write!(f, "Cannot read scenario: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+fn default_threshold() -> f32 { 0.99 }
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+    /// Finds an element and remembers it under `as` (default: the intent).
+    /// If it cannot be found and `selector` is given, the selector is healed instead.
+    Locate {
+        intent: String,
+        screenshot: String,
+        #[serde(default, rename = "as")]
+        alias: Option<String>,
+        #[serde(default)]
+        selector: Option<String>,
+        #[serde(default)]
+        viewport: ViewportContext,
+    },
+    Click { target: String },
+    Type { target: String, text: String },
+    /// Checks a capture against the stored baseline for `page_id`; with `element`,
+    /// only that element is compared and small moves are tolerated.
+    Compare {
+        page_id: String,
+        screenshot: String,
+        #[serde(default = "default_threshold")]
+        threshold: f32,
+        #[serde(default)]
+        element: Option<String>,
+    },
+    /// Checks the recognised label of a located element.
+    AssertText {
+        target: String,
+        #[serde(default)]
+        contains: Option<String>,
+        #[serde(default)]
+        equals: Option<String>,
+    },
+    /// Checks that two captures are visually the same.
+    AssertVisual {
+        expected: String,
+        actual: String,
+        #[serde(default = "default_threshold")]
+        min_similarity: f32,
+        #[serde(default)]
+        page_id: Option<String>,
+    },
+    /// Passes once the page is stable, from raw signals or a HAR capture.
+    WaitStable {
+        url: String,
+        #[serde(default)]
+        pending_network_requests: u32,
+        #[serde(default)]
+        dom_mutation_rate: u32,
+        #[serde(default)]
+        layout_shifts: f32,
+        #[serde(default)]
+        har: Option<String>,
+    },
+}
+
+impl Step {
+    fn action(&self) -> &'static str {
+        match self {
+            Step::Locate { .. } => "locate",
+            Step::Click { .. } => "click",
+            Step::Type { .. } => "type",
+            Step::Compare { .. } => "compare",
+            Step::AssertText { .. } => "assert_text",
+            Step::AssertVisual { .. } => "assert_visual",
+            Step::WaitStable { .. } => "wait_stable",
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Step::Locate { intent, screenshot, .. } => format!("'{}' in {}", intent, screenshot),
+            Step::Click { target } => format!("'{}'", target),
+            Step::Type { target, text } => format!("'{}' into '{}'", text, target),
+            Step::Compare { page_id, screenshot, element: Some(element), .. } => {
+                format!("'{}' in {} against baseline '{}'", element, screenshot, page_id)
+            }
+            Step::Compare { page_id, screenshot, .. } => format!("{} against baseline '{}'", screenshot, page_id),
+            Step::AssertText { target, .. } => format!("text of '{}'", target),
+            Step::AssertVisual { expected, actual, .. } => format!("{} matches {}", actual, expected),
+            Step::WaitStable { url, .. } => url.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ScenarioFile {
+    name: String,
+    #[serde(default)]
+    variables: BTreeMap<String, Value>,
+    #[serde(default)]
+    data: Vec<BTreeMap<String, Value>>,
+    steps: Vec<Value>,
+}
+
+pub struct Scenario {
+    pub name: String,
+    variables: BTreeMap<String, String>,
+    data: Vec<BTreeMap<String, String>>,
+    steps: Vec<Value>,
+    base_dir: PathBuf,
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Replaces every `${name}` in `text`.
+fn substitute(text: &str, vars: &BTreeMap<String, String>, step: usize) -> Result<String, ScenarioError> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| ScenarioError::InvalidStep { step, reason: format!("unterminated '${{' in '{}'", text) })?;
+        let name = after[..end].trim();
+        let value = vars.get(name).ok_or_else(|| ScenarioError::UnknownVariable { step, name: name.to_string() })?;
+        out.push_str(value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn substitute_value(value: &Value, vars: &BTreeMap<String, String>, step: usize) -> Result<Value, ScenarioError> {
+    Ok(match value {
+        Value::String(s) => Value::String(substitute(s, vars, step)?),
+        Value::Array(items) => Value::Array(items.iter().map(|v| substitute_value(v, vars, step)).collect::<Result<_, _>>()?),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), substitute_value(v, vars, step)?)))
+                .collect::<Result<_, ScenarioError>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+/// Directory `RunScenario` resolves file references against, fixed at startup:
+/// `$VERITAS_SCENARIOS_DIR`, or the working directory.
+pub fn scenarios_root() -> PathBuf {
+    PathBuf::from(std::env::var("VERITAS_SCENARIOS_DIR").unwrap_or_else(|_| ".".to_string()))
+}
+
+/// `reference` under `base_dir`. Absolute paths, and paths that lead outside `base_dir`
+/// once `..` and symlinks are resolved, are refused.
+fn resolve(base_dir: &Path, kind: &str, reference: &str) -> Result<PathBuf, String> {
+    if Path::new(reference).has_root() || Path::new(reference).is_absolute() {
+        return Err(format!("{} '{}' must be relative to the scenario directory", kind, reference));
+    }
+    let root = base_dir.canonicalize().map_err(|e| format!("cannot open scenario directory {}: {}", base_dir.display(), e))?;
+    let path = root.join(reference);
+    let path = path.canonicalize().map_err(|e| format!("cannot read {} {}: {}", kind, path.display(), e))?;
+    if !path.starts_with(&root) {
+        return Err(format!("{} '{}' lies outside the scenario directory", kind, reference));
+    }
+    Ok(path)
+}
+
+impl Scenario {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| ScenarioError::Io(format!("{}: {}", path.display(), e)))?;
+        let base_dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        Self::from_yaml(&source, base_dir)
+    }
+
+    /// Parses and validates every run up front, so typos fail before anything executes.
+    pub fn from_yaml(source: &str, base_dir: impl Into<PathBuf>) -> Result<Self, ScenarioError> {
+        let file: ScenarioFile = serde_yaml::from_str(source).map_err(|e| ScenarioError::Parse(e.to_string()))?;
+        if file.steps.is_empty() {
+            return Err(ScenarioError::Parse("scenario has no steps".to_string()));
+        }
+        let scenario = Scenario {
+            name: file.name,
+            variables: file.variables.iter().map(|(k, v)| (k.clone(), as_text(v))).collect(),
+            data: file.data.iter().map(|row| row.iter().map(|(k, v)| (k.clone(), as_text(v))).collect()).collect(),
+            steps: file.steps,
+            base_dir: base_dir.into(),
+        };
+        for parameters in scenario.parameter_sets() {
+            scenario.steps_for(&parameters)?;
+        }
+        Ok(scenario)
+    }
+
+    /// One parameter set per data row (variables overridden by the row), or just the variables.
+    pub fn parameter_sets(&self) -> Vec<BTreeMap<String, String>> {
+        if self.data.is_empty() {
+            return vec![self.variables.clone()];
+        }
+        self.data
+            .iter()
+            .map(|row| {
+                let mut parameters = self.variables.clone();
+                parameters.extend(row.clone());
+                parameters
+            })
+            .collect()
+    }
+
+    pub fn steps_for(&self, parameters: &BTreeMap<String, String>) -> Result<Vec<Step>, ScenarioError> {
+        self.steps
+            .iter()
+            .enumerate()
+            .map(|(i, raw)| {
+                let step = i + 1;
+                let filled = substitute_value(raw, parameters, step)?;
+                serde_json::from_value(filled).map_err(|e| ScenarioError::InvalidStep { step, reason: e.to_string() })
+            })
+            .collect()
+    }
+}
+
+/// An element found by a `locate` step, in CSS pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct LocatedElement {
+    pub location: Option<BoundingBox>,
+    pub label: Option<String>,
+    /// Set when the element was recovered by healing its selector.
+    pub selector: Option<String>,
+}
+
+/// Performs browser actions for the runner.
+pub trait ScenarioDriver {
+    fn click(&mut self, element: &LocatedElement) -> Result<String, String>;
+    fn type_text(&mut self, element: &LocatedElement, text: &str) -> Result<String, String>;
+}
+
+/// Describes each action without performing it.
+pub struct DryRunDriver;
+
+impl DryRunDriver {
+    fn target(element: &LocatedElement) -> String {
+        match (&element.selector, &element.location) {
+            (Some(selector), _) => selector.clone(),
+            (None, Some(b)) => format!("({}, {})", b.x + b.width / 2, b.y + b.height / 2),
+            (None, None) => "unknown position".to_string(),
+        }
+    }
+}
+
+impl ScenarioDriver for DryRunDriver {
+    fn click(&mut self, element: &LocatedElement) -> Result<String, String> {
+        Ok(format!("click at {}", Self::target(element)))
+    }
+
+    fn type_text(&mut self, element: &LocatedElement, text: &str) -> Result<String, String> {
+        Ok(format!("type {} chars at {}", text.chars().count(), Self::target(element)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Passed,
+    Failed,
+    /// Not run because an earlier step failed.
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct StepReport {
+    pub index: usize,
+    pub action: String,
+    pub description: String,
+    pub status: StepStatus,
+    pub message: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct RunReport {
+    pub parameters: BTreeMap<String, String>,
+    pub passed: bool,
+    pub steps: Vec<StepReport>,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ScenarioReport {
+    pub name: String,
+    pub passed: bool,
+    pub runs: Vec<RunReport>,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct ScenarioRequest {
+    /// Scenario file contents (YAML). File references resolve against the server's
+    /// scenarios directory.
+    pub source: String,
+}
+
+pub struct ScenarioRunner {
+    locator: Arc<NeuralLocator>,
+    healer: Arc<SemanticHealer>,
+    observer: Arc<StateChangeObserver>,
+    baselines: Arc<BaselineStore>,
+    elements: ElementComparer,
+    // Last embedding seen per element alias, used as the healing reference
+    embeddings: Mutex<HashMap<String, Vec<f32>>>,
+}
+
+impl ScenarioRunner {
+    pub fn new(
+        locator: Arc<NeuralLocator>,
+        healer: Arc<SemanticHealer>,
+        observer: Arc<StateChangeObserver>,
+        baselines: Arc<BaselineStore>,
+    ) -> Self {
+        let elements = ElementComparer::new(locator.clone(), baselines.clone());
+        ScenarioRunner { locator, healer, observer, baselines, elements, embeddings: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn run(&self, scenario: &Scenario, driver: &mut dyn ScenarioDriver) -> Result<ScenarioReport, ScenarioError> {
+        let mut runs = Vec::new();
+        for parameters in scenario.parameter_sets() {
+            let steps = scenario.steps_for(&parameters)?;
+            runs.push(self.run_once(&scenario.base_dir, parameters, &steps, driver));
+        }
+        Ok(ScenarioReport { name: scenario.name.clone(), passed: runs.iter().all(|r| r.passed), runs })
+    }
+
+    fn run_once(&self, base_dir: &Path, parameters: BTreeMap<String, String>, steps: &[Step], driver: &mut dyn ScenarioDriver) -> RunReport {
+        let mut elements = HashMap::new();
+        let mut reports = Vec::with_capacity(steps.len());
+        let mut failed = false;
+
+        for (i, step) in steps.iter().enumerate() {
+            let start = Instant::now();
+            let (status, message) = if failed {
+                (StepStatus::Skipped, "skipped after an earlier failure".to_string())
+            } else {
+                match self.execute(step, base_dir, &mut elements, driver) {
+                    Ok(message) => (StepStatus::Passed, message),
+                    Err(message) => {
+                        failed = true;
+                        (StepStatus::Failed, message)
+                    }
+                }
+            };
+            reports.push(StepReport {
+                index: i + 1,
+                action: step.action().to_string(),
+                description: step.describe(),
+                status,
+                message,
+                duration_ms: determinism::elapsed_ms(start),
+            });
+        }
+
+        RunReport { parameters, passed: !failed, steps: reports }
+    }
+
+    fn screenshot(base_dir: &Path, reference: &str) -> Result<String, String> {
+        let path = resolve(base_dir, "screenshot", reference)?;
+        let bytes = std::fs::read(&path).map_err(|e| format!("cannot read screenshot {}: {}", path.display(), e))?;
+        Ok(general_purpose::STANDARD.encode(bytes))
+    }
+
+    fn element<'a>(elements: &'a HashMap<String, LocatedElement>, target: &str) -> Result<&'a LocatedElement, String> {
+        elements.get(target).ok_or_else(|| format!("no located element named '{}'; add a locate step first", target))
+    }
+
+    /// Runs one step; `Err` carries the failure message.
+    fn execute(
+        &self,
+        step: &Step,
+        base_dir: &Path,
+        elements: &mut HashMap<String, LocatedElement>,
+        driver: &mut dyn ScenarioDriver,
+    ) -> Result<String, String> {
+        match step {
+            Step::Locate { intent, screenshot, alias, selector, viewport } => {
+                let alias = alias.clone().unwrap_or_else(|| intent.clone());
+                let image_base64 = Self::screenshot(base_dir, screenshot)?;
+                let result = self
+                    .locator
+                    .analyze(&VisionRequest {
+                        image_base64: image_base64.clone(),
+                        intent: intent.clone(),
+                        image_encoding: ImageEncoding::Auto,
+                        viewport: viewport.clone(),
+                    })
+                    .map_err(|e| e.to_string())?;
+
+                if let Some(location) = result.css_location {
+                    self.embeddings.lock().unwrap().insert(alias.clone(), result.semantic_embedding);
+                    let message = format!("found {:?} (confidence {:.2})", location.label.as_deref().unwrap_or("element"), result.confidence);
+                    elements.insert(alias, LocatedElement { label: location.label.clone(), location: Some(location), selector: None });
+                    return Ok(message);
+                }
+
+                let selector = selector.as_ref().ok_or_else(|| format!("'{}' not found", intent))?;
+                let reference = self.embeddings.lock().unwrap().get(&alias).cloned();
+                let healed = self.healer.heal(&HealRequest {
+                    failed_selector: selector.clone(),
+                    last_known_embedding: reference.unwrap_or_else(|| self.locator.vit.encode_text(intent).to_vec()),
+                    current_image: image_base64,
+                });
+                if !healed.healed {
+                    return Err(format!("'{}' not found and selector could not be healed: {}", intent, healed.reason));
+                }
+                let message = format!("not found visually; healed '{}' to '{}'", selector, healed.new_selector);
+                elements.insert(alias, LocatedElement { location: None, label: None, selector: Some(healed.new_selector) });
+                Ok(message)
+            }
+            Step::Click { target } => driver.click(Self::element(elements, target)?),
+            Step::Type { target, text } => driver.type_text(Self::element(elements, target)?, text),
+            Step::Compare { page_id, screenshot, threshold, element: Some(element) } => {
+                let result = self
+                    .elements
+                    .compare(&ElementCompareRequest {
+                        intent: element.clone(),
+                        name: None,
+                        current_base64: Self::screenshot(base_dir, screenshot)?,
+                        baseline_base64: None,
+                        page_id: Some(page_id.clone()),
+                        image_encoding: ImageEncoding::Auto,
+                        viewport: ViewportContext::default(),
+                        padding: element_diff::default_padding(),
+                        max_shift: element_diff::default_max_shift(),
+                        threshold: *threshold,
+                    })
+                    .map_err(|e| e.to_string())?;
+                if result.changed { Err(result.summary) } else { Ok(result.summary) }
+            }
+            Step::Compare { page_id, screenshot, threshold, element: None } => {
+                let result = self
+                    .baselines
+                    .run(&BaselineRequest::Check {
+                        page_id: page_id.clone(),
+                        image_base64: Self::screenshot(base_dir, screenshot)?,
+                        image_encoding: ImageEncoding::Auto,
+                        threshold: *threshold,
+                    })
+                    .map_err(|e| e.to_string())?;
+                let message = format!("similarity {:.4} (threshold {:.4})", result.similarity_score, threshold);
+                if result.passed { Ok(message) } else { Err(message) }
+            }
+            Step::AssertText { target, contains, equals } => {
+                let label = Self::element(elements, target)?.label.clone().unwrap_or_default();
+                if let Some(expected) = equals {
+                    if &label != expected {
+                        return Err(format!("text is '{}', expected '{}'", label, expected));
+                    }
+                }
+                if let Some(expected) = contains {
+                    if !label.to_lowercase().contains(&expected.to_lowercase()) {
+                        return Err(format!("text '{}' does not contain '{}'", label, expected));
+                    }
+                }
+                Ok(format!("text is '{}'", label))
+            }
+            Step::AssertVisual { expected, actual, min_similarity, page_id } => {
+                let mask = self.baselines.mask_for(page_id.as_deref()).map_err(|e| e.to_string())?;
+                let result = self
+                    .locator
+                    .compare(
+                        &VisionCompareRequest {
+                            image_a_base64: Self::screenshot(base_dir, expected)?,
+                            image_b_base64: Self::screenshot(base_dir, actual)?,
+                            image_encoding: ImageEncoding::Auto,
+                            page_id: page_id.clone(),
+                        },
+                        mask.as_ref(),
+                    )
+                    .map_err(|e| e.to_string())?;
+                let message = format!("similarity {:.4} (minimum {:.4})", result.similarity_score, min_similarity);
+                if result.similarity_score >= *min_similarity { Ok(message) } else { Err(message) }
+            }
+            Step::WaitStable { url, pending_network_requests, dom_mutation_rate, layout_shifts, har } => {
+                let state = match har {
+                    Some(path) => {
+                        let path = resolve(base_dir, "HAR", path)?;
+                        let text = std::fs::read_to_string(&path).map_err(|e| format!("cannot read HAR {}: {}", path.display(), e))?;
+                        let har: Value = serde_json::from_str(&text).map_err(|e| format!("invalid HAR {}: {}", path.display(), e))?;
+                        let request: NetworkObserveRequest = serde_json::from_value(serde_json::json!({
+                            "url": url,
+                            "har": har,
+                            "dom_mutation_rate": dom_mutation_rate,
+                            "layout_shifts": layout_shifts,
+                        }))
+                        .map_err(|e| e.to_string())?;
+                        self.observer.observe_network(&request).map_err(|e| e.to_string())?.state
+                    }
+                    None => self.observer.observe(&ObserverRequest {
+                        url: url.clone(),
+                        pending_network_requests: *pending_network_requests,
+                        dom_mutation_rate: *dom_mutation_rate,
+                        layout_shifts: *layout_shifts,
+                    }),
+                };
+                let message = format!("stability score {:.2}", state.amniotic_state_score);
+                if state.stable { Ok(message) } else { Err(format!("page not stable: {}", message)) }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn fixture_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("veritas_scenario_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        RgbaImage::from_pixel(300, 200, Rgba([255, 255, 255, 255])).save(dir.join("cart.png")).unwrap();
+        RgbaImage::from_pixel(300, 200, Rgba([0, 0, 0, 255])).save(dir.join("dark.png")).unwrap();
+        dir
+    }
+
+    fn runner() -> ScenarioRunner {
+        let dir = std::env::temp_dir().join(format!("veritas_scenario_store_{}", std::process::id()));
+        ScenarioRunner::new(
+            Arc::new(NeuralLocator::new()),
+            Arc::new(SemanticHealer::new()),
+            Arc::new(StateChangeObserver::new()),
+            Arc::new(BaselineStore::at(dir)),
+        )
+    }
+
+    #[test]
+    fn test_data_rows_run_parameterised_steps() {
+        let dir = fixture_dir("rows");
+        let yaml = r#"
+name: Checkout coupon
+variables:
+  coupon: SAVE10
+data:
+  - { expected: cart.png }
+  - { expected: dark.png }
+steps:
+  - locate: { intent: "checkout button", screenshot: cart.png, as: checkout }
+  - type: { target: checkout, text: "${coupon}" }
+  - assert_text: { target: checkout, contains: primary }
+  - assert_visual: { expected: "${expected}", actual: cart.png }
+  - wait_stable: { url: "https://shop.test/cart" }
+"#;
+        let scenario = Scenario::from_yaml(yaml, &dir).unwrap();
+        let report = runner().run(&scenario, &mut DryRunDriver).unwrap();
+
+        assert_eq!(report.runs.len(), 2);
+        assert!(report.runs[0].passed, "{:?}", report.runs[0].steps);
+        assert_eq!(report.runs[0].steps[1].message, "type 6 chars at (175, 125)");
+
+        // The second row's baseline differs, so the visual assertion fails and the rest is skipped
+        let second = &report.runs[1];
+        assert!(!report.passed);
+        assert_eq!(second.steps[3].status, StepStatus::Failed);
+        assert_eq!(second.steps[4].status, StepStatus::Skipped);
+    }
+
+    #[test]
+    fn test_unknown_variable_and_step_are_rejected() {
+        let unknown = "name: x\nsteps:\n  - type: { target: a, text: \"${missing}\" }\n";
+        assert_eq!(
+            Scenario::from_yaml(unknown, ".").err(),
+            Some(ScenarioError::UnknownVariable { step: 1, name: "missing".to_string() })
+        );
+
+        let typo = "name: x\nsteps:\n  - clik: { target: a }\n";
+        assert!(matches!(Scenario::from_yaml(typo, "."), Err(ScenarioError::InvalidStep { step: 1, .. })));
+        let misspelt_field = "name: x\nsteps:\n  - click: { taget: a }\n";
+        assert!(matches!(Scenario::from_yaml(misspelt_field, "."), Err(ScenarioError::InvalidStep { step: 1, .. })));
+    }
+
+    #[test]
+    fn test_click_requires_located_element() {
+        let scenario = Scenario::from_yaml("name: x\nsteps:\n  - click: { target: buy }\n", ".").unwrap();
+        let report = runner().run(&scenario, &mut DryRunDriver).unwrap();
+        assert!(report.runs[0].steps[0].message.contains("add a locate step first"));
+    }
+
+    #[test]
+    fn test_references_outside_the_scenario_directory_are_refused() {
+        let dir = fixture_dir("escape");
+        let inner = dir.join("inner");
+        std::fs::create_dir_all(&inner).unwrap();
+        let outside = dir.join("cart.png");
+        for reference in ["../cart.png", outside.to_str().unwrap()] {
+            let yaml = format!("name: x\nsteps:\n  - locate: {{ intent: button, screenshot: '{}', as: b }}\n", reference);
+            let report = runner().run(&Scenario::from_yaml(&yaml, &inner).unwrap(), &mut DryRunDriver).unwrap();
+            assert_eq!(report.runs[0].steps[0].status, StepStatus::Failed);
+            assert!(report.runs[0].steps[0].message.starts_with("screenshot '"), "{}", report.runs[0].steps[0].message);
+        }
+        assert!(resolve(&dir, "HAR", "inner/../cart.png").is_ok());
+    }
+}
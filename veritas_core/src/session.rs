@@ -0,0 +1,224 @@
+//! Session recording and replay.
+//!
+//! A session file is JSON Lines: one `header` entry (format version and seed),
+//! then one `exchange` entry per command with the response it produced. Auth
+//! tokens are never written; replay supplies its own.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use crate::commands::{CommandRegistry, Response};
+use crate::engine::determinism;
+use crate::engine::perf_budget::PerfReport;
+
+pub const SESSION_FORMAT_VERSION: u32 = 1;
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionEntry {
+    Header { version: u32, seed: Option<u64>, recorded_at: u64 },
+    Exchange { seq: u64, command: Value, response: Value },
+}
+
+pub struct SessionRecorder {
+    writer: Mutex<(BufWriter<File>, u64)>,
+}
+
+impl SessionRecorder {
+    pub fn create(path: impl AsRef<Path>, seed: Option<u64>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = SessionEntry::Header { version: SESSION_FORMAT_VERSION, seed, recorded_at: determinism::now_secs() };
+        writeln!(writer, "{}", serde_json::to_string(&header)?)?;
+        writer.flush()?;
+        Ok(SessionRecorder { writer: Mutex::new((writer, 0)) })
+    }
+
+    /// Appends one command line and its response. Flushed immediately so a crash
+    /// still leaves a usable session.
+    pub fn record(&self, line: &str, response: &Response<Value>) -> io::Result<()> {
+        let mut command = serde_json::from_str::<Value>(line).unwrap_or_else(|_| Value::String(line.to_string()));
+        if let Some(token) = command.get_mut("auth_token") {
+            *token = Value::String(REDACTED.to_string());
+        }
+
+        let mut guard = self.writer.lock().unwrap();
+        let (writer, seq) = &mut *guard;
+        *seq += 1;
+        let entry = SessionEntry::Exchange { seq: *seq, command, response: serde_json::to_value(response)? };
+        writeln!(writer, "{}", serde_json::to_string(&entry)?)?;
+        writer.flush()
+    }
+}
+
+pub struct Session {
+    pub seed: Option<u64>,
+    pub exchanges: Vec<(u64, Value, Value)>,
+}
+
+pub fn load_session(path: impl AsRef<Path>) -> io::Result<Session> {
+    let mut session = Session { seed: None, exchanges: Vec::new() };
+    for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: SessionEntry = serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", n + 1, e)))?;
+        match entry {
+            SessionEntry::Header { version, seed, .. } => {
+                if version != SESSION_FORMAT_VERSION {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported session version {}", version)));
+                }
+                session.seed = seed;
+            }
+            SessionEntry::Exchange { seq, command, response } => session.exchanges.push((seq, command, response)),
+        }
+    }
+    Ok(session)
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReplayMismatch {
+    pub seq: u64,
+    pub command: String,
+    pub differences: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReplayReport {
+    pub exchanges: usize,
+    pub matched: usize,
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+/// Re-runs every recorded command and diffs the fresh response against the recorded one.
+pub fn replay(registry: &CommandRegistry, session: &Session, auth_token: &str) -> ReplayReport {
+    let mut mismatches = Vec::new();
+    for (seq, command, expected) in &session.exchanges {
+        let mut command = command.clone();
+        if let Some(token) = command.get_mut("auth_token") {
+            *token = Value::String(auth_token.to_string());
+        }
+        let line = match &command {
+            Value::String(raw) => raw.clone(),
+            other => other.to_string(),
+        };
+        let actual = serde_json::to_value(registry.dispatch_line(&line)).unwrap_or(Value::Null);
+
+        let mut differences = Vec::new();
+        diff_json("$", expected, &actual, &mut differences);
+        if !differences.is_empty() {
+            let name = command.pointer("/command/command").and_then(Value::as_str).unwrap_or("?");
+            mismatches.push(ReplayMismatch { seq: *seq, command: name.to_string(), differences });
+        }
+    }
+
+    ReplayReport {
+        exchanges: session.exchanges.len(),
+        matched: session.exchanges.len() - mismatches.len(),
+        mismatches,
+    }
+}
+
+/// Summary of a recorded session, including every performance check it ran.
+#[derive(Serialize, Debug)]
+pub struct SessionReport {
+    pub seed: Option<u64>,
+    pub exchanges: usize,
+    pub failed: usize,
+    pub performance: Vec<PerfReport>,
+    pub budget_violations: usize,
+    pub regressions: usize,
+}
+
+pub fn report(session: &Session) -> SessionReport {
+    let failed = session.exchanges.iter().filter(|(_, _, response)| response["status"] != "success").count();
+    let performance: Vec<PerfReport> = session
+        .exchanges
+        .iter()
+        .filter(|(_, command, response)| command.pointer("/command/command") == Some(&Value::from("RecordVitals")) && response["status"] == "success")
+        .filter_map(|(_, _, response)| serde_json::from_value(response["data"].clone()).ok())
+        .collect();
+
+    SessionReport {
+        seed: session.seed,
+        exchanges: session.exchanges.len(),
+        failed,
+        budget_violations: performance.iter().map(|p| p.budget_violations.len()).sum(),
+        regressions: performance.iter().map(|p| p.regressions.len()).sum(),
+        performance,
+    }
+}
+
+/// Collects `path: expected != actual` lines for every leaf that differs.
+pub fn diff_json(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(a), Value::Object(b)) => {
+            for key in a.keys().chain(b.keys().filter(|k| !a.contains_key(*k))) {
+                let child = format!("{}.{}", path, key);
+                diff_json(&child, a.get(key).unwrap_or(&Value::Null), b.get(key).unwrap_or(&Value::Null), out);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                diff_json(&format!("{}[{}]", path, i), x, y, out);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => out.push(format!("{}: length {} != {}", path, a.len(), b.len())),
+        (a, b) if a != b => out.push(format!("{}: {} != {}", path, a, b)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_reports_paths() {
+        let mut out = Vec::new();
+        diff_json("$", &json!({"a": 1, "b": [1, 2], "c": {"d": "x"}}), &json!({"a": 1, "b": [1, 3], "c": {"d": "y"}, "e": true}), &mut out);
+        assert_eq!(out, vec!["$.b[1]: 2 != 3", "$.c.d: \"x\" != \"y\"", "$.e: null != true"]);
+    }
+
+    #[test]
+    fn test_recorded_session_replays_identically() {
+        let path = std::env::temp_dir().join(format!("veritas_session_{}.jsonl", std::process::id()));
+        let mut registry = CommandRegistry::with_builtin();
+        registry.set_seed(Some(42));
+        let recorder = SessionRecorder::create(&path, Some(42)).unwrap();
+        let lines = [
+            r#"{"auth_token":"valid_token","user_id":"admin","command":{"command":"Goal","payload":{"goal":"checkout with discount"}}}"#,
+            r#"{"auth_token":"valid_token","user_id":"admin","command":{"command":"Swarm","payload":{"agent_count":10,"regions":["eu"],"task_goal":"smoke"}}}"#,
+        ];
+
+        for line in lines {
+            recorder.record(line, &registry.dispatch_line(line)).unwrap();
+        }
+
+        let session = load_session(&path).unwrap();
+        assert_eq!(session.seed, Some(42));
+        assert_eq!(session.exchanges.len(), 2);
+        assert_eq!(session.exchanges[0].1["auth_token"], REDACTED);
+
+        // A fresh registry with the recorded seed reproduces every response
+        let mut replayer = CommandRegistry::with_builtin();
+        replayer.set_seed(session.seed);
+        let replayed = replay(&replayer, &session, "valid_token");
+        assert_eq!(replayed.matched, 2, "{:?}", replayed.mismatches);
+
+        // Without the seed, random fields (goal ids, throughput) drift
+        let drifted = replay(&CommandRegistry::with_builtin(), &session, "valid_token");
+        assert!(!drifted.mismatches.is_empty());
+
+        let summary = report(&session);
+        assert_eq!((summary.exchanges, summary.failed), (2, 0));
+        assert!(summary.performance.is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,129 @@
+//! The line-delimited JSON protocol over stdin/stdout.
+//!
+//! Commands without a `request_id` run one at a time, in order, as they always have.
+//! Commands with a `request_id` run in the background: each progress event is written
+//! as `{"type":"progress","request_id":..,"event":{..}}` and the final response carries
+//! the same `request_id`, so later lines (such as `Cancel`) are read while it runs.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use std::sync::Mutex;
+use crate::commands::{CommandRegistry, Response};
+use crate::engine::progress::{ProgressEvent, ProgressSink};
+use crate::session::SessionRecorder;
+
+#[derive(Serialize)]
+struct ProgressNotification<'a> {
+    r#type: &'static str,
+    request_id: &'a str,
+    event: &'a ProgressEvent,
+}
+
+/// Writes whole lines, so background commands never interleave mid-line.
+struct LineWriter<W: Write> {
+    out: Mutex<W>,
+}
+
+impl<W: Write> LineWriter<W> {
+    fn write<T: Serialize>(&self, message: &T) {
+        let mut out = self.out.lock().unwrap();
+        let _ = writeln!(out, "{}", serde_json::to_string(message).unwrap());
+        let _ = out.flush();
+    }
+}
+
+struct NotifySink<'a, W: Write> {
+    request_id: &'a str,
+    writer: &'a LineWriter<W>,
+}
+
+impl<W: Write + Send> ProgressSink for NotifySink<'_, W> {
+    fn emit(&self, event: ProgressEvent) {
+        self.writer.write(&ProgressNotification { r#type: "progress", request_id: self.request_id, event: &event });
+    }
+}
+
+/// Serves commands from `input` until EOF, then waits for background commands to finish.
+pub fn serve<W: Write + Send>(
+    registry: &CommandRegistry,
+    input: impl BufRead,
+    output: W,
+    recorder: Option<&SessionRecorder>,
+) -> io::Result<()> {
+    let writer = LineWriter { out: Mutex::new(output) };
+    let respond = |line: &str, response: Response<Value>| -> io::Result<()> {
+        if let Some(recorder) = recorder {
+            recorder.record(line, &response)?;
+        }
+        writer.write(&response);
+        Ok(())
+    };
+
+    std::thread::scope(|scope| {
+        let mut background = Vec::new();
+        for input in input.lines().map_while(Result::ok) {
+            if input.trim().is_empty() { continue; }
+
+            let request_id = serde_json::from_str::<Value>(&input)
+                .ok()
+                .and_then(|v| v.get("request_id").and_then(Value::as_str).map(str::to_string));
+            match request_id {
+                Some(request_id) => {
+                    let (writer, respond) = (&writer, &respond);
+                    background.push(scope.spawn(move || {
+                        let sink = NotifySink { request_id: &request_id, writer };
+                        respond(&input, registry.dispatch_line_with_progress(&input, &sink))
+                    }));
+                }
+                None => respond(&input, registry.dispatch_line(&input))?,
+            }
+        }
+        for handle in background {
+            handle.join().unwrap()?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_request_id_streams_progress_and_tags_response() {
+        let registry = CommandRegistry::with_builtin();
+        let input = concat!(
+            r#"{"request_id":"s1","auth_token":"valid_token","user_id":"admin","command":{"command":"Swarm","payload":{"agent_count":3,"regions":["eu"],"task_goal":"x"}}}"#,
+            "\n",
+            r#"{"auth_token":"valid_token","user_id":"viewer1","command":{"command":"Ping","payload":null}}"#,
+            "\n",
+        );
+        let out = SharedBuffer::default();
+        serve(&registry, input.as_bytes(), out.clone(), None).unwrap();
+
+        let lines: Vec<Value> = String::from_utf8(out.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert!(lines.iter().any(|l| l["type"] == "progress" && l["request_id"] == "s1"));
+        assert!(lines.iter().any(|l| l["data"] == "Pong" && l.get("request_id").is_none()));
+        let last_for_s1 = lines.iter().rfind(|l| l["request_id"] == "s1").unwrap();
+        assert_eq!(last_for_s1["status"], "success");
+    }
+}